@@ -117,6 +117,7 @@ pub struct SourceUnit(pub Vec<SourceUnitPart>);
 pub enum SourceUnitPart {
     ContractDefinition(Box<ContractDefinition>),
     ImportDirective(Import),
+    Using(Box<Using>),
 }
 
 impl SourceUnitPart {
@@ -124,6 +125,7 @@ impl SourceUnitPart {
         match self {
             SourceUnitPart::ContractDefinition(def) => &def.loc,
             SourceUnitPart::ImportDirective(import) => import.loc(),
+            SourceUnitPart::Using(using) => &using.loc,
         }
     }
 }
@@ -168,6 +170,13 @@ pub enum Type {
         /// The optional value identifier.
         value_name: Option<Identifier>,
     },
+    /// `fn(<params>) -> (<returns>)`
+    Function {
+        /// The parameters.
+        params: ParameterList,
+        /// The return values.
+        returns: ParameterList,
+    },
 }
 
 /// Dynamic type location.
@@ -229,6 +238,7 @@ pub enum ContractPart {
     VariableDefinition(Box<VariableDefinition>),
     FunctionDefinition(Box<FunctionDefinition>),
     TypeDefinition(Box<TypeDefinition>),
+    Using(Box<Using>),
     StraySemicolon(Loc),
 }
 
@@ -243,6 +253,7 @@ impl ContractPart {
             ContractPart::VariableDefinition(def) => &def.loc,
             ContractPart::FunctionDefinition(def) => &def.loc,
             ContractPart::TypeDefinition(def) => &def.loc,
+            ContractPart::Using(def) => &def.loc,
             ContractPart::StraySemicolon(loc) => loc,
         }
     }
@@ -340,6 +351,17 @@ pub struct TypeDefinition {
     pub ty: Expression,
 }
 
+/// `using <library> for <type>;`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Using {
+    /// The code location.
+    pub loc: Loc,
+    /// The library whose functions are attached to the type.
+    pub library: Identifier,
+    /// The type.
+    pub ty: Expression,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct StringLiteral {
     pub loc: Loc,
@@ -509,12 +531,72 @@ pub struct Parameter {
     pub name: Option<Identifier>,
 }
 
+/// The `catch` clause of a try statement, with the optional parameter the
+/// revert data is assigned to.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CatchClause {
+    pub loc: Loc,
+    pub param: Option<Parameter>,
+    pub body: Box<Statement>,
+}
+
+/// Function state mutability.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Mutability {
+    /// `pure`
+    Pure(Loc),
+
+    /// `view`
+    View(Loc),
+}
+
+impl Display for Mutability {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Mutability {
+    /// Returns the string representation of this type.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pure(_) => "pure",
+            Self::View(_) => "view",
+        }
+    }
+}
+
+impl CodeLocation for Mutability {
+    fn loc(&self) -> Loc {
+        match self {
+            Self::Pure(loc) => *loc,
+            Self::View(loc) => *loc,
+        }
+    }
+}
+
+/// An attribute written before a function, e.g. `#[selector(0x12345678)]`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FunctionAttribute {
+    pub loc: Loc,
+    pub name: Identifier,
+    pub args: Vec<Expression>,
+}
+
+impl CodeLocation for FunctionAttribute {
+    fn loc(&self) -> Loc {
+        self.loc
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct FunctionDefinition {
     pub loc: Loc,
+    pub attributes: Vec<FunctionAttribute>,
     pub name: Option<Identifier>,
     pub name_loc: Loc,
     pub params: ParameterList,
+    pub mutability: Option<Mutability>,
     pub returns: ParameterList,
     pub body: Option<Statement>,
 }
@@ -545,6 +627,7 @@ pub enum Statement {
     Error(Loc),
     Return(Loc, Option<Expression>),
     Emit(Loc, Expression),
+    Try(Loc, Expression, ParameterList, Box<Statement>, CatchClause),
 }
 
 impl CodeLocation for Statement {
@@ -562,7 +645,8 @@ impl CodeLocation for Statement {
             | Statement::Break(loc)
             | Statement::Error(loc)
             | Statement::Return(loc, ..)
-            | Statement::Emit(loc, ..) => *loc,
+            | Statement::Emit(loc, ..)
+            | Statement::Try(loc, ..) => *loc,
         }
     }
 }
@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: Apache-2.0
+
+// Deployment artifacts: everything needed to deploy and call one contract
+use super::ola_abi::{gen_abi, ABI};
+use crate::codegen::isa::ola::asm::AsmProgram;
+use crate::sema::ast::Namespace;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tiny_keccak::{Hasher, Keccak};
+
+#[derive(Serialize)]
+pub struct Compiler {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Serialize)]
+pub struct Artifact {
+    pub contract: String,
+    pub compiler: Compiler,
+    /// keccak256 of the source file which declares the contract
+    pub source_hash: String,
+    /// Hash of the program, which contracts creating this one refer to it by
+    pub code_hash: String,
+    /// Function signatures and the selectors they are dispatched on
+    pub selectors: BTreeMap<String, String>,
+    pub abi: Vec<ABI>,
+    pub program: AsmProgram,
+}
+
+pub fn gen_artifact(
+    contract_no: usize,
+    ns: &Namespace,
+    source: &str,
+    program: AsmProgram,
+) -> Artifact {
+    let mut hash = [0u8; 32];
+    let mut hasher = Keccak::v256();
+    hasher.update(source.as_bytes());
+    hasher.finalize(&mut hash);
+
+    let selectors = ns.contracts[contract_no]
+        .all_functions
+        .keys()
+        .filter(|function_no| !ns.functions[**function_no].is_internal(ns))
+        .map(|function_no| {
+            let func = &ns.functions[*function_no];
+            (
+                func.signature.clone(),
+                format!("0x{}", hex::encode(func.selector())),
+            )
+        })
+        .collect();
+
+    Artifact {
+        contract: ns.contracts[contract_no].name.clone(),
+        compiler: Compiler {
+            name: "olac".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        },
+        source_hash: format!("0x{}", hex::encode(hash)),
+        code_hash: format!(
+            "0x{}",
+            program
+                .code_hash()
+                .iter()
+                .map(|word| format!("{:016x}", word))
+                .collect::<String>()
+        ),
+        selectors,
+        abi: gen_abi(contract_no, ns),
+        program,
+    }
+}
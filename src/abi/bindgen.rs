@@ -0,0 +1,733 @@
+// SPDX-License-Identifier: Apache-2.0
+
+// Rust bindings generated from the ABI of a contract. The bindings build
+// calldata and decode return data and events with `abi::encoding`.
+use super::encoding::{Event, Function, ParamType};
+use super::ola_abi::{ABIParam, ABI};
+use indexmap::IndexMap;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "do", "dyn", "else",
+    "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop", "macro",
+    "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static", "struct",
+    "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual", "where",
+    "while", "yield",
+];
+
+/// Generate a Rust module with the bindings of contract `name` from its ABI
+pub fn gen_rust_bindings(name: &str, abi: &[ABI]) -> Result<String, String> {
+    let mut structs = IndexMap::new();
+    for entry in abi {
+        for param in entry.inputs.iter().chain(entry.outputs.iter()).flatten() {
+            ParamType::parse(param)?;
+            collect_structs(param, &mut structs);
+        }
+    }
+
+    let contract = camel_case(name);
+    let mut out = String::new();
+
+    writeln!(
+        out,
+        "//! Bindings for the `{}` contract, generated by `olac bindgen` from its ABI.",
+        name
+    )
+    .unwrap();
+    out.push_str(
+        "//! Do not edit.\n\
+         \n\
+         #![allow(dead_code, unused_imports, clippy::all)]\n\
+         \n\
+         use num_bigint::BigInt;\n\
+         use ola_lang::abi::encoding::{decode_return, encode_calldata, Event, ParamType, Value};\n",
+    );
+
+    for (name, fields) in &structs {
+        gen_struct(name, fields, &mut out);
+    }
+
+    gen_functions(&contract, abi, &mut out)?;
+    gen_events(&contract, abi, &mut out)?;
+
+    Ok(out)
+}
+
+/// Find the structs used by a parameter, inner ones first
+fn collect_structs(param: &ABIParam, structs: &mut IndexMap<String, Vec<ABIParam>>) {
+    if let Some((elem, _)) = array_elem(param) {
+        collect_structs(&elem, structs);
+    } else if param.ty == "tuple" {
+        for field in &param.components {
+            collect_structs(field, structs);
+        }
+        structs
+            .entry(struct_name(param))
+            .or_insert_with(|| param.components.clone());
+    }
+}
+
+/// The element of an array parameter and the length of the array. The last
+/// dimension is the outer one.
+fn array_elem(param: &ABIParam) -> Option<(ABIParam, Option<usize>)> {
+    let strip = |ty: &str| -> Option<(String, Option<usize>)> {
+        let (elem, len) = ty.strip_suffix(']')?.rsplit_once('[')?;
+        Some((elem.to_string(), len.parse().ok()))
+    };
+
+    let (ty, len) = strip(&param.ty)?;
+    let internal_type = strip(&param.internal_type)
+        .map(|(ty, _)| ty)
+        .unwrap_or_default();
+
+    Some((
+        ABIParam {
+            ty,
+            internal_type,
+            ..param.clone()
+        },
+        len,
+    ))
+}
+
+/// The name of the struct of a `tuple` parameter, from its internal type
+/// `struct <contract>.<name>`
+fn struct_name(param: &ABIParam) -> String {
+    let name = param
+        .internal_type
+        .trim_start_matches("struct ")
+        .split('[')
+        .next()
+        .unwrap()
+        .rsplit('.')
+        .next()
+        .unwrap();
+
+    if name.is_empty() {
+        camel_case(&param.name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn rust_type(param: &ABIParam) -> String {
+    if let Some((elem, len)) = array_elem(param) {
+        return match len {
+            Some(len) => format!("[{}; {}]", rust_type(&elem), len),
+            None => format!("Vec<{}>", rust_type(&elem)),
+        };
+    }
+
+    match param.ty.as_str() {
+        "u32" => "u32".to_string(),
+        "u256" => "BigInt".to_string(),
+        "bool" => "bool".to_string(),
+        "field" => "u64".to_string(),
+        "address" | "hash" => "[u64; 4]".to_string(),
+        "string" => "String".to_string(),
+        "fields" => "Vec<u64>".to_string(),
+        _ => struct_name(param),
+    }
+}
+
+/// The type of a function argument, which borrows anything not `Copy`
+fn arg_type(param: &ABIParam) -> String {
+    if let Some((elem, len)) = array_elem(param) {
+        return match len {
+            Some(len) => format!("&[{}; {}]", rust_type(&elem), len),
+            None => format!("&[{}]", rust_type(&elem)),
+        };
+    }
+
+    match param.ty.as_str() {
+        "u256" => "&BigInt".to_string(),
+        "string" => "&str".to_string(),
+        "fields" => "&[u64]".to_string(),
+        "tuple" => format!("&{}", struct_name(param)),
+        _ => rust_type(param),
+    }
+}
+
+/// An expression for the `ParamType` of a parameter
+fn param_type(param: &ABIParam) -> String {
+    if let Some((elem, len)) = array_elem(param) {
+        return format!(
+            "ParamType::Array(Box::new({}), {:?})",
+            param_type(&elem),
+            len
+        );
+    }
+
+    match param.ty.as_str() {
+        "u32" => "ParamType::U32".to_string(),
+        "u256" => "ParamType::U256".to_string(),
+        "bool" => "ParamType::Bool".to_string(),
+        "field" => "ParamType::Field".to_string(),
+        "address" => "ParamType::Address".to_string(),
+        "hash" => "ParamType::Hash".to_string(),
+        "string" => "ParamType::String".to_string(),
+        "fields" => "ParamType::Fields".to_string(),
+        _ => format!("{}::param_type()", struct_name(param)),
+    }
+}
+
+/// An expression converting `expr` to a `Value`. If `by_ref` is set, `expr`
+/// is a reference which must be dereferenced to copy it.
+fn to_value(param: &ABIParam, expr: &str, by_ref: bool, depth: usize) -> String {
+    let copy = if by_ref {
+        format!("*{}", expr)
+    } else {
+        expr.to_string()
+    };
+
+    if let Some((elem, _)) = array_elem(param) {
+        let var = format!("e{}", depth);
+        return format!(
+            "Value::Array({}.iter().map(|{}| {}).collect())",
+            expr,
+            var,
+            to_value(&elem, &var, true, depth + 1)
+        );
+    }
+
+    match param.ty.as_str() {
+        "u32" => format!("Value::U32({})", copy),
+        "u256" => format!("Value::U256({}.clone())", expr),
+        "bool" => format!("Value::Bool({})", copy),
+        "field" => format!("Value::Field({})", copy),
+        "address" => format!("Value::Address({})", copy),
+        "hash" => format!("Value::Hash({})", copy),
+        "string" => format!("Value::String({}.to_string())", expr),
+        "fields" => format!("Value::Fields({}.to_vec())", expr),
+        _ => format!("{}.to_value()", expr),
+    }
+}
+
+/// An expression converting the `Value` `expr` to the Rust type of `param`,
+/// using `?` on errors
+fn from_value(param: &ABIParam, expr: &str, depth: usize) -> String {
+    if let Some((elem, len)) = array_elem(param) {
+        let var = format!("e{}", depth);
+        let elem_ty = rust_type(&elem);
+        let elems = format!(
+            "{}.into_array()?.into_iter().map(|{}| -> Result<_, String> {{ Ok({}) }}).collect::<Result<Vec<{}>, String>>()?",
+            expr,
+            var,
+            from_value(&elem, &var, depth + 1),
+            elem_ty
+        );
+        return match len {
+            Some(len) => format!(
+                "<[{}; {}]>::try_from({}).map_err(|elems| format!(\"expected {} elements but got {{}}\", elems.len()))?",
+                elem_ty, len, elems, len
+            ),
+            None => elems,
+        };
+    }
+
+    match param.ty.as_str() {
+        "u32" => format!("{}.into_u32()?", expr),
+        "u256" => format!("{}.into_u256()?", expr),
+        "bool" => format!("{}.into_bool()?", expr),
+        "field" => format!("{}.into_field()?", expr),
+        "address" => format!("{}.into_address()?", expr),
+        "hash" => format!("{}.into_hash()?", expr),
+        "string" => format!("{}.into_string()?", expr),
+        "fields" => format!("{}.into_fields()?", expr),
+        _ => format!("{}::from_value({})?", struct_name(param), expr),
+    }
+}
+
+fn gen_struct(name: &str, fields: &[ABIParam], out: &mut String) {
+    let names: Vec<String> = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| param_name(&field.name, "field", i))
+        .collect();
+
+    writeln!(out, "\n#[derive(Debug, Clone, PartialEq, Eq)]").unwrap();
+    writeln!(out, "pub struct {} {{", name).unwrap();
+    for (field, name) in fields.iter().zip(&names) {
+        writeln!(out, "    pub {}: {},", name, rust_type(field)).unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    writeln!(out, "impl {} {{", name).unwrap();
+    writeln!(out, "    pub fn param_type() -> ParamType {{").unwrap();
+    writeln!(out, "        ParamType::Tuple(vec![").unwrap();
+    for field in fields {
+        writeln!(
+            out,
+            "            ({:?}.to_string(), {}),",
+            field.name,
+            param_type(field)
+        )
+        .unwrap();
+    }
+    writeln!(out, "        ])").unwrap();
+    writeln!(out, "    }}\n").unwrap();
+
+    writeln!(out, "    pub fn to_value(&self) -> Value {{").unwrap();
+    writeln!(out, "        Value::Tuple(vec![").unwrap();
+    for (field, name) in fields.iter().zip(&names) {
+        writeln!(
+            out,
+            "            {},",
+            to_value(field, &format!("self.{}", name), false, 0)
+        )
+        .unwrap();
+    }
+    writeln!(out, "        ])").unwrap();
+    writeln!(out, "    }}\n").unwrap();
+
+    writeln!(
+        out,
+        "    pub fn from_value(value: Value) -> Result<Self, String> {{"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "        let [{}] = <[Value; {}]>::try_from(value.into_tuple()?)",
+        names.join(", "),
+        fields.len()
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "            .map_err(|fields| format!(\"expected {} fields but got {{}}\", fields.len()))?;",
+        fields.len()
+    )
+    .unwrap();
+    writeln!(out, "        Ok({} {{", name).unwrap();
+    for (field, name) in fields.iter().zip(&names) {
+        writeln!(out, "            {}: {},", name, from_value(field, name, 0)).unwrap();
+    }
+    writeln!(out, "        }})").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+}
+
+fn gen_functions(contract: &str, abi: &[ABI], out: &mut String) -> Result<(), String> {
+    let mut used = HashSet::new();
+
+    writeln!(
+        out,
+        "\n/// Calldata builders and return data decoders of `{}`",
+        contract
+    )
+    .unwrap();
+    writeln!(out, "pub struct {};\n", contract).unwrap();
+    writeln!(out, "impl {} {{", contract).unwrap();
+
+    let functions = abi.iter().filter(|entry| entry.ty == "function");
+
+    for (no, entry) in functions.enumerate() {
+        let inputs = entry.inputs.clone().unwrap_or_default();
+        let outputs = entry.outputs.clone().unwrap_or_default();
+        let func = Function::from_abi(entry)?;
+        let method = unique(ident(&snake_case(&entry.name)), &mut used);
+        let selector = method.trim_start_matches("r#").to_uppercase();
+        let args: Vec<String> = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, param)| param_name(&param.name, "arg", i))
+            .collect();
+
+        if no > 0 {
+            out.push('\n');
+        }
+        writeln!(out, "    /// Selector of `{}`", func.signature()).unwrap();
+        writeln!(
+            out,
+            "    pub const {}: u32 = 0x{:08x};\n",
+            selector,
+            func.selector()
+        )
+        .unwrap();
+
+        writeln!(
+            out,
+            "    /// Calldata for calling `{}`, which is `{}`",
+            entry.name, func.state_mutability
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "    pub fn {}({}) -> Result<Vec<u64>, String> {{",
+            method,
+            inputs
+                .iter()
+                .zip(&args)
+                .map(|(param, arg)| format!("{}: {}", arg, arg_type(param)))
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+        .unwrap();
+        writeln!(out, "        encode_calldata(").unwrap();
+        writeln!(out, "            Self::{},", selector).unwrap();
+        writeln!(
+            out,
+            "            &[{}],",
+            inputs
+                .iter()
+                .map(param_type)
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "            &[{}],",
+            inputs
+                .iter()
+                .zip(&args)
+                .map(|(param, arg)| to_value(param, arg, false, 0))
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+        .unwrap();
+        writeln!(out, "        )").unwrap();
+        writeln!(out, "    }}").unwrap();
+
+        if outputs.is_empty() {
+            continue;
+        }
+
+        let rets: Vec<String> = (0..outputs.len()).map(|i| format!("ret{}", i)).collect();
+        let (ty, value) = if outputs.len() == 1 {
+            (rust_type(&outputs[0]), from_value(&outputs[0], &rets[0], 0))
+        } else {
+            (
+                format!(
+                    "({})",
+                    outputs
+                        .iter()
+                        .map(rust_type)
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+                format!(
+                    "({})",
+                    outputs
+                        .iter()
+                        .zip(&rets)
+                        .map(|(param, ret)| from_value(param, ret, 0))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+            )
+        };
+
+        writeln!(out, "\n    /// Decode the return data of `{}`", entry.name).unwrap();
+        writeln!(
+            out,
+            "    pub fn decode_{}(data: &[u64]) -> Result<{}, String> {{",
+            method.trim_start_matches("r#"),
+            ty
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "        let values = decode_return(&[{}], data)?;",
+            outputs
+                .iter()
+                .map(param_type)
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "        let [{}] = <[Value; {}]>::try_from(values).unwrap();",
+            rets.join(", "),
+            outputs.len()
+        )
+        .unwrap();
+        writeln!(out, "        Ok({})", value).unwrap();
+        writeln!(out, "    }}").unwrap();
+    }
+
+    writeln!(out, "}}").unwrap();
+
+    Ok(())
+}
+
+fn gen_events(contract: &str, abi: &[ABI], out: &mut String) -> Result<(), String> {
+    let mut used = HashSet::new();
+    let mut topics = Vec::new();
+
+    for entry in abi.iter().filter(|entry| entry.ty == "event") {
+        let inputs = entry.inputs.clone().unwrap_or_default();
+        let event = Event {
+            name: entry.name.clone(),
+            inputs: inputs
+                .iter()
+                .map(|param| Ok((param.name.clone(), ParamType::parse(param)?, param.indexed)))
+                .collect::<Result<_, String>>()?,
+            anonymous: entry.anonymous.unwrap_or(false),
+        };
+        let name = unique(camel_case(&entry.name), &mut used);
+        let fields: Vec<String> = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, param)| param_name(&param.name, "field", i))
+            .collect();
+        // indexed fields which are not a single value are stored as the hash
+        // of their encoding
+        let hashed = |param: &ABIParam| {
+            param.indexed
+                && !matches!(
+                    param.ty.as_str(),
+                    "address" | "hash" | "u32" | "bool" | "field"
+                )
+        };
+
+        writeln!(out, "\n/// The `{}` event", event.signature()).unwrap();
+        writeln!(out, "#[derive(Debug, Clone, PartialEq, Eq)]").unwrap();
+        writeln!(out, "pub struct {} {{", name).unwrap();
+        for (param, field) in inputs.iter().zip(&fields) {
+            if hashed(param) {
+                writeln!(
+                    out,
+                    "    /// The poseidon hash of the encoded `{}`",
+                    param.ty
+                )
+                .unwrap();
+                writeln!(out, "    pub {}: [u64; 4],", field).unwrap();
+            } else {
+                writeln!(out, "    pub {}: {},", field, rust_type(param)).unwrap();
+            }
+        }
+        writeln!(out, "}}\n").unwrap();
+
+        writeln!(out, "impl {} {{", name).unwrap();
+        if !event.anonymous {
+            writeln!(out, "    /// The first topic of the event").unwrap();
+            writeln!(
+                out,
+                "    pub const TOPIC: [u64; 4] = {:?};\n",
+                event.topic()
+            )
+            .unwrap();
+            topics.push(name.clone());
+        }
+        writeln!(
+            out,
+            "    pub fn decode(topics: &[[u64; 4]], data: &[u64]) -> Result<Self, String> {{"
+        )
+        .unwrap();
+        writeln!(out, "        let event = Event {{").unwrap();
+        writeln!(out, "            name: {:?}.to_string(),", entry.name).unwrap();
+        writeln!(out, "            inputs: vec![").unwrap();
+        for param in &inputs {
+            writeln!(
+                out,
+                "                ({:?}.to_string(), {}, {}),",
+                param.name,
+                param_type(param),
+                param.indexed
+            )
+            .unwrap();
+        }
+        writeln!(out, "            ],").unwrap();
+        writeln!(out, "            anonymous: {},", event.anonymous).unwrap();
+        writeln!(out, "        }};").unwrap();
+        writeln!(
+            out,
+            "        let [{}] = <[Value; {}]>::try_from(event.decode_log(topics, data)?).unwrap();",
+            fields.join(", "),
+            fields.len()
+        )
+        .unwrap();
+        writeln!(out, "        Ok({} {{", name).unwrap();
+        for (param, field) in inputs.iter().zip(&fields) {
+            let value = if hashed(param) {
+                format!("{}.into_hash()?", field)
+            } else {
+                from_value(param, field, 0)
+            };
+            writeln!(out, "            {}: {},", field, value).unwrap();
+        }
+        writeln!(out, "        }})").unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "}}").unwrap();
+    }
+
+    if topics.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "\n/// The events of `{}` which have a topic", contract).unwrap();
+    writeln!(out, "#[derive(Debug, Clone, PartialEq, Eq)]").unwrap();
+    writeln!(out, "pub enum {}Event {{", contract).unwrap();
+    for name in &topics {
+        writeln!(out, "    {}({}),", name, name).unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    writeln!(out, "impl {}Event {{", contract).unwrap();
+    writeln!(out, "    /// Decode a log by its first topic").unwrap();
+    writeln!(
+        out,
+        "    pub fn decode(topics: &[[u64; 4]], data: &[u64]) -> Result<Self, String> {{"
+    )
+    .unwrap();
+    writeln!(out, "        match topics.first() {{").unwrap();
+    for name in &topics {
+        writeln!(
+            out,
+            "            Some(topic) if *topic == {}::TOPIC => Ok({}Event::{}({}::decode(topics, data)?)),",
+            name, contract, name, name
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "            _ => Err(\"log is not an event of {}\".to_string()),",
+        contract
+    )
+    .unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    Ok(())
+}
+
+/// The Rust name of a parameter, or `<prefix><no>` if it has none
+fn param_name(name: &str, prefix: &str, no: usize) -> String {
+    if name.is_empty() {
+        format!("{}{}", prefix, no)
+    } else {
+        ident(&snake_case(name))
+    }
+}
+
+/// Escape Rust keywords
+fn ident(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("r#{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Overloaded functions and events get a numbered name
+fn unique(name: String, used: &mut HashSet<String>) -> String {
+    let mut unique = name.clone();
+    let mut no = 1;
+    while !used.insert(unique.clone()) {
+        unique = format!("{}_{}", name, no);
+        no += 1;
+    }
+    unique
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && prev_lower {
+            snake.push('_');
+        }
+        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(snake_case("createBook"), "create_book");
+        assert_eq!(snake_case("balanceOf2"), "balance_of2");
+        assert_eq!(snake_case("URI"), "uri");
+        assert_eq!(camel_case("books"), "Books");
+        assert_eq!(camel_case("erc20_token"), "Erc20Token");
+        assert_eq!(ident("type"), "r#type");
+
+        let mut used = HashSet::new();
+        assert_eq!(unique("mint".to_string(), &mut used), "mint");
+        assert_eq!(unique("mint".to_string(), &mut used), "mint_1");
+    }
+
+    #[test]
+    fn bindings() {
+        let abi: Vec<ABI> = serde_json::from_str(
+            r#"[
+  {
+    "name": "createBook",
+    "type": "function",
+    "inputs": [
+      { "name": "id", "type": "u32", "internalType": "u32" },
+      { "name": "names", "type": "string[2][]", "internalType": "string[2][]" }
+    ],
+    "outputs": [
+      {
+        "name": "",
+        "type": "tuple",
+        "internalType": "struct BookExample.Book",
+        "components": [
+          { "name": "book_id", "type": "u32", "internalType": "u32" },
+          { "name": "owner", "type": "address", "internalType": "address" }
+        ]
+      }
+    ],
+    "stateMutability": "nonpayable"
+  },
+  {
+    "name": "BookCreated",
+    "type": "event",
+    "inputs": [
+      { "name": "id", "type": "u32", "internalType": "u32", "indexed": true },
+      { "name": "name", "type": "string", "internalType": "string", "indexed": true },
+      { "name": "price", "type": "u256", "internalType": "u256" }
+    ],
+    "anonymous": false
+  }
+]"#,
+        )
+        .unwrap();
+
+        let code = gen_rust_bindings("books", &abi).unwrap();
+        let func = Function::from_abi(&abi[0]).unwrap();
+
+        assert!(
+            code.contains("pub struct Book {\n    pub book_id: u32,\n    pub owner: [u64; 4],\n}")
+        );
+        assert!(code.contains("pub struct Books;"));
+        assert!(code.contains(&format!(
+            "    /// Selector of `createBook(u32,string[2][])`\n    pub const CREATE_BOOK: u32 = 0x{:08x};",
+            func.selector()
+        )));
+        assert!(code.contains(
+            "pub fn create_book(id: u32, names: &[[String; 2]]) -> Result<Vec<u64>, String> {"
+        ));
+        assert!(code.contains(
+            "&[Value::U32(id), Value::Array(names.iter().map(|e0| Value::Array(e0.iter().map(|e1| Value::String(e1.to_string())).collect())).collect())],"
+        ));
+        assert!(code.contains("pub fn decode_create_book(data: &[u64]) -> Result<Book, String> {"));
+        assert!(code.contains("        Ok(Book::from_value(ret0)?)"));
+        assert!(code.contains(
+            "    /// The poseidon hash of the encoded `string`\n    pub name: [u64; 4],\n    pub price: BigInt,"
+        ));
+        assert!(code.contains(
+            "Some(topic) if *topic == BookCreated::TOPIC => Ok(BooksEvent::BookCreated(BookCreated::decode(topics, data)?)),"
+        ));
+    }
+}
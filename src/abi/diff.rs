@@ -0,0 +1,234 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Compare the ABI of a contract with the ABI of an upgraded version of it,
+//! and find the changes which break callers of the old interface.
+
+use super::encoding::{Contract, Event, Function, ParamType};
+
+fn types(params: &[(String, ParamType)]) -> String {
+    params
+        .iter()
+        .map(|(_, ty)| ty.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Functions which are not `nonpayable` can be evaluated without a
+/// transaction, so callers may rely on that
+fn needs_transaction(func: &Function) -> bool {
+    func.state_mutability != "view" && func.state_mutability != "pure"
+}
+
+/// The indexed fields of an event, which decide the topics of its logs
+fn indexed(event: &Event) -> Vec<bool> {
+    event
+        .inputs
+        .iter()
+        .map(|(_, _, indexed)| *indexed)
+        .collect()
+}
+
+/// Find the changes from `old` to `new` which break callers of `old`.
+/// Functions and events which are added do not break anything.
+pub fn abi_diff(old: &Contract, new: &Contract) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    for old_func in &old.functions {
+        let signature = old_func.signature();
+
+        let Some(new_func) = new
+            .functions
+            .iter()
+            .find(|func| func.signature() == signature)
+        else {
+            errors.push(format!("function '{}' has been removed", signature));
+            continue;
+        };
+
+        if new_func.selector() != old_func.selector() {
+            errors.push(format!(
+                "selector of function '{}' has changed from 0x{:08x} to 0x{:08x}",
+                signature,
+                old_func.selector(),
+                new_func.selector()
+            ));
+        }
+
+        let (old_returns, new_returns) = (types(&old_func.outputs), types(&new_func.outputs));
+
+        if old_returns != new_returns {
+            errors.push(format!(
+                "return types of function '{}' have changed from ({}) to ({})",
+                signature, old_returns, new_returns
+            ));
+        }
+
+        if !needs_transaction(old_func) && needs_transaction(new_func) {
+            errors.push(format!(
+                "function '{}' was {} but now needs a transaction",
+                signature, old_func.state_mutability
+            ));
+        }
+    }
+
+    for new_func in &new.functions {
+        let selector = new_func.selector();
+
+        // a function which takes the selector of another old one would be
+        // called with arguments encoded for the old function
+        if let Some(old_func) = old
+            .functions
+            .iter()
+            .find(|func| func.selector() == selector && func.signature() != new_func.signature())
+        {
+            errors.push(format!(
+                "function '{}' takes selector 0x{:08x} of function '{}'",
+                new_func.signature(),
+                selector,
+                old_func.signature()
+            ));
+        }
+    }
+
+    for old_event in &old.events {
+        let signature = old_event.signature();
+
+        let Some(new_event) = new
+            .events
+            .iter()
+            .find(|event| event.signature() == signature)
+        else {
+            errors.push(format!("event '{}' has been removed", signature));
+            continue;
+        };
+
+        if new_event.anonymous != old_event.anonymous {
+            errors.push(format!(
+                "event '{}' is {} anonymous",
+                signature,
+                if new_event.anonymous {
+                    "now"
+                } else {
+                    "no longer"
+                }
+            ));
+        }
+
+        if indexed(new_event) != indexed(old_event) {
+            errors.push(format!(
+                "indexed fields of event '{}' have changed",
+                signature
+            ));
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::abi::ola_abi::gen_abi;
+    use crate::{parse_and_resolve, FileResolver};
+    use std::ffi::OsStr;
+
+    fn contract(src: &str) -> Contract {
+        let mut cache = FileResolver::new();
+        cache.set_file_contents("test.ola", src.to_string());
+        let ns = parse_and_resolve(OsStr::new("test.ola"), &mut cache);
+        assert!(!ns.diagnostics.any_errors());
+
+        Contract::from_abi(&gen_abi(0, &ns)).unwrap()
+    }
+
+    static OLD: &str = r#"
+    contract Token {
+        event Transfer(address indexed from, address indexed to, u32 amount);
+
+        fn transfer(address to, u32 amount) -> (bool) {
+            emit Transfer(to, to, amount);
+            return true;
+        }
+
+        fn balance(address owner) view -> (u32) {
+            return 0;
+        }
+
+        fn name() pure -> (string) {
+            return "token";
+        }
+    }"#;
+
+    #[test]
+    fn compatible() {
+        let old = contract(OLD);
+
+        // functions and events may be added, and parameters renamed
+        let new = contract(
+            r#"
+            contract Token {
+                event Transfer(address indexed sender, address indexed receiver, u32 value);
+                event Approval(address indexed owner, address indexed spender, u32 value);
+
+                fn transfer(address receiver, u32 value) -> (bool) {
+                    emit Transfer(receiver, receiver, value);
+                    emit Approval(receiver, receiver, value);
+                    return true;
+                }
+
+                fn balance(address owner) pure -> (u32) {
+                    return 0;
+                }
+
+                fn name() pure -> (string) {
+                    return "token";
+                }
+
+                fn symbol() pure -> (string) {
+                    return "TOK";
+                }
+            }"#,
+        );
+
+        assert_eq!(abi_diff(&old, &new), Vec::<String>::new());
+    }
+
+    #[test]
+    fn breaking() {
+        let old = contract(OLD);
+
+        let new = contract(
+            r#"
+            contract Token {
+                event Transfer(address indexed from, address to, u32 amount);
+
+                #[selector(0x12345678)]
+                fn transfer(address to, u32 amount) -> (bool) {
+                    emit Transfer(to, to, amount);
+                    return true;
+                }
+
+                fn balance(address owner) -> (u256) {
+                    return 0;
+                }
+            }"#,
+        );
+
+        let selector = old.function("transfer").unwrap().selector();
+
+        assert_eq!(
+            abi_diff(&old, &new),
+            [
+                format!(
+                    "selector of function 'transfer(address,u32)' has changed from 0x{:08x} to 0x12345678",
+                    selector
+                ),
+                "return types of function 'balance(address)' have changed from (u32) to (u256)"
+                    .to_string(),
+                "function 'balance(address)' was view but now needs a transaction".to_string(),
+                "function 'name()' has been removed".to_string(),
+                "indexed fields of event 'Transfer(address,address,u32)' have changed".to_string(),
+            ]
+        );
+    }
+}
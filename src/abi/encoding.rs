@@ -0,0 +1,1194 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Encode calldata and decode return data and event logs on the host, given
+//! the ABI written by `olac`. The layout is the one `irgen::encoding` emits:
+//! every value is a sequence of field elements.
+//!
+//! - `u32`, `bool`, `field` and enums are a single field element
+//! - `address` and `hash` are 4 field elements of 64 bits, most significant
+//!   first
+//! - `u256` is 8 field elements of 32 bits, most significant first
+//! - `string` and `fields` are their length followed by one element per byte
+//!   or field
+//! - a fixed array is its elements, a dynamic array is its length followed by
+//!   its elements, outer dimension first
+//! - a struct is its fields in declaration order
+//!
+//! Calldata is the encoded arguments followed by their length and the function
+//! selector.
+
+use super::ola_abi::{ABIParam, ABI};
+use crate::sema::ast::{event_selector, selector};
+use num_bigint::{BigInt, Sign};
+use num_traits::{ToPrimitive, Zero};
+use std::fmt;
+
+/// The type of a parameter, as described by the `type` and `components` of
+/// an [`ABIParam`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamType {
+    U32,
+    U256,
+    Bool,
+    Field,
+    Address,
+    Hash,
+    String,
+    Fields,
+    /// An array with a fixed length, or a dynamic one when the length is
+    /// `None`
+    Array(Box<ParamType>, Option<usize>),
+    /// A struct, with the names of its fields
+    Tuple(Vec<(String, ParamType)>),
+}
+
+/// A value of a [`ParamType`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    U32(u32),
+    U256(BigInt),
+    Bool(bool),
+    Field(u64),
+    Address([u64; 4]),
+    Hash([u64; 4]),
+    String(String),
+    Fields(Vec<u64>),
+    Array(Vec<Value>),
+    Tuple(Vec<Value>),
+}
+
+/// A function from the ABI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    pub inputs: Vec<(String, ParamType)>,
+    pub outputs: Vec<(String, ParamType)>,
+    pub state_mutability: String,
+    /// The selector given with `#[selector]`, if any
+    pub selector: Option<u32>,
+}
+
+/// An event from the ABI. The `bool` of each input is set if it is indexed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub name: String,
+    pub inputs: Vec<(String, ParamType, bool)>,
+    pub anonymous: bool,
+}
+
+/// All functions and events of a contract ABI
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Contract {
+    pub functions: Vec<Function>,
+    pub events: Vec<Event>,
+}
+
+impl Contract {
+    /// Parse the ABI JSON generated by `olac --gen abi`
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let abi: Vec<ABI> = serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        Contract::from_abi(&abi)
+    }
+
+    pub fn from_abi(abi: &[ABI]) -> Result<Self, String> {
+        let mut contract = Contract::default();
+
+        for entry in abi {
+            let inputs = entry.inputs.as_deref().unwrap_or_default();
+
+            match entry.ty.as_str() {
+                "function" => contract.functions.push(Function::from_abi(entry)?),
+                "event" => contract.events.push(Event {
+                    name: entry.name.clone(),
+                    inputs: inputs
+                        .iter()
+                        .map(|param| {
+                            Ok((param.name.clone(), ParamType::parse(param)?, param.indexed))
+                        })
+                        .collect::<Result<_, String>>()?,
+                    anonymous: entry.anonymous.unwrap_or(false),
+                }),
+                ty => return Err(format!("unknown ABI entry type '{}'", ty)),
+            }
+        }
+
+        Ok(contract)
+    }
+
+    /// Find a function by name. Overloaded functions can be told apart by
+    /// their signature instead.
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions
+            .iter()
+            .find(|func| func.name == name || func.signature() == name)
+    }
+
+    /// Find an event by name or signature
+    pub fn event(&self, name: &str) -> Option<&Event> {
+        self.events
+            .iter()
+            .find(|event| event.name == name || event.signature() == name)
+    }
+
+    /// Find the event which emitted a log with these topics
+    pub fn event_for_topics(&self, topics: &[[u64; 4]]) -> Option<&Event> {
+        let first = topics.first()?;
+
+        self.events
+            .iter()
+            .find(|event| !event.anonymous && &event.topic() == first)
+    }
+
+    /// Decode a log into the event which emitted it and its fields. A log
+    /// whose first topic matches no event is decoded by the first anonymous
+    /// event which accepts it.
+    pub fn decode_log(
+        &self,
+        topics: &[[u64; 4]],
+        data: &[u64],
+    ) -> Result<(&Event, Vec<Value>), String> {
+        if let Some(event) = self.event_for_topics(topics) {
+            return Ok((event, event.decode_log(topics, data)?));
+        }
+
+        self.events
+            .iter()
+            .filter(|event| event.anonymous)
+            .find_map(|event| Some((event, event.decode_log(topics, data).ok()?)))
+            .ok_or_else(|| "log does not match any event".to_string())
+    }
+}
+
+fn params(params: &[ABIParam]) -> Result<Vec<(String, ParamType)>, String> {
+    params
+        .iter()
+        .map(|param| Ok((param.name.clone(), ParamType::parse(param)?)))
+        .collect()
+}
+
+fn signature(name: &str, params: &[&ParamType]) -> String {
+    format!(
+        "{}({})",
+        name,
+        params
+            .iter()
+            .map(|ty| ty.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    )
+}
+
+impl Function {
+    pub fn from_abi(entry: &ABI) -> Result<Self, String> {
+        let selector = match &entry.selector {
+            Some(selector) => Some(
+                selector
+                    .strip_prefix("0x")
+                    .filter(|hex| hex.len() == 8)
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| format!("invalid selector '{}'", selector))?,
+            ),
+            None => None,
+        };
+
+        Ok(Function {
+            name: entry.name.clone(),
+            inputs: params(entry.inputs.as_deref().unwrap_or_default())?,
+            outputs: params(entry.outputs.as_deref().unwrap_or_default())?,
+            state_mutability: entry.state_mutability.clone().unwrap_or_default(),
+            selector,
+        })
+    }
+
+    /// The signature the selector is calculated from, e.g.
+    /// `transfer(address,u32)`
+    pub fn signature(&self) -> String {
+        let params: Vec<&ParamType> = self.inputs.iter().map(|(_, ty)| ty).collect();
+
+        signature(&self.name, &params)
+    }
+
+    /// The selector which the contract dispatches on, calculated like
+    /// `Function::selector` in the compiler
+    pub fn selector(&self) -> u32 {
+        self.selector.unwrap_or_else(|| {
+            let selector = selector(&self.signature());
+
+            u32::from_be_bytes(selector.try_into().unwrap())
+        })
+    }
+
+    fn input_types(&self) -> Vec<ParamType> {
+        self.inputs.iter().map(|(_, ty)| ty.clone()).collect()
+    }
+
+    fn output_types(&self) -> Vec<ParamType> {
+        self.outputs.iter().map(|(_, ty)| ty.clone()).collect()
+    }
+
+    /// Encode the calldata for calling this function with `args`
+    pub fn encode_input(&self, args: &[Value]) -> Result<Vec<u64>, String> {
+        encode_calldata(self.selector(), &self.input_types(), args)
+    }
+
+    /// Encode the calldata from a JSON array of arguments. A struct may be
+    /// given as an array of its fields or an object keyed by field name.
+    pub fn encode_input_json(&self, args: &serde_json::Value) -> Result<Vec<u64>, String> {
+        let Some(args) = args.as_array() else {
+            return Err("arguments must be a JSON array".to_string());
+        };
+
+        if args.len() != self.inputs.len() {
+            return Err(format!(
+                "function '{}' expects {} arguments, {} provided",
+                self.name,
+                self.inputs.len(),
+                args.len()
+            ));
+        }
+
+        let args = self
+            .inputs
+            .iter()
+            .zip(args)
+            .map(|((_, ty), arg)| Value::from_json(ty, arg))
+            .collect::<Result<Vec<Value>, String>>()?;
+
+        self.encode_input(&args)
+    }
+
+    /// Decode the arguments from calldata created by [`Function::encode_input`]
+    pub fn decode_input(&self, calldata: &[u64]) -> Result<Vec<Value>, String> {
+        let [data @ .., length, selector] = calldata else {
+            return Err("calldata is too short".to_string());
+        };
+
+        if *selector != self.selector() as u64 {
+            return Err(format!(
+                "selector 0x{:08x} does not match function '{}'",
+                selector,
+                self.signature()
+            ));
+        }
+
+        if *length != data.len() as u64 {
+            return Err(format!(
+                "calldata length {} does not match the {} fields of the arguments",
+                length,
+                data.len()
+            ));
+        }
+
+        decode_all(&self.input_types(), data)
+    }
+
+    /// Decode the return data of a call. The return data may be followed by
+    /// its length, as it is written to the tape.
+    pub fn decode_output(&self, data: &[u64]) -> Result<Vec<Value>, String> {
+        decode_return(&self.output_types(), data)
+    }
+}
+
+impl Event {
+    /// The signature the topic is calculated from
+    pub fn signature(&self) -> String {
+        let params: Vec<&ParamType> = self.inputs.iter().map(|(_, ty, _)| ty).collect();
+
+        signature(&self.name, &params)
+    }
+
+    /// The first topic of the event, unless it is anonymous
+    pub fn topic(&self) -> [u64; 4] {
+        limbs(&event_selector(&self.signature()))
+    }
+
+    /// Decode the fields of an event from the topics and data of its log.
+    ///
+    /// Indexed fields of type `address`, `hash`, `u32`, `bool`, `field` or an
+    /// enum are decoded from their topic. Any other indexed field is stored as
+    /// the poseidon hash of its encoding, so its value is the topic as a
+    /// [`Value::Hash`].
+    pub fn decode_log(&self, topics: &[[u64; 4]], data: &[u64]) -> Result<Vec<Value>, String> {
+        let mut topics = topics.iter();
+
+        if !self.anonymous {
+            match topics.next() {
+                Some(topic) if *topic == self.topic() => (),
+                _ => {
+                    return Err(format!(
+                        "log topic does not match event '{}'",
+                        self.signature()
+                    ))
+                }
+            }
+        }
+
+        let data_types: Vec<ParamType> = self
+            .inputs
+            .iter()
+            .filter(|(_, _, indexed)| !indexed)
+            .map(|(_, ty, _)| ty.clone())
+            .collect();
+        let mut data = decode_all(&data_types, data)?.into_iter();
+
+        self.inputs
+            .iter()
+            .map(|(name, ty, indexed)| {
+                if !indexed {
+                    return Ok(data.next().unwrap());
+                }
+
+                let Some(topic) = topics.next() else {
+                    return Err(format!("missing topic for indexed field '{}'", name));
+                };
+
+                Ok(match ty {
+                    ParamType::Address => Value::Address(*topic),
+                    ParamType::Hash => Value::Hash(*topic),
+                    ParamType::U32 | ParamType::Bool | ParamType::Field => {
+                        decode(&[ty.clone()], &topic[3..])?.0.remove(0)
+                    }
+                    _ => Value::Hash(*topic),
+                })
+            })
+            .collect()
+    }
+}
+
+impl ParamType {
+    pub fn parse(param: &ABIParam) -> Result<Self, String> {
+        // array dimensions are written innermost first, so the last one is
+        // the outer array
+        if let Some(elem) = param.ty.strip_suffix(']') {
+            let Some((elem, len)) = elem.rsplit_once('[') else {
+                return Err(format!("invalid type '{}'", param.ty));
+            };
+            let len = if len.is_empty() {
+                None
+            } else {
+                Some(
+                    len.parse::<usize>()
+                        .map_err(|_| format!("invalid array length in '{}'", param.ty))?,
+                )
+            };
+            let elem = ABIParam {
+                ty: elem.to_string(),
+                ..param.clone()
+            };
+
+            return Ok(ParamType::Array(Box::new(ParamType::parse(&elem)?), len));
+        }
+
+        Ok(match param.ty.as_str() {
+            "u32" => ParamType::U32,
+            "u256" => ParamType::U256,
+            "bool" => ParamType::Bool,
+            "field" => ParamType::Field,
+            "address" => ParamType::Address,
+            "hash" => ParamType::Hash,
+            "string" => ParamType::String,
+            "fields" => ParamType::Fields,
+            "tuple" => ParamType::Tuple(params(&param.components)?),
+            ty => return Err(format!("unsupported type '{}'", ty)),
+        })
+    }
+}
+
+impl fmt::Display for ParamType {
+    /// Format the type as it is written in a signature
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParamType::U32 => write!(f, "u32"),
+            ParamType::U256 => write!(f, "u256"),
+            ParamType::Bool => write!(f, "bool"),
+            ParamType::Field => write!(f, "field"),
+            ParamType::Address => write!(f, "address"),
+            ParamType::Hash => write!(f, "hash"),
+            ParamType::String => write!(f, "string"),
+            ParamType::Fields => write!(f, "fields"),
+            ParamType::Array(elem, Some(len)) => write!(f, "{}[{}]", elem, len),
+            ParamType::Array(elem, None) => write!(f, "{}[]", elem),
+            ParamType::Tuple(fields) => write!(
+                f,
+                "({})",
+                fields
+                    .iter()
+                    .map(|(_, ty)| ty.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            ),
+        }
+    }
+}
+
+impl Value {
+    /// Convert a JSON value to a value of type `ty`. Numbers may be given as
+    /// JSON numbers, or as decimal or `0x` prefixed hex strings.
+    pub fn from_json(ty: &ParamType, json: &serde_json::Value) -> Result<Self, String> {
+        use serde_json::Value as Json;
+
+        let mismatch = || format!("expected {} but got {}", ty, json);
+
+        Ok(match (ty, json) {
+            (ParamType::Bool, Json::Bool(b)) => Value::Bool(*b),
+            (ParamType::String, Json::String(s)) => Value::String(s.clone()),
+            (ParamType::U32, _) => {
+                let n = json_number(json).ok_or_else(mismatch)?;
+                Value::U32(n.to_u32().ok_or_else(mismatch)?)
+            }
+            (ParamType::Field, _) => {
+                let n = json_number(json).ok_or_else(mismatch)?;
+                Value::Field(n.to_u64().ok_or_else(mismatch)?)
+            }
+            (ParamType::U256, _) => {
+                let n = json_number(json).ok_or_else(mismatch)?;
+                if n.sign() == Sign::Minus || n.bits() > 256 {
+                    return Err(mismatch());
+                }
+                Value::U256(n)
+            }
+            (ParamType::Address | ParamType::Hash, _) => {
+                let n = json_number(json).ok_or_else(mismatch)?;
+                if n.sign() == Sign::Minus || n.bits() > 256 {
+                    return Err(mismatch());
+                }
+                if *ty == ParamType::Address {
+                    Value::Address(limbs(&n))
+                } else {
+                    Value::Hash(limbs(&n))
+                }
+            }
+            (ParamType::Fields, Json::Array(elems)) => Value::Fields(
+                elems
+                    .iter()
+                    .map(|elem| json_number(elem).and_then(|n| n.to_u64()))
+                    .collect::<Option<Vec<u64>>>()
+                    .ok_or_else(mismatch)?,
+            ),
+            (ParamType::Array(elem_ty, len), Json::Array(elems)) => {
+                if matches!(len, Some(len) if *len != elems.len()) {
+                    return Err(mismatch());
+                }
+                Value::Array(
+                    elems
+                        .iter()
+                        .map(|elem| Value::from_json(elem_ty, elem))
+                        .collect::<Result<_, String>>()?,
+                )
+            }
+            (ParamType::Tuple(fields), Json::Array(elems)) if fields.len() == elems.len() => {
+                Value::Tuple(
+                    fields
+                        .iter()
+                        .zip(elems)
+                        .map(|((_, ty), elem)| Value::from_json(ty, elem))
+                        .collect::<Result<_, String>>()?,
+                )
+            }
+            (ParamType::Tuple(fields), Json::Object(object)) if fields.len() == object.len() => {
+                Value::Tuple(
+                    fields
+                        .iter()
+                        .map(|(name, ty)| match object.get(name) {
+                            Some(elem) => Value::from_json(ty, elem),
+                            None => Err(format!("missing field '{}'", name)),
+                        })
+                        .collect::<Result<_, String>>()?,
+                )
+            }
+            _ => return Err(mismatch()),
+        })
+    }
+}
+
+macro_rules! into {
+    ($(#[$doc:meta] $name:ident: $variant:ident -> $ty:ty,)*) => {
+        impl Value {
+            $(
+                #[$doc]
+                pub fn $name(self) -> Result<$ty, String> {
+                    match self {
+                        Value::$variant(v) => Ok(v),
+                        v => Err(format!(
+                            "expected {} but got {:?}",
+                            stringify!($variant),
+                            v
+                        )),
+                    }
+                }
+            )*
+        }
+    };
+}
+
+into! {
+    /// The number of a `u32` value
+    into_u32: U32 -> u32,
+    /// The number of a `u256` value
+    into_u256: U256 -> BigInt,
+    /// The value of a `bool`
+    into_bool: Bool -> bool,
+    /// The element of a `field` value
+    into_field: Field -> u64,
+    /// The limbs of an `address`
+    into_address: Address -> [u64; 4],
+    /// The limbs of a `hash`
+    into_hash: Hash -> [u64; 4],
+    /// The contents of a `string`
+    into_string: String -> String,
+    /// The elements of a `fields` value
+    into_fields: Fields -> Vec<u64>,
+    /// The elements of an array
+    into_array: Array -> Vec<Value>,
+    /// The fields of a struct
+    into_tuple: Tuple -> Vec<Value>,
+}
+
+fn json_number(json: &serde_json::Value) -> Option<BigInt> {
+    match json {
+        serde_json::Value::Number(n) => n.as_u64().map(BigInt::from),
+        serde_json::Value::String(s) => match s.strip_prefix("0x") {
+            Some(hex) => BigInt::parse_bytes(hex.as_bytes(), 16),
+            None => BigInt::parse_bytes(s.as_bytes(), 10),
+        },
+        _ => None,
+    }
+}
+
+/// Split a 256 bit number into 4 limbs of 64 bits, most significant first
+fn limbs(n: &BigInt) -> [u64; 4] {
+    let mut limbs = [0u64; 4];
+    for (i, digit) in n.iter_u64_digits().take(4).enumerate() {
+        limbs[3 - i] = digit;
+    }
+    limbs
+}
+
+/// Encode calldata: the `args` of `types`, followed by their length and the
+/// `selector`
+pub fn encode_calldata(
+    selector: u32,
+    types: &[ParamType],
+    args: &[Value],
+) -> Result<Vec<u64>, String> {
+    let mut calldata = encode(types, args)?;
+
+    calldata.push(calldata.len() as u64);
+    calldata.push(selector as u64);
+
+    Ok(calldata)
+}
+
+/// Decode return data of `types`, which may be followed by its length as it
+/// is written to the tape
+pub fn decode_return(types: &[ParamType], data: &[u64]) -> Result<Vec<Value>, String> {
+    let (values, read) = decode(types, data)?;
+
+    match &data[read..] {
+        [] => Ok(values),
+        [length] if *length == read as u64 => Ok(values),
+        rest => Err(format!(
+            "{} unexpected fields after return data",
+            rest.len()
+        )),
+    }
+}
+
+/// Encode `values` of `types` one after the other
+pub fn encode(types: &[ParamType], values: &[Value]) -> Result<Vec<u64>, String> {
+    if types.len() != values.len() {
+        return Err(format!(
+            "expected {} values, {} provided",
+            types.len(),
+            values.len()
+        ));
+    }
+
+    let mut buffer = Vec::new();
+    for (ty, value) in types.iter().zip(values) {
+        encode_value(ty, value, &mut buffer)?;
+    }
+
+    Ok(buffer)
+}
+
+fn encode_value(ty: &ParamType, value: &Value, buffer: &mut Vec<u64>) -> Result<(), String> {
+    match (ty, value) {
+        (ParamType::U32, Value::U32(n)) => buffer.push(*n as u64),
+        (ParamType::Field, Value::Field(n)) => buffer.push(*n),
+        (ParamType::Bool, Value::Bool(b)) => buffer.push(u64::from(*b)),
+        (ParamType::Address, Value::Address(limbs)) | (ParamType::Hash, Value::Hash(limbs)) => {
+            buffer.extend_from_slice(limbs)
+        }
+        (ParamType::U256, Value::U256(n)) => {
+            if n.sign() == Sign::Minus || n.bits() > 256 {
+                return Err(format!("{} does not fit in u256", n));
+            }
+            let mut limbs = [0u64; 8];
+            for (i, digit) in n.iter_u32_digits().enumerate() {
+                limbs[7 - i] = digit as u64;
+            }
+            buffer.extend_from_slice(&limbs);
+        }
+        (ParamType::String, Value::String(s)) => {
+            buffer.push(s.len() as u64);
+            buffer.extend(s.bytes().map(u64::from));
+        }
+        (ParamType::Fields, Value::Fields(fields)) => {
+            buffer.push(fields.len() as u64);
+            buffer.extend_from_slice(fields);
+        }
+        (ParamType::Array(elem_ty, len), Value::Array(elems)) => {
+            match len {
+                Some(len) if *len != elems.len() => {
+                    return Err(format!(
+                        "expected {} elements for {}, {} provided",
+                        len,
+                        ty,
+                        elems.len()
+                    ))
+                }
+                Some(_) => (),
+                None => buffer.push(elems.len() as u64),
+            }
+            for elem in elems {
+                encode_value(elem_ty, elem, buffer)?;
+            }
+        }
+        (ParamType::Tuple(fields), Value::Tuple(values)) if fields.len() == values.len() => {
+            for ((_, ty), value) in fields.iter().zip(values) {
+                encode_value(ty, value, buffer)?;
+            }
+        }
+        _ => return Err(format!("expected {} but got {:?}", ty, value)),
+    }
+
+    Ok(())
+}
+
+/// Decode values of `types` from the start of `data`. Returns the values and
+/// the number of fields read.
+pub fn decode(types: &[ParamType], data: &[u64]) -> Result<(Vec<Value>, usize), String> {
+    let mut offset = 0;
+    let values = types
+        .iter()
+        .map(|ty| decode_value(ty, data, &mut offset))
+        .collect::<Result<_, String>>()?;
+
+    Ok((values, offset))
+}
+
+/// Decode values of `types` which must use all of `data`
+fn decode_all(types: &[ParamType], data: &[u64]) -> Result<Vec<Value>, String> {
+    let (values, read) = decode(types, data)?;
+
+    if read != data.len() {
+        return Err(format!(
+            "{} unexpected fields after data",
+            data.len() - read
+        ));
+    }
+
+    Ok(values)
+}
+
+fn read<'a>(data: &'a [u64], offset: &mut usize, len: usize) -> Result<&'a [u64], String> {
+    let Some(fields) = data.get(*offset..*offset + len) else {
+        return Err(format!(
+            "data too short: reading {} fields at offset {} of {}",
+            len,
+            offset,
+            data.len()
+        ));
+    };
+    *offset += len;
+
+    Ok(fields)
+}
+
+fn decode_value(ty: &ParamType, data: &[u64], offset: &mut usize) -> Result<Value, String> {
+    Ok(match ty {
+        ParamType::U32 => {
+            let n = read(data, offset, 1)?[0];
+            Value::U32(
+                n.try_into()
+                    .map_err(|_| format!("{} does not fit in u32", n))?,
+            )
+        }
+        ParamType::Field => Value::Field(read(data, offset, 1)?[0]),
+        ParamType::Bool => match read(data, offset, 1)?[0] {
+            0 => Value::Bool(false),
+            1 => Value::Bool(true),
+            n => return Err(format!("{} is not a bool", n)),
+        },
+        ParamType::Address => Value::Address(read(data, offset, 4)?.try_into().unwrap()),
+        ParamType::Hash => Value::Hash(read(data, offset, 4)?.try_into().unwrap()),
+        ParamType::U256 => {
+            let mut n = BigInt::zero();
+            for limb in read(data, offset, 8)? {
+                if *limb > u32::MAX as u64 {
+                    return Err(format!("u256 limb {} does not fit in 32 bits", limb));
+                }
+                n = (n << 32) + *limb;
+            }
+            Value::U256(n)
+        }
+        ParamType::String => {
+            let len = read(data, offset, 1)?[0] as usize;
+            let bytes = read(data, offset, len)?
+                .iter()
+                .map(|b| u8::try_from(*b).map_err(|_| format!("{} is not a byte", b)))
+                .collect::<Result<Vec<u8>, String>>()?;
+            Value::String(String::from_utf8(bytes).map_err(|err| err.to_string())?)
+        }
+        ParamType::Fields => {
+            let len = read(data, offset, 1)?[0] as usize;
+            Value::Fields(read(data, offset, len)?.to_vec())
+        }
+        ParamType::Array(elem_ty, len) => {
+            let len = match len {
+                Some(len) => *len,
+                None => read(data, offset, 1)?[0] as usize,
+            };
+            // every element takes at least one field, so a corrupt length
+            // fails here rather than allocating
+            if len > data.len() - *offset {
+                return Err(format!("array length {} exceeds data", len));
+            }
+            Value::Array(
+                (0..len)
+                    .map(|_| decode_value(elem_ty, data, offset))
+                    .collect::<Result<_, String>>()?,
+            )
+        }
+        ParamType::Tuple(fields) => Value::Tuple(
+            fields
+                .iter()
+                .map(|(_, ty)| decode_value(ty, data, offset))
+                .collect::<Result<_, String>>()?,
+        ),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::abi::ola_abi::gen_abi;
+    use crate::sema::ast::Namespace;
+    use crate::{parse_and_resolve, FileResolver};
+    use serde_json::json;
+    use std::ffi::OsStr;
+
+    static SRC: &str = r#"
+    contract Shop {
+        struct Item {
+            u32 id;
+            string name;
+            address seller;
+            u256 price;
+            u32[2][] sizes;
+        }
+
+        event Sold(address indexed buyer, string indexed name, u256 price);
+
+        fn buy(Item item, u32[][2] lots) -> (Item, u256) {
+            emit Sold(item.seller, item.name, item.price);
+            return (item, item.price);
+        }
+    }"#;
+
+    fn contract() -> (Contract, Namespace) {
+        let mut cache = FileResolver::new();
+        cache.set_file_contents("test.ola", SRC.to_string());
+        let ns = parse_and_resolve(OsStr::new("test.ola"), &mut cache);
+        assert!(!ns.diagnostics.any_errors());
+
+        let json = serde_json::to_string(&gen_abi(0, &ns)).unwrap();
+
+        (Contract::parse(&json).unwrap(), ns)
+    }
+
+    fn item() -> Value {
+        Value::Tuple(vec![
+            Value::U32(7),
+            Value::String("ab".to_string()),
+            Value::Address([0, 0, 0, 0x1234]),
+            Value::U256((BigInt::from(1) << 64) + 3),
+            Value::Array(vec![
+                Value::Array(vec![Value::U32(1), Value::U32(2)]),
+                Value::Array(vec![Value::U32(3), Value::U32(4)]),
+            ]),
+        ])
+    }
+
+    /// The fields of `item()` as irgen lays them out
+    fn item_fields() -> Vec<u64> {
+        let mut fields = vec![7, 2, 97, 98];
+        fields.extend([0, 0, 0, 0x1234]);
+        fields.extend([0, 0, 0, 0, 0, 1, 0, 3]);
+        fields.extend([2, 1, 2, 3, 4]);
+        fields
+    }
+
+    #[test]
+    fn calldata() {
+        let (contract, ns) = contract();
+        let buy = contract.function("buy").unwrap();
+
+        let func = ns.functions.iter().find(|f| f.name == "buy").unwrap();
+        assert_eq!(buy.signature(), func.signature);
+        assert_eq!(
+            buy.signature(),
+            "buy((u32,string,address,u256,u32[2][]),u32[][2])"
+        );
+        assert_eq!(buy.selector().to_be_bytes().to_vec(), func.selector());
+
+        let lots = Value::Array(vec![
+            Value::Array(vec![Value::U32(5)]),
+            Value::Array(vec![Value::U32(6), Value::U32(7)]),
+        ]);
+        let calldata = buy.encode_input(&[item(), lots.clone()]).unwrap();
+
+        let mut expected = item_fields();
+        expected.extend([1, 5, 2, 6, 7]);
+        expected.extend([26, buy.selector() as u64]);
+        assert_eq!(calldata, expected);
+
+        assert_eq!(buy.decode_input(&calldata).unwrap(), [item(), lots]);
+
+        let args = json!([
+            {
+                "id": 7,
+                "name": "ab",
+                "seller": "0x1234",
+                "price": "0x10000000000000003",
+                "sizes": [[1, 2], [3, 4]]
+            },
+            [[5], [6, 7]]
+        ]);
+        assert_eq!(buy.encode_input_json(&args).unwrap(), calldata);
+
+        let args = json!([
+            [7, "ab", "4660", "18446744073709551619", [[1, 2], [3, 4]]],
+            [[5], [6, 7]]
+        ]);
+        assert_eq!(buy.encode_input_json(&args).unwrap(), calldata);
+
+        let args = json!([[7, "ab", "0x1234", 3, [[1, 2, 3]]], [[5], [6, 7]]]);
+        assert_eq!(
+            buy.encode_input_json(&args).unwrap_err(),
+            "expected u32[2] but got [1,2,3]"
+        );
+
+        let mut wrong_selector = calldata.clone();
+        *wrong_selector.last_mut().unwrap() += 1;
+        assert!(buy.decode_input(&wrong_selector).is_err());
+    }
+
+    #[test]
+    fn return_data() {
+        let (contract, _) = contract();
+        let buy = contract.function("buy").unwrap();
+
+        let mut data = item_fields();
+        data.extend([0, 0, 0, 0, 0, 1, 0, 3]);
+        let values = vec![item(), Value::U256((BigInt::from(1) << 64) + 3)];
+
+        assert_eq!(buy.decode_output(&data).unwrap(), values);
+
+        // as written to the tape, followed by its length
+        data.push(29);
+        assert_eq!(buy.decode_output(&data).unwrap(), values);
+
+        data.push(0);
+        assert!(buy.decode_output(&data).is_err());
+        assert!(buy.decode_output(&data[..10]).is_err());
+    }
+
+    /// A contract whose functions echo their arguments, so that what irgen
+    /// decodes from the calldata comes back through its encoding of returns
+    static ECHO_SRC: &str = r#"
+    contract Echo {
+        struct Item {
+            u32 id;
+            string name;
+            address seller;
+            u256 price;
+            u32[2][] sizes;
+        }
+
+        fn relist(Item item, u32[][2] lots) -> (Item) {
+            item.id = item.id + lots[1].length;
+            return item;
+        }
+
+        fn restock(u32[][2] lots) -> (u32[][2]) {
+            return lots;
+        }
+    }"#;
+
+    thread_local! {
+        static TAPE: std::cell::RefCell<Vec<u64>> = std::cell::RefCell::new(Vec::new());
+    }
+
+    extern "C" fn heap_malloc(size: u64) -> *mut u64 {
+        Box::leak(vec![0u64; size as usize].into_boxed_slice()).as_mut_ptr()
+    }
+
+    extern "C" fn vector_new(len: u64) -> *mut u64 {
+        let vector = heap_malloc(len + 1);
+        unsafe { *vector = len };
+        vector
+    }
+
+    extern "C" fn builtin_range_check(n: u64) {
+        assert!(n <= u32::MAX as u64, "range check of {} failed", n);
+    }
+
+    extern "C" fn set_tape_data(data: *const u64, len: u64) {
+        let data = unsafe { std::slice::from_raw_parts(data, len as usize) };
+        TAPE.with(|tape| tape.borrow_mut().extend_from_slice(data));
+    }
+
+    extern "C" fn unexpected() {
+        panic!("unexpected call to a host function");
+    }
+
+    /// Run the function dispatch irgen generates for `ECHO_SRC` on `calldata`
+    /// and return what it writes to the tape. The heap of the VM is addressed
+    /// by field, so it is replaced by one on the host where a field is the 8
+    /// bytes every `getelementptr` of the IR steps over.
+    fn run_dispatch(calldata: &[u64]) -> Vec<u64> {
+        use crate::codegen::core::pass::OptLevel;
+        use crate::irgen::binary::Binary;
+        use crate::sema::contracts::layout;
+        use inkwell::context::Context;
+        use inkwell::targets::{InitializationConfig, Target};
+        use inkwell::OptimizationLevel;
+
+        let mut cache = FileResolver::new();
+        cache.set_file_contents("echo.ola", ECHO_SRC.to_string());
+        let mut ns = parse_and_resolve(OsStr::new("echo.ola"), &mut cache);
+        assert!(!ns.diagnostics.any_errors());
+        layout(0, &mut ns);
+
+        let context = Context::create();
+        let binary = Binary::gen_ir(&context, 0, &ns, "echo", OptLevel::None);
+        Target::initialize_native(&InitializationConfig::default()).unwrap();
+        let host_functions: [(&str, usize); 4] = [
+            ("heap_malloc", heap_malloc as usize),
+            ("vector_new", vector_new as usize),
+            ("builtin_range_check", builtin_range_check as usize),
+            ("set_tape_data", set_tape_data as usize),
+        ];
+        for (name, _) in host_functions {
+            let function = binary.module.get_function(name).unwrap();
+            for block in function.get_basic_blocks() {
+                unsafe { block.delete() }.unwrap();
+            }
+        }
+        let engine = binary
+            .module
+            .create_jit_execution_engine(OptimizationLevel::None)
+            .unwrap();
+        // Any other host function the dispatcher reaches is a bug in the test
+        let mut next = binary.module.get_first_function();
+        while let Some(function) = next {
+            next = function.get_next_function();
+            if function.count_basic_blocks() == 0 {
+                engine.add_global_mapping(&function, unexpected as usize);
+            }
+        }
+        for (name, address) in host_functions {
+            let function = binary.module.get_function(name).unwrap();
+            engine.add_global_mapping(&function, address);
+        }
+
+        let [args @ .., len, selector] = calldata else {
+            panic!("calldata without a selector");
+        };
+        TAPE.with(|tape| tape.borrow_mut().clear());
+        unsafe {
+            let dispatch = engine
+                .get_function::<unsafe extern "C" fn(u64, u64, *const u64)>("function_dispatch")
+                .unwrap();
+            dispatch.call(*selector, *len, args.as_ptr());
+        }
+        TAPE.with(|tape| tape.take())
+    }
+
+    #[test]
+    fn irgen_layout() {
+        let mut cache = FileResolver::new();
+        cache.set_file_contents("echo.ola", ECHO_SRC.to_string());
+        let ns = parse_and_resolve(OsStr::new("echo.ola"), &mut cache);
+        let json = serde_json::to_string(&gen_abi(0, &ns)).unwrap();
+        let contract = Contract::parse(&json).unwrap();
+
+        let lots = Value::Array(vec![
+            Value::Array(vec![Value::U32(5)]),
+            Value::Array(vec![Value::U32(6), Value::U32(7)]),
+        ]);
+
+        // The id is read after the struct and the first array of lots
+        let relist = contract.function("relist").unwrap();
+        let calldata = relist.encode_input(&[item(), lots.clone()]).unwrap();
+        let Value::Tuple(mut relisted) = item() else {
+            unreachable!();
+        };
+        relisted[0] = Value::U32(9);
+        assert_eq!(
+            relist.decode_output(&run_dispatch(&calldata)).unwrap(),
+            [Value::Tuple(relisted)]
+        );
+
+        let echo = contract.function("restock").unwrap();
+        let calldata = echo.encode_input(&[lots.clone()]).unwrap();
+        assert_eq!(
+            echo.decode_output(&run_dispatch(&calldata)).unwrap(),
+            [lots]
+        );
+    }
+
+    #[test]
+    fn event_log() {
+        let (contract, ns) = contract();
+        let sold = contract.event("Sold").unwrap();
+
+        assert_eq!(sold.signature(), "Sold(address,string,u256)");
+        assert_eq!(sold.topic(), limbs(&ns.events[0].selector()));
+
+        let name_hash = [1, 2, 3, 4];
+        let topics = [sold.topic(), [0, 0, 0, 0x1234], name_hash];
+        let data = [0, 0, 0, 0, 0, 0, 0, 9];
+
+        assert_eq!(contract.event_for_topics(&topics), Some(sold));
+        assert_eq!(
+            sold.decode_log(&topics, &data).unwrap(),
+            [
+                Value::Address([0, 0, 0, 0x1234]),
+                Value::Hash(name_hash),
+                Value::U256(BigInt::from(9)),
+            ]
+        );
+
+        assert!(sold.decode_log(&topics[1..], &data).is_err());
+        assert!(sold.decode_log(&topics[..2], &data).is_err());
+
+        let (event, values) = contract.decode_log(&topics, &data).unwrap();
+        assert_eq!(event, sold);
+        assert_eq!(values[0], Value::Address([0, 0, 0, 0x1234]));
+        assert_eq!(
+            contract.decode_log(&topics[1..], &data).unwrap_err(),
+            "log does not match any event"
+        );
+    }
+
+    #[test]
+    fn event_abi() {
+        let mut cache = FileResolver::new();
+        cache.set_file_contents(
+            "test.ola",
+            r#"
+            contract Counter {
+                event Counted(u32 indexed n, hash indexed tag, string indexed note, u32 total);
+
+                fn add(u32 n, hash tag) {
+                    record(n, tag);
+                    emit Counted(n + 1, tag, "add", n + 1);
+                }
+
+                fn record(u32 n, hash tag) {
+                    emit Counted(n, tag, "record", n);
+                }
+            }"#
+            .to_string(),
+        );
+        let ns = parse_and_resolve(OsStr::new("test.ola"), &mut cache);
+        assert!(!ns.diagnostics.any_errors());
+
+        let abi = gen_abi(0, &ns);
+        let events: Vec<_> = abi.iter().filter(|entry| entry.ty == "event").collect();
+
+        // emitted by both functions but listed once
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            serde_json::to_value(events[0]).unwrap()["inputs"],
+            json!([
+                {"name": "n", "type": "u32", "internalType": "u32", "indexed": true, "location": "topic"},
+                {"name": "tag", "type": "hash", "internalType": "hash", "indexed": true, "location": "topic"},
+                {"name": "note", "type": "string", "internalType": "string", "indexed": true, "location": "hashedTopic"},
+                {"name": "total", "type": "u32", "internalType": "u32", "location": "data"},
+            ])
+        );
+        assert_eq!(
+            events[0].topic,
+            Some(format!("0x{:064x}", ns.events[0].selector()))
+        );
+    }
+
+    #[test]
+    fn types() {
+        let param = ABIParam {
+            name: String::new(),
+            ty: "tuple[3][]".to_string(),
+            internal_type: String::new(),
+            components: vec![ABIParam {
+                name: "h".to_string(),
+                ty: "hash".to_string(),
+                internal_type: String::new(),
+                components: Vec::new(),
+                indexed: false,
+                location: None,
+            }],
+            indexed: false,
+            location: None,
+        };
+        let ty = ParamType::parse(&param).unwrap();
+
+        assert_eq!(
+            ty,
+            ParamType::Array(
+                Box::new(ParamType::Array(
+                    Box::new(ParamType::Tuple(vec![("h".to_string(), ParamType::Hash)])),
+                    Some(3)
+                )),
+                None
+            )
+        );
+        assert_eq!(ty.to_string(), "(hash)[3][]");
+
+        let value = Value::Array(vec![Value::Array(vec![
+            Value::Tuple(vec![Value::Hash([
+                1, 2, 3, 4
+            ])]);
+            3
+        ])]);
+        let fields = encode(&[ty.clone()], &[value.clone()]).unwrap();
+        assert_eq!(fields, [1, 1, 2, 3, 4, 1, 2, 3, 4, 1, 2, 3, 4]);
+        assert_eq!(decode(&[ty], &fields).unwrap(), (vec![value], 13));
+
+        let strings = ParamType::Array(Box::new(ParamType::String), None);
+        let value = Value::Array(vec![
+            Value::String(String::new()),
+            Value::String("ola".to_string()),
+        ]);
+        let fields = encode(
+            &[strings.clone(), ParamType::Bool],
+            &[value.clone(), Value::Bool(true)],
+        )
+        .unwrap();
+        assert_eq!(fields, [2, 0, 3, 111, 108, 97, 1]);
+        assert_eq!(
+            decode(&[strings, ParamType::Bool], &fields).unwrap().0,
+            [value, Value::Bool(true)]
+        );
+
+        assert_eq!(
+            decode(&[ParamType::U256], &[0, 0, 0, 0, 0, 0, 0, 1 << 32]).unwrap_err(),
+            "u256 limb 4294967296 does not fit in 32 bits"
+        );
+        assert!(encode(&[ParamType::U32], &[Value::Bool(true)]).is_err());
+    }
+}
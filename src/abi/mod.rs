@@ -2,12 +2,59 @@
 
 use crate::sema::ast::Namespace;
 
-use self::ola_abi::gen_abi;
+use crate::codegen::isa::ola::asm::AsmProgram;
 
+use self::{
+    artifact::gen_artifact,
+    bindgen::gen_rust_bindings,
+    ola_abi::{gen_abi, ABI},
+    storage_layout::gen_storage_layout,
+};
+
+pub mod artifact;
+pub mod bindgen;
+pub mod diff;
+pub mod encoding;
 pub mod ola_abi;
+pub mod storage_layout;
 
 pub fn generate_abi(contract_no: usize, ns: &Namespace) -> (String, &'static str) {
     let abi = gen_abi(contract_no, ns);
 
     (serde_json::to_string_pretty(&abi).unwrap(), "json")
 }
+
+/// Generate the storage layout of a contract, which `olac check-upgrade`
+/// compares against the layout of an upgraded contract.
+pub fn generate_storage_layout(contract_no: usize, ns: &Namespace) -> (String, &'static str) {
+    let layout = gen_storage_layout(contract_no, ns);
+
+    (serde_json::to_string_pretty(&layout).unwrap(), "json")
+}
+
+/// Generate the deployment artifact of a contract: its ABI, selectors and
+/// program along with the compiler version and a hash of its source.
+pub fn generate_artifact(
+    contract_no: usize,
+    ns: &Namespace,
+    source: &str,
+    program: AsmProgram,
+) -> (String, &'static str) {
+    let artifact = gen_artifact(contract_no, ns, source, program);
+
+    (serde_json::to_string_pretty(&artifact).unwrap(), "json")
+}
+
+/// Generate bindings in `lang` for the contract `name` from its ABI JSON.
+pub fn generate_bindings(
+    name: &str,
+    abi: &str,
+    lang: &str,
+) -> Result<(String, &'static str), String> {
+    let abi: Vec<ABI> = serde_json::from_str(abi).map_err(|err| err.to_string())?;
+
+    match lang {
+        "rust" => Ok((gen_rust_bindings(name, &abi)?, "rs")),
+        _ => Err(format!("bindings for '{}' are not supported", lang)),
+    }
+}
@@ -1,25 +1,49 @@
 // SPDX-License-Identifier: Apache-2.0
 
 // ethereum style ABIs
-use crate::sema::ast::{Namespace, Parameter, Type};
-use serde::Serialize;
+use crate::sema::ast::{Mutability, Namespace, Parameter, Type};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[allow(clippy::upper_case_acronyms)]
 pub struct ABIParam {
+    #[serde(default)]
     pub name: String,
     #[serde(rename = "type")]
     pub ty: String,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    /// The type as written in Ola, which names structs and enums
+    #[serde(
+        rename = "internalType",
+        default,
+        skip_serializing_if = "String::is_empty"
+    )]
+    pub internal_type: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub components: Vec<ABIParam>,
-    #[serde(skip_serializing_if = "is_false")]
+    #[serde(default, skip_serializing_if = "is_false")]
     pub indexed: bool,
+    /// Where the field of an event is stored in its log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<EventFieldLocation>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum EventFieldLocation {
+    /// An indexed field stored in a topic as it is
+    Topic,
+    /// An indexed field stored in a topic as the poseidon hash of its
+    /// encoding, since it does not fit in a topic
+    HashedTopic,
+    /// A field encoded in the data of the log
+    Data,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[allow(clippy::upper_case_acronyms)]
 pub struct ABI {
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub name: String,
     #[serde(rename = "type")]
     pub ty: String,
@@ -29,6 +53,16 @@ pub struct ABI {
     pub outputs: Option<Vec<ABIParam>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub anonymous: Option<bool>,
+    /// The first topic of the log of an event which is not anonymous
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    /// The selector of a function which declares it with `#[selector]`,
+    /// instead of the one calculated from its signature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+    /// Whether calling the function needs a transaction
+    #[serde(rename = "stateMutability", skip_serializing_if = "Option::is_none")]
+    pub state_mutability: Option<String>,
 }
 
 #[allow(clippy::trivially_copy_pass_by_ref)]
@@ -62,16 +96,40 @@ pub fn gen_abi(contract_no: usize, ns: &Namespace) -> Vec<ABI> {
         ABIParam {
             name: param.name_as_str().to_owned(),
             ty: param.ty.to_signature_string(true, ns),
+            internal_type: param.ty.to_string(ns),
             components,
             indexed: param.indexed,
+            location: None,
         }
     }
 
+    fn event_field_to_abi(field: &Parameter, ns: &Namespace) -> ABIParam {
+        let location = if !field.indexed {
+            EventFieldLocation::Data
+        } else if field.ty.is_encoded_to_hash() {
+            EventFieldLocation::HashedTopic
+        } else {
+            EventFieldLocation::Topic
+        };
+
+        ABIParam {
+            location: Some(location),
+            ..parameter_to_abi(field, ns)
+        }
+    }
+
+    // An event may be declared more than once, e.g. in a contract and in an
+    // interface it implements, but it is listed once
+    let mut signatures = HashSet::new();
+
     ns.contracts[contract_no]
         .all_functions
         .keys()
         .filter_map(|function_no| {
             let func = &ns.functions[*function_no];
+            if func.is_internal(ns) {
+                return None;
+            }
             return Some(func);
         })
         .map(|func| ABI {
@@ -90,27 +148,41 @@ pub fn gen_abi(contract_no: usize, ns: &Namespace) -> Vec<ABI> {
                     .collect(),
             ),
             anonymous: None,
+            topic: None,
+            selector: func
+                .selector
+                .as_ref()
+                .map(|selector| format!("0x{}", hex::encode(selector))),
+            state_mutability: Some(match func.mutability {
+                Mutability::Mutable => "nonpayable".to_string(),
+                _ => func.mutability.to_string(),
+            }),
         })
         .chain(
             ns.contracts[contract_no]
                 .emits_events
                 .iter()
-                .map(|event_no| {
-                    let event = &ns.events[*event_no];
-
-                    ABI {
-                        name: event.id.name.to_owned(),
-                        inputs: Some(
-                            event
-                                .fields
-                                .iter()
-                                .map(|p| parameter_to_abi(p, ns))
-                                .collect(),
-                        ),
-                        outputs: None,
-                        ty: "event".to_owned(),
-                        anonymous: Some(event.anonymous),
-                    }
+                .map(|event_no| &ns.events[*event_no])
+                .filter(|event| signatures.insert(&event.signature))
+                .map(|event| ABI {
+                    name: event.id.name.to_owned(),
+                    inputs: Some(
+                        event
+                            .fields
+                            .iter()
+                            .map(|p| event_field_to_abi(p, ns))
+                            .collect(),
+                    ),
+                    outputs: None,
+                    ty: "event".to_owned(),
+                    anonymous: Some(event.anonymous),
+                    topic: if event.anonymous {
+                        None
+                    } else {
+                        Some(format!("0x{:064x}", event.selector()))
+                    },
+                    selector: None,
+                    state_mutability: None,
                 }),
         )
         .collect()
@@ -0,0 +1,321 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::sema::ast::{ArrayLength, Mapping, Namespace, Type};
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+/// The storage layout of a contract, as assigned by `layout()`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StorageLayout {
+    pub contract: String,
+    pub variables: Vec<StorageVariable>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StorageVariable {
+    pub name: String,
+    /// The first slot of the variable
+    pub slot: u64,
+    /// The number of slots the variable occupies
+    pub slots: u64,
+    /// The type as it is stored. Structs are written as the tuple of their
+    /// field types, since renaming a struct does not change its storage.
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+pub fn gen_storage_layout(contract_no: usize, ns: &Namespace) -> StorageLayout {
+    let contract = &ns.contracts[contract_no];
+
+    StorageLayout {
+        contract: contract.name.clone(),
+        variables: contract
+            .layout
+            .iter()
+            .map(|layout| StorageVariable {
+                name: ns.contracts[layout.contract_no].variables[layout.var_no]
+                    .name
+                    .clone(),
+                slot: layout.slot.to_u64().unwrap(),
+                slots: layout.ty.storage_slots(ns).to_u64().unwrap_or(u64::MAX),
+                ty: storage_type(&layout.ty, ns),
+            })
+            .collect(),
+    }
+}
+
+fn storage_type(ty: &Type, ns: &Namespace) -> String {
+    match ty {
+        Type::Struct(no) => format!(
+            "({})",
+            ns.structs[*no]
+                .fields
+                .iter()
+                .map(|field| storage_type(&field.ty, ns))
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        Type::Array(elem, dims) => format!(
+            "{}{}",
+            storage_type(elem, ns),
+            dims.iter()
+                .map(|len| match len {
+                    ArrayLength::Fixed(len) => format!("[{}]", len),
+                    _ => "[]".to_string(),
+                })
+                .collect::<String>()
+        ),
+        // the names of the key and value are not part of the layout
+        Type::Mapping(Mapping { key, value, .. }) => format!(
+            "mapping({} => {})",
+            storage_type(key, ns),
+            storage_type(value, ns)
+        ),
+        Type::UserType(no) => storage_type(&ns.user_types[*no].ty, ns),
+        Type::StorageRef(ty) | Type::Ref(ty) => storage_type(ty, ns),
+        _ => ty.to_string(ns),
+    }
+}
+
+/// Split a mapping type into its key and value types
+fn mapping_types(ty: &str) -> Option<(&str, &str)> {
+    let inner = ty.strip_prefix("mapping(")?.strip_suffix(')')?;
+    let mut depth = 0;
+
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '=' if depth == 0 && inner[i..].starts_with("=>") => {
+                return Some((inner[..i].trim_end(), inner[i + 2..].trim_start()))
+            }
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// The element type of an array type
+fn array_element(ty: &str) -> Option<&str> {
+    ty.strip_suffix(']').map(|ty| &ty[..ty.rfind('[').unwrap()])
+}
+
+/// Check that the storage of a contract with layout `old` can be used by a
+/// contract with layout `new`. Existing variables must keep their slot and
+/// type, and new variables may only be appended after them. A variable which
+/// is renamed but keeps its slot and type is compatible. Returns the
+/// incompatible changes.
+pub fn check_upgrade(old: &StorageLayout, new: &StorageLayout) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    let find = |layout: &StorageLayout, name: &str| {
+        layout.variables.iter().position(|var| var.name == name)
+    };
+
+    let mut matched = vec![false; new.variables.len()];
+
+    for old_var in &old.variables {
+        let new_no = find(new, &old_var.name).or_else(|| {
+            // renamed
+            new.variables.iter().position(|var| {
+                var.slot == old_var.slot && var.ty == old_var.ty && find(old, &var.name).is_none()
+            })
+        });
+
+        let Some(new_no) = new_no else {
+            errors.push(format!(
+                "variable '{}' at slot {} has been removed",
+                old_var.name, old_var.slot
+            ));
+            continue;
+        };
+
+        matched[new_no] = true;
+        let new_var = &new.variables[new_no];
+
+        if new_var.slot != old_var.slot {
+            errors.push(format!(
+                "variable '{}' has moved from slot {} to slot {}",
+                old_var.name, old_var.slot, new_var.slot
+            ));
+        }
+
+        if new_var.ty != old_var.ty {
+            match (mapping_types(&old_var.ty), mapping_types(&new_var.ty)) {
+                (Some((old_key, old_value)), Some((new_key, new_value))) if old_key == new_key => {
+                    errors.push(format!(
+                        "value type of mapping '{}' has changed from '{}' to '{}'",
+                        old_var.name, old_value, new_value
+                    ));
+                }
+                _ if new_var.slots < old_var.slots
+                    && array_element(&old_var.ty).is_some()
+                    && array_element(&old_var.ty) == array_element(&new_var.ty) =>
+                {
+                    errors.push(format!(
+                        "variable '{}' has shrunk from '{}' to '{}'",
+                        old_var.name, old_var.ty, new_var.ty
+                    ));
+                }
+                _ => {
+                    errors.push(format!(
+                        "variable '{}' has changed type from '{}' to '{}'",
+                        old_var.name, old_var.ty, new_var.ty
+                    ));
+                }
+            }
+        }
+    }
+
+    let old_size = old
+        .variables
+        .iter()
+        .map(|var| var.slot.saturating_add(var.slots))
+        .max()
+        .unwrap_or(0);
+
+    for (new_var, _) in new
+        .variables
+        .iter()
+        .zip(matched)
+        .filter(|(_, matched)| !matched)
+    {
+        if new_var.slot < old_size {
+            errors.push(format!(
+                "new variable '{}' at slot {} overlaps existing storage, which ends at slot {}",
+                new_var.name, new_var.slot, old_size
+            ));
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sema::contracts::layout;
+    use crate::{parse_and_resolve, FileResolver};
+    use std::ffi::OsStr;
+
+    fn storage_layout(src: &str) -> StorageLayout {
+        let mut cache = FileResolver::new();
+        cache.set_file_contents("test.ola", src.to_string());
+        let mut ns = parse_and_resolve(OsStr::new("test.ola"), &mut cache);
+        assert!(!ns.diagnostics.any_errors());
+
+        layout(0, &mut ns);
+
+        gen_storage_layout(0, &ns)
+    }
+
+    static OLD: &str = r#"
+    contract Vault {
+        struct Account {
+            u32 balance;
+            address owner;
+        }
+
+        u32 total;
+        u32 const FEE = 3;
+        Account admin;
+        u32[4] history;
+        mapping(address => u32) balances;
+    }"#;
+
+    #[test]
+    fn layout_json() {
+        let layout = storage_layout(OLD);
+
+        assert_eq!(
+            serde_json::to_value(&layout).unwrap(),
+            serde_json::json!({
+                "contract": "Vault",
+                "variables": [
+                    {"name": "total", "slot": 0, "slots": 1, "type": "u32"},
+                    {"name": "admin", "slot": 1, "slots": 2, "type": "(u32,address)"},
+                    {"name": "history", "slot": 3, "slots": 4, "type": "u32[4]"},
+                    {"name": "balances", "slot": 7, "slots": 1, "type": "mapping(address => u32)"},
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn compatible() {
+        let old = storage_layout(OLD);
+
+        // appending variables, renaming variables and structs, and adding
+        // constants do not change the storage of the old variables
+        let new = storage_layout(
+            r#"
+            contract Vault {
+                struct Owner {
+                    u32 funds;
+                    address owner;
+                }
+
+                u32 const MAX = 100;
+                u32 supply;
+                Owner admin;
+                u32[4] history;
+                mapping(address owner => u32 amount) balances;
+                string name;
+            }"#,
+        );
+
+        assert_eq!(check_upgrade(&old, &new), Vec::<String>::new());
+    }
+
+    #[test]
+    fn incompatible() {
+        let old = storage_layout(OLD);
+
+        let new = storage_layout(
+            r#"
+            contract Vault {
+                struct Account {
+                    u32 balance;
+                    address owner;
+                }
+
+                u32 paused;
+                u32 total;
+                Account admin;
+                u32[2] history;
+                mapping(address => u256) balances;
+            }"#,
+        );
+
+        assert_eq!(
+            check_upgrade(&old, &new),
+            [
+                "variable 'total' has moved from slot 0 to slot 1",
+                "variable 'admin' has moved from slot 1 to slot 2",
+                "variable 'history' has moved from slot 3 to slot 4",
+                "variable 'history' has shrunk from 'u32[4]' to 'u32[2]'",
+                "variable 'balances' has moved from slot 7 to slot 6",
+                "value type of mapping 'balances' has changed from 'u32' to 'u256'",
+                "new variable 'paused' at slot 0 overlaps existing storage, which ends at slot 8",
+            ]
+        );
+
+        let new = storage_layout(
+            r#"
+            contract Vault {
+                u32 total;
+                address admin;
+            }"#,
+        );
+
+        assert_eq!(
+            check_upgrade(&old, &new),
+            [
+                "variable 'admin' has changed type from '(u32,address)' to 'address'",
+                "variable 'history' at slot 3 has been removed",
+                "variable 'balances' at slot 7 has been removed",
+            ]
+        );
+    }
+}
@@ -2,15 +2,21 @@
 
 use clap::{builder::ValueParser, Arg, ArgMatches, Command};
 
-use num_bigint::BigInt;
-use num_traits::Zero;
 use ola_lang::abi;
-use ola_lang::codegen::isa::ola::Ola;
-use ola_lang::codegen::lower::compile_module;
+use ola_lang::abi::storage_layout::{
+    check_upgrade as check_upgrade_layout, gen_storage_layout, StorageLayout,
+};
+use ola_lang::abi::{diff, encoding};
+use ola_lang::codegen::core::ir::module::Module;
+use ola_lang::codegen::core::pass::{optimize, OptLevel};
+use ola_lang::codegen::isa::ola::{asm, Ola};
+use ola_lang::codegen::lower::{compile_module, LoweringError};
 use ola_lang::file_resolver::FileResolver;
 use ola_lang::irgen::binary;
-use ola_lang::sema::ast::Namespace;
-use ola_lang::{codegen::core::ir::module::Module, sema::ast::Layout};
+use ola_lang::manifest::{Project, MANIFEST_NAME};
+use ola_lang::sema::ast::{Diagnostic, Namespace};
+use ola_lang::sema::contracts::layout;
+use ola_parser::program::Loc;
 use std::env;
 use std::{
     ffi::{OsStr, OsString},
@@ -41,7 +47,14 @@ fn main() {
                             .help("Show compile intermediate status results")
                             .long("gen")
                             .num_args(1)
-                            .value_parser(["ast", "llvm-ir", "abi", "asm"]),
+                            .value_parser([
+                                "ast",
+                                "llvm-ir",
+                                "abi",
+                                "asm",
+                                "artifact",
+                                "storage-layout",
+                            ]),
                     )
                     .arg(
                         Arg::new("OUTPUT")
@@ -50,18 +63,47 @@ fn main() {
                             .long("output")
                             .num_args(1)
                             .value_parser(ValueParser::os_string()),
-                    ),
+                    )
+                    .arg(opt_level_arg()),
             )
             .subcommand(
                 Command::new("compile-ir")
                     .about("Compile ir source files")
                     .arg(
                         Arg::new("INPUT")
-                            .help("Ola input files")
+                            .help("LLVM IR input files (.ll or .bc)")
+                            .required(true)
+                            .value_parser(ValueParser::os_string())
+                            .num_args(1..),
+                    )
+                    .arg(
+                        Arg::new("OUTPUT")
+                            .help("output directory")
+                            .short('o')
+                            .long("output")
+                            .num_args(1)
+                            .value_parser(ValueParser::os_string()),
+                    )
+                    .arg(opt_level_arg()),
+            )
+            .subcommand(
+                Command::new("bindgen")
+                    .about("Generate contract bindings from ABI files")
+                    .arg(
+                        Arg::new("INPUT")
+                            .help("ABI files generated by compile --gen abi")
                             .required(true)
                             .value_parser(ValueParser::os_string())
                             .num_args(1..),
                     )
+                    .arg(
+                        Arg::new("LANG")
+                            .help("Language of the bindings")
+                            .long("lang")
+                            .num_args(1)
+                            .default_value("rust")
+                            .value_parser(["rust"]),
+                    )
                     .arg(
                         Arg::new("OUTPUT")
                             .help("output directory")
@@ -71,12 +113,67 @@ fn main() {
                             .value_parser(ValueParser::os_string()),
                     ),
             )
+            .subcommand(
+                Command::new("check-upgrade")
+                    .about(
+                        "Check that an upgraded contract keeps the storage layout of the old one",
+                    )
+                    .arg(
+                        Arg::new("OLD")
+                            .help("Ola source or storage layout JSON of the old contract")
+                            .required(true)
+                            .value_parser(ValueParser::os_string()),
+                    )
+                    .arg(
+                        Arg::new("NEW")
+                            .help("Ola source or storage layout JSON of the new contract")
+                            .required(true)
+                            .value_parser(ValueParser::os_string()),
+                    )
+                    .arg(
+                        Arg::new("CONTRACT")
+                            .help("Contract to check, if a source file declares more than one")
+                            .long("contract")
+                            .num_args(1),
+                    ),
+            )
+            .subcommand(
+                Command::new("abi-diff")
+                    .about("Report the changes to an ABI which break callers of the old one")
+                    .arg(
+                        Arg::new("OLD")
+                            .help("ABI of the old contract")
+                            .required(true)
+                            .value_parser(ValueParser::os_string()),
+                    )
+                    .arg(
+                        Arg::new("NEW")
+                            .help("ABI of the new contract")
+                            .required(true)
+                            .value_parser(ValueParser::os_string()),
+                    ),
+            )
+            .subcommand(
+                Command::new("build")
+                    .about("Compile the project described by an Ola.toml manifest")
+                    .arg(
+                        Arg::new("MANIFEST")
+                            .help("Path to Ola.toml or the directory containing it")
+                            .long("manifest-path")
+                            .num_args(1)
+                            .value_parser(ValueParser::os_string()),
+                    ),
+            )
     };
     let matches = app().get_matches();
 
     match matches.subcommand() {
         Some(("compile", matches)) => compile(matches),
         Some(("compile-ir", matches)) => compile_ir(matches),
+        Some(("bindgen", matches)) => bindgen(matches),
+        Some(("check-upgrade", matches)) => check_upgrade(matches),
+        Some(("abi-diff", matches)) => abi_diff(matches),
+        Some(("build", matches)) => build(matches),
         None | Some(_) => {
             app().print_help().unwrap();
             println!();
@@ -93,30 +190,63 @@ fn compile_ir(matches: &ArgMatches) {
 }
 
 fn process_ir_file(filename: &OsStr, resolver: &mut FileResolver, matches: &ArgMatches) {
-    match resolver.resolve_file(None, filename) {
-        Err(_) => (),
-        Ok(file) => {
-            let (source_code, _) = resolver.get_file_contents_and_number(&file.full_path);
-            // Parse the assembly and get a module
-            let module = Module::try_from(source_code.to_string().as_str())
-                .expect("failed to parse LLVM IR");
-            // Compile the module for Ola and get a machine module
-            let isa = Ola::default();
-            let code = compile_module(&isa, &module).expect("failed to compile");
-
-            let filename_lossy = filename.to_string_lossy().clone();
-            let filename_string = String::from(filename_lossy);
-            let filename_stem = Path::new(&filename_string).file_prefix().unwrap();
-            let stem = filename_stem.to_string_lossy().to_string() + "_asm";
-            let asm_path = output_file(matches, &stem, "json");
-            let mut asm_file = create_file(&asm_path);
-
-            if let Err(err) = asm_file.write_all(format!("{}", code.display_asm()).as_bytes()) {
-                eprintln!("{}: error: {}", asm_path.display(), err);
-                exit(1);
+    let path = Path::new(filename);
+    let module = if path.extension() == Some(OsStr::new("bc")) {
+        // Bitcode is binary, so it does not go through the file resolver
+        std::fs::read(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Module::try_from(bytes.as_slice()))
+    } else {
+        match resolver.resolve_file(None, filename) {
+            Err(_) => return,
+            Ok(file) => {
+                let (source_code, _) = resolver.get_file_contents_and_number(&file.full_path);
+                // Parse the assembly and get a module
+                Module::try_from(source_code.to_string().as_str())
+                    .map_err(|err| anyhow::anyhow!("{}", err))
             }
         }
+    };
+    let mut module = match module {
+        Ok(module) => module,
+        Err(err) => {
+            eprintln!("{}: error: {}", path.display(), err);
+            exit(1);
+        }
+    };
+    let opt_level = opt_level(matches);
+    let report = optimize(&mut module, opt_level);
+    if report.range_checks_removed > 0 {
+        // There is no source to point at, so the message names the file
+        let mut ns = Namespace::new();
+        ns.diagnostics.push(Diagnostic::debug(
+            Loc::CommandLine,
+            format!(
+                "{}: removed {} range checks which cannot fail",
+                path.display(),
+                report.range_checks_removed
+            ),
+        ));
+        ns.print_diagnostics(resolver, true);
     }
+    // Compile the module for Ola and get a machine module
+    let isa = Ola::new(opt_level);
+    let program = match compile_module(&isa, &module)
+        .and_then(|code| asm::emit(&code).map_err(|e| e.into()))
+    {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("{}: error: {}", path.display(), err);
+            exit(1);
+        }
+    };
+
+    let filename_lossy = filename.to_string_lossy().clone();
+    let filename_string = String::from(filename_lossy);
+    let filename_stem = Path::new(&filename_string).file_prefix().unwrap();
+    let stem = filename_stem.to_string_lossy().to_string() + "_asm";
+    let asm_path = output_file(&output_dir(matches), &stem, "json");
+    write_asm(&asm_path, &program);
 }
 
 fn compile(matches: &ArgMatches) {
@@ -124,22 +254,12 @@ fn compile(matches: &ArgMatches) {
 
     let mut resolver = imports_arg(matches);
 
-    let mut errors = false;
-
     for filename in matches.get_many::<OsString>("INPUT").unwrap() {
         let ns = process_file(filename, &mut resolver, matches);
         namespaces.push(ns);
     }
 
-    for ns in &namespaces {
-        ns.print_diagnostics(&resolver, true);
-        if ns.diagnostics.any_errors() {
-            errors = true;
-        }
-    }
-    if errors {
-        exit(1);
-    }
+    report_diagnostics(&namespaces, &resolver);
 }
 
 fn process_file(filename: &OsStr, resolver: &mut FileResolver, matches: &ArgMatches) -> Namespace {
@@ -150,86 +270,288 @@ fn process_file(filename: &OsStr, resolver: &mut FileResolver, matches: &ArgMatc
         return ns;
     }
 
-    for contract_no in 0..ns.contracts.len() {
-        layout(contract_no, &mut ns);
-    }
+    let output = output_dir(matches);
+    let generate = matches.get_one::<String>("Generate").map(|v| v.as_str());
+    let opt_level = opt_level(matches);
+    let contracts = top_level_contracts(&mut ns);
+    let (source, _) = resolver.get_file_contents_and_number(&ns.files[ns.top_file_no()].path);
 
     // gen llvm ir、asm、abi、ast phase
-    for contract_no in 0..ns.contracts.len() {
-        let resolved_contract = &ns.contracts[contract_no];
+    for &contract_no in &contracts {
+        let filename_lossy = filename.to_string_lossy().clone();
+        let filename_string = String::from(filename_lossy);
+        let filename_stem = Path::new(&filename_string).file_prefix().unwrap();
+        // A file may declare several contracts; name their outputs after the
+        // contract so that they do not overwrite each other
+        let name = if contracts.len() > 1 {
+            format!(
+                "{}_{}",
+                filename_stem.to_string_lossy(),
+                ns.contracts[contract_no].name
+            )
+        } else {
+            filename_stem.to_string_lossy().to_string()
+        };
+
+        emit(
+            contract_no,
+            generate,
+            &output,
+            name,
+            &mut ns,
+            &source,
+            opt_level,
+        );
+    }
+
+    ns
+}
 
-        if !resolved_contract.instantiable {
-            continue;
+fn bindgen(matches: &ArgMatches) {
+    let lang = matches.get_one::<String>("LANG").unwrap();
+    let output = output_dir(matches);
+
+    for filename in matches.get_many::<OsString>("INPUT").unwrap() {
+        let path = Path::new(filename);
+        let abi = std::fs::read_to_string(path).unwrap_or_else(|err| {
+            eprintln!("{}: error: {}", path.display(), err);
+            exit(1);
+        });
+        // `<name>_abi.json` holds the ABI of contract `<name>`
+        let stem = path.file_stem().unwrap().to_string_lossy();
+        let name = stem.strip_suffix("_abi").unwrap_or(&stem);
+
+        let (bindings, ext) = abi::generate_bindings(name, &abi, lang).unwrap_or_else(|err| {
+            eprintln!("{}: error: {}", path.display(), err);
+            exit(1);
+        });
+        let bindings_filename = output_file(&output, &name.to_lowercase(), ext);
+        let mut file = create_file(&bindings_filename);
+        file.write_all(bindings.as_bytes()).unwrap();
+    }
+}
+
+fn check_upgrade(matches: &ArgMatches) {
+    let contract = matches.get_one::<String>("CONTRACT").map(String::as_str);
+    let old = storage_layout(matches.get_one::<OsString>("OLD").unwrap(), contract);
+    let new = storage_layout(matches.get_one::<OsString>("NEW").unwrap(), contract);
+
+    let errors = check_upgrade_layout(&old, &new);
+
+    if errors.is_empty() {
+        println!("storage layout of '{}' is compatible", new.contract);
+    } else {
+        for error in &errors {
+            eprintln!("error: {}", error);
         }
+        exit(1);
+    }
+}
+
+/// Read the storage layout of a contract from a layout generated with
+/// `--gen storage-layout`, or compile it from an Ola source file
+fn storage_layout(filename: &OsStr, contract: Option<&str>) -> StorageLayout {
+    let path = Path::new(filename);
+
+    if path.extension() == Some(OsStr::new("json")) {
+        let layout = std::fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|json| {
+                serde_json::from_str::<StorageLayout>(&json).map_err(|err| err.to_string())
+            })
+            .unwrap_or_else(|err| {
+                eprintln!("{}: error: {}", path.display(), err);
+                exit(1);
+            });
+
+        return layout;
+    }
 
-        if ns.top_file_no() != resolved_contract.loc.file_no() {
-            // contracts that were imported should not be considered. For example, if we
-            // have a file a.sol which imports b.ola, and b.ola defines contract
-            // B, then: olac compile a.ola
-            // should not write the results for contract B
-            continue;
+    let mut resolver = FileResolver::new();
+    if let Ok(path) = path.canonicalize() {
+        let _ = resolver.add_import_path(path.parent().unwrap());
+    }
+    let mut ns = ola_lang::parse_and_resolve(filename, &mut resolver);
+
+    if ns.diagnostics.any_errors() {
+        ns.print_diagnostics(&resolver, true);
+        exit(1);
+    }
+
+    let contracts = top_level_contracts(&mut ns);
+    let found: Vec<usize> = contracts
+        .into_iter()
+        .filter(|contract_no| {
+            contract.is_none() || contract == Some(ns.contracts[*contract_no].name.as_str())
+        })
+        .collect();
+
+    match found.as_slice() {
+        [contract_no] => gen_storage_layout(*contract_no, &ns),
+        [] => {
+            eprintln!("{}: error: contract not found", path.display());
+            exit(1);
+        }
+        _ => {
+            eprintln!(
+                "{}: error: more than one contract, select one with --contract",
+                path.display()
+            );
+            exit(1);
         }
+    }
+}
 
-        let filename_lossy = filename.to_string_lossy().clone();
-        let filename_string = String::from(filename_lossy);
-        let filename_stem = Path::new(&filename_string).file_prefix().unwrap();
+fn abi_diff(matches: &ArgMatches) {
+    let read_abi = |name: &str| {
+        let path = Path::new(matches.get_one::<OsString>(name).unwrap());
 
-        match matches.get_one::<String>("Generate").map(|v| v.as_str()) {
-            Some("llvm-ir") => {
-                generate_llvm_ir(
-                    contract_no,
-                    matches,
-                    filename_stem.to_string_lossy().to_string(),
-                    &mut ns,
-                );
-            }
-            Some("abi") => {
-                generate_abi(
-                    contract_no,
-                    matches,
-                    filename_stem.to_string_lossy().to_string() + "_abi",
-                    &mut ns,
-                );
-            }
-            Some("asm") => {
-                generate_asm(
-                    contract_no,
-                    matches,
-                    filename_stem.to_string_lossy().to_string() + "_asm",
-                    &mut ns,
-                );
+        std::fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|json| encoding::Contract::parse(&json))
+            .unwrap_or_else(|err| {
+                eprintln!("{}: error: {}", path.display(), err);
+                exit(1);
+            })
+    };
+    let old = read_abi("OLD");
+    let new = read_abi("NEW");
+
+    let errors = diff::abi_diff(&old, &new);
+
+    if errors.is_empty() {
+        println!("no breaking changes");
+    } else {
+        for error in &errors {
+            eprintln!("error: {}", error);
+        }
+        exit(1);
+    }
+}
+
+fn build(matches: &ArgMatches) {
+    let manifest_path = matches
+        .get_one::<OsString>("MANIFEST")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(MANIFEST_NAME));
+    let project = Project::load(&manifest_path).unwrap_or_else(|err| {
+        eprintln!("error: {err}");
+        exit(1);
+    });
+    let (mut resolver, source_files) = match (project.resolver(), project.source_files()) {
+        (Ok(resolver), Ok(source_files)) => (resolver, source_files),
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("error: {err}");
+            exit(1);
+        }
+    };
+    let output = project.output_dir();
+    let opt_level = project.opt_level();
+
+    let mut namespaces = Vec::new();
+
+    for source_file in source_files {
+        let mut ns = ola_lang::parse_and_resolve(source_file.as_os_str(), &mut resolver);
+
+        if !ns.diagnostics.any_errors() {
+            let (source, _) =
+                resolver.get_file_contents_and_number(&ns.files[ns.top_file_no()].path);
+            // Mirror the sources directory, with one directory per source
+            // file holding the outputs of each of its contracts
+            let dir = output.join(source_file.with_extension(""));
+            for contract_no in top_level_contracts(&mut ns) {
+                let name = ns.contracts[contract_no].name.clone();
+                emit(contract_no, None, &dir, name, &mut ns, &source, opt_level);
             }
-            Some("ast") => {
-                generate_ast(
-                    matches,
-                    filename_stem.to_string_lossy().to_string(),
-                    &mut ns,
-                );
+        }
+
+        namespaces.push(ns);
+    }
+
+    report_diagnostics(&namespaces, &resolver);
+}
+
+fn report_diagnostics(namespaces: &[Namespace], resolver: &FileResolver) {
+    let mut errors = false;
+
+    for ns in namespaces {
+        ns.print_diagnostics(resolver, true);
+        if ns.diagnostics.any_errors() {
+            errors = true;
+        }
+    }
+    if errors {
+        exit(1);
+    }
+}
+
+/// Lay out the contracts and return the instantiable ones declared in the top
+/// file.
+fn top_level_contracts(ns: &mut Namespace) -> Vec<usize> {
+    for contract_no in 0..ns.contracts.len() {
+        layout(contract_no, ns);
+    }
+
+    // contracts that were imported should not be considered. For example, if we
+    // have a file a.sol which imports b.ola, and b.ola defines contract
+    // B, then: olac compile a.ola
+    // should not write the results for contract B
+    let top_file_no = ns.top_file_no();
+    (0..ns.contracts.len())
+        .filter(|contract_no| {
+            let contract = &ns.contracts[*contract_no];
+            contract.instantiable && contract.loc.file_no() == top_file_no
+        })
+        .collect()
+}
+
+fn emit(
+    contract_no: usize,
+    generate: Option<&str>,
+    output: &Path,
+    name: String,
+    ns: &mut Namespace,
+    source: &str,
+    opt_level: OptLevel,
+) {
+    match generate {
+        Some("llvm-ir") => {
+            generate_llvm_ir(contract_no, output, name, ns, opt_level);
+        }
+        Some("abi") => {
+            generate_abi(contract_no, output, name + "_abi", ns);
+        }
+        Some("asm") => {
+            generate_asm(contract_no, output, name + "_asm", ns, opt_level);
+        }
+        Some("artifact") => {
+            if let Some(program) =
+                generate_asm(contract_no, output, name.clone() + "_asm", ns, opt_level)
+            {
+                generate_artifact(contract_no, output, name + "_artifact", ns, source, program);
             }
-            None | Some(_) => {
-                generate_abi(
-                    contract_no,
-                    matches,
-                    filename_stem.to_string_lossy().to_string() + "_abi",
-                    &mut ns,
-                );
-                generate_asm(
-                    contract_no,
-                    matches,
-                    filename_stem.to_string_lossy().to_string() + "_asm",
-                    &mut ns,
-                );
+        }
+        Some("ast") => {
+            generate_ast(output, name, ns);
+        }
+        Some("storage-layout") => {
+            generate_storage_layout(contract_no, output, name + "_storage", ns);
+        }
+        None | Some(_) => {
+            generate_abi(contract_no, output, name.clone() + "_abi", ns);
+            if let Some(program) =
+                generate_asm(contract_no, output, name.clone() + "_asm", ns, opt_level)
+            {
+                generate_artifact(contract_no, output, name + "_artifact", ns, source, program);
             }
         }
     }
-
-    ns
 }
 
-fn generate_ast(matches: &ArgMatches, name: String, ns: &mut Namespace) {
+fn generate_ast(output: &Path, name: String, ns: &mut Namespace) {
     let filepath = PathBuf::from(name);
     let stem = filepath.file_stem().unwrap().to_string_lossy();
-    let dot_filename = output_file(matches, &stem, "dot");
+    let dot_filename = output_file(output, &stem, "dot");
 
     let dot = ns.dotgraphviz();
 
@@ -241,45 +563,151 @@ fn generate_ast(matches: &ArgMatches, name: String, ns: &mut Namespace) {
     }
 }
 
-fn generate_abi(contract_no: usize, matches: &ArgMatches, name: String, ns: &mut Namespace) {
-    let (metadata, meta_ext) = abi::generate_abi(contract_no, &ns);
-    let meta_filename = output_file(matches, &name, meta_ext);
+fn generate_abi(contract_no: usize, output: &Path, name: String, ns: &mut Namespace) {
+    let (metadata, meta_ext) = abi::generate_abi(contract_no, ns);
+    let meta_filename = output_file(output, &name, meta_ext);
     let mut file = create_file(&meta_filename);
     file.write_all(metadata.as_bytes()).unwrap();
 }
 
-fn generate_llvm_ir(contract_no: usize, matches: &ArgMatches, name: String, ns: &mut Namespace) {
+fn generate_storage_layout(contract_no: usize, output: &Path, name: String, ns: &Namespace) {
+    let (layout, layout_ext) = abi::generate_storage_layout(contract_no, ns);
+    let layout_filename = output_file(output, &name, layout_ext);
+    let mut file = create_file(&layout_filename);
+    file.write_all(layout.as_bytes()).unwrap();
+}
+
+fn generate_llvm_ir(
+    contract_no: usize,
+    output: &Path,
+    name: String,
+    ns: &mut Namespace,
+    opt_level: OptLevel,
+) {
     let context = inkwell::context::Context::create();
-    let binary = binary::Binary::gen_ir(&context, contract_no, &ns, &name);
-    let llvm_filename = output_file(matches, &name, "ll");
+    let binary = binary::Binary::gen_ir(&context, contract_no, ns, &name, opt_level);
+    if binary.take_diagnostics(&mut ns.diagnostics) {
+        return;
+    }
+    let llvm_filename = output_file(output, &name, "ll");
     binary.dump_llvm(&llvm_filename).unwrap();
 }
 
-fn generate_asm(contract_no: usize, matches: &ArgMatches, name: String, ns: &mut Namespace) {
+fn generate_asm(
+    contract_no: usize,
+    output: &Path,
+    name: String,
+    ns: &mut Namespace,
+    opt_level: OptLevel,
+) -> Option<asm::AsmProgram> {
     let context = inkwell::context::Context::create();
-    let binary = binary::Binary::gen_ir(&context, contract_no, &ns, &name);
-    // Parse the assembly and get a module
-    let module =
-        Module::try_from(binary.module.to_string().as_str()).expect("failed to parse LLVM IR");
+    let binary = binary::Binary::gen_ir(&context, contract_no, ns, &name, opt_level);
+    if binary.take_diagnostics(&mut ns.diagnostics) {
+        return None;
+    }
+    // Translate the LLVM module into a module of the code generator
+    let mut module = match Module::try_from(&binary.module) {
+        Ok(module) => module,
+        Err(err) => {
+            ns.diagnostics.push(Diagnostic::error(
+                ns.contracts[contract_no].loc,
+                format!("failed to translate generated LLVM IR: {}", err),
+            ));
+            return None;
+        }
+    };
+    let report = optimize(&mut module, opt_level);
+    if report.range_checks_removed > 0 {
+        ns.diagnostics.push(Diagnostic::debug(
+            ns.contracts[contract_no].loc,
+            format!(
+                "removed {} range checks which cannot fail",
+                report.range_checks_removed
+            ),
+        ));
+    }
     // Compile the module for Ola and get a machine module
-    let isa = Ola::default();
-    let code = compile_module(&isa, &module).expect("failed to compile");
-    let asm_path = output_file(matches, &name, "json");
-    let mut asm_file = create_file(&asm_path);
+    let isa = Ola::new(opt_level);
+    let program = match compile_module(&isa, &module)
+        .and_then(|code| asm::emit(&code).map_err(|e| e.into()))
+    {
+        Ok(program) => program,
+        Err(err) => {
+            let loc = lowering_error_loc(contract_no, &err, ns);
+            ns.diagnostics.push(Diagnostic::error(loc, err.to_string()));
+            return None;
+        }
+    };
+    let asm_path = output_file(output, &name, "json");
+    write_asm(&asm_path, &program);
+    Some(program)
+}
 
-    if let Err(err) = asm_file.write_all(format!("{}", code.display_asm()).as_bytes()) {
+fn generate_artifact(
+    contract_no: usize,
+    output: &Path,
+    name: String,
+    ns: &Namespace,
+    source: &str,
+    program: asm::AsmProgram,
+) {
+    let (artifact, artifact_ext) = abi::generate_artifact(contract_no, ns, source, program);
+    let artifact_filename = output_file(output, &name, artifact_ext);
+    let mut file = create_file(&artifact_filename);
+    file.write_all(artifact.as_bytes()).unwrap();
+}
+
+/// Find the source location of the function a backend error was raised in,
+/// falling back to the contract itself.
+fn lowering_error_loc(contract_no: usize, err: &anyhow::Error, ns: &Namespace) -> Loc {
+    let contract = &ns.contracts[contract_no];
+    if let Some(LoweringError::Function { name, .. }) = err.downcast_ref::<LoweringError>() {
+        if let Some(func_no) = contract
+            .all_functions
+            .keys()
+            .find(|func_no| &ns.functions[**func_no].name == name)
+        {
+            return ns.functions[*func_no].loc;
+        }
+    }
+    contract.loc
+}
+
+fn write_asm(asm_path: &Path, program: &asm::AsmProgram) {
+    let mut asm_file = create_file(asm_path);
+    let serialized = serde_json::to_string_pretty(program).unwrap();
+
+    if let Err(err) = asm_file.write_all(format!("{}\n", serialized).as_bytes()) {
         eprintln!("{}: error: {}", asm_path.display(), err);
         exit(1);
     }
 }
 
-fn output_file(matches: &ArgMatches, stem: &str, ext: &str) -> PathBuf {
-    Path::new(
+fn opt_level_arg() -> Arg {
+    Arg::new("OPT_LEVEL")
+        .help("Optimization level")
+        .short('O')
+        .long("opt-level")
+        .num_args(1)
+        .default_value("0")
+        .value_parser(clap::value_parser!(u8).range(0..=3))
+}
+
+fn opt_level(matches: &ArgMatches) -> OptLevel {
+    OptLevel::try_from(*matches.get_one::<u8>("OPT_LEVEL").unwrap())
+        .expect("the opt level is checked by the parser")
+}
+
+fn output_dir(matches: &ArgMatches) -> PathBuf {
+    PathBuf::from(
         matches
             .get_one::<OsString>("OUTPUT")
             .unwrap_or(&OsString::from(".")),
     )
-    .join(format!("{stem}.{ext}"))
+}
+
+fn output_file(output: &Path, stem: &str, ext: &str) -> PathBuf {
+    output.join(format!("{stem}.{ext}"))
 }
 
 fn create_file(path: &Path) -> File {
@@ -319,26 +747,3 @@ fn imports_arg(matches: &ArgMatches) -> FileResolver {
 
     resolver
 }
-
-/// Layout the contract. We determine the layout of variables and deal with
-/// overriding variables
-fn layout(contract_no: usize, ns: &mut Namespace) {
-    let mut slot = BigInt::zero();
-
-    for var_no in 0..ns.contracts[contract_no].variables.len() {
-        if !ns.contracts[contract_no].variables[var_no].constant {
-            let ty = ns.contracts[contract_no].variables[var_no].ty.clone();
-
-            ns.contracts[contract_no].layout.push(Layout {
-                slot: slot.clone(),
-                contract_no,
-                var_no,
-                ty: ty.clone(),
-            });
-
-            slot += ty.storage_slots(ns);
-        }
-    }
-
-    ns.contracts[contract_no].fixed_layout_size = slot;
-}
@@ -26,7 +26,7 @@ pub struct Instruction {
     pub metadata: FxHashMap<String, Metadata>,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Opcode {
     Alloca,
     Phi,
@@ -65,7 +65,7 @@ pub enum Opcode {
     Invalid,
 }
 
-#[derive(Clone, Copy, Eq, PartialEq)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
 pub enum ICmpCond {
     Eq,
     Ne,
@@ -316,6 +316,17 @@ impl Instruction {
         }
     }
 
+    /// The name of the function a call calls directly
+    pub fn callee_name<'a>(&self, data: &'a Data) -> Option<&'a str> {
+        match &self.operand {
+            Operand::Call(Call { args, .. }) => match data.value_ref(args[0]) {
+                Value::Constant(ConstantValue::GlobalRef(Name::Name(name), _)) => Some(name),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     pub fn display<'a>(&'a self, data: &'a Data, types: &'a Types) -> DisplayInstruction<'a> {
         DisplayInstruction {
             inst: self,
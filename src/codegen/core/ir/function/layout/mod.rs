@@ -93,6 +93,25 @@ impl Layout {
         }
     }
 
+    /// Inserts `block` into the layout right after `after`
+    pub fn insert_block_after(&mut self, block: BasicBlockId, after: BasicBlockId) {
+        let next = self.basic_blocks[&after].next;
+        self.basic_blocks.insert(
+            block,
+            BasicBlockNode {
+                prev: Some(after),
+                next,
+                first_inst: None,
+                last_inst: None,
+            },
+        );
+        self.basic_blocks.get_mut(&after).unwrap().next = Some(block);
+        match next {
+            Some(next) => self.basic_blocks.get_mut(&next).unwrap().prev = Some(block),
+            None => self.last_block = Some(block),
+        }
+    }
+
     pub fn append_inst(&mut self, inst: InstructionId, block: BasicBlockId) {
         self.instructions
             .entry(inst)
@@ -137,6 +156,44 @@ impl Layout {
         }
     }
 
+    /// Inserts `inst` into the layout right before `before`
+    pub fn insert_inst_before(&mut self, inst: InstructionId, before: InstructionId) {
+        let block = self.instructions[&before].block.unwrap();
+        let prev = self.instructions[&before].prev;
+        self.instructions.insert(
+            inst,
+            InstructionNode {
+                prev,
+                next: Some(before),
+                block: Some(block),
+            },
+        );
+        self.instructions.get_mut(&before).unwrap().prev = Some(inst);
+        match prev {
+            Some(prev) => self.instructions.get_mut(&prev).unwrap().next = Some(inst),
+            None => self.basic_blocks.get_mut(&block).unwrap().first_inst = Some(inst),
+        }
+    }
+
+    /// Inserts `inst` into the layout right after `after`
+    pub fn insert_inst_after(&mut self, inst: InstructionId, after: InstructionId) {
+        let block = self.instructions[&after].block.unwrap();
+        let next = self.instructions[&after].next;
+        self.instructions.insert(
+            inst,
+            InstructionNode {
+                prev: Some(after),
+                next,
+                block: Some(block),
+            },
+        );
+        self.instructions.get_mut(&after).unwrap().next = Some(inst);
+        match next {
+            Some(next) => self.instructions.get_mut(&next).unwrap().prev = Some(inst),
+            None => self.basic_blocks.get_mut(&block).unwrap().last_inst = Some(inst),
+        }
+    }
+
     pub fn remove_inst(&mut self, inst: InstructionId) -> Option<()> {
         let block = self.instructions[&inst].block?;
         let prev;
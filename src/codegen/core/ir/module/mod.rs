@@ -140,6 +140,26 @@ impl<'a> TryFrom<&'a str> for Module {
     }
 }
 
+impl<'a> TryFrom<&'a [u8]> for Module {
+    type Error = anyhow::Error;
+
+    /// Reads an LLVM bitcode file.
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        use crate::codegen::core::parser::bitcode::parse;
+        parse(bytes)
+    }
+}
+
+impl<'a, 'ctx> TryFrom<&'a inkwell::module::Module<'ctx>> for Module {
+    type Error = crate::codegen::core::parser::llvm::Error;
+
+    /// Translates an in-memory LLVM module without printing it first.
+    fn try_from(module: &'a inkwell::module::Module<'ctx>) -> Result<Self, Self::Error> {
+        use crate::codegen::core::parser::llvm::module::translate;
+        translate(module)
+    }
+}
+
 impl fmt::Debug for Module {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "source_filename = \"{}\"", self.source_filename)?;
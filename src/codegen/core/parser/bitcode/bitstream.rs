@@ -0,0 +1,374 @@
+//! Reader for the bitstream container format which LLVM bitcode is stored in.
+//! See https://llvm.org/docs/BitCodeFormat.html for the layout.
+
+use anyhow::{anyhow, bail, Result};
+use rustc_hash::FxHashMap;
+use std::rc::Rc;
+
+const END_BLOCK: u64 = 0;
+const ENTER_SUBBLOCK: u64 = 1;
+const DEFINE_ABBREV: u64 = 2;
+const UNABBREV_RECORD: u64 = 3;
+
+pub const BLOCKINFO_BLOCK_ID: u64 = 0;
+const BLOCKINFO_CODE_SETBID: u64 = 1;
+
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Literal(u64),
+    Fixed(u32),
+    Vbr(u32),
+    Array,
+    Char6,
+    Blob,
+}
+
+type Abbrev = Rc<Vec<Operand>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub code: u64,
+    pub ops: Vec<u64>,
+    pub blob: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entry {
+    /// The start of a nested block. Call `enter_block` or `skip_block` next.
+    SubBlock(u64),
+    /// The end of the current block.
+    EndBlock,
+    Record(Record),
+}
+
+struct Scope {
+    block_id: u64,
+    abbrev_width: u32,
+    abbrevs: Vec<Abbrev>,
+}
+
+pub struct BitstreamReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    scopes: Vec<Scope>,
+    block_info: FxHashMap<u64, Vec<Abbrev>>,
+    block_info_target: Option<u64>,
+}
+
+impl<'a> BitstreamReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            scopes: vec![Scope {
+                block_id: u64::MAX,
+                abbrev_width: 2,
+                abbrevs: vec![],
+            }],
+            block_info: FxHashMap::default(),
+            block_info_target: None,
+        }
+    }
+
+    /// Returns whether the top level of the stream has been read completely.
+    pub fn at_end(&self) -> bool {
+        self.scopes.len() == 1 && self.pos + 32 > self.data.len() * 8
+    }
+
+    pub fn bit_position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn seek(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    pub fn read(&mut self, width: u32) -> Result<u64> {
+        if width == 0 {
+            return Ok(0);
+        }
+        if width > 64 || self.pos + width as usize > self.data.len() * 8 {
+            bail!("Unexpected end of bitstream");
+        }
+        let mut value = 0u64;
+        for i in 0..width as usize {
+            let pos = self.pos + i;
+            let bit = (self.data[pos / 8] >> (pos % 8)) & 1;
+            value |= (bit as u64) << i;
+        }
+        self.pos += width as usize;
+        Ok(value)
+    }
+
+    pub fn read_vbr(&mut self, width: u32) -> Result<u64> {
+        let hi_mask = 1u64 << (width - 1);
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let chunk = self.read(width)?;
+            if shift >= 64 {
+                bail!("VBR value is too large");
+            }
+            value |= (chunk & (hi_mask - 1)) << shift;
+            if chunk & hi_mask == 0 {
+                return Ok(value);
+            }
+            shift += width - 1;
+        }
+    }
+
+    fn align32(&mut self) {
+        self.pos = (self.pos + 31) & !31;
+    }
+
+    fn scope(&self) -> &Scope {
+        self.scopes.last().unwrap()
+    }
+
+    /// Reads the next entry of the current block, defining any abbreviations
+    /// on the way.
+    pub fn next(&mut self) -> Result<Entry> {
+        loop {
+            let abbrev_id = self.read(self.scope().abbrev_width)?;
+            match abbrev_id {
+                END_BLOCK => {
+                    if self.scopes.len() == 1 {
+                        bail!("Unbalanced end of block");
+                    }
+                    self.align32();
+                    self.scopes.pop();
+                    return Ok(Entry::EndBlock);
+                }
+                ENTER_SUBBLOCK => return Ok(Entry::SubBlock(self.read_vbr(8)?)),
+                DEFINE_ABBREV => {
+                    let abbrev = self.read_abbrev()?;
+                    if self.scope().block_id == BLOCKINFO_BLOCK_ID {
+                        let block_id = self
+                            .block_info_target
+                            .ok_or_else(|| anyhow!("Abbreviation in BLOCKINFO before SETBID"))?;
+                        self.block_info.entry(block_id).or_default().push(abbrev);
+                    } else {
+                        self.scopes.last_mut().unwrap().abbrevs.push(abbrev);
+                    }
+                }
+                UNABBREV_RECORD => {
+                    let code = self.read_vbr(6)?;
+                    let num_ops = self.read_vbr(6)?;
+                    let ops = (0..num_ops)
+                        .map(|_| self.read_vbr(6))
+                        .collect::<Result<Vec<_>>>()?;
+                    return Ok(Entry::Record(Record {
+                        code,
+                        ops,
+                        blob: None,
+                    }));
+                }
+                id => {
+                    let abbrev = self
+                        .scope()
+                        .abbrevs
+                        .get((id - 4) as usize)
+                        .cloned()
+                        .ok_or_else(|| anyhow!("Undefined abbreviation {}", id))?;
+                    return Ok(Entry::Record(self.read_abbreviated_record(&abbrev)?));
+                }
+            }
+        }
+    }
+
+    /// Enters the block whose `Entry::SubBlock` was just read.
+    pub fn enter_block(&mut self, block_id: u64) -> Result<()> {
+        let abbrev_width = self.read_vbr(4)? as u32;
+        self.align32();
+        let _num_words = self.read(32)?;
+        self.scopes.push(Scope {
+            block_id,
+            abbrev_width,
+            abbrevs: self.block_info.get(&block_id).cloned().unwrap_or_default(),
+        });
+        Ok(())
+    }
+
+    /// Skips the block whose `Entry::SubBlock` was just read.
+    pub fn skip_block(&mut self) -> Result<()> {
+        let _abbrev_width = self.read_vbr(4)?;
+        self.align32();
+        let num_words = self.read(32)? as usize;
+        self.pos += num_words * 32;
+        if self.pos > self.data.len() * 8 {
+            bail!("Block extends past the end of the bitstream");
+        }
+        Ok(())
+    }
+
+    /// Skips the rest of the current block, including its end marker.
+    pub fn skip_to_end(&mut self) -> Result<()> {
+        loop {
+            match self.next()? {
+                Entry::SubBlock(_) => self.skip_block()?,
+                Entry::EndBlock => return Ok(()),
+                Entry::Record(_) => {}
+            }
+        }
+    }
+
+    /// Reads the `BLOCKINFO` block whose `Entry::SubBlock` was just read,
+    /// registering its abbreviations for the blocks they are meant for.
+    pub fn read_block_info(&mut self) -> Result<()> {
+        self.enter_block(BLOCKINFO_BLOCK_ID)?;
+        loop {
+            match self.next()? {
+                Entry::SubBlock(_) => self.skip_block()?,
+                Entry::EndBlock => return Ok(()),
+                Entry::Record(record) => {
+                    if record.code == BLOCKINFO_CODE_SETBID {
+                        self.block_info_target = record.ops.first().copied();
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn block_id(&self) -> u64 {
+        self.scope().block_id
+    }
+
+    fn read_abbrev(&mut self) -> Result<Abbrev> {
+        let num_ops = self.read_vbr(5)?;
+        let mut ops = vec![];
+        for _ in 0..num_ops {
+            if self.read(1)? == 1 {
+                ops.push(Operand::Literal(self.read_vbr(8)?));
+                continue;
+            }
+            let op = match self.read(3)? {
+                1 => match self.read_vbr(5)? {
+                    0 => Operand::Literal(0),
+                    width @ 1..=64 => Operand::Fixed(width as u32),
+                    width => bail!("Fixed operand of width {}", width),
+                },
+                // A chunk of a VBR needs a bit besides the one which marks
+                // that another chunk follows
+                2 => match self.read_vbr(5)? {
+                    0 => Operand::Literal(0),
+                    width @ 2..=64 => Operand::Vbr(width as u32),
+                    width => bail!("VBR operand of width {}", width),
+                },
+                3 => Operand::Array,
+                4 => Operand::Char6,
+                5 => Operand::Blob,
+                encoding => bail!("Unknown abbreviation encoding {}", encoding),
+            };
+            ops.push(op);
+        }
+        Ok(Rc::new(ops))
+    }
+
+    fn read_scalar(&mut self, op: Operand) -> Result<u64> {
+        match op {
+            Operand::Literal(value) => Ok(value),
+            Operand::Fixed(width) => self.read(width),
+            Operand::Vbr(width) => self.read_vbr(width),
+            Operand::Char6 => Ok(decode_char6(self.read(6)? as u8) as u64),
+            Operand::Array | Operand::Blob => bail!("Array or blob used as an element"),
+        }
+    }
+
+    fn read_abbreviated_record(&mut self, abbrev: &[Operand]) -> Result<Record> {
+        let mut values = vec![];
+        let mut blob = None;
+        let mut i = 0;
+        while i < abbrev.len() {
+            match abbrev[i] {
+                Operand::Array => {
+                    let elem = *abbrev
+                        .get(i + 1)
+                        .ok_or_else(|| anyhow!("Array without an element type"))?;
+                    let len = self.read_vbr(6)?;
+                    for _ in 0..len {
+                        values.push(self.read_scalar(elem)?);
+                    }
+                    i += 2;
+                    continue;
+                }
+                Operand::Blob => {
+                    let len = self.read_vbr(6)? as usize;
+                    self.align32();
+                    let start = self.pos / 8;
+                    if start + len > self.data.len() {
+                        bail!("Blob extends past the end of the bitstream");
+                    }
+                    blob = Some(self.data[start..start + len].to_vec());
+                    self.pos += len * 8;
+                    self.align32();
+                }
+                op => values.push(self.read_scalar(op)?),
+            }
+            i += 1;
+        }
+        if values.is_empty() {
+            bail!("Abbreviated record without a code");
+        }
+        let code = values.remove(0);
+        Ok(Record {
+            code,
+            ops: values,
+            blob,
+        })
+    }
+}
+
+fn decode_char6(c: u8) -> u8 {
+    match c {
+        0..=25 => b'a' + c,
+        26..=51 => b'A' + c - 26,
+        52..=61 => b'0' + c - 52,
+        62 => b'.',
+        _ => b'_',
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_fixed_and_vbr() {
+        // 0b101 (fixed 3), then 37 as vbr4: 0b1101 0b0100
+        let data = [0b0110_1101, 0b0000_0010, 0, 0];
+        let mut reader = BitstreamReader::new(&data);
+        assert_eq!(reader.read(3).unwrap(), 0b101);
+        assert_eq!(reader.read_vbr(4).unwrap(), 37);
+    }
+
+    /// Packs the `(value, width)` fields from the least significant bit on
+    fn pack(fields: &[(u64, u32)]) -> Vec<u8> {
+        let mut data = vec![0u8; 8];
+        let mut pos = 0;
+        for &(value, width) in fields {
+            for i in 0..width {
+                data[pos / 8] |= ((value >> i & 1) as u8) << (pos % 8);
+                pos += 1;
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn reject_wide_abbrev_operands() {
+        // An abbreviation of one fixed or VBR operand 65 bits wide, the
+        // width being the vbr5 chunks 0b10001 0b00100
+        for encoding in [1, 2] {
+            let data = pack(&[
+                (DEFINE_ABBREV, 2),
+                (1, 5),
+                (0, 1),
+                (encoding, 3),
+                (0b10001, 5),
+                (0b00100, 5),
+            ]);
+            let err = BitstreamReader::new(&data).next().unwrap_err();
+            assert!(err.to_string().ends_with("of width 65"));
+        }
+    }
+}
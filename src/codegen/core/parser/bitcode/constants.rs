@@ -0,0 +1,257 @@
+use super::{
+    bitstream::{BitstreamReader, Entry},
+    types::{chars, op, TypeEntry, TypeTable},
+};
+use crate::codegen::core::ir::{
+    module::name::Name,
+    types::{Type, Types, I1, I32, I8},
+    value::{ConstantArray, ConstantExpr, ConstantInt, ConstantStruct, ConstantValue},
+};
+use anyhow::{anyhow, bail, Result};
+
+const CST_CODE_SETTYPE: u64 = 1;
+const CST_CODE_NULL: u64 = 2;
+const CST_CODE_UNDEF: u64 = 3;
+const CST_CODE_INTEGER: u64 = 4;
+const CST_CODE_AGGREGATE: u64 = 7;
+const CST_CODE_STRING: u64 = 8;
+const CST_CODE_CSTRING: u64 = 9;
+const CST_CODE_CE_CAST: u64 = 11;
+const CST_CODE_CE_GEP: u64 = 12;
+const CST_CODE_CE_INBOUNDS_GEP: u64 = 20;
+const CST_CODE_DATA: u64 = 22;
+const CST_CODE_CE_GEP_WITH_INRANGE_INDEX: u64 = 24;
+const CST_CODE_POISON: u64 = 26;
+
+const CAST_BITCAST: u64 = 11;
+
+/// An entry of the value list. Values are numbered in the order they are
+/// defined: global variables and functions, module constants, and then, per
+/// function, arguments, function constants and instruction results.
+#[derive(Debug, Clone)]
+pub enum ValueEntry {
+    Global {
+        name: Name,
+    },
+    Constant {
+        ty_id: usize,
+        ty: Type,
+        code: u64,
+        ops: Vec<u64>,
+    },
+    /// An argument or the result of an instruction.
+    Local {
+        ty: Type,
+    },
+}
+
+#[derive(Default)]
+pub struct ValueList {
+    pub entries: Vec<ValueEntry>,
+}
+
+impl ValueList {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn push(&mut self, entry: ValueEntry) {
+        self.entries.push(entry)
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.entries.truncate(len)
+    }
+
+    pub fn get(&self, id: usize) -> Result<&ValueEntry> {
+        self.entries
+            .get(id)
+            .ok_or_else(|| anyhow!("Invalid value id {}", id))
+    }
+
+    /// Returns the type of a value, if it has already been defined.
+    pub fn type_of(&self, types: &Types, id: usize) -> Option<Type> {
+        match self.entries.get(id)? {
+            ValueEntry::Global { .. } => Some(types.base_mut().pointer(I32)),
+            ValueEntry::Constant { ty, .. } | ValueEntry::Local { ty } => Some(*ty),
+        }
+    }
+
+    pub fn is_constant(&self, id: usize) -> bool {
+        matches!(
+            self.entries.get(id),
+            Some(ValueEntry::Global { .. } | ValueEntry::Constant { .. })
+        )
+    }
+
+    /// Reads the `CONSTANTS_BLOCK` the reader has just entered, appending its
+    /// constants to the list. They are materialized on use, since aggregates
+    /// may refer to constants defined after them.
+    pub fn read_constants(
+        &mut self,
+        reader: &mut BitstreamReader,
+        types: &Types,
+        table: &TypeTable,
+    ) -> Result<()> {
+        let mut ty_id = None;
+        loop {
+            let record = match reader.next()? {
+                Entry::EndBlock => return Ok(()),
+                Entry::SubBlock(_) => {
+                    reader.skip_block()?;
+                    continue;
+                }
+                Entry::Record(record) => record,
+            };
+            if record.code == CST_CODE_SETTYPE {
+                ty_id = Some(op(&record.ops, 0)? as usize);
+                continue;
+            }
+            let ty_id = ty_id.ok_or_else(|| anyhow!("Constant without a type"))?;
+            self.push(ValueEntry::Constant {
+                ty_id,
+                ty: table.get(types, ty_id)?,
+                code: record.code,
+                ops: record.ops,
+            });
+        }
+    }
+
+    /// Builds the constant with the given value id.
+    pub fn constant(&self, types: &Types, table: &TypeTable, id: usize) -> Result<ConstantValue> {
+        let (ty_id, ty, code, ops) = match self.get(id)? {
+            ValueEntry::Global { name } => {
+                return Ok(ConstantValue::GlobalRef(
+                    name.clone(),
+                    types.base_mut().pointer(I32),
+                ))
+            }
+            ValueEntry::Constant {
+                ty_id,
+                ty,
+                code,
+                ops,
+            } => (*ty_id, *ty, *code, ops),
+            ValueEntry::Local { .. } => bail!("Value {} is not a constant", id),
+        };
+        let operand = |i: usize| -> Result<ConstantValue> {
+            self.constant(types, table, op(ops, i)? as usize)
+        };
+
+        match code {
+            CST_CODE_UNDEF | CST_CODE_POISON => Ok(ConstantValue::Undef(ty)),
+            CST_CODE_NULL => match table.entry(ty_id)? {
+                TypeEntry::Int(_) => int(ty, 0),
+                TypeEntry::Pointer => Ok(ConstantValue::Null(ty)),
+                _ => Ok(ConstantValue::AggregateZero(ty)),
+            },
+            CST_CODE_INTEGER => int(ty, decode_signed(op(ops, 0)?)),
+            CST_CODE_AGGREGATE => {
+                let elems = (0..ops.len()).map(operand).collect::<Result<Vec<_>>>()?;
+                match table.entry(ty_id)? {
+                    TypeEntry::Array(..) => Ok(ConstantValue::Array(ConstantArray {
+                        ty,
+                        elem_ty: types.get_element(ty).unwrap(),
+                        elems,
+                        is_string: false,
+                    })),
+                    TypeEntry::Struct {
+                        is_packed,
+                        elems: elem_tys,
+                        ..
+                    } => Ok(ConstantValue::Struct(ConstantStruct {
+                        ty,
+                        elems_ty: elem_tys
+                            .iter()
+                            .map(|&elem| table.get(types, elem))
+                            .collect::<Result<Vec<_>>>()?,
+                        elems,
+                        is_packed: *is_packed,
+                    })),
+                    _ => bail!("Aggregate constant of type {}", types.to_string(ty)),
+                }
+            }
+            CST_CODE_STRING | CST_CODE_CSTRING => {
+                let mut bytes = chars(ops);
+                if code == CST_CODE_CSTRING {
+                    bytes.push(0);
+                }
+                Ok(ConstantValue::Array(ConstantArray {
+                    ty,
+                    elem_ty: I8,
+                    elems: bytes
+                        .into_iter()
+                        .map(|c| ConstantValue::Int(ConstantInt::Int8(c as i8)))
+                        .collect(),
+                    is_string: true,
+                }))
+            }
+            CST_CODE_DATA => {
+                let elem_ty = types.get_element(ty).unwrap();
+                let elems = ops
+                    .iter()
+                    .map(|&elem| int(elem_ty, elem as i64))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(ConstantValue::Array(ConstantArray {
+                    ty,
+                    elem_ty,
+                    elems,
+                    is_string: false,
+                }))
+            }
+            CST_CODE_CE_CAST => {
+                if op(ops, 0)? != CAST_BITCAST {
+                    bail!("Unsupported constant cast (opcode {})", ops[0]);
+                }
+                let from = table.get(types, op(ops, 1)? as usize)?;
+                Ok(ConstantValue::Expr(ConstantExpr::Bitcast {
+                    tys: [from, ty],
+                    arg: Box::new(operand(2)?),
+                }))
+            }
+            CST_CODE_CE_GEP | CST_CODE_CE_INBOUNDS_GEP | CST_CODE_CE_GEP_WITH_INRANGE_INDEX => {
+                let (inbounds, start) = match code {
+                    CST_CODE_CE_GEP_WITH_INRANGE_INDEX => (op(ops, 1)? & 1 != 0, 2),
+                    _ => (code == CST_CODE_CE_INBOUNDS_GEP, 1),
+                };
+                let mut tys = vec![table.get(types, op(ops, 0)? as usize)?];
+                let mut args = vec![];
+                for pair in ops[start..].chunks_exact(2) {
+                    tys.push(table.get(types, pair[0] as usize)?);
+                    args.push(self.constant(types, table, pair[1] as usize)?);
+                }
+                Ok(ConstantValue::Expr(ConstantExpr::GetElementPtr {
+                    inbounds,
+                    tys,
+                    args,
+                }))
+            }
+            code => bail!("Unsupported constant (constant code {})", code),
+        }
+    }
+}
+
+fn int(ty: Type, value: i64) -> Result<ConstantValue> {
+    let int = match ty {
+        I1 => ConstantInt::Int1(value != 0),
+        I8 => ConstantInt::Int8(value as i8),
+        I32 => ConstantInt::Int64(value),
+        _ => bail!("Unsupported integer constant"),
+    };
+    Ok(int.into())
+}
+
+/// Decodes a value whose sign is stored in the lowest bit.
+pub(super) fn decode_signed(value: u64) -> i64 {
+    if value & 1 == 0 {
+        (value >> 1) as i64
+    } else if value != 1 {
+        -((value >> 1) as i64)
+    } else {
+        i64::MIN
+    }
+}
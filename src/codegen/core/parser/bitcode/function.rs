@@ -0,0 +1,890 @@
+use super::{
+    bitstream::{BitstreamReader, Entry, Record},
+    constants::{decode_signed, ValueEntry, ValueList},
+    types::{chars, op, TypeEntry, TypeTable},
+};
+use crate::codegen::core::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        data::Data,
+        instruction::{
+            Alloca, Br, Call, Cast, CondBr, ExtractValue, GetElementPtr, ICmp, ICmpCond,
+            InsertValue, Instruction, IntBinary, Load, Opcode, Operand, Phi, Ret, Store, Switch,
+        },
+        layout::Layout,
+        Parameter,
+    },
+    module::name::Name,
+    types::{Type, Types, I1, I32, VOID},
+    value::{ConstantInt, ConstantValue, ValueId},
+};
+use crate::codegen::core::parser::builder::FunctionBuilder;
+use anyhow::{anyhow, bail, Result};
+use rustc_hash::FxHashMap;
+
+const CONSTANTS_BLOCK_ID: u64 = 11;
+const VALUE_SYMTAB_BLOCK_ID: u64 = 14;
+
+const VST_CODE_ENTRY: u64 = 1;
+const VST_CODE_BBENTRY: u64 = 2;
+
+const FUNC_CODE_DECLAREBLOCKS: u64 = 1;
+const FUNC_CODE_INST_BINOP: u64 = 2;
+const FUNC_CODE_INST_CAST: u64 = 3;
+const FUNC_CODE_INST_RET: u64 = 10;
+const FUNC_CODE_INST_BR: u64 = 11;
+const FUNC_CODE_INST_SWITCH: u64 = 12;
+const FUNC_CODE_INST_UNREACHABLE: u64 = 15;
+const FUNC_CODE_INST_PHI: u64 = 16;
+const FUNC_CODE_INST_ALLOCA: u64 = 19;
+const FUNC_CODE_INST_LOAD: u64 = 20;
+const FUNC_CODE_INST_EXTRACTVAL: u64 = 26;
+const FUNC_CODE_INST_INSERTVAL: u64 = 27;
+const FUNC_CODE_INST_CMP2: u64 = 28;
+const FUNC_CODE_DEBUG_LOC_AGAIN: u64 = 33;
+const FUNC_CODE_INST_CALL: u64 = 34;
+const FUNC_CODE_DEBUG_LOC: u64 = 35;
+const FUNC_CODE_INST_GEP: u64 = 43;
+const FUNC_CODE_INST_STORE: u64 = 44;
+
+const ALLOCA_EXPLICIT_TYPE: u64 = 1 << 6;
+const CALL_EXPLICIT_TYPE: u64 = 1 << 15;
+const CALL_FMF: u64 = 1 << 17;
+
+/// An instruction as recorded in the bitcode, with its operands referring to
+/// value ids and basic block numbers.
+enum Inst {
+    Alloca {
+        ty: Type,
+        size: usize,
+        align: u32,
+    },
+    Phi {
+        ty: Type,
+        incoming: Vec<(usize, usize)>,
+    },
+    Load {
+        ty: Type,
+        addr: (Type, usize),
+        align: u32,
+    },
+    Store {
+        src: (Type, usize),
+        dst: (Type, usize),
+        align: u32,
+    },
+    InsertValue {
+        aggre: (Type, usize),
+        elt: (Type, usize),
+        indices: Vec<u64>,
+    },
+    ExtractValue {
+        ty: Type,
+        aggre: (Type, usize),
+        indices: Vec<u64>,
+    },
+    IntBinary {
+        opcode: Opcode,
+        ty: Type,
+        args: [usize; 2],
+        nuw: bool,
+        nsw: bool,
+        exact: bool,
+    },
+    ICmp {
+        ty: Type,
+        args: [usize; 2],
+        cond: ICmpCond,
+    },
+    Cast {
+        opcode: Opcode,
+        tys: [Type; 2],
+        arg: usize,
+    },
+    GetElementPtr {
+        inbounds: bool,
+        tys: Vec<Type>,
+        args: Vec<usize>,
+    },
+    Call {
+        ty: Type,
+        callee: usize,
+        args: Vec<(Type, usize)>,
+    },
+    Br(usize),
+    CondBr {
+        arg: usize,
+        blocks: [usize; 2],
+    },
+    Switch {
+        ty: Type,
+        cond: usize,
+        default: usize,
+        cases: Vec<(usize, usize)>,
+    },
+    Ret(Option<(Type, usize)>),
+    Unreachable,
+}
+
+impl Inst {
+    fn ends_block(&self) -> bool {
+        matches!(
+            self,
+            Self::Br(_)
+                | Self::CondBr { .. }
+                | Self::Switch { .. }
+                | Self::Ret(_)
+                | Self::Unreachable
+        )
+    }
+}
+
+/// The operands of a record, consumed from the front.
+struct Ops<'r> {
+    ops: &'r [u64],
+    pos: usize,
+}
+
+impl<'r> Ops<'r> {
+    fn next(&mut self) -> Result<u64> {
+        let op = op(self.ops, self.pos)?;
+        self.pos += 1;
+        Ok(op)
+    }
+
+    fn next_or(&mut self, default: u64) -> u64 {
+        self.next().unwrap_or(default)
+    }
+
+    fn remaining(&self) -> usize {
+        self.ops.len().saturating_sub(self.pos)
+    }
+
+    fn rest(&mut self) -> &'r [u64] {
+        let rest = &self.ops[self.pos.min(self.ops.len())..];
+        self.pos = self.ops.len();
+        rest
+    }
+}
+
+/// Decodes the records of a function block. Instruction operands may refer
+/// to values defined later, and names only come at the end of the block, so
+/// the function is built once the whole block has been read.
+struct Decoder<'a> {
+    types: &'a Types,
+    table: &'a TypeTable,
+    values: &'a mut ValueList,
+    blocks: Vec<Vec<(Inst, Option<usize>)>>,
+    value_names: FxHashMap<usize, String>,
+    block_names: FxHashMap<usize, String>,
+}
+
+/// Reads the `FUNCTION_BLOCK` the reader has just entered. The values of the
+/// function are appended to `values` while it is read and removed afterwards.
+pub fn read(
+    reader: &mut BitstreamReader,
+    types: &Types,
+    table: &TypeTable,
+    values: &mut ValueList,
+    param_tys: &[Type],
+) -> Result<(Vec<Parameter>, Data, Layout)> {
+    let module_values = values.len();
+    for &ty in param_tys {
+        values.push(ValueEntry::Local { ty });
+    }
+    let result = {
+        let mut decoder = Decoder {
+            types,
+            table,
+            values,
+            blocks: vec![vec![]],
+            value_names: FxHashMap::default(),
+            block_names: FxHashMap::default(),
+        };
+        decoder.read(reader)?;
+        decoder.build(module_values, param_tys)
+    };
+    values.truncate(module_values);
+    result
+}
+
+/// Builds the parameters and the empty body of a function declaration.
+pub fn declare(param_tys: &[Type]) -> (Vec<Parameter>, Data, Layout) {
+    // Declarations are printed without parameter names.
+    let params = param_tys
+        .iter()
+        .enumerate()
+        .map(|(i, &ty)| Parameter {
+            name: Name::Number(i + 1),
+            ty,
+            attrs: vec![],
+        })
+        .collect::<Vec<_>>();
+    let mut builder = FunctionBuilder::<usize, usize>::new();
+    for (i, param) in params.iter().enumerate() {
+        builder.add_argument(i, i, param);
+    }
+    let (data, layout) = builder.finish();
+    (params, data, layout)
+}
+
+impl<'a> Decoder<'a> {
+    fn read(&mut self, reader: &mut BitstreamReader) -> Result<()> {
+        loop {
+            match reader.next()? {
+                Entry::EndBlock => return Ok(()),
+                Entry::SubBlock(CONSTANTS_BLOCK_ID) => {
+                    reader.enter_block(CONSTANTS_BLOCK_ID)?;
+                    self.values.read_constants(reader, self.types, self.table)?;
+                }
+                Entry::SubBlock(VALUE_SYMTAB_BLOCK_ID) => {
+                    reader.enter_block(VALUE_SYMTAB_BLOCK_ID)?;
+                    self.read_names(reader)?;
+                }
+                // Metadata, attachments and use lists do not affect codegen.
+                Entry::SubBlock(_) => reader.skip_block()?,
+                Entry::Record(record) => self.record(&record)?,
+            }
+        }
+    }
+
+    fn read_names(&mut self, reader: &mut BitstreamReader) -> Result<()> {
+        loop {
+            let record = match reader.next()? {
+                Entry::EndBlock => return Ok(()),
+                Entry::SubBlock(_) => {
+                    reader.skip_block()?;
+                    continue;
+                }
+                Entry::Record(record) => record,
+            };
+            let names = match record.code {
+                VST_CODE_ENTRY => &mut self.value_names,
+                VST_CODE_BBENTRY => &mut self.block_names,
+                _ => continue,
+            };
+            let id = op(&record.ops, 0)? as usize;
+            names.insert(
+                id,
+                String::from_utf8_lossy(&chars(&record.ops[1..])).into_owned(),
+            );
+        }
+    }
+
+    fn ty(&self, ops: &mut Ops) -> Result<Type> {
+        self.table.get(self.types, ops.next()? as usize)
+    }
+
+    /// Reads a value id relative to the next value id.
+    fn value(&self, ops: &mut Ops) -> Result<usize> {
+        let next = self.values.len() as u32;
+        Ok(next.wrapping_sub(ops.next()? as u32) as usize)
+    }
+
+    /// Reads a relative value id followed by its type if it is a forward
+    /// reference.
+    fn value_and_type(&self, ops: &mut Ops) -> Result<(Type, usize)> {
+        let id = self.value(ops)?;
+        let ty = match self.values.type_of(self.types, id) {
+            Some(ty) => ty,
+            None => self.ty(ops)?,
+        };
+        Ok((ty, id))
+    }
+
+    fn signed_value(&self, ops: &mut Ops) -> Result<usize> {
+        Ok((self.values.len() as i64 - decode_signed(ops.next()?)) as usize)
+    }
+
+    fn record(&mut self, record: &Record) -> Result<()> {
+        let mut ops = Ops {
+            ops: &record.ops,
+            pos: 0,
+        };
+        let ops = &mut ops;
+        let (inst, ty) = match record.code {
+            FUNC_CODE_DECLAREBLOCKS | FUNC_CODE_DEBUG_LOC | FUNC_CODE_DEBUG_LOC_AGAIN => {
+                return Ok(())
+            }
+            FUNC_CODE_INST_BINOP => {
+                let (ty, lhs) = self.value_and_type(ops)?;
+                let rhs = self.value(ops)?;
+                let opcode = binary_opcode(ops.next()?)?;
+                let flags = ops.next_or(0);
+                let (nuw, nsw, exact) = match opcode {
+                    Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Shl => {
+                        (flags & 1 != 0, flags & 2 != 0, false)
+                    }
+                    Opcode::SDiv | Opcode::LShr | Opcode::AShr => (false, false, flags & 1 != 0),
+                    _ => (false, false, false),
+                };
+                let inst = Inst::IntBinary {
+                    opcode,
+                    ty,
+                    args: [lhs, rhs],
+                    nuw,
+                    nsw,
+                    exact,
+                };
+                (inst, ty)
+            }
+            FUNC_CODE_INST_CAST => {
+                let (from, arg) = self.value_and_type(ops)?;
+                let to = self.ty(ops)?;
+                let opcode = cast_opcode(ops.next()?)?;
+                let inst = Inst::Cast {
+                    opcode,
+                    tys: [from, to],
+                    arg,
+                };
+                (inst, to)
+            }
+            FUNC_CODE_INST_RET => {
+                let val = if ops.remaining() == 0 {
+                    None
+                } else {
+                    Some(self.value_and_type(ops)?)
+                };
+                (Inst::Ret(val), VOID)
+            }
+            FUNC_CODE_INST_BR => {
+                let inst = if ops.remaining() == 1 {
+                    Inst::Br(ops.next()? as usize)
+                } else {
+                    let blocks = [ops.next()? as usize, ops.next()? as usize];
+                    Inst::CondBr {
+                        arg: self.value(ops)?,
+                        blocks,
+                    }
+                };
+                (inst, VOID)
+            }
+            FUNC_CODE_INST_SWITCH => {
+                if op(&record.ops, 0)? >> 16 == 0x4B5 {
+                    bail!("Unsupported switch with case ranges");
+                }
+                let ty = self.ty(ops)?;
+                let cond = self.value(ops)?;
+                let default = ops.next()? as usize;
+                let cases = ops
+                    .rest()
+                    .chunks_exact(2)
+                    .map(|case| (case[0] as usize, case[1] as usize))
+                    .collect();
+                let inst = Inst::Switch {
+                    ty,
+                    cond,
+                    default,
+                    cases,
+                };
+                (inst, VOID)
+            }
+            FUNC_CODE_INST_UNREACHABLE => (Inst::Unreachable, VOID),
+            FUNC_CODE_INST_PHI => {
+                let ty = self.ty(ops)?;
+                let mut incoming = vec![];
+                // A trailing odd operand holds fast-math flags.
+                while ops.remaining() >= 2 {
+                    let value = self.signed_value(ops)?;
+                    incoming.push((value, ops.next()? as usize));
+                }
+                (Inst::Phi { ty, incoming }, ty)
+            }
+            FUNC_CODE_INST_ALLOCA => {
+                let ty = self.ty(ops)?;
+                let _size_ty = ops.next()?;
+                let size = ops.next()? as usize;
+                let packed = ops.next()?;
+                if packed & ALLOCA_EXPLICIT_TYPE == 0 {
+                    bail!("Typed pointers are not supported, use opaque pointers");
+                }
+                let align = decode_align((packed & 0x1f) | ((packed >> 8) & 0x7) << 5);
+                let inst = Inst::Alloca { ty, size, align };
+                (inst, self.types.base_mut().pointer(I32))
+            }
+            FUNC_CODE_INST_LOAD => {
+                let addr = self.value_and_type(ops)?;
+                if ops.remaining() != 3 {
+                    bail!("Typed pointers are not supported, use opaque pointers");
+                }
+                let ty = self.ty(ops)?;
+                let align = decode_align(ops.next()?);
+                (Inst::Load { ty, addr, align }, ty)
+            }
+            FUNC_CODE_INST_STORE => {
+                let dst = self.value_and_type(ops)?;
+                let src = self.value_and_type(ops)?;
+                let align = decode_align(ops.next()?);
+                (Inst::Store { src, dst, align }, VOID)
+            }
+            FUNC_CODE_INST_EXTRACTVAL => {
+                let aggre = self.value_and_type(ops)?;
+                let indices = ops.rest().to_vec();
+                let ty = self
+                    .types
+                    .base()
+                    .element_at_(aggre.0, indices.iter().map(|&idx| idx as usize))
+                    .ok_or_else(|| anyhow!("Invalid extractvalue index"))?;
+                let inst = Inst::ExtractValue { ty, aggre, indices };
+                (inst, ty)
+            }
+            FUNC_CODE_INST_INSERTVAL => {
+                let aggre = self.value_and_type(ops)?;
+                let elt = self.value_and_type(ops)?;
+                let indices = ops.rest().to_vec();
+                (
+                    Inst::InsertValue {
+                        aggre,
+                        elt,
+                        indices,
+                    },
+                    aggre.0,
+                )
+            }
+            FUNC_CODE_INST_CMP2 => {
+                let (ty, lhs) = self.value_and_type(ops)?;
+                let rhs = self.value(ops)?;
+                let cond = icmp_cond(ops.next()?)?;
+                let inst = Inst::ICmp {
+                    ty,
+                    args: [lhs, rhs],
+                    cond,
+                };
+                (inst, I1)
+            }
+            FUNC_CODE_INST_GEP => {
+                let inbounds = ops.next()? != 0;
+                let mut tys = vec![self.ty(ops)?];
+                let mut args = vec![];
+                while ops.remaining() > 0 {
+                    let (ty, arg) = self.value_and_type(ops)?;
+                    tys.push(ty);
+                    args.push(arg);
+                }
+                let inst = Inst::GetElementPtr {
+                    inbounds,
+                    tys,
+                    args,
+                };
+                (inst, self.types.base_mut().pointer(I32))
+            }
+            FUNC_CODE_INST_CALL => self.call(ops)?,
+            code => bail!("Unsupported instruction (instruction code {})", code),
+        };
+
+        let result = if ty == VOID {
+            None
+        } else {
+            self.values.push(ValueEntry::Local { ty });
+            Some(self.values.len() - 1)
+        };
+        let ends_block = inst.ends_block();
+        self.blocks.last_mut().unwrap().push((inst, result));
+        if ends_block {
+            self.blocks.push(vec![]);
+        }
+        Ok(())
+    }
+
+    fn call(&self, ops: &mut Ops) -> Result<(Inst, Type)> {
+        let _attrs = ops.next()?;
+        let cc = ops.next()?;
+        if cc & CALL_FMF != 0 {
+            let _fmf = ops.next()?;
+        }
+        if cc & CALL_EXPLICIT_TYPE == 0 {
+            bail!("Calls without an explicit function type are not supported");
+        }
+        let fn_ty_id = ops.next()? as usize;
+        let (is_var_arg, ret, params) = match self.table.entry(fn_ty_id)? {
+            TypeEntry::Function {
+                is_var_arg,
+                ret,
+                params,
+            } => (*is_var_arg, *ret, params.clone()),
+            _ => bail!("Call through a non-function type"),
+        };
+        let (_, callee) = self.value_and_type(ops)?;
+
+        let mut args = vec![];
+        for param in params {
+            if matches!(self.table.entry(param)?, TypeEntry::Label) {
+                bail!("Unsupported label argument");
+            }
+            let ty = self.table.get(self.types, param)?;
+            args.push((ty, self.value(ops)?));
+        }
+        if is_var_arg {
+            while ops.remaining() > 0 {
+                args.push(self.value_and_type(ops)?);
+            }
+        }
+
+        let ret = self.table.get(self.types, ret)?;
+        // Calls to variadic functions spell out the whole function type.
+        let ty = if is_var_arg {
+            self.table.get(self.types, fn_ty_id)?
+        } else {
+            ret
+        };
+        Ok((Inst::Call { ty, callee, args }, ret))
+    }
+
+    fn build(self, first_arg: usize, param_tys: &[Type]) -> Result<(Vec<Parameter>, Data, Layout)> {
+        let mut blocks = self.blocks;
+        if blocks.last().is_some_and(|insts| insts.is_empty()) {
+            blocks.pop();
+        }
+
+        // Name the unnamed values the way the LLVM assembly writer does.
+        let mut next = 0;
+        let mut name_of = |name: Option<&String>| {
+            name.map_or_else(
+                || {
+                    next += 1;
+                    Name::Number(next - 1)
+                },
+                |name| Name::Name(name.clone()),
+            )
+        };
+        let mut names = FxHashMap::default();
+        let mut block_names = FxHashMap::default();
+        let params = param_tys
+            .iter()
+            .enumerate()
+            .map(|(i, &ty)| Parameter {
+                name: name_of(self.value_names.get(&(first_arg + i))),
+                ty,
+                attrs: vec![],
+            })
+            .collect::<Vec<_>>();
+        for (i, insts) in blocks.iter().enumerate() {
+            block_names.insert(i, name_of(self.block_names.get(&i)));
+            for (_, result) in insts {
+                if let Some(id) = result {
+                    names.insert(*id, name_of(self.value_names.get(id)));
+                }
+            }
+        }
+
+        let mut builder = Builder {
+            types: self.types,
+            table: self.table,
+            values: self.values,
+            builder: FunctionBuilder::new(),
+            block_names,
+        };
+        for (i, param) in params.iter().enumerate() {
+            builder.builder.add_argument(first_arg + i, i, param);
+        }
+        for (i, insts) in blocks.into_iter().enumerate() {
+            let block = builder.block(i);
+            builder.builder.switch_to_block(block);
+            for (inst, result) in insts {
+                let inst = builder.instruction(inst)?;
+                let dest = result.map(|id| (id, names[&id].clone()));
+                builder.builder.append(inst, dest);
+            }
+        }
+        let (data, layout) = builder.builder.finish();
+        Ok((params, data, layout))
+    }
+}
+
+struct Builder<'a> {
+    types: &'a Types,
+    table: &'a TypeTable,
+    values: &'a ValueList,
+    builder: FunctionBuilder<usize, usize>,
+    block_names: FxHashMap<usize, Name>,
+}
+
+impl<'a> Builder<'a> {
+    fn value(&mut self, id: usize) -> Result<ValueId> {
+        if self.values.is_constant(id) {
+            let konst = self.values.constant(self.types, self.table, id)?;
+            return Ok(self.builder.constant(konst));
+        }
+        Ok(self.builder.value(id))
+    }
+
+    fn block(&mut self, block: usize) -> BasicBlockId {
+        let block_names = &self.block_names;
+        self.builder.block(block, || block_names[&block].clone())
+    }
+
+    fn indices(&mut self, indices: &[u64]) -> Vec<ValueId> {
+        indices
+            .iter()
+            .map(|&idx| self.builder.constant(ConstantInt::Int64(idx as i64).into()))
+            .collect()
+    }
+
+    fn instruction(&mut self, inst: Inst) -> Result<Instruction> {
+        let cur_block = self.builder.cur_block();
+        let inst = match inst {
+            Inst::Alloca { ty, size, align } => {
+                let num_elements = match self.values.constant(self.types, self.table, size) {
+                    Ok(ConstantValue::Int(
+                        ConstantInt::Int64(1) | ConstantInt::Int8(1) | ConstantInt::Int1(true),
+                    )) => 1.into(),
+                    Ok(konst) => konst,
+                    Err(_) => bail!("Unsupported alloca of a dynamic size"),
+                };
+                Opcode::Alloca
+                    .with_block(cur_block)
+                    .with_operand(Operand::Alloca(Alloca {
+                        tys: [ty, I32],
+                        num_elements,
+                        align,
+                    }))
+                    .with_ty(self.types.base_mut().pointer(ty))
+            }
+            Inst::Phi { ty, incoming } => {
+                let mut args = vec![];
+                let mut blocks = vec![];
+                for (value, block) in incoming {
+                    args.push(self.value(value)?);
+                    blocks.push(self.block(block));
+                }
+                Opcode::Phi
+                    .with_block(cur_block)
+                    .with_operand(Operand::Phi(Phi { ty, args, blocks }))
+                    .with_ty(ty)
+            }
+            Inst::Load { ty, addr, align } => {
+                let (addr_ty, addr) = (addr.0, self.value(addr.1)?);
+                Opcode::Load
+                    .with_block(cur_block)
+                    .with_operand(Operand::Load(Load {
+                        tys: [ty, addr_ty],
+                        addr,
+                        align,
+                    }))
+                    .with_ty(ty)
+            }
+            Inst::Store { src, dst, align } => {
+                let src_val = self.value(src.1)?;
+                let dst_val = self.value(dst.1)?;
+                Opcode::Store
+                    .with_block(cur_block)
+                    .with_operand(Operand::Store(Store {
+                        tys: [src.0, dst.0],
+                        args: [src_val, dst_val],
+                        align,
+                    }))
+            }
+            Inst::InsertValue {
+                aggre,
+                elt,
+                indices,
+            } => {
+                let mut args = vec![self.value(aggre.1)?, self.value(elt.1)?];
+                args.extend(self.indices(&indices));
+                Opcode::InsertValue
+                    .with_block(cur_block)
+                    .with_operand(Operand::InsertValue(InsertValue {
+                        tys: [aggre.0, elt.0],
+                        args,
+                    }))
+                    .with_ty(aggre.0)
+            }
+            Inst::ExtractValue { ty, aggre, indices } => {
+                let mut args = vec![self.value(aggre.1)?];
+                args.extend(self.indices(&indices));
+                Opcode::ExtractValue
+                    .with_block(cur_block)
+                    .with_ty(ty)
+                    .with_operand(Operand::ExtractValue(ExtractValue { ty: aggre.0, args }))
+            }
+            Inst::IntBinary {
+                opcode,
+                ty,
+                args,
+                nuw,
+                nsw,
+                exact,
+            } => {
+                let lhs = self.value(args[0])?;
+                let rhs = self.value(args[1])?;
+                opcode
+                    .with_block(cur_block)
+                    .with_operand(Operand::IntBinary(IntBinary {
+                        ty,
+                        args: [lhs, rhs],
+                        nuw,
+                        nsw,
+                        exact,
+                    }))
+                    .with_ty(ty)
+            }
+            Inst::ICmp { ty, args, cond } => {
+                let lhs = self.value(args[0])?;
+                let rhs = self.value(args[1])?;
+                Opcode::ICmp
+                    .with_block(cur_block)
+                    .with_operand(Operand::ICmp(ICmp {
+                        ty,
+                        args: [lhs, rhs],
+                        cond,
+                    }))
+                    .with_ty(I1)
+            }
+            Inst::Cast { opcode, tys, arg } => {
+                let arg = self.value(arg)?;
+                opcode
+                    .with_block(cur_block)
+                    .with_operand(Operand::Cast(Cast { tys, arg }))
+                    .with_ty(tys[1])
+            }
+            Inst::GetElementPtr {
+                inbounds,
+                tys,
+                args,
+            } => {
+                let args = args
+                    .into_iter()
+                    .map(|arg| self.value(arg))
+                    .collect::<Result<Vec<_>>>()?;
+                Opcode::GetElementPtr
+                    .with_block(cur_block)
+                    .with_ty(self.types.base_mut().pointer(I32))
+                    .with_operand(Operand::GetElementPtr(GetElementPtr {
+                        inbounds,
+                        tys,
+                        args,
+                    }))
+            }
+            Inst::Call { ty, callee, args } => {
+                let callee = match self.values.get(callee)? {
+                    ValueEntry::Global { name } => self
+                        .builder
+                        .constant(ConstantValue::GlobalRef(name.clone(), ty)),
+                    _ => self.value(callee)?,
+                };
+                let mut tys = vec![ty];
+                let mut vals = vec![callee];
+                let mut param_attrs = vec![];
+                for (arg_ty, arg) in args {
+                    tys.push(arg_ty);
+                    vals.push(self.value(arg)?);
+                    param_attrs.push(vec![]);
+                }
+                Opcode::Call
+                    .with_block(cur_block)
+                    .with_operand(Operand::Call(Call {
+                        tys,
+                        args: vals,
+                        param_attrs,
+                        ret_attrs: vec![],
+                        func_attrs: vec![],
+                    }))
+                    .with_ty(ty)
+            }
+            Inst::Br(block) => {
+                let block = self.block(block);
+                Opcode::Br
+                    .with_block(cur_block)
+                    .with_operand(Operand::Br(Br { block }))
+            }
+            Inst::CondBr { arg, blocks } => {
+                let arg = self.value(arg)?;
+                let iftrue = self.block(blocks[0]);
+                let iffalse = self.block(blocks[1]);
+                Opcode::CondBr
+                    .with_block(cur_block)
+                    .with_operand(Operand::CondBr(CondBr {
+                        arg,
+                        blocks: [iftrue, iffalse],
+                    }))
+            }
+            Inst::Switch {
+                ty,
+                cond,
+                default,
+                cases,
+            } => {
+                let cond = self.value(cond)?;
+                let default = self.block(default);
+                let mut tys = vec![ty];
+                let mut args = vec![cond];
+                let mut blocks = vec![default];
+                for (case, block) in cases {
+                    tys.push(ty);
+                    args.push(self.value(case)?);
+                    blocks.push(self.block(block));
+                }
+                Opcode::Switch
+                    .with_block(cur_block)
+                    .with_operand(Operand::Switch(Switch { tys, args, blocks }))
+            }
+            Inst::Ret(val) => {
+                let (ty, val) = match val {
+                    Some((ty, val)) => (ty, Some(self.value(val)?)),
+                    None => (VOID, None),
+                };
+                Opcode::Ret
+                    .with_block(cur_block)
+                    .with_operand(Operand::Ret(Ret { val, ty }))
+            }
+            Inst::Unreachable => Opcode::Unreachable
+                .with_block(cur_block)
+                .with_operand(Operand::Unreachable),
+        };
+        Ok(inst)
+    }
+}
+
+/// Decodes an alignment stored as its logarithm plus one.
+pub(super) fn decode_align(encoded: u64) -> u32 {
+    if encoded == 0 {
+        0
+    } else {
+        1 << (encoded - 1)
+    }
+}
+
+fn binary_opcode(opcode: u64) -> Result<Opcode> {
+    Ok(match opcode {
+        0 => Opcode::Add,
+        1 => Opcode::Sub,
+        2 => Opcode::Mul,
+        4 => Opcode::SDiv,
+        6 => Opcode::SRem,
+        7 => Opcode::Shl,
+        8 => Opcode::LShr,
+        9 => Opcode::AShr,
+        10 => Opcode::And,
+        11 => Opcode::Or,
+        12 => Opcode::Xor,
+        opcode => bail!("Unsupported binary operator (opcode {})", opcode),
+    })
+}
+
+fn cast_opcode(opcode: u64) -> Result<Opcode> {
+    Ok(match opcode {
+        0 => Opcode::Trunc,
+        1 => Opcode::Zext,
+        2 => Opcode::Sext,
+        9 => Opcode::PtrToInt,
+        10 => Opcode::IntToPtr,
+        11 => Opcode::Bitcast,
+        opcode => bail!("Unsupported cast (opcode {})", opcode),
+    })
+}
+
+fn icmp_cond(predicate: u64) -> Result<ICmpCond> {
+    Ok(match predicate {
+        32 => ICmpCond::Eq,
+        33 => ICmpCond::Ne,
+        34 => ICmpCond::Ugt,
+        35 => ICmpCond::Uge,
+        36 => ICmpCond::Ult,
+        37 => ICmpCond::Ule,
+        38 => ICmpCond::Sgt,
+        39 => ICmpCond::Sge,
+        40 => ICmpCond::Slt,
+        41 => ICmpCond::Sle,
+        predicate => bail!("Unsupported comparison (predicate {})", predicate),
+    })
+}
@@ -1,16 +1,25 @@
+//! Reader for LLVM bitcode files. The result is the same module the assembly
+//! parser produces for the textual form of the bitcode.
+
+pub mod bitstream;
+pub mod constants;
+pub mod function;
 pub mod module;
+pub mod types;
 
-use anyhow::Result;
+use crate::codegen::core::ir::module::Module;
+use anyhow::{anyhow, Result};
 use std::io::{BufRead, Cursor, Read};
 
-pub fn parse<T>(cursor: &mut Cursor<T>) -> Result<()>
-where
-    T: BufRead + Read + PartialEq + AsRef<[u8]>,
-{
-    if !parse_magic(cursor, b"\x42\x43\xc0\xde")? {
-        return Err(anyhow::anyhow!("Invalid magic number"));
+const WRAPPER_MAGIC: u32 = 0x0B17C0DE;
+
+pub fn parse(bytes: &[u8]) -> Result<Module> {
+    let bytes = strip_wrapper(bytes)?;
+    let mut cursor = Cursor::new(bytes);
+    if !parse_magic(&mut cursor, b"\x42\x43\xc0\xde")? {
+        return Err(anyhow!("Invalid magic number"));
     }
-    Ok(())
+    module::parse(&bytes[4..])
 }
 
 pub fn parse_magic<T>(cursor: &mut Cursor<T>, magic: &[u8]) -> Result<bool>
@@ -22,14 +31,236 @@ where
     Ok(buf == magic)
 }
 
+/// Returns the bitcode inside a wrapper header, as emitted for Darwin targets.
+fn strip_wrapper(bytes: &[u8]) -> Result<&[u8]> {
+    let word = |i: usize| {
+        bytes
+            .get(i * 4..i * 4 + 4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+    };
+    if word(0) != Some(WRAPPER_MAGIC) {
+        return Ok(bytes);
+    }
+    let (offset, size) = match (word(2), word(3)) {
+        (Some(offset), Some(size)) => (offset as usize, size as usize),
+        _ => return Err(anyhow!("Truncated bitcode wrapper header")),
+    };
+    bytes
+        .get(offset..offset + size)
+        .ok_or_else(|| anyhow!("Bitcode wrapper points outside of the file"))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::codegen::isa::ola::{emit, Ola};
+    use inkwell::{context::Context, memory_buffer::MemoryBuffer};
+
+    /// The bitcode LLVM writes for the assembly `source`, with the assembly
+    /// LLVM prints for the same module
+    fn llvm_output(source: &str) -> (Vec<u8>, String) {
+        let context = Context::create();
+        let buffer = MemoryBuffer::create_from_memory_range_copy(source.as_bytes(), "bitcode");
+        let llvm_module = context
+            .create_module_from_ir(buffer)
+            .expect("invalid LLVM IR");
+        let bitcode = llvm_module.write_bitcode_to_memory();
+        (bitcode.as_slice().to_vec(), llvm_module.to_string())
+    }
+
+    /// Checks that the bitcode of `source` reads as the module the assembly
+    /// parser makes of what LLVM prints for it
+    fn assert_reads_as_parsed(source: &str) {
+        let (bitcode, printed) = llvm_output(source);
+        let read = parse(&bitcode).expect("failed to read bitcode");
+        let parsed = Module::try_from(printed.as_str()).expect("failed to parse");
+        assert_eq!(format!("{:?}", read), format!("{:?}", parsed));
+    }
+
+    #[test]
+    fn reject_invalid_magic() {
+        assert!(parse(b"\x42\x43\xc0\xdf").is_err());
+    }
+
+    #[test]
+    fn reject_malformed_bitcode() {
+        let (bitcode, _) = llvm_output("define i64 @main() {\nentry:\n  ret i64 0\n}\n");
+        assert!(parse(&[]).is_err());
+        assert!(parse(&bitcode[..bitcode.len() / 2]).is_err());
+
+        // The stream ends before the module block does
+        assert!(parse(&bitcode[..bitcode.len() - 4]).is_err());
+
+        // The wrapper header points past the end of the file, or is cut short
+        let mut wrapper = vec![];
+        for word in [WRAPPER_MAGIC, 0, 20, bitcode.len() as u32 + 1, 0] {
+            wrapper.extend(word.to_le_bytes());
+        }
+        wrapper.extend(&bitcode);
+        assert!(parse(&wrapper).is_err());
+        assert!(parse(&wrapper[..12]).is_err());
+    }
+
+    #[test]
+    fn read_wrapped_bitcode() {
+        let source = "define i64 @main(i64 %0) {\nentry:\n  ret i64 %0\n}\n";
+        let (bitcode, _) = llvm_output(source);
+        let mut wrapper = vec![];
+        for word in [WRAPPER_MAGIC, 0, 20, bitcode.len() as u32, 0] {
+            wrapper.extend(word.to_le_bytes());
+        }
+        wrapper.extend(&bitcode);
+        assert_eq!(
+            format!("{:?}", parse(&wrapper).expect("failed to read bitcode")),
+            format!("{:?}", parse(&bitcode).expect("failed to read bitcode"))
+        );
+    }
+
+    #[test]
+    fn bitcode_matches_assembly_parser() {
+        assert_reads_as_parsed(
+            r#"
+source_filename = "bitcode.ola"
+
+@heap_address = internal global i64 -4294967353
+
+declare void @builtin_assert(i64)
+
+define void @main() {
+entry:
+  %0 = call i64 @sum(i64 10)
+  %1 = call i64 @select(i64 %0)
+  call void @builtin_assert(i64 %1)
+  ret void
+}
+
+define i64 @sum(i64 %n) {
+entry:
+  %total = alloca i64, align 8
+  store i64 0, ptr %total, align 8
+  br label %cond
+
+cond:
+  %i = phi i64 [ 0, %entry ], [ %next, %body ]
+  %done = icmp uge i64 %i, %n
+  br i1 %done, label %endfor, label %body
+
+body:
+  %0 = load i64, ptr %total, align 8
+  %1 = add nuw i64 %0, %i
+  store i64 %1, ptr %total, align 8
+  %next = add i64 %i, 1
+  br label %cond
+
+endfor:
+  %2 = load i64, ptr %total, align 8
+  ret i64 %2
+}
+
+define i64 @select(i64 %0) {
+entry:
+  switch i64 %0, label %default [
+    i64 45, label %hit
+    i64 7, label %miss
+  ]
+
+hit:
+  ret i64 1
+
+miss:
+  ret i64 0
+
+default:
+  %1 = sub i64 %0, 45
+  %2 = icmp eq i64 %1, 0
+  %3 = zext i1 %2 to i64
+  ret i64 %3
+}
+"#,
+        );
+    }
 
     #[test]
-    #[ignore]
-    fn test1() {
-        let mut cursor = Cursor::new(b"\x42\x43\xc0\xde".as_slice());
-        parse(&mut cursor).unwrap();
+    fn read_structs_and_gep() {
+        assert_reads_as_parsed(
+            r#"
+%struct.Point = type { i64, i64 }
+
+define i64 @get_y(ptr %0) {
+entry:
+  %1 = getelementptr inbounds %struct.Point, ptr %0, i32 0, i32 1
+  %2 = load i64, ptr %1, align 8
+  ret i64 %2
+}
+
+define i64 @main() {
+entry:
+  %p = alloca %struct.Point, align 8
+  %arr = alloca [3 x i64], align 8
+  %x = getelementptr inbounds %struct.Point, ptr %p, i32 0, i32 0
+  store i64 3, ptr %x, align 8
+  %e = getelementptr [3 x i64], ptr %arr, i64 0, i64 2
+  store i64 4, ptr %e, align 8
+  %v = insertvalue { i64, i64 } undef, i64 1, 0
+  %w = extractvalue { i64, i64 } %v, 0
+  %y = call i64 @get_y(ptr %p)
+  %z = add i64 %y, %w
+  ret i64 %z
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn read_global_initializers() {
+        assert_reads_as_parsed(
+            r#"
+%struct.Pair = type { i64, ptr }
+
+@count = global i64 7
+@zeros = internal global [4 x i64] zeroinitializer
+@name = private constant [5 x i8] c"hello"
+@table = global [3 x i64] [i64 1, i64 2, i64 3]
+@second = global ptr getelementptr ([3 x i64], ptr @table, i64 0, i64 1)
+@pair = global %struct.Pair { i64 5, ptr @count }
+@nothing = global ptr null
+@later = global i64 undef
+
+define ptr @first() {
+entry:
+  %0 = load i64, ptr @count, align 8
+  %1 = icmp eq i64 %0, 0
+  br i1 %1, label %none, label %some
+
+none:
+  ret ptr null
+
+some:
+  ret ptr @table
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn skip_metadata() {
+        // The metadata is not read, but the code is the same
+        let source = r#"
+define i64 @main(i64 %0) {
+entry:
+  %1 = add i64 %0, 1, !ola.note !0
+  ret i64 %1
+}
+
+!llvm.ident = !{!1}
+
+!0 = !{!"note"}
+!1 = !{!"olac"}
+"#;
+        let (bitcode, _) = llvm_output(source);
+        let read = parse(&bitcode).expect("failed to read bitcode");
+        let parsed = Module::try_from(source).expect("failed to parse");
+        let isa = Ola::default();
+        assert_eq!(emit(&read, &isa).program, emit(&parsed, &isa).program);
     }
 }
@@ -1 +1,410 @@
+use super::{
+    bitstream::{BitstreamReader, Entry, BLOCKINFO_BLOCK_ID},
+    constants::{ValueEntry, ValueList},
+    function::decode_align,
+    types::{chars, op, TypeEntry, TypeTable},
+};
+use crate::codegen::core::ir::{
+    function::Function,
+    module::{
+        global_variable::GlobalVariable, linkage::Linkage, name::Name,
+        preemption_specifier::PreemptionSpecifier, unnamed_addr::UnnamedAddr,
+        visibility::Visibility, Module,
+    },
+    types::I32,
+};
+use anyhow::{anyhow, bail, Result};
+use std::collections::VecDeque;
 
+const MODULE_BLOCK_ID: u64 = 8;
+const CONSTANTS_BLOCK_ID: u64 = 11;
+const FUNCTION_BLOCK_ID: u64 = 12;
+const TYPE_BLOCK_ID_NEW: u64 = 17;
+const STRTAB_BLOCK_ID: u64 = 23;
+
+const MODULE_CODE_VERSION: u64 = 1;
+const MODULE_CODE_TRIPLE: u64 = 2;
+const MODULE_CODE_DATALAYOUT: u64 = 3;
+const MODULE_CODE_SECTIONNAME: u64 = 5;
+const MODULE_CODE_GLOBALVAR: u64 = 7;
+const MODULE_CODE_FUNCTION: u64 = 8;
+const MODULE_CODE_SOURCE_FILENAME: u64 = 16;
+
+const STRTAB_BLOB: u64 = 1;
+
+/// A `FUNCTION` record, kept until the constants it refers to are known.
+struct FunctionRecord {
+    name: String,
+    ty: usize,
+    linkage: Linkage,
+    preemption_specifier: PreemptionSpecifier,
+    visibility: Visibility,
+    unnamed_addr: Option<UnnamedAddr>,
+    section: Option<String>,
+    personality: Option<usize>,
+}
+
+struct ModuleReader<'a> {
+    module: Module,
+    strtab: &'a [u8],
+    table: TypeTable,
+    values: ValueList,
+    sections: Vec<String>,
+    globals: Vec<(GlobalVariable, Option<usize>)>,
+    functions: Vec<FunctionRecord>,
+    bodies: Vec<Option<Function>>,
+    pending_bodies: VecDeque<usize>,
+    unnamed: usize,
+}
+
+/// Reads the bitstream that follows the bitcode magic number.
+pub fn parse(stream: &[u8]) -> Result<Module> {
+    let mut reader = BitstreamReader::new(stream);
+
+    // Global names live in the string table, which follows the module block.
+    let mut module_pos = None;
+    let mut strtab = vec![];
+    while !reader.at_end() {
+        match reader.next()? {
+            Entry::SubBlock(MODULE_BLOCK_ID) => {
+                module_pos = Some(reader.bit_position());
+                reader.skip_block()?;
+            }
+            Entry::SubBlock(STRTAB_BLOCK_ID) => {
+                reader.enter_block(STRTAB_BLOCK_ID)?;
+                loop {
+                    match reader.next()? {
+                        Entry::EndBlock => break,
+                        Entry::SubBlock(_) => reader.skip_block()?,
+                        Entry::Record(record) => {
+                            if record.code == STRTAB_BLOB {
+                                strtab = record.blob.unwrap_or_default();
+                            }
+                        }
+                    }
+                }
+            }
+            Entry::SubBlock(BLOCKINFO_BLOCK_ID) => reader.read_block_info()?,
+            Entry::SubBlock(_) => reader.skip_block()?,
+            Entry::Record(_) | Entry::EndBlock => {
+                bail!("Malformed bitcode: record outside of a block")
+            }
+        }
+    }
+
+    let module_pos = module_pos.ok_or_else(|| anyhow!("Bitcode contains no module"))?;
+    reader.seek(module_pos);
+    reader.enter_block(MODULE_BLOCK_ID)?;
+    ModuleReader {
+        module: Module::new(),
+        strtab: &strtab,
+        table: TypeTable::new(),
+        values: ValueList::default(),
+        sections: vec![],
+        globals: vec![],
+        functions: vec![],
+        bodies: vec![],
+        pending_bodies: VecDeque::new(),
+        unnamed: 0,
+    }
+    .read(&mut reader)
+}
+
+impl<'a> ModuleReader<'a> {
+    fn read(mut self, reader: &mut BitstreamReader) -> Result<Module> {
+        loop {
+            match reader.next()? {
+                Entry::EndBlock => return self.finish(),
+                Entry::SubBlock(BLOCKINFO_BLOCK_ID) => reader.read_block_info()?,
+                Entry::SubBlock(TYPE_BLOCK_ID_NEW) => {
+                    reader.enter_block(TYPE_BLOCK_ID_NEW)?;
+                    self.table.read(reader)?;
+                }
+                Entry::SubBlock(CONSTANTS_BLOCK_ID) => {
+                    reader.enter_block(CONSTANTS_BLOCK_ID)?;
+                    self.values
+                        .read_constants(reader, &self.module.types, &self.table)?;
+                }
+                Entry::SubBlock(FUNCTION_BLOCK_ID) => {
+                    reader.enter_block(FUNCTION_BLOCK_ID)?;
+                    self.read_function(reader)?;
+                }
+                // Attributes, metadata and symbol tables do not affect codegen.
+                Entry::SubBlock(_) => reader.skip_block()?,
+                Entry::Record(record) => {
+                    let ops = &record.ops;
+                    match record.code {
+                        MODULE_CODE_VERSION => {
+                            if op(ops, 0)? != 2 {
+                                bail!("Unsupported bitcode version {}", ops[0]);
+                            }
+                        }
+                        MODULE_CODE_TRIPLE => {
+                            self.module.target.triple = string(ops);
+                        }
+                        MODULE_CODE_DATALAYOUT => {
+                            self.module.target.datalayout = string(ops).into();
+                        }
+                        MODULE_CODE_SOURCE_FILENAME => {
+                            self.module.source_filename = string(ops);
+                        }
+                        MODULE_CODE_SECTIONNAME => self.sections.push(string(ops)),
+                        MODULE_CODE_GLOBALVAR => self.global_variable(ops)?,
+                        MODULE_CODE_FUNCTION => self.function(ops)?,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn name(&self, ops: &[u64]) -> Result<Option<String>> {
+        let (offset, size) = (op(ops, 0)? as usize, op(ops, 1)? as usize);
+        let name = self
+            .strtab
+            .get(offset..offset + size)
+            .ok_or_else(|| anyhow!("Name outside of the string table"))?;
+        Ok((!name.is_empty()).then(|| String::from_utf8_lossy(name).into_owned()))
+    }
+
+    fn section(&self, index: u64) -> Result<Option<String>> {
+        if index == 0 {
+            return Ok(None);
+        }
+        self.sections
+            .get(index as usize - 1)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| anyhow!("Invalid section index {}", index))
+    }
+
+    // [strtab offset, strtab size, type, flags, initid, linkage, alignment,
+    //  section, visibility, threadlocal, unnamed_addr, externally_initialized,
+    //  dllstorageclass, comdat, attributes, preemption specifier]
+    fn global_variable(&mut self, ops: &[u64]) -> Result<()> {
+        let name = match self.name(ops)? {
+            Some(name) => Name::Name(name),
+            None => {
+                self.unnamed += 1;
+                Name::Number(self.unnamed - 1)
+            }
+        };
+        let flags = op(ops, 3)?;
+        if flags & 2 == 0 {
+            bail!("Typed pointers are not supported, use opaque pointers");
+        }
+        let ty = self.table.get(&self.module.types, op(ops, 2)? as usize)?;
+        let init = op(ops, 4)?.checked_sub(1).map(|id| id as usize);
+        let linkage = linkage(op(ops, 5)?);
+        let visibility = visibility(field(ops, 8));
+        let preemption_specifier = preemption_specifier(field(ops, 15), linkage, visibility);
+
+        self.values.push(ValueEntry::Global { name: name.clone() });
+        self.globals.push((
+            GlobalVariable {
+                name,
+                linkage: match linkage {
+                    Linkage::External if init.is_some() => None,
+                    linkage => Some(linkage),
+                },
+                preemption_specifier: (preemption_specifier == PreemptionSpecifier::DsoLocal)
+                    .then_some(preemption_specifier),
+                visibility: match visibility {
+                    Visibility::Default => None,
+                    visibility => Some(visibility),
+                },
+                unnamed_addr: unnamed_addr(field(ops, 10)),
+                is_constant: flags & 1 != 0,
+                ty,
+                init: None,
+                align: decode_align(field(ops, 6)),
+            },
+            init,
+        ));
+        Ok(())
+    }
+
+    // [strtab offset, strtab size, type, callingconv, isproto, linkage,
+    //  paramattrs, alignment, section, visibility, gc, unnamed_addr,
+    //  prologuedata, dllstorageclass, comdat, prefixdata, personalityfn,
+    //  preemption specifier]
+    fn function(&mut self, ops: &[u64]) -> Result<()> {
+        let name = self
+            .name(ops)?
+            .ok_or_else(|| anyhow!("Unsupported unnamed function"))?;
+        let ty = op(ops, 2)? as usize;
+        if !matches!(self.table.entry(ty)?, TypeEntry::Function { .. }) {
+            bail!("Typed pointers are not supported, use opaque pointers");
+        }
+        let is_prototype = op(ops, 4)? != 0;
+        let linkage = linkage(op(ops, 5)?);
+        let visibility = visibility(field(ops, 9));
+
+        self.values.push(ValueEntry::Global {
+            name: Name::Name(name.clone()),
+        });
+        if !is_prototype {
+            self.pending_bodies.push_back(self.functions.len());
+        }
+        self.functions.push(FunctionRecord {
+            name,
+            ty,
+            linkage,
+            preemption_specifier: preemption_specifier(field(ops, 17), linkage, visibility),
+            visibility,
+            unnamed_addr: unnamed_addr(field(ops, 11)),
+            section: self.section(field(ops, 8))?,
+            personality: field(ops, 16).checked_sub(1).map(|id| id as usize),
+        });
+        self.bodies.push(None);
+        Ok(())
+    }
+
+    /// Reads the body of the next function defined in the module. Bodies come
+    /// in the order of the `FUNCTION` records.
+    fn read_function(&mut self, reader: &mut BitstreamReader) -> Result<()> {
+        let index = self
+            .pending_bodies
+            .pop_front()
+            .ok_or_else(|| anyhow!("Function body without a definition"))?;
+        let func = self.build_function(index, Some(reader))?;
+        self.bodies[index] = Some(func);
+        Ok(())
+    }
+
+    fn build_function(
+        &mut self,
+        index: usize,
+        reader: Option<&mut BitstreamReader>,
+    ) -> Result<Function> {
+        let types = &self.module.types;
+        let record = &self.functions[index];
+        let (is_var_arg, ret, params) = match self.table.entry(record.ty)? {
+            TypeEntry::Function {
+                is_var_arg,
+                ret,
+                params,
+            } => (*is_var_arg, *ret, params.clone()),
+            _ => unreachable!(),
+        };
+        let result_ty = self.table.get(types, ret)?;
+        let param_tys = params
+            .into_iter()
+            .map(|param| self.table.get(types, param))
+            .collect::<Result<Vec<_>>>()?;
+        let (params, data, layout) = match reader {
+            Some(reader) => {
+                super::function::read(reader, types, &self.table, &mut self.values, &param_tys)?
+            }
+            None => super::function::declare(&param_tys),
+        };
+        let personality = match record.personality {
+            Some(id) => Some((
+                types.base_mut().pointer(I32),
+                self.values.constant(types, &self.table, id)?,
+            )),
+            None => None,
+        };
+
+        Ok(Function {
+            name: record.name.clone(),
+            is_var_arg,
+            result_ty,
+            linkage: record.linkage,
+            preemption_specifier: record.preemption_specifier,
+            visibility: record.visibility,
+            unnamed_addr: record.unnamed_addr,
+            ret_attrs: vec![],
+            func_attrs: vec![],
+            section: record.section.clone(),
+            params,
+            data,
+            layout,
+            types: types.clone(),
+            personality,
+        })
+    }
+
+    fn finish(mut self) -> Result<Module> {
+        if !self.pending_bodies.is_empty() {
+            bail!("Function definition without a body");
+        }
+
+        for (mut gv, init) in std::mem::take(&mut self.globals) {
+            if let Some(init) = init {
+                gv.init = Some(
+                    self.values
+                        .constant(&self.module.types, &self.table, init)?,
+                );
+            }
+            self.module.global_variables.insert(gv.name.clone(), gv);
+        }
+
+        for index in 0..self.functions.len() {
+            let func = match self.bodies[index].take() {
+                Some(func) => func,
+                None => self.build_function(index, None)?,
+            };
+            self.module.functions.alloc(func);
+        }
+
+        Ok(self.module)
+    }
+}
+
+fn string(ops: &[u64]) -> String {
+    String::from_utf8_lossy(&chars(ops)).into_owned()
+}
+
+/// Returns an optional trailing field of a record, which is zero when absent.
+fn field(ops: &[u64], i: usize) -> u64 {
+    ops.get(i).copied().unwrap_or(0)
+}
+
+fn linkage(code: u64) -> Linkage {
+    match code {
+        2 => Linkage::Appending,
+        3 => Linkage::Internal,
+        7 => Linkage::ExternalWeak,
+        8 => Linkage::Common,
+        9 | 13 | 14 => Linkage::Private,
+        12 => Linkage::AvailableExternally,
+        1 | 16 => Linkage::WeakAny,
+        10 | 17 => Linkage::WeakODR,
+        4 | 18 => Linkage::LinkOnceAny,
+        11 | 15 | 19 => Linkage::LinkOnceODR,
+        _ => Linkage::External,
+    }
+}
+
+fn visibility(code: u64) -> Visibility {
+    match code {
+        1 => Visibility::Hidden,
+        2 => Visibility::Protected,
+        _ => Visibility::Default,
+    }
+}
+
+fn unnamed_addr(code: u64) -> Option<UnnamedAddr> {
+    match code {
+        1 => Some(UnnamedAddr::Global),
+        2 => Some(UnnamedAddr::Local),
+        _ => None,
+    }
+}
+
+/// The textual form only spells out `dso_local` where it is not implied by
+/// the linkage or visibility, so neither does this.
+fn preemption_specifier(
+    code: u64,
+    linkage: Linkage,
+    visibility: Visibility,
+) -> PreemptionSpecifier {
+    let implied = matches!(linkage, Linkage::Internal | Linkage::Private)
+        || visibility != Visibility::Default;
+    if code == 1 && !implied {
+        PreemptionSpecifier::DsoLocal
+    } else {
+        PreemptionSpecifier::DsoPreemptable
+    }
+}
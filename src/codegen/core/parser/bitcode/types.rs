@@ -0,0 +1,203 @@
+use super::bitstream::{BitstreamReader, Entry};
+use crate::codegen::core::ir::{
+    module::name::Name,
+    types::{ArrayType, FunctionType, Type, Types, I1, I16, I32, I8, VOID},
+};
+use anyhow::{anyhow, bail, Result};
+use rustc_hash::FxHashMap;
+use std::cell::RefCell;
+
+const TYPE_CODE_NUMENTRY: u64 = 1;
+const TYPE_CODE_VOID: u64 = 2;
+const TYPE_CODE_LABEL: u64 = 5;
+const TYPE_CODE_OPAQUE: u64 = 6;
+const TYPE_CODE_INTEGER: u64 = 7;
+const TYPE_CODE_POINTER: u64 = 8;
+const TYPE_CODE_ARRAY: u64 = 11;
+const TYPE_CODE_METADATA: u64 = 16;
+const TYPE_CODE_STRUCT_ANON: u64 = 18;
+const TYPE_CODE_STRUCT_NAME: u64 = 19;
+const TYPE_CODE_STRUCT_NAMED: u64 = 20;
+const TYPE_CODE_FUNCTION: u64 = 21;
+const TYPE_CODE_OPAQUE_POINTER: u64 = 25;
+
+/// An entry of the type table, referring to other entries by index.
+#[derive(Debug, Clone)]
+pub enum TypeEntry {
+    Void,
+    Label,
+    Metadata,
+    Int(u32),
+    Pointer,
+    Array(u32, usize),
+    Struct {
+        name: Option<String>,
+        is_packed: bool,
+        elems: Vec<usize>,
+    },
+    Function {
+        is_var_arg: bool,
+        ret: usize,
+        params: Vec<usize>,
+    },
+    Unsupported(u64),
+}
+
+/// The type table of a module, with its entries translated to the core IR on
+/// first use.
+pub struct TypeTable {
+    entries: Vec<TypeEntry>,
+    translated: RefCell<FxHashMap<usize, Type>>,
+}
+
+impl TypeTable {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![],
+            translated: RefCell::new(FxHashMap::default()),
+        }
+    }
+
+    /// Reads the `TYPE_BLOCK_NEW` block the reader has just entered.
+    pub fn read(&mut self, reader: &mut BitstreamReader) -> Result<()> {
+        let mut struct_name = None;
+        loop {
+            let record = match reader.next()? {
+                Entry::EndBlock => return Ok(()),
+                Entry::SubBlock(_) => {
+                    reader.skip_block()?;
+                    continue;
+                }
+                Entry::Record(record) => record,
+            };
+            let ops = &record.ops;
+            let entry = match record.code {
+                TYPE_CODE_NUMENTRY => {
+                    self.entries
+                        .reserve(ops.first().copied().unwrap_or(0) as usize);
+                    continue;
+                }
+                TYPE_CODE_VOID => TypeEntry::Void,
+                TYPE_CODE_LABEL => TypeEntry::Label,
+                TYPE_CODE_METADATA => TypeEntry::Metadata,
+                TYPE_CODE_INTEGER => TypeEntry::Int(op(ops, 0)? as u32),
+                TYPE_CODE_OPAQUE_POINTER => TypeEntry::Pointer,
+                TYPE_CODE_POINTER => {
+                    bail!("Typed pointers are not supported, use opaque pointers")
+                }
+                TYPE_CODE_ARRAY => TypeEntry::Array(op(ops, 0)? as u32, op(ops, 1)? as usize),
+                TYPE_CODE_STRUCT_NAME => {
+                    struct_name = Some(String::from_utf8_lossy(&chars(ops)).into_owned());
+                    continue;
+                }
+                TYPE_CODE_STRUCT_ANON | TYPE_CODE_STRUCT_NAMED => TypeEntry::Struct {
+                    name: if record.code == TYPE_CODE_STRUCT_NAMED {
+                        struct_name.take()
+                    } else {
+                        None
+                    },
+                    is_packed: op(ops, 0)? != 0,
+                    elems: ops[1..].iter().map(|&ty| ty as usize).collect(),
+                },
+                TYPE_CODE_OPAQUE => TypeEntry::Struct {
+                    name: struct_name.take(),
+                    is_packed: false,
+                    elems: vec![],
+                },
+                TYPE_CODE_FUNCTION => TypeEntry::Function {
+                    is_var_arg: op(ops, 0)? != 0,
+                    ret: op(ops, 1)? as usize,
+                    params: ops[2..].iter().map(|&ty| ty as usize).collect(),
+                },
+                code => TypeEntry::Unsupported(code),
+            };
+            self.entries.push(entry);
+        }
+    }
+
+    pub fn entry(&self, id: usize) -> Result<&TypeEntry> {
+        self.entries
+            .get(id)
+            .ok_or_else(|| anyhow!("Invalid type id {}", id))
+    }
+
+    pub fn is_void(&self, id: usize) -> bool {
+        matches!(self.entries.get(id), Some(TypeEntry::Void))
+    }
+
+    /// Translates an entry into a type of the core IR. Like the assembly
+    /// parser, `i64` is treated as the native `i32` and every pointer points
+    /// to `i32`.
+    pub fn get(&self, types: &Types, id: usize) -> Result<Type> {
+        if let Some(ty) = self.translated.borrow().get(&id) {
+            return Ok(*ty);
+        }
+        let ty = match self.entry(id)? {
+            TypeEntry::Void => VOID,
+            TypeEntry::Metadata => types.metadata(),
+            TypeEntry::Int(1) => I1,
+            TypeEntry::Int(8) => I8,
+            TypeEntry::Int(16) => I16,
+            TypeEntry::Int(32) | TypeEntry::Int(64) => I32,
+            TypeEntry::Int(width) => bail!("Unsupported integer type i{}", width),
+            TypeEntry::Pointer => types.base_mut().pointer(I32),
+            TypeEntry::Array(len, elem) => {
+                let elem = self.get(types, *elem)?;
+                types.base_mut().array(ArrayType::new(elem, *len))
+            }
+            TypeEntry::Struct {
+                name,
+                is_packed,
+                elems,
+            } => {
+                let elems = elems
+                    .iter()
+                    .map(|&elem| self.get(types, elem))
+                    .collect::<Result<Vec<_>>>()?;
+                let strukt = types.base_mut().anonymous_struct(elems, *is_packed);
+                match name {
+                    Some(name) => {
+                        let name = Name::Name(name.clone());
+                        types.base_mut().change_to_named_type(strukt, name.clone());
+                        types.base_mut().empty_named_type(name)
+                    }
+                    None => strukt,
+                }
+            }
+            TypeEntry::Function {
+                is_var_arg,
+                ret,
+                params,
+            } => {
+                let ret = self.get(types, *ret)?;
+                let params = params
+                    .iter()
+                    .map(|&param| self.get(types, param))
+                    .collect::<Result<Vec<_>>>()?;
+                types
+                    .base_mut()
+                    .function(FunctionType::new(ret, params, *is_var_arg))
+            }
+            TypeEntry::Label => bail!("Label type used as a value type"),
+            TypeEntry::Unsupported(code) => bail!("Unsupported type (type code {})", code),
+        };
+        self.translated.borrow_mut().insert(id, ty);
+        Ok(ty)
+    }
+}
+
+impl Default for TypeTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(super) fn op(ops: &[u64], i: usize) -> Result<u64> {
+    ops.get(i)
+        .copied()
+        .ok_or_else(|| anyhow!("Record has too few operands"))
+}
+
+pub(super) fn chars(ops: &[u64]) -> Vec<u8> {
+    ops.iter().map(|&c| c as u8).collect()
+}
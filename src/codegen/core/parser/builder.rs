@@ -0,0 +1,139 @@
+//! Bookkeeping shared by the readers which build functions from an LLVM module
+//! that is not in textual form. They create values, instructions and blocks in
+//! the same order as the assembly parser does, so that every reader yields the
+//! same function.
+
+use crate::codegen::core::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        data::Data,
+        instruction::{Instruction, InstructionId, Opcode},
+        layout::Layout,
+        Parameter,
+    },
+    module::name::Name,
+    value::{ArgumentValue, ConstantValue, Value, ValueId},
+};
+use rustc_hash::FxHashMap;
+use std::hash::Hash;
+
+/// Builds the body of a function. `V` identifies the values and `B` the basic
+/// blocks of the function being read.
+pub struct FunctionBuilder<V, B> {
+    pub data: Data,
+    pub layout: Layout,
+    values: FxHashMap<V, ValueId>,
+    blocks: FxHashMap<B, BasicBlockId>,
+    cur_block: BasicBlockId,
+}
+
+impl<V: Hash + Eq, B: Hash + Eq> FunctionBuilder<V, B> {
+    pub fn new() -> Self {
+        let mut data = Data::new();
+        let dummy_block = data.create_block();
+        Self {
+            data,
+            layout: Layout::new(),
+            values: FxHashMap::default(),
+            blocks: FxHashMap::default(),
+            cur_block: dummy_block,
+        }
+    }
+
+    pub fn add_argument(&mut self, key: V, index: usize, param: &Parameter) {
+        let arg = self.data.create_value(Value::Argument(ArgumentValue::new(
+            index,
+            param.ty,
+            Some(param.name.clone()),
+        )));
+        self.values.insert(key, arg);
+    }
+
+    pub fn cur_block(&self) -> BasicBlockId {
+        self.cur_block
+    }
+
+    /// Returns the value of an argument or instruction. An instruction which is
+    /// used before it is defined gets a placeholder, replaced by `append` once
+    /// the definition is reached.
+    pub fn value(&mut self, key: V) -> ValueId {
+        if let Some(value) = self.values.get(&key) {
+            return *value;
+        }
+        let dummy = self
+            .data
+            .create_inst(Opcode::Invalid.with_block(self.cur_block));
+        let id = self.data.create_value(Value::Instruction(dummy));
+        self.values.insert(key, id);
+        id
+    }
+
+    /// Creates a value for a use of a constant. Every use gets its own value.
+    pub fn constant(&mut self, konst: ConstantValue) -> ValueId {
+        self.data.create_value(Value::Constant(konst))
+    }
+
+    pub fn block(&mut self, key: B, name: impl FnOnce() -> Name) -> BasicBlockId {
+        if let Some(block) = self.blocks.get(&key) {
+            return *block;
+        }
+        let block = self.data.create_block();
+        self.data.block_ref_mut(block).name = Some(name());
+        self.blocks.insert(key, block);
+        block
+    }
+
+    /// Appends `block` to the layout and makes it the insertion point.
+    pub fn switch_to_block(&mut self, block: BasicBlockId) {
+        self.layout.append_block(block);
+        self.cur_block = block;
+    }
+
+    /// Appends `inst` to the current block. `dest` names the result of the
+    /// instruction, if it has one.
+    pub fn append(&mut self, inst: Instruction, dest: Option<(V, Name)>) -> InstructionId {
+        let id = match dest {
+            Some((key, name)) => {
+                if let Some(value) = self.values.get(&key)
+                    && let Value::Instruction(id) = self.data.values[*value]
+                {
+                    self.data.replace_inst(id, inst.with_dest(name));
+                    id
+                } else {
+                    let id = self.data.create_inst(inst.with_dest(name));
+                    let value = self.data.create_value(Value::Instruction(id));
+                    self.values.insert(key, value);
+                    id
+                }
+            }
+            None => self.data.create_inst(inst),
+        };
+        self.layout.append_inst(id, self.cur_block);
+        id
+    }
+
+    /// Fills in the predecessors and successors of every block.
+    pub fn finish(mut self) -> (Data, Layout) {
+        for block_id in self.layout.block_iter() {
+            let br = match self.layout.block_node(block_id).last_inst() {
+                Some(br) => *br,
+                None => continue,
+            };
+            let br = &self.data.instructions[br];
+            if !br.opcode.is_terminator() {
+                continue;
+            }
+            for &block in br.operand.blocks() {
+                self.data.basic_blocks[br.parent].succs.insert(block);
+                self.data.basic_blocks[block].preds.insert(br.parent);
+            }
+        }
+        (self.data, self.layout)
+    }
+}
+
+impl<V: Hash + Eq, B: Hash + Eq> Default for FunctionBuilder<V, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,557 @@
+use super::{c_string, non_null, value::operands, value_name, Error};
+use crate::codegen::core::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{
+            Alloca, Br, Call, Cast, CondBr, ExtractValue, GetElementPtr, ICmp, ICmpCond,
+            InsertValue, Instruction, InstructionId, IntBinary, Load, Opcode, Operand, Phi, Ret,
+            Store, Switch,
+        },
+        Function, Parameter,
+    },
+    module::{attributes::Attribute, name::Name, preemption_specifier::PreemptionSpecifier},
+    types::{Type, Types, I1, I32, VOID},
+    value::{ConstantInt, ConstantValue, ValueId},
+};
+use crate::codegen::core::parser::builder::FunctionBuilder;
+use llvm_sys::{
+    core::{
+        LLVMConstIntGetZExtValue, LLVMCountIncoming, LLVMCountParams, LLVMGetAlignment,
+        LLVMGetAllocatedType, LLVMGetBasicBlockName, LLVMGetCalledFunctionType, LLVMGetCalledValue,
+        LLVMGetCondition, LLVMGetEnumAttributeAtIndex, LLVMGetEnumAttributeKindForName,
+        LLVMGetFirstBasicBlock, LLVMGetFirstInstruction, LLVMGetGEPSourceElementType,
+        LLVMGetICmpPredicate, LLVMGetIncomingBlock, LLVMGetIncomingValue, LLVMGetIndices,
+        LLVMGetInstructionOpcode, LLVMGetLinkage, LLVMGetNextBasicBlock, LLVMGetNextInstruction,
+        LLVMGetNumArgOperands, LLVMGetNumIndices, LLVMGetNumOperands, LLVMGetNumSuccessors,
+        LLVMGetOperand, LLVMGetParam, LLVMGetPersonalityFn, LLVMGetReturnType, LLVMGetSection,
+        LLVMGetSuccessor, LLVMGetSwitchDefaultDest, LLVMGetTypeKind, LLVMGetUnnamedAddress,
+        LLVMGlobalGetValueType, LLVMHasPersonalityFn, LLVMIsAArgument, LLVMIsAConstant,
+        LLVMIsAConstantInt, LLVMIsAGlobalValue, LLVMIsAInstruction, LLVMIsConditional,
+        LLVMIsDeclaration, LLVMIsFunctionVarArg, LLVMIsInBounds, LLVMTypeOf,
+    },
+    prelude::{LLVMBasicBlockRef, LLVMTypeRef, LLVMValueRef},
+    LLVMAttributeFunctionIndex, LLVMIntPredicate, LLVMOpcode, LLVMTypeKind,
+};
+use rustc_hash::FxHashMap;
+use std::{iter, slice};
+
+struct Context<'a> {
+    types: &'a Types,
+    builder: FunctionBuilder<LLVMValueRef, LLVMBasicBlockRef>,
+    names: FxHashMap<LLVMValueRef, Name>,
+    block_names: FxHashMap<LLVMBasicBlockRef, Name>,
+}
+
+pub fn translate(function: LLVMValueRef, types: Types) -> Result<Function, Error> {
+    let name = value_name(function).ok_or_else(|| Error::Unsupported("unnamed function".into()))?;
+    let fn_ty = unsafe { LLVMGlobalGetValueType(function) };
+    let result_ty = super::types::translate(&types, unsafe { LLVMGetReturnType(fn_ty) })?;
+    let is_var_arg = unsafe { LLVMIsFunctionVarArg(fn_ty) } != 0;
+    let is_prototype = unsafe { LLVMIsDeclaration(function) } != 0;
+    let (names, block_names) = slot_names(function);
+
+    let mut params = vec![];
+    for (i, param) in params_of(function).enumerate() {
+        params.push(Parameter {
+            // Declarations are printed without parameter names.
+            name: if is_prototype {
+                Name::Number(i + 1)
+            } else {
+                names[&param].clone()
+            },
+            ty: super::types::translate(&types, unsafe { LLVMTypeOf(param) })?,
+            attrs: vec![],
+        });
+    }
+
+    let mut builder = FunctionBuilder::new();
+    for (i, (param, llvm_param)) in params.iter().zip(params_of(function)).enumerate() {
+        builder.add_argument(llvm_param, i, param);
+    }
+
+    let (data, layout) = if is_prototype {
+        builder.finish()
+    } else {
+        let mut ctx = Context {
+            types: &types,
+            builder,
+            names,
+            block_names,
+        };
+        for block in blocks_of(function) {
+            let block_id = ctx.block(block);
+            ctx.builder.switch_to_block(block_id);
+            for inst in instructions_of(block) {
+                ctx.instruction(inst)?;
+            }
+        }
+        ctx.builder.finish()
+    };
+
+    let personality = if unsafe { LLVMHasPersonalityFn(function) } != 0 {
+        let personality = unsafe { LLVMGetPersonalityFn(function) };
+        Some((
+            super::types::translate(&types, unsafe { LLVMTypeOf(personality) })?,
+            super::value::translate_constant(&types, personality)?,
+        ))
+    } else {
+        None
+    };
+
+    Ok(Function {
+        name,
+        is_var_arg,
+        result_ty,
+        linkage: super::linkage(unsafe { LLVMGetLinkage(function) }),
+        // The C API cannot tell whether a function is `dso_local`, and the
+        // code generator does not mark any.
+        preemption_specifier: PreemptionSpecifier::DsoPreemptable,
+        visibility: super::visibility(function),
+        unnamed_addr: super::unnamed_addr(unsafe { LLVMGetUnnamedAddress(function) }),
+        ret_attrs: vec![],
+        func_attrs: func_attrs(function),
+        section: c_string(unsafe { LLVMGetSection(function) }),
+        params,
+        data,
+        layout,
+        types,
+        personality,
+    })
+}
+
+/// Translates the function attributes which the optimizer looks at.
+fn func_attrs(function: LLVMValueRef) -> Vec<Attribute> {
+    [
+        ("alwaysinline", Attribute::AlwaysInline),
+        ("noinline", Attribute::NoInline),
+    ]
+    .into_iter()
+    .filter(|(name, _)| {
+        let kind = unsafe { LLVMGetEnumAttributeKindForName(name.as_ptr().cast(), name.len()) };
+        !unsafe { LLVMGetEnumAttributeAtIndex(function, LLVMAttributeFunctionIndex, kind) }
+            .is_null()
+    })
+    .map(|(_, attr)| attr)
+    .collect()
+}
+
+/// Names arguments, blocks and instructions the way the LLVM assembly writer
+/// does, numbering the unnamed ones in order.
+fn slot_names(
+    function: LLVMValueRef,
+) -> (
+    FxHashMap<LLVMValueRef, Name>,
+    FxHashMap<LLVMBasicBlockRef, Name>,
+) {
+    let mut next = 0;
+    let mut name_of = |name: Option<String>| {
+        name.map_or_else(
+            || {
+                next += 1;
+                Name::Number(next - 1)
+            },
+            Name::Name,
+        )
+    };
+
+    let mut names = FxHashMap::default();
+    let mut block_names = FxHashMap::default();
+    for param in params_of(function) {
+        names.insert(param, name_of(value_name(param)));
+    }
+    for block in blocks_of(function) {
+        block_names.insert(
+            block,
+            name_of(c_string(unsafe { LLVMGetBasicBlockName(block) })),
+        );
+        for inst in instructions_of(block) {
+            if !is_void(unsafe { LLVMTypeOf(inst) }) {
+                names.insert(inst, name_of(value_name(inst)));
+            }
+        }
+    }
+    (names, block_names)
+}
+
+fn params_of(function: LLVMValueRef) -> impl Iterator<Item = LLVMValueRef> {
+    let num = unsafe { LLVMCountParams(function) };
+    (0..num).map(move |i| unsafe { LLVMGetParam(function, i) })
+}
+
+fn blocks_of(function: LLVMValueRef) -> impl Iterator<Item = LLVMBasicBlockRef> {
+    iter::successors(
+        non_null(unsafe { LLVMGetFirstBasicBlock(function) }),
+        |block| non_null(unsafe { LLVMGetNextBasicBlock(*block) }),
+    )
+}
+
+fn instructions_of(block: LLVMBasicBlockRef) -> impl Iterator<Item = LLVMValueRef> {
+    iter::successors(
+        non_null(unsafe { LLVMGetFirstInstruction(block) }),
+        |inst| non_null(unsafe { LLVMGetNextInstruction(*inst) }),
+    )
+}
+
+fn align(inst: LLVMValueRef) -> u32 {
+    unsafe { LLVMGetAlignment(inst) }
+}
+
+fn is_void(ty: LLVMTypeRef) -> bool {
+    matches!(
+        unsafe { LLVMGetTypeKind(ty) },
+        LLVMTypeKind::LLVMVoidTypeKind
+    )
+}
+
+impl<'a> Context<'a> {
+    fn ty(&self, ty: LLVMTypeRef) -> Result<Type, Error> {
+        super::types::translate(self.types, ty)
+    }
+
+    fn type_of(&self, value: LLVMValueRef) -> Result<Type, Error> {
+        self.ty(unsafe { LLVMTypeOf(value) })
+    }
+
+    fn operand(&mut self, inst: LLVMValueRef, i: u32) -> Result<(Type, ValueId), Error> {
+        let operand = unsafe { LLVMGetOperand(inst, i) };
+        Ok((self.type_of(operand)?, self.value(operand)?))
+    }
+
+    fn value(&mut self, value: LLVMValueRef) -> Result<ValueId, Error> {
+        if !unsafe { LLVMIsAArgument(value) }.is_null()
+            || !unsafe { LLVMIsAInstruction(value) }.is_null()
+        {
+            return Ok(self.builder.value(value));
+        }
+        if !unsafe { LLVMIsAConstant(value) }.is_null() {
+            let konst = super::value::translate_constant(self.types, value)?;
+            return Ok(self.builder.constant(konst));
+        }
+        Err(Error::Unsupported(
+            "operand which is neither a constant nor an instruction".into(),
+        ))
+    }
+
+    fn block(&mut self, block: LLVMBasicBlockRef) -> BasicBlockId {
+        let block_names = &self.block_names;
+        self.builder.block(block, || block_names[&block].clone())
+    }
+
+    fn indices(&mut self, inst: LLVMValueRef) -> Vec<ValueId> {
+        let num = unsafe { LLVMGetNumIndices(inst) } as usize;
+        let indices = unsafe { slice::from_raw_parts(LLVMGetIndices(inst), num) };
+        indices
+            .iter()
+            .map(|&idx| self.builder.constant(ConstantInt::Int64(idx as i64).into()))
+            .collect()
+    }
+
+    fn instruction(&mut self, inst: LLVMValueRef) -> Result<InstructionId, Error> {
+        let opcode = unsafe { LLVMGetInstructionOpcode(inst) };
+        let translated = match opcode {
+            LLVMOpcode::LLVMAlloca => self.alloca(inst)?,
+            LLVMOpcode::LLVMPHI => {
+                let ty = self.type_of(inst)?;
+                let mut args = vec![];
+                let mut blocks = vec![];
+                for i in 0..unsafe { LLVMCountIncoming(inst) } {
+                    args.push(self.value(unsafe { LLVMGetIncomingValue(inst, i) })?);
+                    blocks.push(self.block(unsafe { LLVMGetIncomingBlock(inst, i) }));
+                }
+                Opcode::Phi
+                    .with_block(self.builder.cur_block())
+                    .with_operand(Operand::Phi(Phi { ty, args, blocks }))
+                    .with_ty(ty)
+            }
+            LLVMOpcode::LLVMLoad => {
+                let ty = self.type_of(inst)?;
+                let (addr_ty, addr) = self.operand(inst, 0)?;
+                Opcode::Load
+                    .with_block(self.builder.cur_block())
+                    .with_operand(Operand::Load(Load {
+                        tys: [ty, addr_ty],
+                        addr,
+                        align: align(inst),
+                    }))
+                    .with_ty(ty)
+            }
+            LLVMOpcode::LLVMStore => {
+                let (src_ty, src) = self.operand(inst, 0)?;
+                let (dst_ty, dst) = self.operand(inst, 1)?;
+                Opcode::Store
+                    .with_block(self.builder.cur_block())
+                    .with_operand(Operand::Store(Store {
+                        tys: [src_ty, dst_ty],
+                        args: [src, dst],
+                        align: align(inst),
+                    }))
+            }
+            LLVMOpcode::LLVMInsertValue => {
+                let (aggre_ty, val) = self.operand(inst, 0)?;
+                let (ty, elt) = self.operand(inst, 1)?;
+                let mut args = vec![val, elt];
+                args.extend(self.indices(inst));
+                Opcode::InsertValue
+                    .with_block(self.builder.cur_block())
+                    .with_operand(Operand::InsertValue(InsertValue {
+                        tys: [aggre_ty, ty],
+                        args,
+                    }))
+                    .with_ty(aggre_ty)
+            }
+            LLVMOpcode::LLVMExtractValue => {
+                let (aggre_ty, val) = self.operand(inst, 0)?;
+                let num = unsafe { LLVMGetNumIndices(inst) } as usize;
+                let indices = unsafe { slice::from_raw_parts(LLVMGetIndices(inst), num) };
+                let ty = self
+                    .types
+                    .base()
+                    .element_at_(aggre_ty, indices.iter().map(|&idx| idx as usize))
+                    .ok_or_else(|| Error::Unsupported("extractvalue index".into()))?;
+                let mut args = vec![val];
+                args.extend(self.indices(inst));
+                Opcode::ExtractValue
+                    .with_block(self.builder.cur_block())
+                    .with_ty(ty)
+                    .with_operand(Operand::ExtractValue(ExtractValue { ty: aggre_ty, args }))
+            }
+            LLVMOpcode::LLVMAdd
+            | LLVMOpcode::LLVMSub
+            | LLVMOpcode::LLVMMul
+            | LLVMOpcode::LLVMSDiv
+            | LLVMOpcode::LLVMSRem
+            | LLVMOpcode::LLVMAnd
+            | LLVMOpcode::LLVMOr
+            | LLVMOpcode::LLVMXor
+            | LLVMOpcode::LLVMShl
+            | LLVMOpcode::LLVMAShr
+            | LLVMOpcode::LLVMLShr => {
+                let ty = self.type_of(inst)?;
+                let (_, lhs) = self.operand(inst, 0)?;
+                let (_, rhs) = self.operand(inst, 1)?;
+                binary_opcode(opcode)
+                    .with_block(self.builder.cur_block())
+                    .with_operand(Operand::IntBinary(IntBinary {
+                        ty,
+                        args: [lhs, rhs],
+                        // The LLVM 15 C API cannot read these flags back, and
+                        // irgen never sets them.
+                        nuw: false,
+                        nsw: false,
+                        exact: false,
+                    }))
+                    .with_ty(ty)
+            }
+            LLVMOpcode::LLVMICmp => {
+                let (ty, lhs) = self.operand(inst, 0)?;
+                let (_, rhs) = self.operand(inst, 1)?;
+                Opcode::ICmp
+                    .with_block(self.builder.cur_block())
+                    .with_operand(Operand::ICmp(ICmp {
+                        ty,
+                        args: [lhs, rhs],
+                        cond: icmp_cond(unsafe { LLVMGetICmpPredicate(inst) }),
+                    }))
+                    .with_ty(I1)
+            }
+            LLVMOpcode::LLVMSExt
+            | LLVMOpcode::LLVMZExt
+            | LLVMOpcode::LLVMBitCast
+            | LLVMOpcode::LLVMTrunc
+            | LLVMOpcode::LLVMIntToPtr
+            | LLVMOpcode::LLVMPtrToInt => {
+                let (from, arg) = self.operand(inst, 0)?;
+                let to = self.type_of(inst)?;
+                cast_opcode(opcode)
+                    .with_block(self.builder.cur_block())
+                    .with_operand(Operand::Cast(Cast {
+                        tys: [from, to],
+                        arg,
+                    }))
+                    .with_ty(to)
+            }
+            LLVMOpcode::LLVMGetElementPtr => {
+                let mut tys = vec![self.ty(unsafe { LLVMGetGEPSourceElementType(inst) })?];
+                let mut args = vec![];
+                for operand in operands(inst) {
+                    tys.push(self.type_of(operand)?);
+                    args.push(self.value(operand)?);
+                }
+                Opcode::GetElementPtr
+                    .with_block(self.builder.cur_block())
+                    .with_ty(self.types.base_mut().pointer(I32))
+                    .with_operand(Operand::GetElementPtr(GetElementPtr {
+                        inbounds: unsafe { LLVMIsInBounds(inst) } != 0,
+                        tys,
+                        args,
+                    }))
+            }
+            LLVMOpcode::LLVMCall => self.call(inst)?,
+            LLVMOpcode::LLVMBr => {
+                if unsafe { LLVMIsConditional(inst) } != 0 {
+                    let arg = self.value(unsafe { LLVMGetCondition(inst) })?;
+                    let iftrue = self.block(unsafe { LLVMGetSuccessor(inst, 0) });
+                    let iffalse = self.block(unsafe { LLVMGetSuccessor(inst, 1) });
+                    Opcode::CondBr
+                        .with_block(self.builder.cur_block())
+                        .with_operand(Operand::CondBr(CondBr {
+                            arg,
+                            blocks: [iftrue, iffalse],
+                        }))
+                } else {
+                    let block = self.block(unsafe { LLVMGetSuccessor(inst, 0) });
+                    Opcode::Br
+                        .with_block(self.builder.cur_block())
+                        .with_operand(Operand::Br(Br { block }))
+                }
+            }
+            LLVMOpcode::LLVMSwitch => {
+                let (cond_ty, cond) = self.operand(inst, 0)?;
+                let default_block = self.block(unsafe { LLVMGetSwitchDefaultDest(inst) });
+                let mut tys = vec![cond_ty];
+                let mut args = vec![cond];
+                let mut blocks = vec![default_block];
+                for i in 1..unsafe { LLVMGetNumSuccessors(inst) } {
+                    let (case_ty, case) = self.operand(inst, 2 * i)?;
+                    tys.push(case_ty);
+                    args.push(case);
+                    blocks.push(self.block(unsafe { LLVMGetSuccessor(inst, i) }));
+                }
+                Opcode::Switch
+                    .with_block(self.builder.cur_block())
+                    .with_operand(Operand::Switch(Switch { tys, args, blocks }))
+            }
+            LLVMOpcode::LLVMRet => {
+                let (ty, val) = if unsafe { LLVMGetNumOperands(inst) } == 0 {
+                    (VOID, None)
+                } else {
+                    let (ty, val) = self.operand(inst, 0)?;
+                    (ty, Some(val))
+                };
+                Opcode::Ret
+                    .with_block(self.builder.cur_block())
+                    .with_operand(Operand::Ret(Ret { val, ty }))
+            }
+            LLVMOpcode::LLVMUnreachable => Opcode::Unreachable
+                .with_block(self.builder.cur_block())
+                .with_operand(Operand::Unreachable),
+            opcode => {
+                return Err(Error::Unsupported(format!("instruction {:?}", opcode)));
+            }
+        };
+
+        let dest = if is_void(unsafe { LLVMTypeOf(inst) }) {
+            None
+        } else {
+            Some((inst, self.names[&inst].clone()))
+        };
+        Ok(self.builder.append(translated, dest))
+    }
+
+    fn alloca(&mut self, inst: LLVMValueRef) -> Result<Instruction, Error> {
+        let ty = self.ty(unsafe { LLVMGetAllocatedType(inst) })?;
+        let size = unsafe { LLVMGetOperand(inst, 0) };
+        let num_elements = if !unsafe { LLVMIsAConstantInt(size) }.is_null()
+            && unsafe { LLVMConstIntGetZExtValue(size) } == 1
+        {
+            1.into()
+        } else if !unsafe { LLVMIsAConstant(size) }.is_null() {
+            super::value::translate_constant(self.types, size)?
+        } else {
+            return Err(Error::Unsupported("alloca of a dynamic size".into()));
+        };
+        Ok(Opcode::Alloca
+            .with_block(self.builder.cur_block())
+            .with_operand(Operand::Alloca(Alloca {
+                tys: [ty, I32],
+                num_elements,
+                align: align(inst),
+            }))
+            .with_ty(self.types.base_mut().pointer(ty)))
+    }
+
+    fn call(&mut self, inst: LLVMValueRef) -> Result<Instruction, Error> {
+        // Calls to variadic functions spell out the whole function type.
+        let fn_ty = unsafe { LLVMGetCalledFunctionType(inst) };
+        let ty = if unsafe { LLVMIsFunctionVarArg(fn_ty) } != 0 {
+            self.ty(fn_ty)?
+        } else {
+            self.ty(unsafe { LLVMGetReturnType(fn_ty) })?
+        };
+
+        let callee = unsafe { LLVMGetCalledValue(inst) };
+        let callee = if !unsafe { LLVMIsAGlobalValue(callee) }.is_null() {
+            let name = value_name(callee)
+                .ok_or_else(|| Error::Unsupported("call to an unnamed function".into()))?;
+            self.builder
+                .constant(ConstantValue::GlobalRef(Name::Name(name), ty))
+        } else {
+            self.value(callee)?
+        };
+
+        let mut tys = vec![ty];
+        let mut args = vec![callee];
+        let mut param_attrs = vec![];
+        for i in 0..unsafe { LLVMGetNumArgOperands(inst) } {
+            let (arg_ty, arg) = self.operand(inst, i)?;
+            tys.push(arg_ty);
+            args.push(arg);
+            param_attrs.push(vec![]);
+        }
+
+        Ok(Opcode::Call
+            .with_block(self.builder.cur_block())
+            .with_operand(Operand::Call(Call {
+                tys,
+                args,
+                param_attrs,
+                ret_attrs: vec![],
+                func_attrs: vec![],
+            }))
+            .with_ty(ty))
+    }
+}
+
+fn binary_opcode(opcode: LLVMOpcode) -> Opcode {
+    match opcode {
+        LLVMOpcode::LLVMAdd => Opcode::Add,
+        LLVMOpcode::LLVMSub => Opcode::Sub,
+        LLVMOpcode::LLVMMul => Opcode::Mul,
+        LLVMOpcode::LLVMSDiv => Opcode::SDiv,
+        LLVMOpcode::LLVMSRem => Opcode::SRem,
+        LLVMOpcode::LLVMAnd => Opcode::And,
+        LLVMOpcode::LLVMOr => Opcode::Or,
+        LLVMOpcode::LLVMXor => Opcode::Xor,
+        LLVMOpcode::LLVMShl => Opcode::Shl,
+        LLVMOpcode::LLVMAShr => Opcode::AShr,
+        LLVMOpcode::LLVMLShr => Opcode::LShr,
+        _ => unreachable!(),
+    }
+}
+
+fn cast_opcode(opcode: LLVMOpcode) -> Opcode {
+    match opcode {
+        LLVMOpcode::LLVMSExt => Opcode::Sext,
+        LLVMOpcode::LLVMZExt => Opcode::Zext,
+        LLVMOpcode::LLVMBitCast => Opcode::Bitcast,
+        LLVMOpcode::LLVMTrunc => Opcode::Trunc,
+        LLVMOpcode::LLVMIntToPtr => Opcode::IntToPtr,
+        LLVMOpcode::LLVMPtrToInt => Opcode::PtrToInt,
+        _ => unreachable!(),
+    }
+}
+
+fn icmp_cond(predicate: LLVMIntPredicate) -> ICmpCond {
+    match predicate {
+        LLVMIntPredicate::LLVMIntEQ => ICmpCond::Eq,
+        LLVMIntPredicate::LLVMIntNE => ICmpCond::Ne,
+        LLVMIntPredicate::LLVMIntUGT => ICmpCond::Ugt,
+        LLVMIntPredicate::LLVMIntUGE => ICmpCond::Uge,
+        LLVMIntPredicate::LLVMIntULT => ICmpCond::Ult,
+        LLVMIntPredicate::LLVMIntULE => ICmpCond::Ule,
+        LLVMIntPredicate::LLVMIntSGT => ICmpCond::Sgt,
+        LLVMIntPredicate::LLVMIntSGE => ICmpCond::Sge,
+        LLVMIntPredicate::LLVMIntSLT => ICmpCond::Slt,
+        LLVMIntPredicate::LLVMIntSLE => ICmpCond::Sle,
+    }
+}
@@ -0,0 +1,50 @@
+use super::{global_name, Error};
+use crate::codegen::core::ir::{
+    module::{global_variable::GlobalVariable, linkage::Linkage, visibility::Visibility},
+    types::Types,
+};
+use llvm_sys::{
+    core::{
+        LLVMGetAlignment, LLVMGetInitializer, LLVMGetLinkage, LLVMGetUnnamedAddress,
+        LLVMGlobalGetValueType, LLVMIsDeclaration, LLVMIsGlobalConstant,
+    },
+    prelude::LLVMValueRef,
+};
+
+/// Translates a global variable. Attributes which the textual form omits when
+/// they have their default value are left as `None`, as the assembly parser
+/// would.
+pub fn translate(
+    global: LLVMValueRef,
+    types: &Types,
+    unnamed: &mut usize,
+) -> Result<GlobalVariable, Error> {
+    let name = global_name(global, unnamed);
+    let ty = super::types::translate(types, unsafe { LLVMGlobalGetValueType(global) })?;
+    let is_declaration = unsafe { LLVMIsDeclaration(global) } != 0;
+    let linkage = match super::linkage(unsafe { LLVMGetLinkage(global) }) {
+        Linkage::External if !is_declaration => None,
+        linkage => Some(linkage),
+    };
+    let init = if is_declaration {
+        None
+    } else {
+        Some(super::value::translate_constant(types, unsafe {
+            LLVMGetInitializer(global)
+        })?)
+    };
+    Ok(GlobalVariable {
+        name,
+        linkage,
+        preemption_specifier: None,
+        visibility: match super::visibility(global) {
+            Visibility::Default => None,
+            visibility => Some(visibility),
+        },
+        unnamed_addr: super::unnamed_addr(unsafe { LLVMGetUnnamedAddress(global) }),
+        is_constant: unsafe { LLVMIsGlobalConstant(global) } != 0,
+        ty,
+        init,
+        align: unsafe { LLVMGetAlignment(global) },
+    })
+}
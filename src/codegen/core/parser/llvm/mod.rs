@@ -0,0 +1,99 @@
+//! Translation of an in-memory LLVM module (as built by inkwell) into the core
+//! IR. The result is the same module the assembly parser produces for the
+//! printed form of the LLVM module, without the round trip through text.
+
+pub mod function;
+pub mod global_variable;
+pub mod module;
+pub mod types;
+pub mod value;
+
+use crate::codegen::core::ir::module::{
+    linkage::Linkage, name::Name, unnamed_addr::UnnamedAddr, visibility::Visibility,
+};
+use llvm_sys::{
+    core::{LLVMGetValueName2, LLVMGetVisibility},
+    prelude::LLVMValueRef,
+    LLVMLinkage, LLVMUnnamedAddr, LLVMVisibility,
+};
+use std::{ffi::CStr, os::raw::c_char, slice};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Unsupported LLVM construct: {0}")]
+    Unsupported(String),
+}
+
+/// Returns the name of `value`, or `None` if it is unnamed.
+pub(crate) fn value_name(value: LLVMValueRef) -> Option<String> {
+    let mut len = 0;
+    let ptr = unsafe { LLVMGetValueName2(value, &mut len) };
+    if ptr.is_null() || len == 0 {
+        return None;
+    }
+    let bytes = unsafe { slice::from_raw_parts(ptr as *const u8, len) };
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+pub(crate) fn c_string(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    let s = unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned();
+    (!s.is_empty()).then_some(s)
+}
+
+pub(crate) fn non_null<T>(ptr: *mut T) -> Option<*mut T> {
+    (!ptr.is_null()).then_some(ptr)
+}
+
+pub(crate) fn global_name(value: LLVMValueRef, unnamed: &mut usize) -> Name {
+    match value_name(value) {
+        Some(name) => Name::Name(name),
+        None => {
+            *unnamed += 1;
+            Name::Number(*unnamed - 1)
+        }
+    }
+}
+
+pub(crate) fn linkage(linkage: LLVMLinkage) -> Linkage {
+    match linkage {
+        LLVMLinkage::LLVMExternalLinkage => Linkage::External,
+        LLVMLinkage::LLVMAvailableExternallyLinkage => Linkage::AvailableExternally,
+        LLVMLinkage::LLVMLinkOnceAnyLinkage => Linkage::LinkOnceAny,
+        LLVMLinkage::LLVMLinkOnceODRLinkage => Linkage::LinkOnceODR,
+        LLVMLinkage::LLVMLinkOnceODRAutoHideLinkage => Linkage::LinkOnceODRAutoHide,
+        LLVMLinkage::LLVMWeakAnyLinkage => Linkage::WeakAny,
+        LLVMLinkage::LLVMWeakODRLinkage => Linkage::WeakODR,
+        LLVMLinkage::LLVMAppendingLinkage => Linkage::Appending,
+        LLVMLinkage::LLVMInternalLinkage => Linkage::Internal,
+        LLVMLinkage::LLVMPrivateLinkage => Linkage::Private,
+        LLVMLinkage::LLVMDLLImportLinkage => Linkage::DLLImport,
+        LLVMLinkage::LLVMDLLExportLinkage => Linkage::DLLExport,
+        LLVMLinkage::LLVMExternalWeakLinkage => Linkage::ExternalWeak,
+        LLVMLinkage::LLVMGhostLinkage => Linkage::Ghost,
+        LLVMLinkage::LLVMCommonLinkage => Linkage::Common,
+        LLVMLinkage::LLVMLinkerPrivateLinkage => Linkage::LinkerPrivate,
+        LLVMLinkage::LLVMLinkerPrivateWeakLinkage => Linkage::LinkerPrivateWeak,
+    }
+}
+
+pub(crate) fn visibility(value: LLVMValueRef) -> Visibility {
+    match unsafe { LLVMGetVisibility(value) } {
+        LLVMVisibility::LLVMDefaultVisibility => Visibility::Default,
+        LLVMVisibility::LLVMHiddenVisibility => Visibility::Hidden,
+        LLVMVisibility::LLVMProtectedVisibility => Visibility::Protected,
+    }
+}
+
+pub(crate) fn unnamed_addr(unnamed_addr: LLVMUnnamedAddr) -> Option<UnnamedAddr> {
+    match unnamed_addr {
+        LLVMUnnamedAddr::LLVMNoUnnamedAddr => None,
+        LLVMUnnamedAddr::LLVMLocalUnnamedAddr => Some(UnnamedAddr::Local),
+        LLVMUnnamedAddr::LLVMGlobalUnnamedAddr => Some(UnnamedAddr::Global),
+    }
+}
@@ -0,0 +1,135 @@
+use super::{c_string, non_null, Error};
+use crate::codegen::core::ir::module::Module;
+use llvm_sys::core::{
+    LLVMGetDataLayoutStr, LLVMGetFirstFunction, LLVMGetFirstGlobal, LLVMGetNextFunction,
+    LLVMGetNextGlobal, LLVMGetSourceFileName, LLVMGetTarget,
+};
+use std::{iter, slice};
+
+pub fn translate(llvm_module: &inkwell::module::Module) -> Result<Module, Error> {
+    let llvm_module = llvm_module.as_mut_ptr();
+    let mut module = Module::new();
+
+    let mut len = 0;
+    let source_filename = unsafe { LLVMGetSourceFileName(llvm_module, &mut len) };
+    if !source_filename.is_null() {
+        let bytes = unsafe { slice::from_raw_parts(source_filename as *const u8, len) };
+        module.source_filename = String::from_utf8_lossy(bytes).into_owned();
+    }
+    if let Some(datalayout) = c_string(unsafe { LLVMGetDataLayoutStr(llvm_module) }) {
+        module.target.datalayout = datalayout.into();
+    }
+    if let Some(triple) = c_string(unsafe { LLVMGetTarget(llvm_module) }) {
+        module.target.triple = triple;
+    }
+
+    let mut unnamed = 0;
+    let globals = iter::successors(
+        non_null(unsafe { LLVMGetFirstGlobal(llvm_module) }),
+        |global| non_null(unsafe { LLVMGetNextGlobal(*global) }),
+    );
+    for global in globals {
+        let gv = super::global_variable::translate(global, &module.types, &mut unnamed)?;
+        module.global_variables.insert(gv.name.clone(), gv);
+    }
+
+    let functions = iter::successors(
+        non_null(unsafe { LLVMGetFirstFunction(llvm_module) }),
+        |function| non_null(unsafe { LLVMGetNextFunction(*function) }),
+    );
+    for function in functions {
+        let func = super::function::translate(function, module.types.clone())?;
+        module.functions.alloc(func);
+    }
+
+    Ok(module)
+}
+
+#[cfg(test)]
+mod test {
+    use super::translate;
+    use crate::codegen::{
+        core::ir::module::Module,
+        isa::ola::{emit, Ola},
+    };
+    use inkwell::{context::Context, memory_buffer::MemoryBuffer};
+
+    #[test]
+    fn translate_matches_assembly_parser() {
+        let source = r#"
+source_filename = "translate.ola"
+
+@heap_address = internal global i64 -4294967353
+
+declare void @builtin_assert(i64)
+
+define void @main() {
+entry:
+  %0 = call i64 @sum(i64 10)
+  %1 = call i64 @select(i64 %0)
+  call void @builtin_assert(i64 %1)
+  ret void
+}
+
+define i64 @sum(i64 %n) {
+entry:
+  %total = alloca i64, align 8
+  store i64 0, ptr %total, align 8
+  br label %cond
+
+cond:
+  %i = phi i64 [ 0, %entry ], [ %next, %body ]
+  %done = icmp uge i64 %i, %n
+  br i1 %done, label %endfor, label %body
+
+body:
+  %0 = load i64, ptr %total, align 8
+  %1 = add i64 %0, %i
+  store i64 %1, ptr %total, align 8
+  %next = add i64 %i, 1
+  br label %cond
+
+endfor:
+  %2 = load i64, ptr %total, align 8
+  ret i64 %2
+}
+
+define i64 @select(i64 %0) {
+entry:
+  switch i64 %0, label %default [
+    i64 45, label %hit
+    i64 7, label %miss
+  ]
+
+hit:
+  ret i64 1
+
+miss:
+  ret i64 0
+
+default:
+  %1 = sub i64 %0, 45
+  %2 = icmp eq i64 %1, 0
+  %3 = zext i1 %2 to i64
+  ret i64 %3
+}
+"#;
+        let context = Context::create();
+        let buffer = MemoryBuffer::create_from_memory_range_copy(source.as_bytes(), "translate");
+        let llvm_module = context
+            .create_module_from_ir(buffer)
+            .expect("failed to parse LLVM IR");
+
+        let parsed = Module::try_from(llvm_module.to_string().as_str())
+            .expect("failed to parse printed LLVM IR");
+        let translated = translate(&llvm_module).expect("failed to translate LLVM module");
+
+        assert_eq!(
+            translated.global_variables().len(),
+            parsed.global_variables().len()
+        );
+        assert_eq!(translated.functions().len(), parsed.functions().len());
+        let isa = Ola::default();
+        assert_eq!(emit(&translated, &isa).program, emit(&parsed, &isa).program);
+    }
+}
@@ -0,0 +1,94 @@
+use super::{c_string, Error};
+use crate::codegen::core::ir::{
+    module::name::Name,
+    types::{ArrayType, FunctionType, Type, Types, I1, I16, I32, I8, VOID},
+};
+use llvm_sys::{
+    core::{
+        LLVMCountParamTypes, LLVMCountStructElementTypes, LLVMGetArrayLength, LLVMGetElementType,
+        LLVMGetIntTypeWidth, LLVMGetParamTypes, LLVMGetReturnType, LLVMGetStructElementTypes,
+        LLVMGetStructName, LLVMGetTypeKind, LLVMIsFunctionVarArg, LLVMIsLiteralStruct,
+        LLVMIsOpaqueStruct, LLVMIsPackedStruct, LLVMPointerTypeIsOpaque,
+    },
+    prelude::LLVMTypeRef,
+    LLVMTypeKind,
+};
+use std::ptr;
+
+pub fn translate(types: &Types, ty: LLVMTypeRef) -> Result<Type, Error> {
+    match unsafe { LLVMGetTypeKind(ty) } {
+        LLVMTypeKind::LLVMVoidTypeKind => Ok(VOID),
+        LLVMTypeKind::LLVMMetadataTypeKind => Ok(types.metadata()),
+        // Like the assembly parser, `i64` is treated as the native `i32`.
+        LLVMTypeKind::LLVMIntegerTypeKind => match unsafe { LLVMGetIntTypeWidth(ty) } {
+            1 => Ok(I1),
+            8 => Ok(I8),
+            16 => Ok(I16),
+            32 | 64 => Ok(I32),
+            width => Err(Error::Unsupported(format!("integer type i{width}"))),
+        },
+        LLVMTypeKind::LLVMPointerTypeKind => {
+            let pointee = if unsafe { LLVMPointerTypeIsOpaque(ty) } != 0 {
+                I32
+            } else {
+                translate(types, unsafe { LLVMGetElementType(ty) })?
+            };
+            Ok(types.base_mut().pointer(pointee))
+        }
+        LLVMTypeKind::LLVMArrayTypeKind => {
+            let elem = translate(types, unsafe { LLVMGetElementType(ty) })?;
+            let len = unsafe { LLVMGetArrayLength(ty) };
+            Ok(types.base_mut().array(ArrayType::new(elem, len)))
+        }
+        LLVMTypeKind::LLVMStructTypeKind => translate_struct(types, ty),
+        LLVMTypeKind::LLVMFunctionTypeKind => {
+            let ret = translate(types, unsafe { LLVMGetReturnType(ty) })?;
+            let mut params = vec![ptr::null_mut(); unsafe { LLVMCountParamTypes(ty) } as usize];
+            unsafe { LLVMGetParamTypes(ty, params.as_mut_ptr()) };
+            let params = params
+                .into_iter()
+                .map(|param| translate(types, param))
+                .collect::<Result<Vec<_>, _>>()?;
+            let is_var_arg = unsafe { LLVMIsFunctionVarArg(ty) } != 0;
+            Ok(types
+                .base_mut()
+                .function(FunctionType::new(ret, params, is_var_arg)))
+        }
+        kind => Err(Error::Unsupported(format!("type {:?}", kind))),
+    }
+}
+
+fn translate_struct(types: &Types, ty: LLVMTypeRef) -> Result<Type, Error> {
+    let name = if unsafe { LLVMIsLiteralStruct(ty) } != 0 {
+        None
+    } else {
+        c_string(unsafe { LLVMGetStructName(ty) })
+    };
+    if let Some(name) = &name
+        && let Some(named) = types.base().get_struct(name)
+    {
+        return Ok(named);
+    }
+
+    let is_packed = unsafe { LLVMIsPackedStruct(ty) } != 0;
+    let elems = if unsafe { LLVMIsOpaqueStruct(ty) } != 0 {
+        vec![]
+    } else {
+        let mut elems = vec![ptr::null_mut(); unsafe { LLVMCountStructElementTypes(ty) } as usize];
+        unsafe { LLVMGetStructElementTypes(ty, elems.as_mut_ptr()) };
+        elems
+            .into_iter()
+            .map(|elem| translate(types, elem))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    let strukt = types.base_mut().anonymous_struct(elems, is_packed);
+
+    match name {
+        Some(name) => {
+            let name = Name::Name(name);
+            types.base_mut().change_to_named_type(strukt, name.clone());
+            Ok(types.base_mut().empty_named_type(name))
+        }
+        None => Ok(strukt),
+    }
+}
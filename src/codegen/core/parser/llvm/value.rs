@@ -0,0 +1,158 @@
+use super::{value_name, Error};
+use crate::codegen::core::ir::{
+    module::name::Name,
+    types::{Type, Types, I1, I32, I8},
+    value::{ConstantArray, ConstantExpr, ConstantInt, ConstantStruct, ConstantValue},
+};
+use llvm_sys::{
+    core::{
+        LLVMConstIntGetSExtValue, LLVMConstIntGetZExtValue, LLVMGetArrayLength, LLVMGetAsString,
+        LLVMGetConstOpcode, LLVMGetAggregateElement, LLVMGetGEPSourceElementType,
+        LLVMGetNumOperands, LLVMGetOperand, LLVMIsAConstantAggregateZero, LLVMIsAConstantArray,
+        LLVMIsAConstantDataArray, LLVMIsAConstantExpr, LLVMIsAConstantInt,
+        LLVMIsAConstantPointerNull, LLVMIsAConstantStruct, LLVMIsAGlobalValue,
+        LLVMIsConstantString, LLVMIsInBounds, LLVMIsPackedStruct, LLVMIsUndef, LLVMTypeOf,
+    },
+    prelude::LLVMValueRef,
+    LLVMOpcode,
+};
+
+pub fn translate_constant(types: &Types, value: LLVMValueRef) -> Result<ConstantValue, Error> {
+    let ty = super::types::translate(types, unsafe { LLVMTypeOf(value) })?;
+
+    if unsafe { LLVMIsUndef(value) } != 0 {
+        return Ok(ConstantValue::Undef(ty));
+    }
+    if !unsafe { LLVMIsAConstantPointerNull(value) }.is_null() {
+        return Ok(ConstantValue::Null(ty));
+    }
+    if !unsafe { LLVMIsAConstantAggregateZero(value) }.is_null() {
+        return Ok(ConstantValue::AggregateZero(ty));
+    }
+    if !unsafe { LLVMIsAConstantInt(value) }.is_null() {
+        let int = match ty {
+            I1 => ConstantInt::Int1(unsafe { LLVMConstIntGetZExtValue(value) } != 0),
+            I8 => ConstantInt::Int8(unsafe { LLVMConstIntGetSExtValue(value) } as i8),
+            I32 => ConstantInt::Int64(unsafe { LLVMConstIntGetSExtValue(value) }),
+            _ => {
+                return Err(Error::Unsupported(format!(
+                    "integer constant of type {}",
+                    types.to_string(ty)
+                )))
+            }
+        };
+        return Ok(int.into());
+    }
+    if !unsafe { LLVMIsAConstantDataArray(value) }.is_null() {
+        return translate_data_array(types, value, ty);
+    }
+    if !unsafe { LLVMIsAConstantArray(value) }.is_null() {
+        let elems = operands(value)
+            .map(|elem| translate_constant(types, elem))
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(ConstantValue::Array(ConstantArray {
+            ty,
+            elem_ty: types.get_element(ty).unwrap(),
+            elems,
+            is_string: false,
+        }));
+    }
+    if !unsafe { LLVMIsAConstantStruct(value) }.is_null() {
+        let mut elems_ty = vec![];
+        let mut elems = vec![];
+        for elem in operands(value) {
+            elems_ty.push(super::types::translate(types, unsafe { LLVMTypeOf(elem) })?);
+            elems.push(translate_constant(types, elem)?);
+        }
+        return Ok(ConstantValue::Struct(ConstantStruct {
+            ty,
+            elems_ty,
+            elems,
+            is_packed: unsafe { LLVMIsPackedStruct(LLVMTypeOf(value)) } != 0,
+        }));
+    }
+    if !unsafe { LLVMIsAGlobalValue(value) }.is_null() {
+        let name = value_name(value)
+            .ok_or_else(|| Error::Unsupported("reference to an unnamed global".into()))?;
+        return Ok(ConstantValue::GlobalRef(Name::Name(name), ty));
+    }
+    if !unsafe { LLVMIsAConstantExpr(value) }.is_null() {
+        return translate_constant_expr(types, value);
+    }
+
+    Err(Error::Unsupported(format!(
+        "constant of type {}",
+        types.to_string(ty)
+    )))
+}
+
+fn translate_data_array(
+    types: &Types,
+    value: LLVMValueRef,
+    ty: Type,
+) -> Result<ConstantValue, Error> {
+    if unsafe { LLVMIsConstantString(value) } != 0 {
+        let mut len = 0;
+        let ptr = unsafe { LLVMGetAsString(value, &mut len) };
+        let bytes = unsafe { std::slice::from_raw_parts(ptr as *const u8, len) };
+        return Ok(ConstantValue::Array(ConstantArray {
+            ty,
+            elem_ty: I8,
+            elems: bytes
+                .iter()
+                .map(|c| ConstantValue::Int(ConstantInt::Int8(*c as i8)))
+                .collect(),
+            is_string: true,
+        }));
+    }
+
+    let elem_ty = types.get_element(ty).unwrap();
+    let len = unsafe { LLVMGetArrayLength(LLVMTypeOf(value)) };
+    let elems = (0..len)
+        .map(|i| translate_constant(types, unsafe { LLVMGetAggregateElement(value, i) }))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ConstantValue::Array(ConstantArray {
+        ty,
+        elem_ty,
+        elems,
+        is_string: false,
+    }))
+}
+
+fn translate_constant_expr(types: &Types, value: LLVMValueRef) -> Result<ConstantValue, Error> {
+    match unsafe { LLVMGetConstOpcode(value) } {
+        LLVMOpcode::LLVMGetElementPtr => {
+            let source_ty =
+                super::types::translate(types, unsafe { LLVMGetGEPSourceElementType(value) })?;
+            let mut tys = vec![source_ty];
+            let mut args = vec![];
+            for arg in operands(value) {
+                tys.push(super::types::translate(types, unsafe { LLVMTypeOf(arg) })?);
+                args.push(translate_constant(types, arg)?);
+            }
+            Ok(ConstantValue::Expr(ConstantExpr::GetElementPtr {
+                inbounds: unsafe { LLVMIsInBounds(value) } != 0,
+                tys,
+                args,
+            }))
+        }
+        LLVMOpcode::LLVMBitCast => {
+            let arg = unsafe { LLVMGetOperand(value, 0) };
+            let from = super::types::translate(types, unsafe { LLVMTypeOf(arg) })?;
+            let to = super::types::translate(types, unsafe { LLVMTypeOf(value) })?;
+            Ok(ConstantValue::Expr(ConstantExpr::Bitcast {
+                tys: [from, to],
+                arg: Box::new(translate_constant(types, arg)?),
+            }))
+        }
+        opcode => Err(Error::Unsupported(format!(
+            "constant expression {:?}",
+            opcode
+        ))),
+    }
+}
+
+pub(crate) fn operands(value: LLVMValueRef) -> impl Iterator<Item = LLVMValueRef> {
+    let num = unsafe { LLVMGetNumOperands(value) } as u32;
+    (0..num).map(move |i| unsafe { LLVMGetOperand(value, i) })
+}
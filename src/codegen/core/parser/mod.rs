@@ -1,2 +1,4 @@
 pub mod assembly;
 pub mod bitcode;
+pub mod builder;
+pub mod llvm;
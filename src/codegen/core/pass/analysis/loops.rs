@@ -0,0 +1,229 @@
+use super::dom_tree::DominatorTree;
+use crate::codegen::core::traits::basic_block::{BasicBlock, BasicBlockData, BasicBlockLayout};
+use id_arena::Id;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// The natural loops of a function, nested in one another
+#[derive(Debug)]
+pub struct LoopForest<BB: BasicBlock> {
+    loops: Vec<Loop<BB>>,
+    /// The innermost loop of each block which is in a loop
+    innermost: FxHashMap<Id<BB>, LoopId>,
+}
+
+pub type LoopId = usize;
+
+#[derive(Debug)]
+pub struct Loop<BB: BasicBlock> {
+    header: Id<BB>,
+    blocks: FxHashSet<Id<BB>>,
+    latches: Vec<Id<BB>>,
+    parent: Option<LoopId>,
+    children: Vec<LoopId>,
+}
+
+impl<BB: BasicBlock> LoopForest<BB> {
+    pub fn new<F: BasicBlockData<BB> + BasicBlockLayout<BB>>(
+        f: &F,
+        dom_tree: &DominatorTree<BB>,
+    ) -> Self {
+        let order = f.order().collect::<Vec<_>>();
+
+        // A back edge goes to a block which dominates its source
+        let mut loops = vec![];
+        for &header in &order {
+            let mut latches = f
+                .get(header)
+                .preds()
+                .iter()
+                .copied()
+                .filter(|&pred| dom_tree.level_of(pred).is_some())
+                .filter(|&pred| dom_tree.dominates(header, pred))
+                .collect::<Vec<_>>();
+            if latches.is_empty() {
+                continue;
+            }
+            latches.sort_by_key(|latch| latch.index());
+
+            // The blocks which reach a latch without going through the header
+            let mut blocks = FxHashSet::default();
+            blocks.insert(header);
+            let mut worklist = latches.clone();
+            while let Some(block) = worklist.pop() {
+                if blocks.insert(block) {
+                    worklist.extend(f.get(block).preds().iter().copied());
+                }
+            }
+
+            loops.push(Loop {
+                header,
+                blocks,
+                latches,
+                parent: None,
+                children: vec![],
+            });
+        }
+
+        // Loops are listed from the innermost out, so that the parent of a
+        // loop is the first larger one containing its header
+        loops.sort_by_key(|l| l.blocks.len());
+        for id in 0..loops.len() {
+            let parent = (id + 1..loops.len()).find(|&other| {
+                loops[other].blocks.len() > loops[id].blocks.len()
+                    && loops[other].blocks.contains(&loops[id].header)
+            });
+            loops[id].parent = parent;
+            if let Some(parent) = parent {
+                loops[parent].children.push(id);
+            }
+        }
+
+        let mut innermost = FxHashMap::default();
+        for (id, l) in loops.iter().enumerate() {
+            for &block in &l.blocks {
+                innermost.entry(block).or_insert(id);
+            }
+        }
+
+        Self { loops, innermost }
+    }
+
+    /// The loops, inner loops coming before the loops containing them
+    pub fn loops(&self) -> impl Iterator<Item = (LoopId, &Loop<BB>)> {
+        self.loops.iter().enumerate()
+    }
+
+    pub fn get(&self, id: LoopId) -> &Loop<BB> {
+        &self.loops[id]
+    }
+
+    pub fn innermost_loop_of(&self, block: Id<BB>) -> Option<LoopId> {
+        self.innermost.get(&block).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.loops.is_empty()
+    }
+}
+
+impl<BB: BasicBlock> Loop<BB> {
+    pub fn header(&self) -> Id<BB> {
+        self.header
+    }
+
+    pub fn blocks(&self) -> &FxHashSet<Id<BB>> {
+        &self.blocks
+    }
+
+    pub fn contains(&self, block: Id<BB>) -> bool {
+        self.blocks.contains(&block)
+    }
+
+    /// The blocks branching back to the header
+    pub fn latches(&self) -> &[Id<BB>] {
+        &self.latches
+    }
+
+    pub fn parent(&self) -> Option<LoopId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[LoopId] {
+        &self.children
+    }
+
+    /// The block outside the loop which is the only one entering it, if it
+    /// does nothing but enter it
+    pub fn preheader<F: BasicBlockData<BB>>(&self, f: &F) -> Option<Id<BB>> {
+        let mut outside = f
+            .get(self.header)
+            .preds()
+            .iter()
+            .filter(|pred| !self.blocks.contains(pred));
+        let preheader = *outside.next()?;
+        if outside.next().is_some() || f.get(preheader).succs().len() != 1 {
+            return None;
+        }
+        Some(preheader)
+    }
+
+    /// The blocks of the loop which branch out of it
+    pub fn exiting_blocks<F: BasicBlockData<BB>>(&self, f: &F) -> Vec<Id<BB>> {
+        let mut exiting = self
+            .blocks
+            .iter()
+            .copied()
+            .filter(|&block| {
+                f.get(block)
+                    .succs()
+                    .iter()
+                    .any(|succ| !self.blocks.contains(succ))
+            })
+            .collect::<Vec<_>>();
+        exiting.sort_by_key(|block| block.index());
+        exiting
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::core::{
+        ir::function::basic_block::BasicBlock as IrBasicBlock,
+        parser::assembly::module::parse as parse_assembly,
+    };
+
+    #[test]
+    fn nested_loops() {
+        let module = parse_assembly(
+            r#"
+define void @main(i64 %0) {
+entry:
+  br label %outer
+
+outer:
+  %1 = phi i64 [ 0, %entry ], [ %5, %outer_latch ]
+  %2 = icmp ult i64 %1, %0
+  br i1 %2, label %inner, label %done
+
+inner:
+  %3 = phi i64 [ 0, %outer ], [ %4, %inner ]
+  %4 = add i64 %3, 1
+  %c = icmp ult i64 %4, 10
+  br i1 %c, label %inner, label %outer_latch
+
+outer_latch:
+  %5 = add i64 %1, 1
+  br label %outer
+
+done:
+  ret void
+}
+"#,
+        )
+        .expect("failed to parse IR");
+        let func = &module.functions[module.find_function_by_name("main").unwrap()];
+        let dom_tree = DominatorTree::<IrBasicBlock>::new(func);
+        let forest = LoopForest::new(func, &dom_tree);
+
+        let blocks = func.layout.block_iter().collect::<Vec<_>>();
+        let (entry, outer, inner, outer_latch, done) =
+            (blocks[0], blocks[1], blocks[2], blocks[3], blocks[4]);
+
+        let inner_loop = forest.innermost_loop_of(inner).unwrap();
+        let outer_loop = forest.innermost_loop_of(outer_latch).unwrap();
+        assert_eq!(forest.loops().count(), 2);
+        assert_eq!(forest.get(inner_loop).header(), inner);
+        assert_eq!(forest.get(inner_loop).latches(), [inner]);
+        assert_eq!(forest.get(inner_loop).parent(), Some(outer_loop));
+        assert_eq!(forest.get(inner_loop).preheader(func), None);
+        assert_eq!(forest.get(outer_loop).header(), outer);
+
+        let outer = forest.get(outer_loop);
+        assert_eq!(outer.blocks().len(), 3);
+        assert_eq!(outer.children(), [inner_loop]);
+        assert_eq!(outer.preheader(func), Some(entry));
+        assert_eq!(outer.exiting_blocks(func), [blocks[1]]);
+        assert!(forest.innermost_loop_of(done).is_none());
+    }
+}
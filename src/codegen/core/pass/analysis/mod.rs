@@ -1 +1,4 @@
 pub mod dom_tree;
+pub mod loops;
+pub mod tail_call;
+pub mod value_range;
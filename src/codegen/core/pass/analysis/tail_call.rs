@@ -0,0 +1,155 @@
+use crate::codegen::core::ir::{
+    function::{
+        instruction::{InstructionId, Opcode, Operand, Ret},
+        Function,
+    },
+    value::Value,
+};
+use rustc_hash::FxHashSet;
+
+/// The calls of a function in tail position: calls directly followed by a
+/// `ret` of their result, or by a `ret void` when nothing uses their result.
+/// The callee may return to the caller of the function in its place, which is
+/// only correct when the callee cannot reach the locals of the function, so
+/// there are none if the address of an alloca is taken.
+pub fn tail_calls(func: &Function) -> FxHashSet<InstructionId> {
+    let mut calls = FxHashSet::default();
+    if func.is_prototype() || allocas_escape(func) {
+        return calls;
+    }
+
+    for block in func.layout.block_iter() {
+        let insts = func.layout.inst_iter(block).collect::<Vec<_>>();
+        for pair in insts.windows(2) {
+            let (call, ret) = (pair[0], pair[1]);
+            if func.data.inst_ref(call).opcode != Opcode::Call {
+                continue;
+            }
+            let Operand::Ret(Ret { val, .. }) = &func.data.inst_ref(ret).operand else {
+                continue;
+            };
+            let users = func.data.users_of(call);
+            let returned = match val {
+                Some(val) => {
+                    matches!(func.data.value_ref(*val), Value::Instruction(id) if *id == call)
+                        && users.len() == 1
+                }
+                None => users.is_empty(),
+            };
+            if returned {
+                calls.insert(call);
+            }
+        }
+    }
+    calls
+}
+
+/// Whether an alloca is used other than as the address of a load or store
+fn allocas_escape(func: &Function) -> bool {
+    func.layout.block_iter().any(|block| {
+        func.layout.inst_iter(block).any(|id| {
+            func.data.inst_ref(id).opcode == Opcode::Alloca
+                && func.data.users_of(id).iter().any(|&user_id| {
+                    let user = func.data.inst_ref(user_id);
+                    match &user.operand {
+                        Operand::Load(_) => false,
+                        Operand::Store(store) => matches!(
+                            func.data.value_ref(store.src_val()),
+                            Value::Instruction(src) if *src == id
+                        ),
+                        _ => true,
+                    }
+                })
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::core::parser::assembly::module::parse as parse_assembly;
+
+    /// The names of the callees of the tail calls of `name`
+    fn tail_callees(source: &str, name: &str) -> Vec<String> {
+        let module = parse_assembly(source).expect("failed to parse IR");
+        let func = &module.functions[module.find_function_by_name(name).unwrap()];
+        let calls = tail_calls(func);
+        let mut callees = func
+            .layout
+            .block_iter()
+            .flat_map(|block| func.layout.inst_iter(block))
+            .filter(|id| calls.contains(id))
+            .map(|id| {
+                func.data
+                    .inst_ref(id)
+                    .callee_name(&func.data)
+                    .unwrap()
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        callees.sort();
+        callees
+    }
+
+    #[test]
+    fn tail_calls_return_the_result() {
+        let source = r#"
+declare i64 @f(i64)
+
+declare void @g()
+
+define i64 @main(i64 %0) {
+entry:
+  %1 = icmp eq i64 %0, 0
+  br i1 %1, label %then, label %else
+
+then:
+  call void @g()
+  %2 = call i64 @f(i64 %0)
+  ret i64 %2
+
+else:
+  %3 = call i64 @f(i64 1)
+  %4 = add i64 %3, 1
+  ret i64 %4
+}
+
+define void @h() {
+entry:
+  %0 = call i64 @f(i64 2)
+  call void @g()
+  ret void
+}
+"#;
+        assert_eq!(tail_callees(source, "main"), ["f"]);
+        assert_eq!(tail_callees(source, "h"), ["g"]);
+    }
+
+    #[test]
+    fn tail_calls_escaping_alloca() {
+        let source = r#"
+declare void @f(ptr)
+
+declare i64 @g(i64)
+
+define i64 @main(i64 %0) {
+entry:
+  %1 = alloca i64, align 8
+  store i64 %0, ptr %1, align 4
+  %2 = load i64, ptr %1, align 4
+  %3 = call i64 @g(i64 %2)
+  ret i64 %3
+}
+
+define void @escape() {
+entry:
+  %0 = alloca i64, align 8
+  store i64 1, ptr %0, align 4
+  call void @f(ptr %0)
+  ret void
+}
+"#;
+        assert_eq!(tail_callees(source, "main"), ["g"]);
+        assert!(tail_callees(source, "escape").is_empty());
+    }
+}
@@ -0,0 +1,538 @@
+use super::dom_tree::DominatorTree;
+use crate::codegen::core::ir::{
+    function::{
+        basic_block::{BasicBlock, BasicBlockId},
+        instruction::{ICmpCond, InstructionId, Opcode, Operand},
+        Function,
+    },
+    types::Type,
+    value::{ConstantInt, ConstantValue, Value, ValueId},
+};
+use rustc_hash::FxHashMap;
+
+/// The order of the field the values of the Ola VM are elements of
+pub const FIELD_ORDER: u64 = 0xFFFF_FFFF_0000_0001;
+
+/// The values `builtin_range_check` accepts are at most this
+pub const U32_MAX: u64 = u32::MAX as u64;
+
+/// Ranges are not refined through longer chains of operands and conditions
+const MAX_DEPTH: usize = 8;
+
+/// Phis whose range still changes after this many rounds are widened
+const WIDEN_AFTER: usize = 3;
+
+/// Rounds of narrowing the phis down again once their ranges are stable
+const NARROW_ROUNDS: usize = 2;
+
+/// An inclusive range of field elements. It is empty if `lo > hi`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub lo: u64,
+    pub hi: u64,
+}
+
+/// The values the integers of a function may take. Ranges follow from the
+/// constants, the widths of `zext` and `trunc`, and the arithmetic on them,
+/// and are narrowed by the comparisons of the branches leading to a point of
+/// the function and by the calls to `builtin_range_check` before it.
+pub struct ValueRanges<'a> {
+    func: &'a Function,
+    idom: FxHashMap<BasicBlockId, BasicBlockId>,
+    /// The comparisons which hold in each block, as it can only be entered
+    /// through the branches taken on them
+    conds: FxHashMap<BasicBlockId, Vec<Cond>>,
+    /// The arguments of `builtin_range_check` in each block, with the
+    /// position of the call
+    checks: FxHashMap<BasicBlockId, Vec<(usize, ValueId)>>,
+    positions: FxHashMap<InstructionId, usize>,
+    phis: FxHashMap<InstructionId, Range>,
+}
+
+/// `args[0] cond args[1]` holds
+#[derive(Clone, Copy)]
+struct Cond {
+    cond: ICmpCond,
+    args: [ValueId; 2],
+}
+
+/// A point of the function, right before the instruction at `pos` of `block`
+#[derive(Clone, Copy)]
+struct Point {
+    block: BasicBlockId,
+    pos: usize,
+}
+
+impl Range {
+    pub const FULL: Range = Range {
+        lo: 0,
+        hi: FIELD_ORDER - 1,
+    };
+
+    pub const EMPTY: Range = Range { lo: 1, hi: 0 };
+
+    pub fn new(lo: u64, hi: u64) -> Self {
+        Self { lo, hi }
+    }
+
+    pub fn exact(val: u64) -> Self {
+        Self { lo: val, hi: val }
+    }
+
+    /// The values which fit in `bits` bits
+    pub fn bits(bits: u32) -> Self {
+        match bits {
+            0..=63 => Self::new(0, (1 << bits) - 1),
+            _ => Self::FULL,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lo > self.hi
+    }
+
+    pub fn intersect(self, other: Self) -> Self {
+        Self::new(self.lo.max(other.lo), self.hi.min(other.hi))
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        match (self.is_empty(), other.is_empty()) {
+            (true, _) => other,
+            (_, true) => self,
+            _ => Self::new(self.lo.min(other.lo), self.hi.max(other.hi)),
+        }
+    }
+
+    fn add(self, other: Self) -> Self {
+        match self.hi.checked_add(other.hi) {
+            Some(hi) if hi < FIELD_ORDER => Self::new(self.lo + other.lo, hi),
+            _ => Self::FULL,
+        }
+    }
+
+    fn sub(self, other: Self) -> Self {
+        if self.lo >= other.hi {
+            Self::new(self.lo - other.hi, self.hi - other.lo)
+        } else {
+            Self::FULL
+        }
+    }
+
+    fn mul(self, other: Self) -> Self {
+        match self.hi.checked_mul(other.hi) {
+            Some(hi) if hi < FIELD_ORDER => Self::new(self.lo * other.lo, hi),
+            _ => Self::FULL,
+        }
+    }
+}
+
+impl<'a> ValueRanges<'a> {
+    pub fn new(func: &'a Function) -> Self {
+        let dom_tree = DominatorTree::<BasicBlock>::new(func);
+        let mut ranges = Self {
+            func,
+            idom: FxHashMap::default(),
+            conds: FxHashMap::default(),
+            checks: FxHashMap::default(),
+            positions: FxHashMap::default(),
+            phis: FxHashMap::default(),
+        };
+
+        let mut phis = vec![];
+        for block in func.layout.block_iter() {
+            for (pos, id) in func.layout.inst_iter(block).enumerate() {
+                ranges.positions.insert(id, pos);
+                let inst = func.data.inst_ref(id);
+                if inst.opcode == Opcode::Phi {
+                    phis.push(id);
+                    ranges.phis.insert(id, Range::EMPTY);
+                }
+                if inst.callee_name(&func.data) == Some("builtin_range_check") {
+                    let arg = inst.operand.args()[1];
+                    ranges.checks.entry(block).or_default().push((pos, arg));
+                }
+            }
+        }
+
+        // The conditions of a block are the ones of its immediate dominator,
+        // and the one of the branch to it if that is the only way in
+        let mut worklist = vec![*dom_tree.root()];
+        ranges.conds.insert(*dom_tree.root(), vec![]);
+        while let Some(block) = worklist.pop() {
+            let Some(children) = dom_tree.children_of(block) else {
+                continue;
+            };
+            for &child in children {
+                ranges.idom.insert(child, block);
+                let mut conds = ranges.conds[&block].clone();
+                conds.extend(ranges.edge_cond(child));
+                ranges.conds.insert(child, conds);
+                worklist.push(child);
+            }
+        }
+
+        ranges.compute_phis(&phis);
+        ranges
+    }
+
+    /// The range of `val` right before the instruction `at`
+    pub fn range_before(&self, at: InstructionId, val: ValueId) -> Range {
+        let point = Point {
+            block: self.func.data.inst_ref(at).parent,
+            pos: self.positions[&at],
+        };
+        self.range_at(val, point, 0, true)
+    }
+
+    /// The comparison which holds in `block` because of the branch to it
+    fn edge_cond(&self, block: BasicBlockId) -> Option<Cond> {
+        let preds = self.func.data.block_ref(block).preds();
+        if preds.len() != 1 {
+            return None;
+        }
+        let pred = *preds.iter().next().unwrap();
+        let terminator = (*self.func.layout.block_node(pred).last_inst())?;
+        let Operand::CondBr(br) = &self.func.data.inst_ref(terminator).operand else {
+            return None;
+        };
+        let Value::Instruction(cmp) = self.func.data.value_ref(br.arg) else {
+            return None;
+        };
+        let Operand::ICmp(icmp) = &self.func.data.inst_ref(*cmp).operand else {
+            return None;
+        };
+        match br.blocks {
+            [then, otherwise] if then == otherwise => None,
+            [then, _] if then == block => Some(Cond {
+                cond: icmp.cond,
+                args: icmp.args,
+            }),
+            _ => Some(Cond {
+                cond: negate(icmp.cond),
+                args: icmp.args,
+            }),
+        }
+    }
+
+    /// Find the ranges of the phis by iterating from empty ranges. Comparisons
+    /// bound the values coming around loops, but the range checks do not, as
+    /// a check may only be removed because of the ranges of values it does
+    /// not bound itself.
+    fn compute_phis(&mut self, phis: &[InstructionId]) {
+        let mut round = 0;
+        loop {
+            let mut changed = false;
+            for &phi in phis {
+                let old = self.phis[&phi];
+                let mut range = old.union(self.incoming_range(phi));
+                if range == old {
+                    continue;
+                }
+                if round >= WIDEN_AFTER {
+                    if range.lo < old.lo {
+                        range.lo = 0;
+                    }
+                    if range.hi > old.hi {
+                        range.hi = Range::FULL.hi;
+                    }
+                }
+                self.phis.insert(phi, range);
+                changed = true;
+            }
+            if !changed {
+                break;
+            }
+            round += 1;
+        }
+
+        // Widened ranges are narrowed back down to what comes into the phis
+        for _ in 0..NARROW_ROUNDS {
+            for &phi in phis {
+                let range = self.incoming_range(phi);
+                self.phis.insert(phi, range);
+            }
+        }
+    }
+
+    /// The union of the ranges of the values `phi` takes from its predecessors
+    fn incoming_range(&self, phi: InstructionId) -> Range {
+        let Operand::Phi(operand) = &self.func.data.inst_ref(phi).operand else {
+            unreachable!()
+        };
+        let mut range = Range::EMPTY;
+        for (&arg, &pred) in operand.args.iter().zip(&operand.blocks) {
+            let end = Point {
+                block: pred,
+                pos: usize::MAX,
+            };
+            range = range.union(self.range_at(arg, end, 0, false));
+        }
+        range
+    }
+
+    fn range_at(&self, val: ValueId, point: Point, depth: usize, use_checks: bool) -> Range {
+        if depth > MAX_DEPTH {
+            return Range::FULL;
+        }
+        let mut range = self.base_range(val, point, depth, use_checks);
+
+        for cond in self.conds.get(&point.block).into_iter().flatten() {
+            let (cond_kind, other) = if self.same(cond.args[0], val) {
+                (cond.cond, cond.args[1])
+            } else if self.same(cond.args[1], val) {
+                (swap(cond.cond), cond.args[0])
+            } else {
+                continue;
+            };
+            let other = self.range_at(other, point, depth + 1, use_checks);
+            if other.is_empty() {
+                continue;
+            }
+            let bound = match cond_kind {
+                ICmpCond::Eq => other,
+                ICmpCond::Ult if other.hi > 0 => Range::new(0, other.hi - 1),
+                ICmpCond::Ule => Range::new(0, other.hi),
+                ICmpCond::Ugt if other.lo < Range::FULL.hi => {
+                    Range::new(other.lo + 1, Range::FULL.hi)
+                }
+                ICmpCond::Uge => Range::new(other.lo, Range::FULL.hi),
+                _ => continue,
+            };
+            range = range.intersect(bound);
+        }
+
+        if use_checks && self.is_checked(val, point) {
+            range = range.intersect(Range::bits(32));
+        }
+        range
+    }
+
+    /// Whether a call to `builtin_range_check` of `val` is always made before
+    /// `point`
+    fn is_checked(&self, val: ValueId, point: Point) -> bool {
+        let mut block = point.block;
+        let mut before = point.pos;
+        loop {
+            let checked = self.checks.get(&block).map_or(false, |checks| {
+                checks
+                    .iter()
+                    .any(|&(pos, arg)| pos < before && self.same(arg, val))
+            });
+            if checked {
+                return true;
+            }
+            match self.idom.get(&block) {
+                Some(&idom) => {
+                    block = idom;
+                    before = usize::MAX;
+                }
+                None => return false,
+            }
+        }
+    }
+
+    fn base_range(&self, val: ValueId, point: Point, depth: usize, use_checks: bool) -> Range {
+        let operand = |val: ValueId| self.range_at(val, point, depth + 1, use_checks);
+
+        let id = match self.func.data.value_ref(val) {
+            Value::Constant(ConstantValue::Int(int)) => {
+                return Range::exact(match *int {
+                    ConstantInt::Int1(i) => i as u64,
+                    ConstantInt::Int8(i) => i as u8 as u64,
+                    ConstantInt::Int32(i) => i as u32 as u64,
+                    ConstantInt::Int64(i) => i as u64,
+                })
+            }
+            Value::Argument(arg) => return type_range(arg.ty),
+            Value::Instruction(id) => *id,
+            _ => return Range::FULL,
+        };
+
+        let inst = self.func.data.inst_ref(id);
+        let range = match &inst.operand {
+            Operand::Phi(_) => self.phis[&id],
+            Operand::ICmp(_) => Range::new(0, 1),
+            Operand::Cast(cast) if inst.opcode == Opcode::Zext => {
+                operand(cast.arg).intersect(type_range(cast.tys[0]))
+            }
+            Operand::Trunc(trunc) => {
+                let arg = operand(trunc.arg);
+                let to = type_range(trunc.tys[1]);
+                if arg.hi <= to.hi {
+                    arg
+                } else {
+                    to
+                }
+            }
+            Operand::IntBinary(bin) => {
+                let (lhs, rhs) = (operand(bin.args[0]), operand(bin.args[1]));
+                if lhs.is_empty() || rhs.is_empty() {
+                    return Range::EMPTY;
+                }
+                let range = match inst.opcode {
+                    Opcode::Add => lhs.add(rhs),
+                    Opcode::Sub => lhs.sub(rhs),
+                    Opcode::Mul => lhs.mul(rhs),
+                    Opcode::And => Range::new(0, lhs.hi.min(rhs.hi)),
+                    Opcode::Or | Opcode::Xor => {
+                        let bits = 64 - lhs.hi.max(rhs.hi).leading_zeros();
+                        Range::bits(bits)
+                    }
+                    Opcode::LShr if rhs.lo == rhs.hi && rhs.lo < 64 => {
+                        Range::new(lhs.lo >> rhs.lo, lhs.hi >> rhs.lo)
+                    }
+                    _ => Range::FULL,
+                };
+                // narrower integers wrap around
+                let ty = type_range(bin.ty);
+                if range.hi <= ty.hi {
+                    range
+                } else {
+                    ty
+                }
+            }
+            _ => type_range(inst.ty),
+        };
+        range
+    }
+
+    /// Whether `a` and `b` are the same value
+    fn same(&self, a: ValueId, b: ValueId) -> bool {
+        a == b
+            || match (self.func.data.value_ref(a), self.func.data.value_ref(b)) {
+                (Value::Instruction(a), Value::Instruction(b)) => a == b,
+                (Value::Argument(a), Value::Argument(b)) => a.nth == b.nth,
+                _ => false,
+            }
+    }
+}
+
+/// The values of type `ty`. Both `i32` and `i64` are read as `I32`, which
+/// holds any field element, so only the narrower types bound a value.
+fn type_range(ty: Type) -> Range {
+    if ty.is_i1() {
+        Range::bits(1)
+    } else if ty.is_i8() {
+        Range::bits(8)
+    } else if ty.is_i16() {
+        Range::bits(16)
+    } else {
+        Range::FULL
+    }
+}
+
+/// The condition which holds when `cond` does not
+fn negate(cond: ICmpCond) -> ICmpCond {
+    match cond {
+        ICmpCond::Eq => ICmpCond::Ne,
+        ICmpCond::Ne => ICmpCond::Eq,
+        ICmpCond::Ugt => ICmpCond::Ule,
+        ICmpCond::Uge => ICmpCond::Ult,
+        ICmpCond::Ult => ICmpCond::Uge,
+        ICmpCond::Ule => ICmpCond::Ugt,
+        ICmpCond::Sgt => ICmpCond::Sle,
+        ICmpCond::Sge => ICmpCond::Slt,
+        ICmpCond::Slt => ICmpCond::Sge,
+        ICmpCond::Sle => ICmpCond::Sgt,
+    }
+}
+
+/// The condition which holds with the operands swapped
+fn swap(cond: ICmpCond) -> ICmpCond {
+    match cond {
+        ICmpCond::Ugt => ICmpCond::Ult,
+        ICmpCond::Uge => ICmpCond::Ule,
+        ICmpCond::Ult => ICmpCond::Ugt,
+        ICmpCond::Ule => ICmpCond::Uge,
+        ICmpCond::Sgt => ICmpCond::Slt,
+        ICmpCond::Sge => ICmpCond::Sle,
+        ICmpCond::Slt => ICmpCond::Sgt,
+        ICmpCond::Sle => ICmpCond::Sge,
+        cond => cond,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::core::parser::assembly::module::parse as parse_assembly;
+
+    /// The ranges of the arguments of the calls to `builtin_range_check` in
+    /// `main`, in order
+    fn checked_ranges(source: &str) -> Vec<Range> {
+        let module = parse_assembly(source).expect("failed to parse IR");
+        let id = module.find_function_by_name("main").unwrap();
+        let func = &module.functions()[id];
+        let ranges = ValueRanges::new(func);
+        let mut checked = vec![];
+        for block in func.layout.block_iter() {
+            for id in func.layout.inst_iter(block) {
+                let inst = func.data.inst_ref(id);
+                if inst.callee_name(&func.data) == Some("builtin_range_check") {
+                    checked.push(ranges.range_before(id, inst.operand.args()[1]));
+                }
+            }
+        }
+        checked
+    }
+
+    #[test]
+    fn range_arithmetic_overflow_is_full() {
+        let big = Range::new(FIELD_ORDER - 2, FIELD_ORDER - 1);
+        assert_eq!(Range::new(1, 2).add(Range::new(3, 4)), Range::new(4, 6));
+        assert_eq!(big.add(Range::exact(1)), Range::FULL);
+        assert_eq!(Range::FULL.add(Range::FULL), Range::FULL);
+
+        assert_eq!(Range::new(5, 9).sub(Range::new(1, 5)), Range::new(0, 8));
+        assert_eq!(Range::new(5, 9).sub(Range::new(1, 6)), Range::FULL);
+        assert_eq!(Range::exact(0).sub(Range::exact(1)), Range::FULL);
+
+        assert_eq!(Range::new(2, 3).mul(Range::new(4, 5)), Range::new(8, 15));
+        assert_eq!(Range::bits(33).mul(Range::bits(32)), Range::FULL);
+        assert_eq!(big.mul(Range::exact(2)), Range::FULL);
+    }
+
+    #[test]
+    fn range_branch_bounds_at_the_limits() {
+        // x < 0 and x > p - 1 never hold, and bound nothing rather than
+        // wrapping around
+        let ranges = checked_ranges(
+            r#"
+declare void @builtin_range_check(i64)
+
+define void @main(i64 %0) {
+entry:
+  %1 = icmp ult i64 %0, 0
+  br i1 %1, label %below_zero, label %next
+
+below_zero:
+  call void @builtin_range_check(i64 %0)
+  br label %next
+
+next:
+  %2 = icmp ugt i64 %0, -4294967296
+  br i1 %2, label %above_max, label %bounded
+
+above_max:
+  call void @builtin_range_check(i64 %0)
+  br label %bounded
+
+bounded:
+  %3 = icmp ugt i64 %0, 10
+  br i1 %3, label %above_ten, label %done
+
+above_ten:
+  call void @builtin_range_check(i64 %0)
+  br label %done
+
+done:
+  ret void
+}
+"#,
+        );
+        assert_eq!(
+            ranges,
+            vec![Range::FULL, Range::FULL, Range::new(11, Range::FULL.hi)]
+        );
+    }
+}
@@ -100,6 +100,81 @@ impl PassManager<Function> {
     }
 }
 
+/// How much effort is spent on optimizing the IR before it is lowered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    /// The IR is lowered as it is
+    #[default]
+    None,
+    /// Promote stack slots to registers, remove redundant computations and
+    /// storage accesses, inline trivial functions, hoist invariant
+    /// computations out of loops, remove range checks which cannot fail and
+    /// remove dead code
+    Less,
+    /// Also inline small functions
+    Default,
+    /// Also fully unroll loops with a small constant number of iterations
+    Aggressive,
+}
+
+impl TryFrom<u8> for OptLevel {
+    type Error = String;
+
+    fn try_from(level: u8) -> Result<Self, Self::Error> {
+        match level {
+            0 => Ok(OptLevel::None),
+            1 => Ok(OptLevel::Less),
+            2 => Ok(OptLevel::Default),
+            3 => Ok(OptLevel::Aggressive),
+            _ => Err(format!(
+                "invalid optimization level {}, expected 0, 1, 2 or 3",
+                level
+            )),
+        }
+    }
+}
+
+/// What the optimization pipeline did to a module
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OptReport {
+    /// The calls to `builtin_range_check` which could not fail
+    pub range_checks_removed: usize,
+}
+
+/// Run the optimization pipeline of the given level over a module.
+pub fn optimize(module: &mut Module, level: OptLevel) -> OptReport {
+    let mut report = OptReport::default();
+    if level == OptLevel::None {
+        return report;
+    }
+
+    let mut pm = PassManager::new();
+    pm.add_transform(transform::mem2reg::Mem2RegPass);
+    pm.add_transform(transform::gvn::GvnPass);
+    pm.run_on_module(module);
+
+    let threshold = match level {
+        OptLevel::Less => transform::inline::LESS_THRESHOLD,
+        _ => transform::inline::DEFAULT_THRESHOLD,
+    };
+    transform::inline::run_on_module(module, threshold);
+
+    // inlining exposes redundancies across the old call boundaries
+    let mut pm = PassManager::new();
+    pm.add_transform(transform::gvn::GvnPass);
+    pm.add_transform(transform::licm::LicmPass);
+    if level == OptLevel::Aggressive {
+        // the copies of the body repeat what does not depend on the iteration
+        pm.add_transform(transform::unroll::UnrollPass);
+        pm.add_transform(transform::gvn::GvnPass);
+    }
+    pm.run_on_module(module);
+
+    report.range_checks_removed = transform::range_check::run_on_module(module);
+    transform::dce::run_on_module(module);
+    report
+}
+
 impl<T> Pass<T> {
     pub fn analysis<P: 'static + AnalysisPass<T>>(pass: P) -> Self {
         Self::Analysis(Box::new(pass))
@@ -0,0 +1,1076 @@
+use crate::codegen::core::{
+    ir::{
+        function::{
+            basic_block::{BasicBlock, BasicBlockId},
+            data::Data,
+            instruction::{GetElementPtr, ICmpCond, InstructionId, Load, Opcode, Operand, Store},
+            Function,
+        },
+        module::name::Name,
+        types::{Type, I32},
+        value::{ConstantInt, ConstantValue, Value, ValueId},
+    },
+    pass::{analysis::dom_tree::DominatorTree, TransformPass},
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::{any::Any, hash::Hash};
+
+/// Removes instructions which compute a value that is already available,
+/// including loads of memory which has not changed since, and calls to
+/// `poseidon_hash` whose input has already been hashed. Values read from or
+/// written to storage are forwarded to later `get_storage` calls of the same
+/// slot, and a `set_storage` overwritten later in its block is removed.
+pub struct GvnPass;
+
+pub struct Gvn<'a> {
+    func: &'a mut Function,
+    dom_tree: DominatorTree<BasicBlock>,
+    exprs: FxHashMap<Expr, ValueNumber>,
+    next_vn: ValueNumber,
+    inst_vns: FxHashMap<InstructionId, ValueNumber>,
+    value_vns: FxHashMap<ValueId, ValueNumber>,
+    /// The buffer a pointer points into, and the word it points to
+    addrs: FxHashMap<InstructionId, (InstructionId, usize)>,
+    /// The values available in the current block, by value number
+    leaders: ScopedMap<ValueNumber, ValueId>,
+    /// The buffers holding the hash of their input, by the input
+    hashes: ScopedMap<Vec<ValueNumber>, ValueId>,
+    /// Buffers which are only written by the call to `poseidon_hash` which
+    /// fills them
+    hash_results: FxHashSet<InstructionId>,
+    /// The integer constants, by value number
+    ints: FxHashMap<ValueNumber, i64>,
+    /// The calls to `set_storage` in the current block whose slot has not
+    /// been read since, by the slot
+    pending_stores: FxHashMap<Vec<ValueNumber>, InstructionId>,
+    next_epoch: usize,
+}
+
+type ValueNumber = usize;
+
+/// Words written by `poseidon_hash`
+const HASH_WORDS: usize = 4;
+
+/// Words of a storage slot and of its value
+const SLOT_WORDS: usize = 4;
+
+/// Buffers larger than this are not tracked word by word
+const MAX_TRACKED_WORDS: usize = 64;
+
+/// The identity of a value. Values with the same expression are equal.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Expr {
+    Int(u8, i64),
+    Arg(usize),
+    Global(Name),
+    Inst {
+        opcode: Opcode,
+        cond: Option<ICmpCond>,
+        tys: Vec<Type>,
+        args: Vec<ValueNumber>,
+    },
+    /// A word of memory which is not tracked, as it was in an epoch
+    Mem(ValueNumber, usize, usize),
+    Load(Type, ValueNumber, usize),
+    /// A word of the hash of the given words
+    Hash(Vec<ValueNumber>, usize),
+}
+
+/// A word of a tracked buffer
+#[derive(Clone)]
+struct Word {
+    vn: ValueNumber,
+    /// A value holding the word, and its type
+    value: Option<(ValueId, Type)>,
+}
+
+/// What is known about memory at some point of the function. Buffers which
+/// are freshly allocated cannot be written through any other pointer until
+/// their address escapes, so their words are tracked. A write to any other
+/// memory starts a new epoch.
+#[derive(Clone, Default)]
+struct Memory {
+    epoch: usize,
+    buffers: FxHashMap<InstructionId, Vec<Option<Word>>>,
+    /// The known values of storage slots, by the words of the slot
+    storage: FxHashMap<Vec<ValueNumber>, Slot>,
+}
+
+/// The value of a storage slot, as held by the buffer passed to the call of
+/// `get_storage` or `set_storage` which last accessed it
+#[derive(Clone)]
+struct Slot {
+    words: Vec<Word>,
+    call: InstructionId,
+    buffer: ValueId,
+}
+
+struct ScopedMap<K, V> {
+    map: FxHashMap<K, V>,
+    undo: Vec<(K, Option<V>)>,
+}
+
+impl<'a> Gvn<'a> {
+    pub fn new(func: &'a mut Function) -> Self {
+        Self {
+            dom_tree: DominatorTree::new(func),
+            hash_results: hash_results(func),
+            func,
+            exprs: FxHashMap::default(),
+            next_vn: 0,
+            inst_vns: FxHashMap::default(),
+            value_vns: FxHashMap::default(),
+            addrs: FxHashMap::default(),
+            leaders: ScopedMap::default(),
+            hashes: ScopedMap::default(),
+            ints: FxHashMap::default(),
+            pending_stores: FxHashMap::default(),
+            next_epoch: 1,
+        }
+    }
+
+    pub fn run(&mut self) {
+        let Some(entry) = self.func.layout.get_entry_block() else {
+            return;
+        };
+        self.visit(entry, Memory::default());
+    }
+
+    fn visit(&mut self, block: BasicBlockId, mut memory: Memory) {
+        let leaders = self.leaders.mark();
+        let hashes = self.hashes.mark();
+        self.pending_stores.clear();
+
+        let insts = self.func.layout.inst_iter(block).collect::<Vec<_>>();
+        for inst in insts {
+            self.visit_inst(inst, &mut memory);
+        }
+
+        let mut children = self
+            .dom_tree
+            .children_of(block)
+            .map_or(vec![], |children| children.iter().copied().collect());
+        children.sort_by_key(|child| child.index());
+
+        for child in children {
+            // The memory is only known in a block which is entered from here
+            let preds = self.func.data.block_ref(child).preds();
+            let memory = if preds.len() == 1 && preds.contains(&block) {
+                memory.clone()
+            } else {
+                Memory {
+                    epoch: self.new_epoch(),
+                    ..Memory::default()
+                }
+            };
+            self.visit(child, memory);
+        }
+
+        self.leaders.reset(leaders);
+        self.hashes.reset(hashes);
+    }
+
+    fn visit_inst(&mut self, id: InstructionId, memory: &mut Memory) {
+        let inst = self.func.data.inst_ref(id);
+        let is_void = inst.ty.is_void();
+        let args = inst.operand.args().to_vec();
+
+        match inst.operand.clone() {
+            Operand::IntBinary(_)
+            | Operand::ICmp(_)
+            | Operand::Cast(_)
+            | Operand::Trunc(_)
+            | Operand::ExtractValue(_)
+            | Operand::InsertValue(_) => {
+                self.escape(memory, &args);
+                let expr = self.expr_of(id);
+                self.number_pure(id, expr);
+            }
+            Operand::GetElementPtr(gep) => {
+                let offset = match gep.args.as_slice() {
+                    [_, idx] if gep.tys[0].is_integer() => const_int(&self.func.data, *idx),
+                    _ => None,
+                };
+                match (self.base_of(gep.args[0]), offset) {
+                    (Some((buffer, base)), Some(offset)) if offset >= 0 => {
+                        self.addrs.insert(id, (buffer, base + offset as usize));
+                    }
+                    _ => self.escape(memory, &args),
+                }
+                let expr = self.expr_of(id);
+                self.number_pure(id, expr);
+            }
+            Operand::Load(load) => {
+                let ty = load.tys[0];
+                let addr = load.addr;
+                match self.word_of(memory, addr) {
+                    Some(Some(Word {
+                        value: Some((value, value_ty)),
+                        ..
+                    })) if value_ty == ty => self.replace(id, value),
+                    Some(word) => {
+                        let vn = match word {
+                            Some(word) => word.vn,
+                            None => self.fresh(),
+                        };
+                        self.inst_vns.insert(id, vn);
+                        let value = self.func.data.create_value(Value::Instruction(id));
+                        self.set_word(memory, addr, vn, Some((value, ty)));
+                    }
+                    None => {
+                        let addr = self.vn_of(addr);
+                        let expr = Expr::Load(ty, addr, memory.epoch);
+                        self.number_pure(id, expr);
+                    }
+                }
+            }
+            Operand::Store(store) => {
+                let (src, dst, ty) = (store.src_val(), store.dst_val(), store.tys[0]);
+                self.escape(memory, &[src]);
+                let vn = self.vn_of(src);
+                if self.word_of(memory, dst).is_some() {
+                    self.set_word(memory, dst, vn, Some((src, ty)));
+                } else {
+                    if let Some((buffer, _)) = self.base_of(dst) {
+                        memory.buffers.remove(&buffer);
+                    }
+                    memory.epoch = self.new_epoch();
+                    // the stored value can be loaded back until memory changes
+                    let addr = self.vn_of(dst);
+                    let expr = Expr::Load(ty, addr, memory.epoch);
+                    let vn = self.number(expr);
+                    self.leaders.insert(vn, src);
+                }
+            }
+            Operand::Call(_) => self.visit_call(id, memory),
+            _ => {
+                self.escape(memory, &args);
+                if !is_void {
+                    let vn = self.fresh();
+                    self.inst_vns.insert(id, vn);
+                }
+            }
+        }
+    }
+
+    fn visit_call(&mut self, id: InstructionId, memory: &mut Memory) {
+        let inst = self.func.data.inst_ref(id);
+        let args = inst.operand.args()[1..].to_vec();
+        let name = inst.callee_name(&self.func.data).map(str::to_string);
+        let vn = self.fresh();
+        self.inst_vns.insert(id, vn);
+
+        match (name.as_deref(), args.as_slice()) {
+            (Some("heap_malloc"), &[size]) => {
+                if let Some(size) = const_int(&self.func.data, size) {
+                    if (0..=MAX_TRACKED_WORDS as i64).contains(&size) {
+                        memory.buffers.insert(id, vec![None; size as usize]);
+                    }
+                }
+            }
+            (Some("vector_new"), &[len]) => {
+                if let Some(size) = const_int(&self.func.data, len) {
+                    if (0..MAX_TRACKED_WORDS as i64).contains(&size) {
+                        let ty = self.func.data.inst_ref(id).operand.types()[1];
+                        let mut words = vec![None; size as usize + 1];
+                        words[0] = Some(Word {
+                            vn: self.vn_of(len),
+                            value: Some((len, ty)),
+                        });
+                        memory.buffers.insert(id, words);
+                    }
+                }
+            }
+            (Some("memcpy"), &[src, dst, len]) => {
+                let len = const_int(&self.func.data, len).unwrap_or(-1);
+                let words = (len >= 0)
+                    .then(|| self.read(memory, src, len as usize))
+                    .flatten();
+                self.write(memory, dst, words);
+            }
+            (Some("poseidon_hash"), &[src, dst, len]) => {
+                let len = const_int(&self.func.data, len).unwrap_or(-1);
+                let input = (len >= 0)
+                    .then(|| self.read_vns(memory, src, len as usize))
+                    .flatten();
+                let Some(input) = input else {
+                    return self.write(memory, dst, None);
+                };
+                let words = (0..HASH_WORDS)
+                    .map(|i| {
+                        Some(Word {
+                            vn: self.number(Expr::Hash(input.clone(), i)),
+                            value: None,
+                        })
+                    })
+                    .collect();
+
+                let buffer = match self.func.data.value_ref(dst) {
+                    Value::Instruction(buffer) if self.hash_results.contains(buffer) => {
+                        Some(*buffer)
+                    }
+                    _ => None,
+                };
+                match (buffer, self.hashes.get(&input).copied()) {
+                    (Some(buffer), Some(hashed)) => {
+                        // the buffers are only read from, so the earlier one
+                        // can be read instead
+                        self.func.remove_inst(id);
+                        self.func.data.replace_all_uses(buffer, hashed);
+                        self.func.remove_inst(buffer);
+                    }
+                    (Some(_), None) => {
+                        self.hashes.insert(input, dst);
+                        self.write(memory, dst, Some(words));
+                    }
+                    (None, _) => self.write(memory, dst, Some(words)),
+                }
+            }
+            (Some("get_storage"), &[key, value]) => self.visit_get_storage(id, memory, key, value),
+            (Some("set_storage"), &[key, value]) => self.visit_set_storage(id, memory, key, value),
+            (
+                Some(
+                    "builtin_assert"
+                    | "builtin_range_check"
+                    | "prophet_u32_sqrt"
+                    | "prophet_u32_div"
+                    | "prophet_u32_mod"
+                    | "prophet_split_field_high"
+                    | "prophet_split_field_low",
+                ),
+                _,
+            ) => {}
+            _ => {
+                // the callee may access storage, or call a contract which does
+                self.escape(memory, &args);
+                memory.epoch = self.new_epoch();
+                memory.storage.clear();
+                self.pending_stores.clear();
+            }
+        }
+    }
+
+    fn visit_get_storage(
+        &mut self,
+        id: InstructionId,
+        memory: &mut Memory,
+        key: ValueId,
+        value: ValueId,
+    ) {
+        let key = self.read_vns(memory, key, SLOT_WORDS);
+        let Some(key) = key else {
+            self.pending_stores.clear();
+            return self.write(memory, value, None);
+        };
+
+        if let Some(slot) = memory.storage.get(&key).cloned() {
+            // the slot is known, so the value is copied instead of read
+            let call = self.func.data.inst_ref(id);
+            let (parent, ptr_ty) = (call.parent, call.operand.types()[2]);
+            let mut words = vec![];
+            for (i, word) in slot.words.iter().enumerate() {
+                let word_value = match word.value {
+                    Some((word_value, ty)) if ty == I32 => word_value,
+                    _ => self.materialize(&slot, i),
+                };
+                let addr = self.gep(parent, value, ptr_ty, i);
+                self.func.layout.insert_inst_before(addr, id);
+                let addr = self.func.data.create_value(Value::Instruction(addr));
+                let store = Opcode::Store
+                    .with_block(parent)
+                    .with_operand(Operand::Store(Store {
+                        tys: [I32, ptr_ty],
+                        args: [word_value, addr],
+                        align: 0,
+                    }));
+                let store = self.func.data.create_inst(store);
+                self.func.layout.insert_inst_before(store, id);
+                words.push(Word {
+                    vn: word.vn,
+                    value: Some((word_value, I32)),
+                });
+            }
+            self.func.remove_inst(id);
+            let slot = Slot {
+                words: words.clone(),
+                ..slot
+            };
+            memory.storage.insert(key, slot);
+            return self.write(memory, value, Some(words.into_iter().map(Some).collect()));
+        }
+
+        self.pending_stores
+            .retain(|slot, _| must_differ(&self.ints, slot, &key));
+        self.write(memory, value, None);
+        self.remember_slot(memory, key, id, value);
+    }
+
+    fn visit_set_storage(
+        &mut self,
+        id: InstructionId,
+        memory: &mut Memory,
+        key: ValueId,
+        value: ValueId,
+    ) {
+        let key = self.read_vns(memory, key, SLOT_WORDS);
+        let Some(key) = key else {
+            memory.storage.clear();
+            return;
+        };
+
+        let words = self.read_vns(memory, value, SLOT_WORDS);
+        if let (Some(slot), Some(words)) = (memory.storage.get(&key), &words) {
+            if slot
+                .words
+                .iter()
+                .map(|word| word.vn)
+                .eq(words.iter().copied())
+            {
+                // the slot already holds the value
+                self.func.remove_inst(id);
+                return;
+            }
+        }
+
+        if let Some(overwritten) = self.pending_stores.insert(key.clone(), id) {
+            self.func.remove_inst(overwritten);
+        }
+        memory
+            .storage
+            .retain(|slot, _| must_differ(&self.ints, slot, &key));
+        self.remember_slot(memory, key, id, value);
+    }
+
+    /// Remember that `buffer` holds the value of the slot `key` at `call`
+    fn remember_slot(
+        &mut self,
+        memory: &mut Memory,
+        key: Vec<ValueNumber>,
+        call: InstructionId,
+        buffer: ValueId,
+    ) {
+        let words = self
+            .read(memory, buffer, SLOT_WORDS)
+            .and_then(|words| words.into_iter().collect::<Option<Vec<_>>>());
+        if let Some(words) = words {
+            memory.storage.insert(
+                key,
+                Slot {
+                    words,
+                    call,
+                    buffer,
+                },
+            );
+        }
+    }
+
+    /// Load the `i`th word of a slot from the buffer which held it at the
+    /// time of the call accessing the slot
+    fn materialize(&mut self, slot: &Slot, i: usize) -> ValueId {
+        let call = self.func.data.inst_ref(slot.call);
+        let is_get = call.callee_name(&self.func.data) == Some("get_storage");
+        let ptr_ty = call.operand.types()[2];
+        let parent = call.parent;
+
+        let addr = self.gep(parent, slot.buffer, ptr_ty, i);
+        let addr_value = self.func.data.create_value(Value::Instruction(addr));
+        let load = Opcode::Load
+            .with_block(parent)
+            .with_operand(Operand::Load(Load {
+                tys: [I32, ptr_ty],
+                addr: addr_value,
+                align: 0,
+            }))
+            .with_ty(I32);
+        let load = self.func.data.create_inst(load);
+        self.inst_vns.insert(load, slot.words[i].vn);
+        if is_get {
+            // `get_storage` writes the buffer, so it is read afterwards
+            self.func.layout.insert_inst_after(addr, slot.call);
+            self.func.layout.insert_inst_after(load, addr);
+        } else {
+            self.func.layout.insert_inst_before(addr, slot.call);
+            self.func.layout.insert_inst_before(load, slot.call);
+        }
+        self.func.data.create_value(Value::Instruction(load))
+    }
+
+    /// A pointer to the `i`th word of `buffer`, which is yet to be inserted
+    fn gep(
+        &mut self,
+        parent: BasicBlockId,
+        buffer: ValueId,
+        ptr_ty: Type,
+        i: usize,
+    ) -> InstructionId {
+        let idx = self
+            .func
+            .data
+            .create_value(Value::Constant(ConstantValue::Int(ConstantInt::Int64(
+                i as i64,
+            ))));
+        let gep = Opcode::GetElementPtr
+            .with_block(parent)
+            .with_ty(self.func.types.base_mut().pointer(I32))
+            .with_operand(Operand::GetElementPtr(GetElementPtr {
+                inbounds: false,
+                tys: vec![I32, ptr_ty, I32],
+                args: vec![buffer, idx],
+            }));
+        self.func.data.create_inst(gep)
+    }
+
+    /// The words at `addr`, if it points into a tracked buffer
+    fn read(&mut self, memory: &Memory, addr: ValueId, len: usize) -> Option<Vec<Option<Word>>> {
+        match self.base_of(addr) {
+            Some((buffer, offset)) if memory.buffers.contains_key(&buffer) => memory.buffers
+                [&buffer]
+                .get(offset..offset + len)
+                .map(|words| words.to_vec()),
+            Some(_) => None,
+            None => {
+                let addr = self.vn_of(addr);
+                Some(
+                    (0..len)
+                        .map(|i| {
+                            Some(Word {
+                                vn: self.number(Expr::Mem(addr, i, memory.epoch)),
+                                value: None,
+                            })
+                        })
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    /// The value numbers of the words at `addr`, if they are all known
+    fn read_vns(&mut self, memory: &Memory, addr: ValueId, len: usize) -> Option<Vec<ValueNumber>> {
+        self.read(memory, addr, len)?
+            .iter()
+            .map(|word| word.as_ref().map(|word| word.vn))
+            .collect()
+    }
+
+    /// Write unknown words, or the given ones, to `addr`
+    fn write(&mut self, memory: &mut Memory, addr: ValueId, words: Option<Vec<Option<Word>>>) {
+        let Some((buffer, offset)) = self.base_of(addr) else {
+            memory.epoch = self.new_epoch();
+            return;
+        };
+        let Some(buffer_words) = memory.buffers.get_mut(&buffer) else {
+            memory.epoch = self.new_epoch();
+            return;
+        };
+        let len = buffer_words.len();
+        match words {
+            Some(words) if offset + words.len() <= len => {
+                for (i, word) in words.into_iter().enumerate() {
+                    let word = word.unwrap_or_else(|| Word {
+                        vn: self.fresh(),
+                        value: None,
+                    });
+                    buffer_words[offset + i] = Some(word);
+                }
+            }
+            Some(_) => {
+                memory.buffers.remove(&buffer);
+            }
+            None => {
+                for word in &mut buffer_words[offset.min(len)..] {
+                    *word = Some(Word {
+                        vn: self.fresh(),
+                        value: None,
+                    });
+                }
+            }
+        }
+    }
+
+    /// The word at `addr`: `None` if it is not in a tracked buffer, and
+    /// `Some(None)` if nothing is known about it
+    fn word_of(&self, memory: &Memory, addr: ValueId) -> Option<Option<Word>> {
+        let (buffer, offset) = self.base_of(addr)?;
+        memory
+            .buffers
+            .get(&buffer)
+            .and_then(|words| words.get(offset))
+            .cloned()
+    }
+
+    fn set_word(
+        &mut self,
+        memory: &mut Memory,
+        addr: ValueId,
+        vn: ValueNumber,
+        value: Option<(ValueId, Type)>,
+    ) {
+        let (buffer, offset) = self.base_of(addr).unwrap();
+        memory.buffers.get_mut(&buffer).unwrap()[offset] = Some(Word { vn, value });
+    }
+
+    /// The allocation `val` points into, and the offset in words
+    fn base_of(&self, val: ValueId) -> Option<(InstructionId, usize)> {
+        match self.func.data.value_ref(val) {
+            Value::Instruction(id) => self.addrs.get(id).copied().or_else(|| {
+                let inst = self.func.data.inst_ref(*id);
+                matches!(
+                    inst.callee_name(&self.func.data),
+                    Some("heap_malloc" | "vector_new")
+                )
+                .then_some((*id, 0))
+            }),
+            _ => None,
+        }
+    }
+
+    /// Stop tracking the buffers which `args` point into, since they are used
+    /// in a way which may change them later
+    fn escape(&self, memory: &mut Memory, args: &[ValueId]) {
+        for &arg in args {
+            if let Some((buffer, _)) = self.base_of(arg) {
+                memory.buffers.remove(&buffer);
+            }
+        }
+    }
+
+    fn number_pure(&mut self, id: InstructionId, expr: Expr) {
+        let vn = self.number(expr);
+        match self.leaders.get(&vn) {
+            Some(&leader) => self.replace(id, leader),
+            None => {
+                self.inst_vns.insert(id, vn);
+                let value = self.func.data.create_value(Value::Instruction(id));
+                self.leaders.insert(vn, value);
+            }
+        }
+    }
+
+    fn replace(&mut self, id: InstructionId, value: ValueId) {
+        self.func.data.replace_all_uses(id, value);
+        self.func.remove_inst(id);
+        if let Some((buffer, offset)) = self.addrs.remove(&id) {
+            // keep pointers derived from this one in their buffer
+            if let Value::Instruction(leader) = self.func.data.value_ref(value) {
+                self.addrs.insert(*leader, (buffer, offset));
+            }
+        }
+    }
+
+    fn expr_of(&mut self, id: InstructionId) -> Expr {
+        let inst = self.func.data.inst_ref(id);
+        let (opcode, tys, inst_args) = (
+            inst.opcode,
+            inst.operand.types().to_vec(),
+            inst.operand.args().to_vec(),
+        );
+        let cond = match &inst.operand {
+            Operand::ICmp(icmp) => Some(icmp.cond),
+            _ => None,
+        };
+        let mut args = inst_args
+            .iter()
+            .map(|&arg| self.vn_of(arg))
+            .collect::<Vec<_>>();
+        let commutative = matches!(
+            opcode,
+            Opcode::Add | Opcode::Mul | Opcode::And | Opcode::Or | Opcode::Xor
+        ) || matches!(cond, Some(ICmpCond::Eq | ICmpCond::Ne));
+        if commutative {
+            args.sort_unstable();
+        }
+        Expr::Inst {
+            opcode,
+            cond,
+            tys,
+            args,
+        }
+    }
+
+    fn vn_of(&mut self, val: ValueId) -> ValueNumber {
+        let expr = match self.func.data.value_ref(val) {
+            &Value::Instruction(id) => {
+                if let Some(&vn) = self.inst_vns.get(&id) {
+                    return vn;
+                }
+                let vn = self.fresh();
+                self.inst_vns.insert(id, vn);
+                return vn;
+            }
+            Value::Argument(arg) => Expr::Arg(arg.nth),
+            Value::Constant(ConstantValue::Int(int)) => {
+                let (bits, i) = match *int {
+                    ConstantInt::Int1(i) => (1, i as i64),
+                    ConstantInt::Int8(i) => (8, i as i64),
+                    ConstantInt::Int32(i) => (32, i as i64),
+                    ConstantInt::Int64(i) => (64, i),
+                };
+                let vn = self.number(Expr::Int(bits, i));
+                self.ints.insert(vn, i);
+                return vn;
+            }
+            Value::Constant(ConstantValue::GlobalRef(name, _)) => Expr::Global(name.clone()),
+            _ => {
+                if let Some(&vn) = self.value_vns.get(&val) {
+                    return vn;
+                }
+                let vn = self.fresh();
+                self.value_vns.insert(val, vn);
+                return vn;
+            }
+        };
+        self.number(expr)
+    }
+
+    fn number(&mut self, expr: Expr) -> ValueNumber {
+        if let Some(&vn) = self.exprs.get(&expr) {
+            return vn;
+        }
+        let vn = self.fresh();
+        self.exprs.insert(expr, vn);
+        vn
+    }
+
+    fn fresh(&mut self) -> ValueNumber {
+        self.next_vn += 1;
+        self.next_vn - 1
+    }
+
+    fn new_epoch(&mut self) -> usize {
+        self.next_epoch += 1;
+        self.next_epoch - 1
+    }
+}
+
+fn const_int(data: &Data, val: ValueId) -> Option<i64> {
+    match data.value_ref(val) {
+        Value::Constant(ConstantValue::Int(int)) => match *int {
+            ConstantInt::Int1(i) => Some(i as i64),
+            ConstantInt::Int8(i) => Some(i as i64),
+            ConstantInt::Int32(i) => Some(i as i64),
+            ConstantInt::Int64(i) => Some(i),
+        },
+        _ => None,
+    }
+}
+
+/// Whether the slots `a` and `b` are known to be different, as some word of
+/// theirs is a different constant
+fn must_differ(ints: &FxHashMap<ValueNumber, i64>, a: &[ValueNumber], b: &[ValueNumber]) -> bool {
+    a.iter()
+        .zip(b)
+        .any(|(a, b)| matches!((ints.get(a), ints.get(b)), (Some(a), Some(b)) if a != b))
+}
+
+/// The buffers allocated with `heap_malloc` which are only written by the one
+/// call to `poseidon_hash` which fills them, so that they hold the hash for
+/// good
+pub(super) fn hash_results(func: &Function) -> FxHashSet<InstructionId> {
+    // Is every use of `ptr` a read, counting the writes of `poseidon_hash`
+    fn only_read(data: &Data, ptr: InstructionId, hashes: &mut usize) -> bool {
+        data.users_of(ptr).iter().all(|&user| {
+            let inst = data.inst_ref(user);
+            let args = inst.operand.args();
+            let is_ptr =
+                |i: usize| matches!(data.value_ref(args[i]), Value::Instruction(id) if *id == ptr);
+            match (&inst.operand, inst.callee_name(data)) {
+                (Operand::Load(_), _) => true,
+                (Operand::GetElementPtr(_), _) if is_ptr(0) => {
+                    args[1..].iter().all(
+                        |&arg| !matches!(data.value_ref(arg), Value::Instruction(id) if *id == ptr),
+                    ) && only_read(data, user, hashes)
+                }
+                // the callee comes first, so the pointers start at 1
+                (_, Some("poseidon_hash")) if is_ptr(2) => {
+                    *hashes += 1;
+                    !is_ptr(1) && !is_ptr(3)
+                }
+                (_, Some("poseidon_hash" | "set_storage")) => true,
+                (_, Some("memcpy" | "get_storage")) => !is_ptr(2),
+                _ => false,
+            }
+        })
+    }
+
+    let mut results = FxHashSet::default();
+    for block in func.layout.block_iter() {
+        for id in func.layout.inst_iter(block) {
+            let inst = func.data.inst_ref(id);
+            if inst.callee_name(&func.data) != Some("heap_malloc") {
+                continue;
+            }
+            let mut hashes = 0;
+            if only_read(&func.data, id, &mut hashes) && hashes == 1 {
+                // the hash must write to the start of the buffer
+                let written_at_start = func.data.users_of(id).iter().any(|&user| {
+                    let inst = func.data.inst_ref(user);
+                    inst.callee_name(&func.data) == Some("poseidon_hash")
+                        && matches!(
+                            func.data.value_ref(inst.operand.args()[2]),
+                            Value::Instruction(dst) if *dst == id
+                        )
+                });
+                if written_at_start {
+                    results.insert(id);
+                }
+            }
+        }
+    }
+    results
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> ScopedMap<K, V> {
+    fn get(&self, key: &K) -> Option<&V> {
+        self.map.get(key)
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        let old = self.map.insert(key.clone(), value);
+        self.undo.push((key, old));
+    }
+
+    fn mark(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Undo the insertions since `mark`
+    fn reset(&mut self, mark: usize) {
+        while self.undo.len() > mark {
+            let (key, old) = self.undo.pop().unwrap();
+            match old {
+                Some(old) => self.map.insert(key, old),
+                None => self.map.remove(&key),
+            };
+        }
+    }
+}
+
+impl<K, V> Default for ScopedMap<K, V> {
+    fn default() -> Self {
+        Self {
+            map: FxHashMap::default(),
+            undo: vec![],
+        }
+    }
+}
+
+impl TransformPass<Function> for GvnPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        Gvn::new(func).run();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::{
+        core::{ir::module::Module, parser::assembly::module::parse as parse_assembly},
+        isa::ola::{asm, Ola},
+        lower::compile_module,
+    };
+
+    fn run(source: &str) -> Module {
+        let mut module = parse_assembly(source).expect("failed to parse IR");
+        let id = module.find_function_by_name("main").unwrap();
+        Gvn::new(&mut module.functions[id]).run();
+        let isa = Ola::default();
+        let code = compile_module(&isa, &module).expect("failed to compile");
+        asm::emit(&code).expect("failed to emit");
+        module
+    }
+
+    fn count(module: &Module, opcode: Opcode, callee: Option<&str>) -> usize {
+        let func = &module.functions[module.find_function_by_name("main").unwrap()];
+        func.layout
+            .block_iter()
+            .flat_map(|block| func.layout.inst_iter(block))
+            .map(|id| func.data.inst_ref(id))
+            .filter(|inst| inst.opcode == opcode && inst.callee_name(&func.data) == callee)
+            .count()
+    }
+
+    #[test]
+    fn gvn_pure_expressions() {
+        let module = run(r#"
+define void @main(ptr %0, i64 %1) {
+entry:
+  %2 = add i64 %1, 3
+  %3 = add i64 3, %1
+  %4 = mul i64 %2, %3
+  %5 = getelementptr i64, ptr %0, i64 %4
+  %6 = icmp ult i64 %4, 10
+  br i1 %6, label %then, label %else
+
+then:
+  %7 = add i64 %1, 3
+  %8 = mul i64 %7, %7
+  %9 = getelementptr i64, ptr %0, i64 %8
+  store i64 1, ptr %9, align 4
+  br label %else
+
+else:
+  %10 = add i64 %1, 4
+  call void @builtin_assert(i64 %10)
+  ret void
+}
+
+declare void @builtin_assert(i64)
+"#);
+
+        assert_eq!(count(&module, Opcode::Add, None), 2);
+        assert_eq!(count(&module, Opcode::Mul, None), 1);
+        assert_eq!(count(&module, Opcode::GetElementPtr, None), 1);
+    }
+
+    const HASH_TWICE: &str = r#"
+define void @main(i64 %0) {
+entry:
+  %1 = call ptr @heap_malloc(i64 2)
+  %2 = getelementptr i64, ptr %1, i64 0
+  store i64 %0, ptr %2, align 4
+  %3 = getelementptr i64, ptr %1, i64 1
+  store i64 7, ptr %3, align 4
+  %4 = call ptr @heap_malloc(i64 4)
+  call void @poseidon_hash(ptr %1, ptr %4, i64 2)
+  call void @get_storage(ptr %4, ptr %1)
+  %5 = call ptr @heap_malloc(i64 2)
+  %6 = getelementptr i64, ptr %5, i64 0
+  store i64 %0, ptr %6, align 4
+  %7 = getelementptr i64, ptr %5, i64 1
+  store i64 7, ptr %7, align 4
+  ;CHANGE
+  %8 = call ptr @heap_malloc(i64 4)
+  call void @poseidon_hash(ptr %5, ptr %8, i64 2)
+  %9 = getelementptr i64, ptr %8, i64 3
+  %10 = load i64, ptr %9, align 4
+  call void @builtin_assert(i64 %10)
+  ret void
+}
+
+define ptr @heap_malloc(i64 %0) {
+entry:
+  %current_address = load i64, ptr @heap_address, align 4
+  %updated_address = add i64 %current_address, %0
+  store i64 %updated_address, ptr @heap_address, align 4
+  %1 = inttoptr i64 %current_address to ptr
+  ret ptr %1
+}
+
+@heap_address = internal global i64 -12884901885
+
+declare void @builtin_assert(i64)
+
+declare void @get_storage(ptr, ptr)
+
+declare void @poseidon_hash(ptr, ptr, i64)
+"#;
+
+    #[test]
+    fn gvn_redundant_hash() {
+        let module = run(HASH_TWICE);
+        assert_eq!(count(&module, Opcode::Call, Some("poseidon_hash")), 1);
+        assert_eq!(count(&module, Opcode::Call, Some("heap_malloc")), 3);
+    }
+
+    #[test]
+    fn gvn_hash_of_changed_input() {
+        let module = run(&HASH_TWICE.replace(";CHANGE", "store i64 8, ptr %7, align 4"));
+        assert_eq!(count(&module, Opcode::Call, Some("poseidon_hash")), 2);
+
+        // the hash is kept when its input may have been written in between
+        let module = run(&HASH_TWICE.replace(";CHANGE", "call void @get_storage(ptr %4, ptr %5)"));
+        assert_eq!(count(&module, Opcode::Call, Some("poseidon_hash")), 2);
+    }
+
+    const STORAGE_TWICE: &str = r#"
+define void @main(i64 %0, ptr %1) {
+entry:
+  %key = call ptr @heap_malloc(i64 4)
+  %key0 = getelementptr i64, ptr %key, i64 0
+  store i64 0, ptr %key0, align 4
+  %key1 = getelementptr i64, ptr %key, i64 1
+  store i64 0, ptr %key1, align 4
+  %key2 = getelementptr i64, ptr %key, i64 2
+  store i64 0, ptr %key2, align 4
+  %key3 = getelementptr i64, ptr %key, i64 3
+  store i64 %0, ptr %key3, align 4
+  %old = call ptr @heap_malloc(i64 4)
+  call void @get_storage(ptr %key, ptr %old)
+  %old3 = getelementptr i64, ptr %old, i64 3
+  %old_value = load i64, ptr %old3, align 4
+  %new_value = add i64 %old_value, 1
+  %new = call ptr @heap_malloc(i64 4)
+  %new0 = getelementptr i64, ptr %new, i64 0
+  store i64 0, ptr %new0, align 4
+  %new1 = getelementptr i64, ptr %new, i64 1
+  store i64 0, ptr %new1, align 4
+  %new2 = getelementptr i64, ptr %new, i64 2
+  store i64 0, ptr %new2, align 4
+  %new3 = getelementptr i64, ptr %new, i64 3
+  store i64 %new_value, ptr %new3, align 4
+  call void @set_storage(ptr %key, ptr %new)
+  ;BARRIER
+  %again = call ptr @heap_malloc(i64 4)
+  call void @get_storage(ptr %key, ptr %again)
+  %again3 = getelementptr i64, ptr %again, i64 3
+  %again_value = load i64, ptr %again3, align 4
+  %last_value = add i64 %again_value, 2
+  %last = call ptr @heap_malloc(i64 4)
+  %last0 = getelementptr i64, ptr %last, i64 0
+  store i64 0, ptr %last0, align 4
+  %last1 = getelementptr i64, ptr %last, i64 1
+  store i64 0, ptr %last1, align 4
+  %last2 = getelementptr i64, ptr %last, i64 2
+  store i64 0, ptr %last2, align 4
+  %last3 = getelementptr i64, ptr %last, i64 3
+  store i64 %last_value, ptr %last3, align 4
+  call void @set_storage(ptr %key, ptr %last)
+  ret void
+}
+
+define ptr @heap_malloc(i64 %0) {
+entry:
+  %current_address = load i64, ptr @heap_address, align 4
+  %updated_address = add i64 %current_address, %0
+  store i64 %updated_address, ptr @heap_address, align 4
+  %1 = inttoptr i64 %current_address to ptr
+  ret ptr %1
+}
+
+@heap_address = internal global i64 -12884901885
+
+declare void @get_storage(ptr, ptr)
+
+declare void @set_storage(ptr, ptr)
+
+declare i64 @contract_call(ptr, i64)
+"#;
+
+    #[test]
+    fn gvn_storage_forwarded() {
+        let module = run(STORAGE_TWICE);
+        assert_eq!(count(&module, Opcode::Call, Some("get_storage")), 1);
+        assert_eq!(count(&module, Opcode::Call, Some("set_storage")), 1);
+
+        // a slot read twice is only read once, and the value is loaded from
+        // the first read
+        let module = run(&STORAGE_TWICE.replace(
+            "call void @set_storage(ptr %key, ptr %new)",
+            "call void @set_storage(ptr %1, ptr %new)",
+        ));
+        assert_eq!(count(&module, Opcode::Call, Some("get_storage")), 2);
+        let module = run(&STORAGE_TWICE.replace("call void @set_storage(ptr %key, ptr %new)", ""));
+        assert_eq!(count(&module, Opcode::Call, Some("get_storage")), 1);
+        assert_eq!(count(&module, Opcode::Load, None), 5);
+    }
+
+    #[test]
+    fn gvn_storage_across_contract_call() {
+        let module = run(&STORAGE_TWICE.replace(
+            ";BARRIER",
+            "%status = call i64 @contract_call(ptr %1, i64 0)",
+        ));
+        assert_eq!(count(&module, Opcode::Call, Some("get_storage")), 2);
+        assert_eq!(count(&module, Opcode::Call, Some("set_storage")), 2);
+    }
+}
@@ -0,0 +1,502 @@
+use crate::codegen::core::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{Br, InstructionId, Opcode, Operand, Phi},
+        Function, FunctionId,
+    },
+    module::{attributes::Attribute, name::Name, Module},
+    types,
+    value::{ConstantValue, Value, ValueId},
+};
+use rustc_hash::FxHashMap;
+use std::mem;
+
+/// Calls to functions whose cost is at most this, plus the number of
+/// arguments, are inlined at `OptLevel::Default`
+pub const DEFAULT_THRESHOLD: usize = 40;
+
+/// Only inline wrappers which are about as cheap as the call itself
+pub const LESS_THRESHOLD: usize = 4;
+
+/// Functions are not grown beyond this cost, except for `alwaysinline` callees
+const MAX_CALLER_COST: usize = 2000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Visiting,
+    Done,
+}
+
+/// Inline calls to functions which are cheap enough according to `threshold`,
+/// or which are marked `alwaysinline`. Callees are handled before their
+/// callers, so calls are inlined along with whatever the callee inlined
+/// itself. Recursive calls are never inlined.
+pub fn run_on_module(module: &mut Module, threshold: usize) {
+    let names = module
+        .functions
+        .iter()
+        .map(|(id, func)| (func.name.clone(), id))
+        .collect::<FxHashMap<_, _>>();
+    let mut states = FxHashMap::default();
+
+    let ids = module
+        .functions
+        .iter()
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+    for id in ids {
+        visit(module, &names, &mut states, id, threshold);
+    }
+}
+
+fn visit(
+    module: &mut Module,
+    names: &FxHashMap<String, FunctionId>,
+    states: &mut FxHashMap<FunctionId, State>,
+    id: FunctionId,
+    threshold: usize,
+) {
+    if states.contains_key(&id) {
+        return;
+    }
+    states.insert(id, State::Visiting);
+
+    let calls = calls_in(&module.functions[id], names);
+    for &(_, callee) in &calls {
+        visit(module, names, states, callee, threshold);
+    }
+
+    // Take the caller out of the module so that callees can be read while it
+    // is modified. Callees which are still being visited are part of a cycle
+    // through the caller, so the caller itself is never read.
+    let placeholder = Function::new("", types::VOID, vec![], false, module.types.clone());
+    let mut caller = mem::replace(&mut module.functions[id], placeholder);
+    let mut caller_cost = cost(&caller);
+
+    for (call, callee_id) in calls {
+        if states[&callee_id] != State::Done {
+            continue;
+        }
+        let callee = &module.functions[callee_id];
+        let callee_cost = cost(callee);
+        let num_args = caller.data.inst_ref(call).operand.args().len() - 1;
+
+        let always = has_attr(module, &callee.func_attrs, &Attribute::AlwaysInline);
+        let never = has_attr(module, &callee.func_attrs, &Attribute::NoInline)
+            || has_attr(module, call_attrs(&caller, call), &Attribute::NoInline);
+        let cheap =
+            callee_cost <= threshold + num_args && caller_cost + callee_cost <= MAX_CALLER_COST;
+
+        if never || !(always || cheap) || !is_inlinable(callee, num_args) {
+            continue;
+        }
+
+        inline_call(&mut caller, call, callee);
+        caller_cost += callee_cost;
+    }
+
+    module.functions[id] = caller;
+    states.insert(id, State::Done);
+}
+
+/// The calls in `func` to functions of the module which have a body
+fn calls_in(
+    func: &Function,
+    names: &FxHashMap<String, FunctionId>,
+) -> Vec<(InstructionId, FunctionId)> {
+    let mut calls = vec![];
+    for block in func.layout.block_iter() {
+        for inst_id in func.layout.inst_iter(block) {
+            let inst = func.data.inst_ref(inst_id);
+            if !inst.opcode.is_call() {
+                continue;
+            }
+            if let Value::Constant(ConstantValue::GlobalRef(Name::Name(name), _)) =
+                func.data.value_ref(inst.operand.args()[0])
+            {
+                if let Some(&callee) = names.get(name) {
+                    calls.push((inst_id, callee));
+                }
+            }
+        }
+    }
+    calls
+}
+
+fn call_attrs(func: &Function, call: InstructionId) -> &[Attribute] {
+    match &func.data.inst_ref(call).operand {
+        Operand::Call(call) => &call.func_attrs,
+        _ => &[],
+    }
+}
+
+/// Does `attrs` contain `attr`, either directly or through an attribute group
+fn has_attr(module: &Module, attrs: &[Attribute], attr: &Attribute) -> bool {
+    attrs.iter().any(|a| match a {
+        Attribute::Ref(group) => module
+            .attributes
+            .get(group)
+            .map_or(false, |group| group.contains(attr)),
+        a => a == attr,
+    })
+}
+
+fn is_inlinable(callee: &Function, num_args: usize) -> bool {
+    !callee.is_prototype()
+        && !callee.is_var_arg()
+        && callee.params().len() == num_args
+        && callee.layout.block_iter().all(|block| {
+            callee.layout.inst_iter(block).all(|inst| {
+                !matches!(
+                    callee.data.inst_ref(inst).opcode,
+                    Opcode::Invoke | Opcode::LandingPad | Opcode::Resume
+                )
+            })
+        })
+}
+
+/// An estimate of the number of instructions `func` is lowered to. Phis and
+/// allocas do not become instructions of their own, and calls also have to
+/// move their arguments into registers.
+fn cost(func: &Function) -> usize {
+    let mut cost = 0;
+    for block in func.layout.block_iter() {
+        for inst_id in func.layout.inst_iter(block) {
+            let inst = func.data.inst_ref(inst_id);
+            cost += match inst.opcode {
+                Opcode::Phi | Opcode::Alloca => 0,
+                Opcode::Call => inst.operand.args().len(),
+                _ => 1,
+            };
+        }
+    }
+    cost
+}
+
+/// Replace `call` in `caller` by a copy of the body of `callee`. The block of
+/// the call is split after it, the copied returns branch to the second half
+/// and their values are merged with a phi.
+fn inline_call(caller: &mut Function, call: InstructionId, callee: &Function) {
+    let block = caller.data.inst_ref(call).parent;
+    let args = caller.data.inst_ref(call).operand.args()[1..].to_vec();
+
+    let cont = split_block_after(caller, block, call);
+
+    // Copy the blocks, and create the instructions up front since phis may
+    // refer to instructions further down
+    let mut blocks = FxHashMap::default();
+    let mut after = block;
+    for callee_block in callee.layout.block_iter() {
+        let new_block = caller.data.create_block();
+        caller.layout.insert_block_after(new_block, after);
+        blocks.insert(callee_block, new_block);
+        after = new_block;
+    }
+
+    let mut insts = FxHashMap::default();
+    for callee_block in callee.layout.block_iter() {
+        for inst in callee.layout.inst_iter(callee_block) {
+            let new_inst = caller
+                .data
+                .create_inst(Opcode::Invalid.with_block(blocks[&callee_block]));
+            insts.insert(inst, new_inst);
+        }
+    }
+
+    let mut values = FxHashMap::default();
+    let mut map_value = |caller: &mut Function, val: ValueId| -> ValueId {
+        *values
+            .entry(val)
+            .or_insert_with(|| match callee.data.value_ref(val) {
+                Value::Argument(arg) => args[arg.nth],
+                Value::Instruction(id) => caller.data.create_value(Value::Instruction(insts[id])),
+                val => caller.data.create_value(val.clone()),
+            })
+    };
+
+    let caller_entry = caller.layout.get_entry_block().unwrap();
+    let callee_entry = callee.layout.get_entry_block().unwrap();
+    let mut returns = vec![];
+
+    for callee_block in callee.layout.block_iter() {
+        let new_block = blocks[&callee_block];
+
+        for inst_id in callee.layout.inst_iter(callee_block) {
+            let inst = callee.data.inst_ref(inst_id);
+            let new_inst = insts[&inst_id];
+
+            let mut opcode = inst.opcode;
+            let mut operand = inst.operand.clone();
+            for arg in operand.args_mut() {
+                *arg = map_value(caller, *arg);
+            }
+            match &mut operand {
+                Operand::Phi(Phi {
+                    blocks: targets, ..
+                }) => targets.iter_mut().for_each(|b| *b = blocks[b]),
+                Operand::Br(br) => br.block = blocks[&br.block],
+                Operand::CondBr(br) => br.blocks.iter_mut().for_each(|b| *b = blocks[b]),
+                Operand::Switch(switch) => switch.blocks.iter_mut().for_each(|b| *b = blocks[b]),
+                Operand::Ret(ret) => {
+                    returns.push((new_block, ret.val));
+                    opcode = Opcode::Br;
+                    operand = Operand::Br(Br { block: cont });
+                }
+                _ => {}
+            }
+
+            // Allocas in a loop of the caller would be evaluated again on every
+            // iteration, so keep them in the entry block
+            let parent = if opcode.is_alloca() && callee_block == callee_entry {
+                caller_entry
+            } else {
+                new_block
+            };
+
+            // The names of the callee are dropped, since they may clash with
+            // the names of the caller
+            caller
+                .data
+                .replace_inst(new_inst, opcode.with_block(parent).with_operand(operand));
+            if opcode != Opcode::Br {
+                caller.data.inst_ref_mut(new_inst).ty = inst.ty;
+            }
+
+            if parent == caller_entry && parent != new_block {
+                caller.layout.insert_inst_at_start(new_inst, parent);
+            } else {
+                caller.layout.append_inst(new_inst, new_block);
+            }
+        }
+
+        let callee_bb = callee.data.block_ref(callee_block);
+        let preds = callee_bb.preds().iter().map(|b| blocks[b]).collect();
+        let succs = callee_bb.succs().iter().map(|b| blocks[b]).collect();
+        let new_bb = caller.data.block_ref_mut(new_block);
+        new_bb.preds = preds;
+        new_bb.succs = succs;
+    }
+
+    for &(ret_block, _) in &returns {
+        caller.data.block_ref_mut(ret_block).succs.insert(cont);
+        caller.data.block_ref_mut(cont).preds.insert(ret_block);
+    }
+
+    // The result of the call is the returned value, merged over all returns
+    let result_ty = callee.result_ty;
+    let result = match returns.as_slice() {
+        _ if result_ty.is_void() => None,
+        [] => Some(
+            caller
+                .data
+                .create_value(Value::Constant(ConstantValue::Undef(result_ty))),
+        ),
+        [(_, Some(val))] => Some(*val),
+        returns => {
+            let phi = Opcode::Phi
+                .with_block(cont)
+                .with_operand(Operand::Phi(Phi {
+                    ty: result_ty,
+                    args: returns.iter().map(|(_, val)| val.unwrap()).collect(),
+                    blocks: returns.iter().map(|(block, _)| *block).collect(),
+                }))
+                .with_ty(result_ty);
+            let phi = caller.data.create_inst(phi);
+            caller.layout.insert_inst_at_start(phi, cont);
+            Some(caller.data.create_value(Value::Instruction(phi)))
+        }
+    };
+    if let Some(result) = result {
+        caller.data.replace_all_uses(call, result);
+    }
+
+    caller.remove_inst(call);
+    let entry = blocks[&callee_entry];
+    let br = caller.data.create_inst(
+        Opcode::Br
+            .with_block(block)
+            .with_operand(Operand::Br(Br { block: entry })),
+    );
+    caller.layout.append_inst(br, block);
+    caller.data.block_ref_mut(block).succs.insert(entry);
+    caller.data.block_ref_mut(entry).preds.insert(block);
+}
+
+/// Move the instructions after `inst` to a new block following `block`, which
+/// takes over the successors of `block`
+fn split_block_after(
+    func: &mut Function,
+    block: BasicBlockId,
+    inst: InstructionId,
+) -> BasicBlockId {
+    let new_block = func.data.create_block();
+    func.layout.insert_block_after(new_block, block);
+
+    let rest = func
+        .layout
+        .inst_iter(block)
+        .skip_while(|&id| id != inst)
+        .skip(1)
+        .collect::<Vec<_>>();
+    for id in rest {
+        func.layout.remove_inst(id);
+        func.layout.append_inst(id, new_block);
+        func.data.inst_ref_mut(id).parent = new_block;
+    }
+
+    let succs = mem::take(&mut func.data.block_ref_mut(block).succs);
+    for &succ in &succs {
+        func.data.block_ref_mut(succ).preds.remove(&block);
+        func.data.block_ref_mut(succ).preds.insert(new_block);
+
+        for id in func.layout.inst_iter(succ).collect::<Vec<_>>() {
+            match func.data.inst_ref_mut(id).operand.as_phi_mut() {
+                Some(phi) => phi
+                    .blocks_mut()
+                    .iter_mut()
+                    .filter(|b| **b == block)
+                    .for_each(|b| *b = new_block),
+                None => break,
+            }
+        }
+    }
+    func.data.block_ref_mut(new_block).succs = succs;
+
+    new_block
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::{
+        core::parser::assembly::module::parse as parse_assembly,
+        isa::ola::{asm, Ola},
+        lower::compile_module,
+    };
+
+    fn calls(module: &Module, name: &str) -> Vec<String> {
+        let func = &module.functions[module.find_function_by_name(name).unwrap()];
+        let names = module
+            .functions
+            .iter()
+            .map(|(id, func)| (func.name.clone(), id))
+            .collect();
+        calls_in(func, &names)
+            .into_iter()
+            .map(|(_, callee)| module.functions[callee].name.clone())
+            .collect()
+    }
+
+    fn compiles(module: &Module) {
+        let isa = Ola::default();
+        let code = compile_module(&isa, module).expect("failed to compile");
+        asm::emit(&code).expect("failed to emit");
+    }
+
+    #[test]
+    fn inline_small_functions() {
+        let mut module = parse_assembly(
+            r#"
+define void @main() {
+entry:
+  %0 = call i64 @max(i64 3, i64 5)
+  %1 = call i64 @max(i64 %0, i64 4)
+  %2 = call i64 @fib(i64 %1)
+  call void @builtin_assert(i64 %2)
+  ret void
+}
+
+define i64 @max(i64 %0, i64 %1) {
+entry:
+  %2 = icmp ugt i64 %0, %1
+  br i1 %2, label %left, label %right
+
+left:
+  ret i64 %0
+
+right:
+  ret i64 %1
+}
+
+define i64 @fib(i64 %0) {
+entry:
+  %1 = icmp ult i64 %0, 2
+  br i1 %1, label %done, label %recurse
+
+done:
+  ret i64 %0
+
+recurse:
+  %2 = sub i64 %0, 1
+  %3 = call i64 @fib(i64 %2)
+  %4 = sub i64 %0, 2
+  %5 = call i64 @fib(i64 %4)
+  %6 = add i64 %3, %5
+  ret i64 %6
+}
+
+declare void @builtin_assert(i64)
+"#,
+        )
+        .expect("failed to parse IR");
+
+        run_on_module(&mut module, DEFAULT_THRESHOLD);
+
+        // recursive calls are left alone, and only the outer call to `fib`
+        // is inlined
+        assert_eq!(calls(&module, "main"), ["fib", "fib", "builtin_assert"]);
+        assert_eq!(calls(&module, "fib"), ["fib", "fib"]);
+
+        compiles(&module);
+    }
+
+    #[test]
+    fn inline_attributes() {
+        let source = r#"
+define void @main() {
+entry:
+  %0 = call i64 @big(i64 1)
+  %1 = call i64 @small(i64 %0)
+  call void @builtin_assert(i64 %1)
+  ret void
+}
+
+define i64 @big(i64 %0) #0 {
+entry:
+  %1 = mul i64 %0, %0
+  %2 = mul i64 %1, %1
+  %3 = mul i64 %2, %2
+  %4 = mul i64 %3, %3
+  %5 = mul i64 %4, %4
+  %6 = mul i64 %5, %5
+  ret i64 %6
+}
+
+define i64 @small(i64 %0) #1 {
+entry:
+  %1 = add i64 %0, 1
+  ret i64 %1
+}
+
+declare void @builtin_assert(i64)
+
+attributes #0 = { alwaysinline }
+attributes #1 = { noinline }
+"#;
+        let mut module = parse_assembly(source).expect("failed to parse IR");
+
+        run_on_module(&mut module, LESS_THRESHOLD);
+
+        assert_eq!(calls(&module, "main"), ["small", "builtin_assert"]);
+        compiles(&module);
+
+        // without the attributes, it is the other way round
+        let mut module = parse_assembly(&source.replace(" #0 {", " {").replace(" #1 {", " {"))
+            .expect("failed to parse IR");
+
+        run_on_module(&mut module, LESS_THRESHOLD);
+
+        assert_eq!(calls(&module, "main"), ["big", "builtin_assert"]);
+        compiles(&module);
+    }
+}
@@ -0,0 +1,452 @@
+use super::gvn::hash_results;
+use crate::codegen::core::{
+    ir::{
+        function::{
+            basic_block::{BasicBlock, BasicBlockId},
+            instruction::{InstructionId, Opcode},
+            Function,
+        },
+        value::{Value, ValueId},
+    },
+    pass::{
+        analysis::{
+            dom_tree::DominatorTree,
+            loops::{Loop, LoopForest},
+        },
+        TransformPass,
+    },
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::any::Any;
+
+/// Hoists the computations which give the same result on every iteration of
+/// a loop into the block entering the loop. Loops which are entered from more
+/// than one block, or from a block which also branches elsewhere, are left
+/// alone.
+pub struct LicmPass;
+
+pub struct Licm<'a> {
+    func: &'a mut Function,
+    dom_tree: DominatorTree<BasicBlock>,
+    hash_results: FxHashSet<InstructionId>,
+}
+
+/// Builtins whose result only depends on their arguments
+const PURE_BUILTINS: [&str; 5] = [
+    "prophet_u32_sqrt",
+    "prophet_u32_div",
+    "prophet_u32_mod",
+    "prophet_split_field_high",
+    "prophet_split_field_low",
+];
+
+/// Builtins which do not write memory, besides the pure ones
+const READ_ONLY_BUILTINS: [&str; 3] = ["set_storage", "builtin_assert", "builtin_range_check"];
+
+impl<'a> Licm<'a> {
+    pub fn new(func: &'a mut Function) -> Self {
+        Self {
+            dom_tree: DominatorTree::new(func),
+            hash_results: hash_results(func),
+            func,
+        }
+    }
+
+    pub fn run(&mut self) {
+        let forest = LoopForest::new(self.func, &self.dom_tree);
+
+        // Inner loops come first, so what they hoist may be hoisted again out
+        // of the loops containing them
+        for (_, l) in forest.loops() {
+            if let Some(preheader) = l.preheader(self.func) {
+                self.hoist_from(l, preheader);
+            }
+        }
+    }
+
+    fn hoist_from(&mut self, l: &Loop<BasicBlock>, preheader: BasicBlockId) {
+        // Definitions are visited before their uses
+        let mut blocks = l.blocks().iter().copied().collect::<Vec<_>>();
+        blocks.sort_by_key(|&block| (self.dom_tree.level_of(block), block.index()));
+        let exiting = l.exiting_blocks(self.func);
+
+        loop {
+            let writes_memory = self.writes_memory(l);
+            let mut changed = false;
+
+            for &block in &blocks {
+                // Loads and builtins are only hoisted if they would be
+                // evaluated anyway once the loop is entered
+                let executed = exiting
+                    .iter()
+                    .all(|&exiting| self.dom_tree.dominates(block, exiting));
+
+                for id in self.func.layout.inst_iter(block).collect::<Vec<_>>() {
+                    if !l.contains(self.func.data.inst_ref(id).parent) {
+                        continue;
+                    }
+                    if self.is_invariant(l, id, writes_memory, executed) {
+                        self.move_to(id, preheader);
+                        changed = true;
+                    } else if let Some(group) = self.invariant_hash(l, id) {
+                        for id in group {
+                            self.move_to(id, preheader);
+                        }
+                        changed = true;
+                    }
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    /// Whether `id` computes the same value on every iteration, and can be
+    /// evaluated before the loop
+    fn is_invariant(
+        &self,
+        l: &Loop<BasicBlock>,
+        id: InstructionId,
+        writes_memory: bool,
+        executed: bool,
+    ) -> bool {
+        let inst = self.func.data.inst_ref(id);
+        let invariant_args = inst
+            .operand
+            .args()
+            .iter()
+            .all(|&arg| self.is_defined_outside(l, arg));
+
+        match inst.opcode {
+            Opcode::Add
+            | Opcode::Sub
+            | Opcode::Mul
+            | Opcode::And
+            | Opcode::Or
+            | Opcode::Xor
+            | Opcode::Shl
+            | Opcode::AShr
+            | Opcode::LShr
+            | Opcode::ICmp
+            | Opcode::Sext
+            | Opcode::Zext
+            | Opcode::Bitcast
+            | Opcode::Trunc
+            | Opcode::IntToPtr
+            | Opcode::PtrToInt
+            | Opcode::GetElementPtr
+            | Opcode::ExtractValue
+            | Opcode::InsertValue => invariant_args,
+            Opcode::Load => invariant_args && executed && !writes_memory,
+            Opcode::Call => {
+                invariant_args
+                    && executed
+                    && inst
+                        .callee_name(&self.func.data)
+                        .map_or(false, |name| PURE_BUILTINS.contains(&name))
+            }
+            _ => false,
+        }
+    }
+
+    /// The instructions computing `poseidon_hash` of a buffer which is filled
+    /// with the same words on every iteration, as is done for the slots of
+    /// storage variables. The buffers are allocated for the hash alone, so
+    /// they can be allocated once before the loop instead.
+    fn invariant_hash(
+        &self,
+        l: &Loop<BasicBlock>,
+        call: InstructionId,
+    ) -> Option<Vec<InstructionId>> {
+        let data = &self.func.data;
+        let inst = data.inst_ref(call);
+        if inst.callee_name(data) != Some("poseidon_hash") {
+            return None;
+        }
+        let block = inst.parent;
+        let &[_, src, dst, len] = inst.operand.args() else {
+            return None;
+        };
+        if !self.is_defined_outside(l, len) {
+            return None;
+        }
+        let src = self.malloc_in(l, src, block)?;
+        let dst = self.malloc_in(l, dst, block)?;
+        if src == dst || !self.hash_results.contains(&dst) {
+            return None;
+        }
+
+        let positions = self
+            .func
+            .layout
+            .inst_iter(block)
+            .enumerate()
+            .map(|(i, id)| (id, i))
+            .collect::<FxHashMap<_, _>>();
+        let before_call = |id: InstructionId| {
+            positions
+                .get(&id)
+                .map_or(false, |&pos| pos < positions[&call])
+        };
+
+        // The input is only written with invariant words before the hash
+        let mut group = vec![src, dst, call];
+        let is_invariant_store = |id: InstructionId, ptr: InstructionId| {
+            let store = data.inst_ref(id);
+            let args = store.operand.args();
+            store.opcode == Opcode::Store
+                && before_call(id)
+                && is_inst(data.value_ref(args[1]), ptr)
+                && !is_inst(data.value_ref(args[0]), ptr)
+                && self.is_defined_outside(l, args[0])
+        };
+        for &user in data.users_of(src) {
+            let user_inst = data.inst_ref(user);
+            let args = user_inst.operand.args();
+            if user == call {
+                if is_inst(data.value_ref(args[2]), src) || is_inst(data.value_ref(args[3]), src) {
+                    return None;
+                }
+            } else if user_inst.opcode == Opcode::GetElementPtr {
+                let invariant_offset = is_inst(data.value_ref(args[0]), src)
+                    && args[1..].iter().all(|&arg| self.is_defined_outside(l, arg));
+                if !invariant_offset || !before_call(user) {
+                    return None;
+                }
+                for &store in data.users_of(user) {
+                    if !is_invariant_store(store, user) {
+                        return None;
+                    }
+                    group.push(store);
+                }
+                group.push(user);
+            } else if is_invariant_store(user, src) {
+                group.push(user);
+            } else {
+                return None;
+            }
+        }
+
+        // Nothing in the loop reads the hash before it is computed
+        for &user in data.users_of(dst) {
+            let user_block = data.inst_ref(user).parent;
+            let after_call = if user_block == block {
+                positions
+                    .get(&user)
+                    .map_or(false, |&pos| pos > positions[&call])
+            } else {
+                self.dom_tree.dominates(block, user_block)
+            };
+            if user != call && l.contains(user_block) && !after_call {
+                return None;
+            }
+        }
+
+        group.sort_by_key(|id| positions[id]);
+        Some(group)
+    }
+
+    /// The call to `heap_malloc` of an invariant size in `block`, which `val`
+    /// is the result of
+    fn malloc_in(
+        &self,
+        l: &Loop<BasicBlock>,
+        val: ValueId,
+        block: BasicBlockId,
+    ) -> Option<InstructionId> {
+        let &Value::Instruction(id) = self.func.data.value_ref(val) else {
+            return None;
+        };
+        let inst = self.func.data.inst_ref(id);
+        let is_malloc = inst.parent == block
+            && inst.callee_name(&self.func.data) == Some("heap_malloc")
+            && self.is_defined_outside(l, inst.operand.args()[1]);
+        is_malloc.then_some(id)
+    }
+
+    fn writes_memory(&self, l: &Loop<BasicBlock>) -> bool {
+        l.blocks().iter().any(|&block| {
+            self.func.layout.inst_iter(block).any(|id| {
+                let inst = self.func.data.inst_ref(id);
+                match inst.opcode {
+                    Opcode::Store => true,
+                    Opcode::Call => !inst.callee_name(&self.func.data).map_or(false, |name| {
+                        PURE_BUILTINS.contains(&name) || READ_ONLY_BUILTINS.contains(&name)
+                    }),
+                    _ => false,
+                }
+            })
+        })
+    }
+
+    fn is_defined_outside(&self, l: &Loop<BasicBlock>, val: ValueId) -> bool {
+        match self.func.data.value_ref(val) {
+            Value::Instruction(id) => !l.contains(self.func.data.inst_ref(*id).parent),
+            _ => true,
+        }
+    }
+
+    fn move_to(&mut self, id: InstructionId, preheader: BasicBlockId) {
+        let terminator = self.func.layout.block_node(preheader).last_inst().unwrap();
+        self.func.layout.remove_inst(id);
+        self.func.layout.insert_inst_before(id, terminator);
+        self.func.data.inst_ref_mut(id).parent = preheader;
+    }
+}
+
+fn is_inst(val: &Value, id: InstructionId) -> bool {
+    matches!(val, Value::Instruction(val) if *val == id)
+}
+
+impl TransformPass<Function> for LicmPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        if func.layout.get_entry_block().is_none() {
+            return;
+        }
+        Licm::new(func).run();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::{
+        core::{
+            ir::module::{name::Name, Module},
+            parser::assembly::module::parse as parse_assembly,
+        },
+        isa::ola::{asm, Ola},
+        lower::compile_module,
+    };
+
+    fn run(source: &str) -> Module {
+        let mut module = parse_assembly(source).expect("failed to parse IR");
+        let id = module.find_function_by_name("main").unwrap();
+        Licm::new(&mut module.functions[id]).run();
+        let isa = Ola::default();
+        let code = compile_module(&isa, &module).expect("failed to compile");
+        asm::emit(&code).expect("failed to emit");
+        module
+    }
+
+    /// The names of the blocks the instructions with `opcode` are in
+    fn blocks_of(module: &Module, opcode: Opcode, callee: Option<&str>) -> Vec<String> {
+        let func = &module.functions[module.find_function_by_name("main").unwrap()];
+        func.layout
+            .block_iter()
+            .flat_map(|block| func.layout.inst_iter(block))
+            .map(|id| func.data.inst_ref(id))
+            .filter(|inst| inst.opcode == opcode && inst.callee_name(&func.data) == callee)
+            .map(|inst| {
+                func.data
+                    .block_ref(inst.parent)
+                    .name
+                    .as_ref()
+                    .and_then(Name::to_string)
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn licm_pure_and_loads() {
+        let source = r#"
+define void @main(ptr %0, i64 %1) {
+entry:
+  br label %cond
+
+cond:
+  %i = phi i64 [ 0, %entry ], [ %next, %body ]
+  %len = load i64, ptr %0, align 4
+  %c = icmp ult i64 %i, %len
+  br i1 %c, label %body, label %done
+
+body:
+  %scaled = mul i64 %1, 3
+  %sum = add i64 %scaled, %i
+  call void @builtin_assert(i64 %sum)
+  ;WRITE
+  %next = add i64 %i, 1
+  br label %cond
+
+done:
+  ret void
+}
+
+declare void @builtin_assert(i64)
+"#;
+        let module = run(source);
+        assert_eq!(blocks_of(&module, Opcode::Mul, None), ["entry"]);
+        assert_eq!(blocks_of(&module, Opcode::Add, None), ["body", "body"]);
+        assert_eq!(blocks_of(&module, Opcode::Load, None), ["entry"]);
+
+        // the length may change when the loop writes memory
+        let module = run(&source.replace(";WRITE", "store i64 %sum, ptr %0, align 4"));
+        assert_eq!(blocks_of(&module, Opcode::Load, None), ["cond"]);
+    }
+
+    #[test]
+    fn licm_slot_hash() {
+        let source = r#"
+define void @main(i64 %0) {
+entry:
+  br label %body
+
+body:
+  %i = phi i64 [ 0, %entry ], [ %next, %body ]
+  %key = call ptr @heap_malloc(i64 4)
+  %key0 = getelementptr i64, ptr %key, i64 0
+  store i64 0, ptr %key0, align 4
+  %key3 = getelementptr i64, ptr %key, i64 3
+  store i64 KEY, ptr %key3, align 4
+  %slot = call ptr @heap_malloc(i64 4)
+  call void @poseidon_hash(ptr %key, ptr %slot, i64 4)
+  call void @set_storage(ptr %slot, ptr %slot)
+  %next = add i64 %i, 1
+  %c = icmp ult i64 %next, 10
+  br i1 %c, label %body, label %done
+
+done:
+  ret void
+}
+
+define ptr @heap_malloc(i64 %0) {
+entry:
+  %current_address = load i64, ptr @heap_address, align 4
+  %updated_address = add i64 %current_address, %0
+  store i64 %updated_address, ptr @heap_address, align 4
+  %1 = inttoptr i64 %current_address to ptr
+  ret ptr %1
+}
+
+@heap_address = internal global i64 -12884901885
+
+declare void @poseidon_hash(ptr, ptr, i64)
+
+declare void @set_storage(ptr, ptr)
+"#;
+        let module = run(&source.replace("KEY", "%0"));
+        assert_eq!(
+            blocks_of(&module, Opcode::Call, Some("poseidon_hash")),
+            ["entry"]
+        );
+        assert_eq!(
+            blocks_of(&module, Opcode::Call, Some("heap_malloc")),
+            ["entry", "entry"]
+        );
+        assert_eq!(
+            blocks_of(&module, Opcode::Call, Some("set_storage")),
+            ["body"]
+        );
+
+        // the slot of a key which changes is hashed on every iteration
+        let module = run(&source.replace("KEY", "%i"));
+        assert_eq!(
+            blocks_of(&module, Opcode::Call, Some("poseidon_hash")),
+            ["body"]
+        );
+    }
+}
@@ -1,3 +1,8 @@
 pub mod dce;
+pub mod gvn;
+pub mod inline;
+pub mod licm;
 pub mod mem2reg;
+pub mod range_check;
 pub mod sccp;
+pub mod unroll;
@@ -0,0 +1,103 @@
+use crate::codegen::core::{
+    ir::{function::Function, module::Module},
+    pass::analysis::value_range::{ValueRanges, U32_MAX},
+};
+
+/// Remove the calls to `builtin_range_check` of every function which cannot
+/// fail, returning how many were removed.
+pub fn run_on_module(module: &mut Module) -> usize {
+    let mut removed = 0;
+    for (_, function) in module.functions_mut().iter_mut() {
+        if function.is_prototype() {
+            continue;
+        }
+        removed += run_on_function(function);
+    }
+    removed
+}
+
+/// Remove the calls to `builtin_range_check` whose argument is known to fit in
+/// 32 bits, returning how many were removed.
+pub fn run_on_function(func: &mut Function) -> usize {
+    let redundant = {
+        let ranges = ValueRanges::new(func);
+        let mut redundant = vec![];
+        for block in func.layout.block_iter() {
+            for id in func.layout.inst_iter(block) {
+                let inst = func.data.inst_ref(id);
+                if inst.callee_name(&func.data) != Some("builtin_range_check") {
+                    continue;
+                }
+                let range = ranges.range_before(id, inst.operand.args()[1]);
+                if range.hi <= U32_MAX {
+                    redundant.push(id);
+                }
+            }
+        }
+        redundant
+    };
+
+    for &id in &redundant {
+        func.remove_inst(id).unwrap();
+    }
+    redundant.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::core::parser::assembly::module::parse as parse_assembly;
+
+    fn run(source: &str) -> usize {
+        let mut module = parse_assembly(source).expect("failed to parse IR");
+        run_on_module(&mut module)
+    }
+
+    #[test]
+    fn range_check_bounded_loop() {
+        let removed = run(r#"
+declare void @builtin_range_check(i64)
+
+define void @main(i64 %0) {
+entry:
+  br label %cond
+
+cond:
+  %i = phi i64 [ 0, %entry ], [ %next, %body ]
+  %c = icmp ult i64 %i, 10
+  br i1 %c, label %body, label %done
+
+body:
+  %next = add i64 %i, 1
+  call void @builtin_range_check(i64 %next)
+  br label %cond
+
+done:
+  %sum = add i64 %0, %i
+  call void @builtin_range_check(i64 %sum)
+  ret void
+}
+"#);
+        assert_eq!(removed, 1);
+    }
+
+    #[test]
+    fn range_check_zext_and_checked_operands() {
+        let removed = run(r#"
+declare void @builtin_range_check(i64)
+
+define void @main(i8 %0, i64 %1, i64 %2) {
+entry:
+  %3 = zext i8 %0 to i64
+  call void @builtin_range_check(i64 %3)
+  call void @builtin_range_check(i64 %1)
+  %4 = lshr i64 %1, 1
+  call void @builtin_range_check(i64 %4)
+  %5 = sub i64 %1, %2
+  call void @builtin_range_check(i64 %5)
+  ret void
+}
+"#);
+        assert_eq!(removed, 2);
+    }
+}
@@ -0,0 +1,368 @@
+use crate::codegen::core::{
+    ir::{
+        function::{
+            basic_block::{BasicBlock, BasicBlockId},
+            instruction::{Br, ICmpCond, InstructionId, Opcode, Operand},
+            Function,
+        },
+        types::Type,
+        value::{ConstantInt, ConstantValue, Value, ValueId},
+    },
+    pass::{
+        analysis::{dom_tree::DominatorTree, loops::LoopForest},
+        TransformPass,
+    },
+};
+use rustc_hash::FxHashMap;
+use std::any::Any;
+
+/// Loops running at most this many times are fully unrolled
+pub const MAX_TRIP_COUNT: usize = 8;
+
+/// Loops are not unrolled into more instructions than this
+const MAX_UNROLLED_INSTS: usize = 256;
+
+/// Fully unrolls the loops made of a single block, such as the ones of
+/// `Binary::emit_static_loop_with_int`, whose number of iterations is a small
+/// constant. The body is repeated that many times in the block, which then
+/// branches out of the loop.
+pub struct UnrollPass;
+
+pub struct Unroll<'a> {
+    func: &'a mut Function,
+}
+
+impl<'a> Unroll<'a> {
+    pub fn new(func: &'a mut Function) -> Self {
+        Self { func }
+    }
+
+    pub fn run(&mut self) {
+        let dom_tree = DominatorTree::<BasicBlock>::new(self.func);
+        let forest = LoopForest::new(self.func, &dom_tree);
+
+        // Single block loops do not contain other loops, so unrolling one
+        // does not change the others
+        let loops = forest
+            .loops()
+            .filter(|(_, l)| l.blocks().len() == 1)
+            .filter_map(|(_, l)| Some((l.header(), l.preheader(self.func)?)))
+            .collect::<Vec<_>>();
+        for (block, preheader) in loops {
+            if let Some((trip_count, exit)) = self.trip_count(block, preheader) {
+                self.unroll(block, preheader, exit, trip_count);
+            }
+        }
+    }
+
+    /// The number of iterations of the loop of `block`, found by evaluating
+    /// it, and the block it exits to
+    fn trip_count(
+        &self,
+        block: BasicBlockId,
+        preheader: BasicBlockId,
+    ) -> Option<(usize, BasicBlockId)> {
+        let data = &self.func.data;
+        let insts = self.func.layout.inst_iter(block).collect::<Vec<_>>();
+        let terminator = data.inst_ref(*insts.last()?);
+        let Operand::CondBr(br) = &terminator.operand else {
+            return None;
+        };
+        let (repeat_if, exit) = match br.blocks {
+            [then, exit] if then == block && exit != block => (true, exit),
+            [exit, otherwise] if otherwise == block && exit != block => (false, exit),
+            _ => return None,
+        };
+
+        let body_len = insts.len() - 1;
+        let unsupported = insts.iter().any(|&id| {
+            matches!(
+                data.inst_ref(id).opcode,
+                Opcode::Alloca | Opcode::Invoke | Opcode::LandingPad | Opcode::Resume
+            )
+        });
+        if unsupported || body_len == 0 {
+            return None;
+        }
+
+        let mut values = FxHashMap::default();
+        for iteration in 1..=MAX_TRIP_COUNT {
+            if body_len * iteration > MAX_UNROLLED_INSTS {
+                return None;
+            }
+
+            let mut next = FxHashMap::default();
+            for &id in &insts[..body_len] {
+                let inst = data.inst_ref(id);
+                let value = match &inst.operand {
+                    // phis take the values of the previous iteration
+                    Operand::Phi(phi) => {
+                        let pred = if iteration == 1 { preheader } else { block };
+                        let incoming = phi.blocks.iter().position(|&b| b == pred)?;
+                        self.eval(&values, phi.args[incoming])
+                    }
+                    Operand::IntBinary(bin) => {
+                        let [lhs, rhs] = bin.args;
+                        match (self.eval(&next, lhs), self.eval(&next, rhs)) {
+                            (Some(lhs), Some(rhs)) => binary(inst.opcode, bin.ty, lhs, rhs),
+                            _ => None,
+                        }
+                    }
+                    Operand::ICmp(icmp) => {
+                        let [lhs, rhs] = icmp.args;
+                        match (self.eval(&next, lhs), self.eval(&next, rhs)) {
+                            (Some(lhs), Some(rhs)) => Some(compare(icmp.cond, icmp.ty, lhs, rhs)),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                if let Some(value) = value {
+                    next.insert(id, value);
+                }
+            }
+            values = next;
+
+            let Value::Instruction(cond) = data.value_ref(br.arg) else {
+                return None;
+            };
+            let repeats = (*values.get(cond)? != 0) == repeat_if;
+            if !repeats {
+                return Some((iteration, exit));
+            }
+        }
+        None
+    }
+
+    /// The value of `val`, if it is an integer known in `values`
+    fn eval(&self, values: &FxHashMap<InstructionId, u64>, val: ValueId) -> Option<u64> {
+        match self.func.data.value_ref(val) {
+            Value::Instruction(id) => values.get(id).copied(),
+            Value::Constant(ConstantValue::Int(int)) => Some(match *int {
+                ConstantInt::Int1(i) => i as u64,
+                ConstantInt::Int8(i) => i as u8 as u64,
+                ConstantInt::Int32(i) => i as u32 as u64,
+                ConstantInt::Int64(i) => i as u64,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Repeat the body of the loop of `block` `trip_count` times in it, and
+    /// branch to `exit` afterwards
+    fn unroll(
+        &mut self,
+        block: BasicBlockId,
+        preheader: BasicBlockId,
+        exit: BasicBlockId,
+        trip_count: usize,
+    ) {
+        let insts = self.func.layout.inst_iter(block).collect::<Vec<_>>();
+        let (terminator, body) = insts.split_last().unwrap();
+        let (phis, body): (Vec<_>, Vec<_>) = body
+            .iter()
+            .copied()
+            .partition(|&id| self.func.data.inst_ref(id).opcode == Opcode::Phi);
+
+        // The values of the instructions of the body in the iteration being
+        // copied, starting with what the phis take from the preheader
+        let mut values = FxHashMap::default();
+        for &phi in &phis {
+            let incoming = self.incoming(phi, preheader);
+            values.insert(phi, incoming);
+        }
+
+        for iteration in 0..trip_count {
+            if iteration > 0 {
+                let next = phis
+                    .iter()
+                    .map(|&phi| (phi, self.map(&values, self.incoming(phi, block))))
+                    .collect::<Vec<_>>();
+                values.extend(next);
+            }
+
+            for &id in &body {
+                let inst = self.func.data.inst_ref(id);
+                let mut operand = inst.operand.clone();
+                let (opcode, ty) = (inst.opcode, inst.ty);
+                for arg in operand.args_mut() {
+                    *arg = self.map(&values, *arg);
+                }
+                let copy = opcode.with_block(block).with_operand(operand).with_ty(ty);
+                let copy = self.func.data.create_inst(copy);
+                self.func.layout.insert_inst_before(copy, *terminator);
+                let value = self.func.data.create_value(Value::Instruction(copy));
+                values.insert(id, value);
+            }
+        }
+
+        // Uses after the loop see the values of the last iteration
+        for &id in phis.iter().chain(&body) {
+            self.func.data.replace_all_uses(id, values[&id]);
+        }
+        for &id in phis.iter().chain(&body).chain([terminator]) {
+            self.func.remove_inst(id);
+        }
+
+        let br = Opcode::Br
+            .with_block(block)
+            .with_operand(Operand::Br(Br { block: exit }));
+        let br = self.func.data.create_inst(br);
+        self.func.layout.append_inst(br, block);
+        self.func.data.block_ref_mut(block).succs.remove(&block);
+        self.func.data.block_ref_mut(block).preds.remove(&block);
+    }
+
+    /// The value `phi` takes when coming from `pred`
+    fn incoming(&self, phi: InstructionId, pred: BasicBlockId) -> ValueId {
+        let phi = self.func.data.inst_ref(phi).operand.as_phi().unwrap();
+        let i = phi.blocks.iter().position(|&b| b == pred).unwrap();
+        phi.args[i]
+    }
+
+    /// `val` as copied into the current iteration
+    fn map(&self, values: &FxHashMap<InstructionId, ValueId>, val: ValueId) -> ValueId {
+        match self.func.data.value_ref(val) {
+            Value::Instruction(id) => values.get(id).copied().unwrap_or(val),
+            _ => val,
+        }
+    }
+}
+
+/// Mask `val` to the width of `ty`
+fn truncate(ty: Type, val: u64) -> u64 {
+    match bits(ty) {
+        64 => val,
+        bits => val & ((1 << bits) - 1),
+    }
+}
+
+fn bits(ty: Type) -> u32 {
+    if ty.is_i1() {
+        1
+    } else if ty.is_i8() {
+        8
+    } else if ty.is_i16() {
+        16
+    } else if ty.is_i32() {
+        32
+    } else {
+        64
+    }
+}
+
+fn signed(ty: Type, val: u64) -> i64 {
+    let shift = 64 - bits(ty);
+    ((val << shift) as i64) >> shift
+}
+
+fn binary(opcode: Opcode, ty: Type, lhs: u64, rhs: u64) -> Option<u64> {
+    let val = match opcode {
+        Opcode::Add => lhs.wrapping_add(rhs),
+        Opcode::Sub => lhs.wrapping_sub(rhs),
+        Opcode::Mul => lhs.wrapping_mul(rhs),
+        Opcode::And => lhs & rhs,
+        Opcode::Or => lhs | rhs,
+        Opcode::Xor => lhs ^ rhs,
+        _ => return None,
+    };
+    Some(truncate(ty, val))
+}
+
+fn compare(cond: ICmpCond, ty: Type, lhs: u64, rhs: u64) -> u64 {
+    let (slhs, srhs) = (signed(ty, lhs), signed(ty, rhs));
+    let result = match cond {
+        ICmpCond::Eq => lhs == rhs,
+        ICmpCond::Ne => lhs != rhs,
+        ICmpCond::Ugt => lhs > rhs,
+        ICmpCond::Uge => lhs >= rhs,
+        ICmpCond::Ult => lhs < rhs,
+        ICmpCond::Ule => lhs <= rhs,
+        ICmpCond::Sgt => slhs > srhs,
+        ICmpCond::Sge => slhs >= srhs,
+        ICmpCond::Slt => slhs < srhs,
+        ICmpCond::Sle => slhs <= srhs,
+    };
+    result as u64
+}
+
+impl TransformPass<Function> for UnrollPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        if func.layout.get_entry_block().is_none() {
+            return;
+        }
+        Unroll::new(func).run();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::{
+        core::{ir::module::Module, parser::assembly::module::parse as parse_assembly},
+        isa::ola::{asm, Ola},
+        lower::compile_module,
+    };
+
+    fn run(source: &str) -> Module {
+        let mut module = parse_assembly(source).expect("failed to parse IR");
+        let id = module.find_function_by_name("main").unwrap();
+        Unroll::new(&mut module.functions[id]).run();
+        let isa = Ola::default();
+        let code = compile_module(&isa, &module).expect("failed to compile");
+        asm::emit(&code).expect("failed to emit");
+        module
+    }
+
+    fn count(module: &Module, opcode: Opcode) -> usize {
+        let func = &module.functions[module.find_function_by_name("main").unwrap()];
+        func.layout
+            .block_iter()
+            .flat_map(|block| func.layout.inst_iter(block))
+            .filter(|&id| func.data.inst_ref(id).opcode == opcode)
+            .count()
+    }
+
+    const STATIC_LOOP: &str = r#"
+define void @main(ptr %0) {
+entry:
+  br label %body
+
+body:
+  %i = phi i64 [ 0, %entry ], [ %next, %body ]
+  %addr = getelementptr i64, ptr %0, i64 %i
+  store i64 %i, ptr %addr, align 4
+  %next = add i64 %i, 1
+  %c = icmp ult i64 %next, BOUND
+  br i1 %c, label %body, label %done
+
+done:
+  %last = phi i64 [ %next, %body ]
+  call void @builtin_assert(i64 %last)
+  ret void
+}
+
+declare void @builtin_assert(i64)
+"#;
+
+    #[test]
+    fn unroll_static_loop() {
+        let module = run(&STATIC_LOOP.replace("BOUND", "4"));
+        assert_eq!(count(&module, Opcode::Store), 4);
+        assert_eq!(count(&module, Opcode::CondBr), 0);
+        assert_eq!(count(&module, Opcode::Phi), 1);
+
+        // the loop runs too many times
+        let module = run(&STATIC_LOOP.replace("BOUND", "100"));
+        assert_eq!(count(&module, Opcode::Store), 1);
+        assert_eq!(count(&module, Opcode::CondBr), 1);
+
+        // the bound is not known
+        let module = run(
+            &STATIC_LOOP
+                .replace("ptr %0)", "ptr %0, i64 %1)")
+                .replace("BOUND", "%1"),
+        );
+        assert_eq!(count(&module, Opcode::CondBr), 1);
+    }
+}
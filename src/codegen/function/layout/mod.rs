@@ -12,7 +12,7 @@ pub struct Layout<Inst: TargetInst> {
 }
 
 pub struct BasicBlockNode<Inst: TargetInst> {
-    _prev: Option<BasicBlockId>,
+    prev: Option<BasicBlockId>,
     next: Option<BasicBlockId>,
     first_inst: Option<InstructionId<Inst>>,
     last_inst: Option<InstructionId<Inst>>,
@@ -70,9 +70,65 @@ impl<Inst: TargetInst> Layout<Inst> {
         self.basic_blocks[&block].next
     }
 
+    pub fn prev_block_of(&self, block: BasicBlockId) -> Option<BasicBlockId> {
+        self.basic_blocks[&block].prev
+    }
+
+    pub fn is_block_inserted(&self, block: BasicBlockId) -> bool {
+        self.basic_blocks.contains_key(&block)
+    }
+
+    /// Removes `block` and its instructions from the layout
+    pub fn remove_block(&mut self, block: BasicBlockId) {
+        let insts = self.inst_iter(block).collect::<Vec<_>>();
+        for inst in insts {
+            self.instructions.remove(&inst);
+        }
+        let node = self.basic_blocks.remove(&block).unwrap();
+        match node.prev {
+            Some(prev) => self.basic_blocks.get_mut(&prev).unwrap().next = node.next,
+            None => self.first_block = node.next,
+        }
+        match node.next {
+            Some(next) => self.basic_blocks.get_mut(&next).unwrap().prev = node.prev,
+            None => self.last_block = node.prev,
+        }
+    }
+
+    /// Lays out the blocks in the given order, which must contain every block
+    /// of the layout exactly once
+    pub fn reorder_blocks(&mut self, order: &[BasicBlockId]) {
+        assert_eq!(order.len(), self.basic_blocks.len());
+        for (i, block) in order.iter().enumerate() {
+            let node = self.basic_blocks.get_mut(block).unwrap();
+            node.prev = if i == 0 { None } else { Some(order[i - 1]) };
+            node.next = order.get(i + 1).copied();
+        }
+        self.first_block = order.first().copied();
+        self.last_block = order.last().copied();
+    }
+
+    /// Moves the instructions of `from` to the end of `to`, leaving `from`
+    /// empty
+    pub fn move_insts_to_end(&mut self, from: BasicBlockId, to: BasicBlockId) {
+        let insts = self.inst_iter(from).collect::<Vec<_>>();
+        for inst in insts {
+            self.remove_inst(inst);
+            self.append_inst(inst, to);
+        }
+    }
+
+    pub fn block_of(&self, inst: InstructionId<Inst>) -> Option<BasicBlockId> {
+        self.instructions.get(&inst).and_then(|node| node.block)
+    }
+
+    pub fn first_inst_of(&self, block: BasicBlockId) -> Option<InstructionId<Inst>> {
+        self.basic_blocks[&block].first_inst
+    }
+
     pub fn append_block(&mut self, block: BasicBlockId) {
         self.basic_blocks.entry(block).or_insert(BasicBlockNode {
-            _prev: self.last_block,
+            prev: self.last_block,
             next: None,
             first_inst: None,
             last_inst: None,
@@ -80,7 +136,7 @@ impl<Inst: TargetInst> Layout<Inst> {
 
         if let Some(last_block) = self.last_block {
             self.basic_blocks.get_mut(&last_block).unwrap().next = Some(block);
-            self.basic_blocks.get_mut(&block).unwrap()._prev = Some(last_block);
+            self.basic_blocks.get_mut(&block).unwrap().prev = Some(last_block);
         }
 
         self.last_block = Some(block);
@@ -90,6 +146,25 @@ impl<Inst: TargetInst> Layout<Inst> {
         }
     }
 
+    /// Inserts `block` right after `after`, which must be in the layout
+    pub fn insert_block_after(&mut self, block: BasicBlockId, after: BasicBlockId) {
+        let next = self.basic_blocks[&after].next;
+        self.basic_blocks.insert(
+            block,
+            BasicBlockNode {
+                prev: Some(after),
+                next,
+                first_inst: None,
+                last_inst: None,
+            },
+        );
+        self.basic_blocks.get_mut(&after).unwrap().next = Some(block);
+        match next {
+            Some(next) => self.basic_blocks.get_mut(&next).unwrap().prev = Some(block),
+            None => self.last_block = Some(block),
+        }
+    }
+
     pub fn last_inst_of(&self, block: BasicBlockId) -> Option<InstructionId<Inst>> {
         self.basic_blocks[&block].last_inst
     }
@@ -20,6 +20,7 @@ pub struct Slot {
     #[allow(dead_code)]
     pub(crate) align: u32,
     pub offset: u32,
+    pub(crate) removed: bool,
 }
 
 impl<'a, T: TargetIsa> Slots<'a, T> {
@@ -39,16 +40,23 @@ impl<'a, T: TargetIsa> Slots<'a, T> {
             num_elements: 0,
             align,
             offset: 0,
+            removed: false,
         })
     }
 
+    /// Removes the slot from the frame. Nothing may refer to it any longer.
+    pub fn remove_slot(&mut self, id: SlotId) {
+        self.aligned_size = 0;
+        self.arena[id].removed = true;
+    }
+
     pub fn get(&self, id: SlotId) -> &Slot {
         &self.arena[id]
     }
 
     pub fn unaligned_size(&self) -> u32 {
         let mut total = 0;
-        for (_, slot) in &self.arena {
+        for (_, slot) in self.arena.iter().filter(|(_, slot)| !slot.removed) {
             total += slot.size;
         }
         total
@@ -63,7 +71,7 @@ impl<'a, T: TargetIsa> Slots<'a, T> {
 
         let mut offset = 0;
         let mut align = 1;
-        for (_id, slot) in &mut self.arena {
+        for (_id, slot) in self.arena.iter_mut().filter(|(_, slot)| !slot.removed) {
             if !is_aligned(slot.align, offset) {
                 offset = align_to(offset, slot.align);
             }
@@ -5,9 +5,11 @@ use crate::codegen::{
         register::reg_to_str,
         Ola,
     },
+    lower::LoweringError,
     module::{DisplayAsm, Module},
     register::Reg,
 };
+use mini_goldilocks::poseidon::unsafe_poseidon_bytes_auto_padded;
 use std::{fmt, str};
 
 use serde::{Deserialize, Serialize};
@@ -18,6 +20,20 @@ pub struct AsmProgram {
     pub prophets: Vec<Prophet>,
 }
 
+impl AsmProgram {
+    /// The hash which identifies the code of a deployed contract: the
+    /// poseidon hash of the program text, as four field elements
+    pub fn code_hash(&self) -> [u64; 4] {
+        let hash = unsafe_poseidon_bytes_auto_padded(self.program.as_bytes());
+        let bytes: Vec<u8> = hash.iter().flat_map(|w| w.to_be_bytes()).collect();
+        let mut words = [0; 4];
+        for (word, chunk) in words.iter_mut().zip(bytes.chunks(8)) {
+            *word = u64::from_be_bytes(chunk.try_into().unwrap());
+        }
+        words
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Prophet {
     pub label: String,
@@ -124,8 +140,8 @@ const SPLITLO: &'static str = "%{
     }
 %}";
 
-pub fn from_prophet(name: &str, fn_idx: usize, pht_idx: usize) -> Prophet {
-    match name {
+pub fn from_prophet(name: &str, fn_idx: usize, pht_idx: usize) -> Result<Prophet, LoweringError> {
+    let prophet = match name {
         "prophet_u32_sqrt" => Prophet {
             code: SQRT.to_string(),
             label: format!(".PROPHET{}_{}", fn_idx.to_string(), pht_idx.to_string()),
@@ -330,8 +346,14 @@ pub fn from_prophet(name: &str, fn_idx: usize, pht_idx: usize) -> Prophet {
             }]
             .to_vec(),
         },
-        e => todo!("{:?}", e),
-    }
+        e => {
+            return Err(LoweringError::Unsupported(format!(
+                "prophet {} is not supported",
+                e
+            )))
+        }
+    };
+    Ok(prophet)
 }
 
 impl fmt::Display for DisplayAsm<'_, Ola> {
@@ -340,14 +362,23 @@ impl fmt::Display for DisplayAsm<'_, Ola> {
     }
 }
 
-pub fn print(f: &mut fmt::Formatter<'_>, module: &Module<Ola>) -> fmt::Result {
+/// Emit the assembly program for a module, failing if an instruction has
+/// operands which have no assembly syntax.
+pub fn emit(module: &Module<Ola>) -> Result<AsmProgram, LoweringError> {
     let mut prophets = vec![];
     let mut program = "".to_string();
     for (i, (_, func)) in module.functions.iter().enumerate() {
-        let inst = print_function(func, i, &mut prophets);
+        let inst = print_function(func, i, &mut prophets).map_err(|e| LoweringError::Function {
+            name: func.ir.name().to_string(),
+            error: e.to_string(),
+        })?;
         program.push_str(&format!("{}", inst));
     }
-    let asm_program = AsmProgram { program, prophets };
+    Ok(AsmProgram { program, prophets })
+}
+
+pub fn print(f: &mut fmt::Formatter<'_>, module: &Module<Ola>) -> fmt::Result {
+    let asm_program = emit(module).map_err(|_| fmt::Error)?;
     let serialized = serde_json::to_string_pretty(&asm_program).unwrap();
     writeln!(f, "{}", serialized)?;
 
@@ -358,9 +389,9 @@ pub fn print_function(
     function: &Function<Ola>,
     fn_idx: usize,
     prophets: &mut Vec<Prophet>,
-) -> String {
+) -> Result<String, LoweringError> {
     if function.is_declaration {
-        return "".to_string();
+        return Ok("".to_string());
     }
 
     let mut prophet_index: usize = 0;
@@ -390,14 +421,14 @@ pub fn print_function(
                 }
                 code.push_str(&format!("{}", term));
             } else if inst.data.opcode == Opcode::PROPHET {
-                let name = write_operand(&inst.data.operands[1].data, fn_idx);
+                let name = write_operand(&inst.data.operands[1].data, fn_idx)?;
                 code.push_str(&format!(".PROPHET{}_{}:\n", fn_idx, prophet_index));
                 if name != "prophet_printf".to_string() {
                     code.push_str(&format!("  mov r0 psp\n"));
                     code.push_str(&format!("  mload r0 [r0]\n"));
                     assert_eq!(inst.data.operands.len(), 2);
                 }
-                prophets.push(from_prophet(name.as_str(), fn_idx, prophet_index));
+                prophets.push(from_prophet(name.as_str(), fn_idx, prophet_index)?);
 
                 prophet_index += 1;
 
@@ -419,10 +450,10 @@ pub fn print_function(
                     if !sz.is_empty() {
                         code.push_str(&format!(" "));
                     }
-                    code.push_str(&format!("{}", mem_op(&inst.data.operands[i..i + 6])));
+                    code.push_str(&format!("{}", mem_op(&inst.data.operands[i..i + 6])?));
                     i += 6 - 1;
                 } else {
-                    code.push_str(&format!("{}", write_operand(&operand.data, fn_idx)));
+                    code.push_str(&format!("{}", write_operand(&operand.data, fn_idx)?));
                 }
                 if i < inst.data.operands.len() - 1 {
                     code.push_str(&format!(" "))
@@ -433,7 +464,7 @@ pub fn print_function(
         }
     }
 
-    code
+    Ok(code)
 }
 
 impl fmt::Display for Opcode {
@@ -450,9 +481,10 @@ impl fmt::Display for Opcode {
                 Self::MOVri | Self::MOVrr | Self::MOV => "mov",
                 Self::JMPi | Self::JMPr => "jmp",
                 Self::CJMPi | Self::CJMPr => "cjmp",
-                Self::CALL => "call",
+                Self::CALL | Self::CALLr => "call",
                 Self::SCCALL => "sccall",
                 Self::RET => "ret",
+                Self::END => "end",
                 Self::Phi => "PHI",
                 Self::SSTORE => "sstore",
                 Self::SLOAD => "sload",
@@ -470,15 +502,14 @@ impl fmt::Display for Opcode {
                 Self::ASSERTri | Self::ASSERTrr => "assert",
                 Self::TLOADri | Self::TLOADrr => "tload",
                 Self::TSTOREi | Self::TSTOREr => "tstore",
-                e => todo!("{:?}", e),
             }
         )
     }
 }
 
-fn write_operand(op: &OperandData, fn_idx: usize) -> String {
-    match op {
-        OperandData::Reg(r) => format!("{}", reg_to_str(r)),
+fn write_operand(op: &OperandData, fn_idx: usize) -> Result<String, LoweringError> {
+    let op = match op {
+        OperandData::Reg(r) => format!("{}", reg_to_str(r)?),
         OperandData::VReg(r) => format!("%{}", r.0),
         OperandData::Slot(slot) => format!("{:?}", slot),
         OperandData::Int8(i) => format!("{}", i),
@@ -495,7 +526,8 @@ fn write_operand(op: &OperandData, fn_idx: usize) -> String {
         OperandData::MemStart => format!(""),
         OperandData::GlobalAddress(name) => format!("offset {}", name),
         OperandData::None => format!("none"),
-    }
+    };
+    Ok(op)
 }
 
 fn negative_as_field(immediate: i64) -> bool {
@@ -511,10 +543,16 @@ fn mem_size(_opcode: &Opcode) -> &'static str {
     ""
 }
 
-fn mem_op(args: &[Operand]) -> String {
+fn mem_op(args: &[Operand]) -> Result<String, LoweringError> {
     // return  format!("[{}]", "abc");
-    assert!(matches!(&args[1].data, &OperandData::None)); // assure slot is eliminated
-    match (
+    if !matches!(&args[1].data, &OperandData::None) {
+        // slots must have been eliminated by now
+        return Err(LoweringError::Unsupported(format!(
+            "memory operand with slot {:?} is not supported",
+            args[1].data
+        )));
+    }
+    let op = match (
         &args[0].data,
         &args[2].data,
         &args[3].data,
@@ -538,11 +576,11 @@ fn mem_op(args: &[Operand]) -> String {
             OperandData::None,
         ) => {
             if *imm == 0 {
-                format!("[{}]", reg_to_str(reg))
+                format!("[{}]", reg_to_str(reg)?)
             } else {
                 format!(
                     "[{},{}{}]",
-                    reg_to_str(reg),
+                    reg_to_str(reg)?,
                     if *imm < 0 { "" } else { "+" },
                     *imm
                 )
@@ -556,11 +594,11 @@ fn mem_op(args: &[Operand]) -> String {
             OperandData::None,
         ) => {
             if *imm == 0 {
-                format!("[{}]", reg_to_str(reg))
+                format!("[{}]", reg_to_str(reg)?)
             } else {
                 format!(
                     "[{},{}{}]",
-                    reg_to_str(reg),
+                    reg_to_str(reg)?,
                     if *imm < 0 { "" } else { "+" },
                     *imm
                 )
@@ -576,8 +614,8 @@ fn mem_op(args: &[Operand]) -> String {
             if *imm == 0 {
                 format!(
                     "[{},{},{}{}]",
-                    reg_to_str(reg1),
-                    reg_to_str(reg2),
+                    reg_to_str(reg1)?,
+                    reg_to_str(reg2)?,
                     if *shift < 0 { "" } else { "+" },
                     *shift
                 )
@@ -585,7 +623,7 @@ fn mem_op(args: &[Operand]) -> String {
                 if *shift == 0 {
                     format!(
                         "[{},{}{}]",
-                        reg_to_str(reg1),
+                        reg_to_str(reg1)?,
                         if *imm < 0 { "" } else { "+" },
                         *imm
                     )
@@ -593,17 +631,17 @@ fn mem_op(args: &[Operand]) -> String {
                     if *shift == 0 {
                         format!(
                             "[{},{}{}]",
-                            reg_to_str(reg1),
+                            reg_to_str(reg1)?,
                             if *imm < 0 { "" } else { "+" },
                             *imm,
                         )
                     } else {
                         format!(
                             "[{},{}{},{},{}{}]",
-                            reg_to_str(reg1),
+                            reg_to_str(reg1)?,
                             if *imm < 0 { "" } else { "+" },
                             *imm,
-                            reg_to_str(reg2),
+                            reg_to_str(reg2)?,
                             if *shift < 0 { "" } else { "+" },
                             *shift
                         )
@@ -618,7 +656,7 @@ fn mem_op(args: &[Operand]) -> String {
             OperandData::None,
             OperandData::None,
         ) => {
-            format!("[{}]", reg_to_str(reg1))
+            format!("[{}]", reg_to_str(reg1)?)
         }
         (
             OperandData::None,
@@ -628,12 +666,12 @@ fn mem_op(args: &[Operand]) -> String {
             OperandData::Int32(mul),
         ) => {
             if *mul == 0 {
-                format!("[{}]", reg_to_str(reg1),)
+                format!("[{}]", reg_to_str(reg1)?,)
             } else {
                 format!(
                     "[{},{},{}{}]",
-                    reg_to_str(reg1),
-                    reg_to_str(reg2),
+                    reg_to_str(reg1)?,
+                    reg_to_str(reg2)?,
                     if *mul < 0 { "" } else { "+" },
                     *mul
                 )
@@ -646,7 +684,7 @@ fn mem_op(args: &[Operand]) -> String {
             OperandData::Reg(reg2),
             OperandData::None,
         ) => {
-            format!("[{},{}]", reg_to_str(reg1), reg_to_str(reg2),)
+            format!("[{},{}]", reg_to_str(reg1)?, reg_to_str(reg2)?,)
         }
         (
             OperandData::None,
@@ -656,12 +694,12 @@ fn mem_op(args: &[Operand]) -> String {
             OperandData::Int64(mul),
         ) => {
             if *mul == 0 {
-                format!("[{}]", reg_to_str(reg1),)
+                format!("[{}]", reg_to_str(reg1)?,)
             } else {
                 format!(
                     "[{},{},{}{}]",
-                    reg_to_str(reg1),
-                    reg_to_str(reg2),
+                    reg_to_str(reg1)?,
+                    reg_to_str(reg2)?,
                     if *mul < 0 { "" } else { "+" },
                     *mul
                 )
@@ -674,7 +712,7 @@ fn mem_op(args: &[Operand]) -> String {
             OperandData::None,
             OperandData::None,
         ) => {
-            format!("[{} + {lbl}]", reg_to_str(reg1))
+            format!("[{} + {lbl}]", reg_to_str(reg1)?)
         }
         (
             OperandData::None,
@@ -684,12 +722,12 @@ fn mem_op(args: &[Operand]) -> String {
             OperandData::None,
         ) => {
             if *imm == 0 {
-                format!("[{},{}]", reg_to_str(reg1), reg_to_str(reg2))
+                format!("[{},{}]", reg_to_str(reg1)?, reg_to_str(reg2)?)
             } else {
                 format!(
                     "[{},{},{}{}]",
-                    reg_to_str(reg1),
-                    reg_to_str(reg2),
+                    reg_to_str(reg1)?,
+                    reg_to_str(reg2)?,
                     if *imm < 0 { "" } else { "+" },
                     *imm
                 )
@@ -702,8 +740,14 @@ fn mem_op(args: &[Operand]) -> String {
             OperandData::None,
             OperandData::None,
         ) => {
-            format!("[{}]", reg_to_str(reg))
+            format!("[{}]", reg_to_str(reg)?)
         }
-        e => todo!("{:?}", e),
-    }
+        e => {
+            return Err(LoweringError::Unsupported(format!(
+                "memory operand {:?} is not supported",
+                e
+            )))
+        }
+    };
+    Ok(op)
 }
@@ -47,6 +47,8 @@ pub enum Opcode {
     CJMPi,
     CJMPr,
     CALL,
+    /// Call the function whose address is in a register
+    CALLr,
     SCCALL,
     RET,
     END,
@@ -100,6 +102,18 @@ pub enum OperandData {
     None,
 }
 
+impl InstructionData {
+    /// Whether this is a call in tail position, lowered as a jump to the
+    /// callee which then returns to the caller of this function
+    pub fn is_tail_call(&self) -> bool {
+        self.opcode == Opcode::JMPi
+            && matches!(
+                self.operands.first().map(|op| &op.data),
+                Some(OperandData::Label(_))
+            )
+    }
+}
+
 impl TargetInst for InstructionData {
     fn input_vregs(&self) -> Vec<VReg> {
         let mut vrs = vec![];
@@ -242,7 +256,7 @@ impl TargetInst for InstructionData {
     }
 
     fn is_call(&self) -> bool {
-        self.opcode == Opcode::CALL
+        matches!(self.opcode, Opcode::CALL | Opcode::CALLr)
     }
 
     fn is_phi(&self) -> bool {
@@ -358,24 +372,24 @@ impl Operand {
 }
 
 impl OperandData {
-    pub fn as_reg(&self) -> &Reg {
+    pub fn as_reg(&self) -> Option<&Reg> {
         match self {
-            Self::Reg(r) => r,
-            e => todo!("{:?}", e),
+            Self::Reg(r) => Some(r),
+            _ => None,
         }
     }
 
-    pub fn as_vreg(&self) -> &VReg {
+    pub fn as_vreg(&self) -> Option<&VReg> {
         match self {
-            Self::VReg(r) => r,
-            _ => todo!(),
+            Self::VReg(r) => Some(r),
+            _ => None,
         }
     }
 
-    pub fn as_block(&self) -> &BasicBlockId {
+    pub fn as_block(&self) -> Option<&BasicBlockId> {
         match self {
-            Self::Block(b) => b,
-            _ => todo!(),
+            Self::Block(b) => Some(b),
+            _ => None,
         }
     }
 
@@ -501,7 +515,10 @@ impl fmt::Debug for Operand {
 impl fmt::Debug for OperandData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Reg(r) => write!(f, "{}", reg_to_str(r)),
+            Self::Reg(r) => match reg_to_str(r) {
+                Ok(name) => write!(f, "{}", name),
+                Err(_) => write!(f, "{:?}", r),
+            },
             Self::VReg(vr) => write!(f, "%{}", vr.0),
             Self::Int8(i) => write!(f, "{}", i),
             Self::Int32(i) => write!(f, "{}", i),
@@ -1,22 +1,27 @@
 use super::{get_operand_for_val, get_vreg_for_val, new_empty_inst_output};
-use crate::codegen::core::ir::{
-    function::{
-        basic_block::BasicBlockId,
-        data::Data as IrData,
-        instruction::{Cast, ICmp, ICmpCond, InstructionId, Operand},
+use crate::codegen::core::{
+    ir::{
+        function::{
+            basic_block::BasicBlockId,
+            data::Data as IrData,
+            instruction::{Cast, ICmp, ICmpCond, InstructionId, Operand},
+        },
+        types::Type,
+        value::{ConstantInt, ConstantValue, Value, ValueId},
     },
-    types::Type,
-    value::{ConstantInt, ConstantValue, Value, ValueId},
+    pass::OptLevel,
 };
 use crate::codegen::{
-    function::instruction::Instruction as MachInstruction,
+    function::{
+        basic_block::BasicBlockId as MachBasicBlockId, instruction::Instruction as MachInstruction,
+    },
     isa::ola::{
         instruction::{InstructionData, Opcode, Operand as MO, OperandData},
         register::GR,
         Ola,
     },
     lower::{LoweringContext, LoweringError},
-    register::Reg,
+    register::{Reg, VReg},
 };
 use anyhow::Result;
 
@@ -425,17 +430,176 @@ pub fn lower_condbr(
     Err(LoweringError::Todo("Unsupported conditional br pattern".into()).into())
 }
 
+/// The cases of a switch below which they are compared one by one, since a
+/// search or a table would not be any faster
+const MIN_SEARCH_CASES: usize = 4;
+/// The cases a search compares one by one once it has narrowed them down
+const MAX_LEAF_CASES: usize = 3;
+/// The least percentage of the entries of a jump table which are cases
+const MIN_JUMP_TABLE_DENSITY: u64 = 40;
+/// The most entries of a jump table
+const MAX_JUMP_TABLE_SIZE: u64 = 1024;
+/// The size in words of an entry of a jump table, a `jmp` with an immediate
+const JUMP_TABLE_ENTRY_SIZE: i64 = 2;
+
+/// How a `switch` is lowered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SwitchStrategy {
+    /// Compare the selector with each case in turn
+    Linear,
+    /// Halve the sorted cases with a `gte` until few are left, which are
+    /// compared in turn
+    BinarySearch,
+    /// Jump into a table with an entry for every value from the least case to
+    /// the greatest
+    JumpTable,
+}
+
+/// How to lower a switch over the sorted case values `values`, by how many
+/// there are and how densely they cover the values in between
+fn switch_strategy(opt_level: OptLevel, values: &[u64]) -> SwitchStrategy {
+    if opt_level == OptLevel::None || values.len() < MIN_SEARCH_CASES {
+        return SwitchStrategy::Linear;
+    }
+    let range = values[values.len() - 1] - values[0] + 1;
+    if range <= MAX_JUMP_TABLE_SIZE && values.len() as u64 * 100 >= range * MIN_JUMP_TABLE_DENSITY {
+        SwitchStrategy::JumpTable
+    } else {
+        SwitchStrategy::BinarySearch
+    }
+}
+
 pub fn lower_switch(
     ctx: &mut LoweringContext<Ola>,
     _id: InstructionId,
     tys: &[Type],
     args: &[ValueId],
     blocks: &[BasicBlockId],
+) -> Result<()> {
+    let Some(cases) = sorted_cases(ctx, args, blocks) else {
+        return lower_linear_switch(ctx, tys, args, blocks);
+    };
+    let values = cases.iter().map(|&(value, _)| value).collect::<Vec<_>>();
+    let strategy = switch_strategy(ctx.isa.opt_level(), &values);
+    if strategy == SwitchStrategy::Linear {
+        return lower_linear_switch(ctx, tys, args, blocks);
+    }
+
+    let sel = get_vreg_for_val(ctx, tys[0], args[0])?;
+    let block = ctx.block_map[&ctx.cur_block];
+    // The destinations are now reached through the blocks the switch is split
+    // into, which add the edges to them
+    let dests = std::mem::take(&mut ctx.mach_data.block_ref_mut(block).succs);
+    for dest in dests {
+        ctx.mach_data.block_ref_mut(dest).preds.remove(&block);
+    }
+
+    let edge_blocks = split_phi_edges(ctx, blocks);
+    let target = |block: MachBasicBlockId| {
+        edge_blocks
+            .iter()
+            .find(|&&(dest, _)| dest == block)
+            .map_or(block, |&(_, edge_block)| edge_block)
+    };
+    let default = target(ctx.block_map[&blocks[0]]);
+    let cases = cases
+        .into_iter()
+        .map(|(value, block)| (value, target(ctx.block_map[&block])))
+        .collect::<Vec<_>>();
+    let switch = SwitchLowering {
+        ty: tys[0],
+        sel,
+        default,
+    };
+    if strategy == SwitchStrategy::JumpTable {
+        switch.lower_jump_table(ctx, block, &cases);
+    } else {
+        switch.lower_search(ctx, block, &cases);
+    }
+    for (dest, edge_block) in edge_blocks {
+        switch.jump(ctx, edge_block, dest);
+    }
+    Ok(())
+}
+
+/// Put a block of its own on the edge to each destination of the switch whose
+/// phis take a value from the block of the switch. The switch leaves its block
+/// from the middle, and from the blocks it is split into, while the copies
+/// into the phis go at the end of the block the value comes from. Returns the
+/// destinations with the blocks on the edges to them.
+fn split_phi_edges(
+    ctx: &mut LoweringContext<Ola>,
+    dests: &[BasicBlockId],
+) -> Vec<(MachBasicBlockId, MachBasicBlockId)> {
+    let from = ctx.cur_block;
+    let mut edge_blocks = vec![];
+    for &dest in dests {
+        let has_phis = ctx.ir_data.instructions.iter().any(|(_, inst)| {
+            inst.parent == dest
+                && matches!(&inst.operand, Operand::Phi(phi) if phi.blocks.contains(&from))
+        });
+        if !has_phis || ctx.edge_blocks.contains_key(&(from, dest)) {
+            continue;
+        }
+        let edge_block = ctx.mach_data.create_block();
+        ctx.edge_blocks.insert((from, dest), edge_block);
+        edge_blocks.push((ctx.block_map[&dest], edge_block));
+    }
+
+    // The phis lowered already, those of the block itself if the switch loops
+    // back to it among them, take the value from the new blocks too
+    let from = ctx.block_map[&from];
+    let insts = ctx
+        .mach_data
+        .instructions
+        .iter_mut()
+        .map(|(_, inst)| inst)
+        .chain(ctx.inst_seq.iter_mut());
+    for inst in insts {
+        if inst.data.opcode != Opcode::Phi {
+            continue;
+        }
+        let Some(&(_, edge_block)) = edge_blocks.iter().find(|&&(dest, _)| dest == inst.parent)
+        else {
+            continue;
+        };
+        for op in inst.data.operands[2..].iter_mut().step_by(2) {
+            if matches!(op.data, OperandData::Block(block) if block == from) {
+                op.data = OperandData::Block(edge_block);
+            }
+        }
+    }
+    edge_blocks
+}
+
+/// The values of the cases of a switch in order with their destinations, if
+/// they are all constants a `gte` can compare
+fn sorted_cases(
+    ctx: &LoweringContext<Ola>,
+    args: &[ValueId],
+    blocks: &[BasicBlockId],
+) -> Option<Vec<(u64, BasicBlockId)>> {
+    let mut cases = args[1..]
+        .iter()
+        .zip(&blocks[1..])
+        .map(|(arg, dest)| match ctx.ir_data.value_ref(*arg) {
+            Value::Constant(ConstantValue::Int(i)) => u32::try_from(i.cast_to_i64())
+                .ok()
+                .map(|v| (v as u64, *dest)),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    cases.sort_by_key(|&(value, _)| value);
+    Some(cases)
+}
+
+fn lower_linear_switch(
+    ctx: &mut LoweringContext<Ola>,
+    tys: &[Type],
+    args: &[ValueId],
+    blocks: &[BasicBlockId],
 ) -> Result<()> {
     let src = get_operand_for_val(ctx, tys[0], args[0])?;
-    //let output = new_empty_inst_output(ctx, tys[0], id);
-    // let mut vreg = ctx.mach_data.vregs.add_vreg_data(tys[0]);
-    // ctx.inst_id_to_vreg.insert(id, vec![vreg]);
     let switch_reg: Reg = GR::R8.into();
     for (idx, dst) in args[1..].iter().enumerate() {
         let dest = get_operand_for_val(ctx, tys[idx], *dst)?;
@@ -472,13 +636,284 @@ pub fn lower_switch(
     Ok(())
 }
 
+/// A switch being lowered into machine blocks of its own
+struct SwitchLowering {
+    ty: Type,
+    sel: VReg,
+    default: MachBasicBlockId,
+}
+
+impl SwitchLowering {
+    /// Look for the selector among the sorted `cases` from `block`: below the
+    /// middle case it goes on in `block`, otherwise in a new block
+    fn lower_search(
+        &self,
+        ctx: &mut LoweringContext<Ola>,
+        block: MachBasicBlockId,
+        cases: &[(u64, MachBasicBlockId)],
+    ) {
+        if cases.len() <= MAX_LEAF_CASES {
+            for &(value, dest) in cases {
+                let cond = ctx.mach_data.vregs.add_vreg_data(self.ty);
+                push(
+                    ctx,
+                    block,
+                    Opcode::EQri,
+                    vec![
+                        MO::output(cond.into()),
+                        MO::input(self.sel.into()),
+                        MO::new((value as i64).into()),
+                    ],
+                );
+                self.jump_if(ctx, block, cond, dest);
+            }
+            self.jump(ctx, block, self.default);
+            return;
+        }
+
+        let (lower, upper) = cases.split_at(cases.len() / 2);
+        let upper_block = ctx.mach_data.create_block();
+        let cond = ctx.mach_data.vregs.add_vreg_data(self.ty);
+        push(
+            ctx,
+            block,
+            Opcode::GTE,
+            vec![
+                MO::output(cond.into()),
+                MO::input(self.sel.into()),
+                MO::new((upper[0].0 as i64).into()),
+            ],
+        );
+        self.jump_if(ctx, block, cond, upper_block);
+        self.lower_search(ctx, block, lower);
+        self.lower_search(ctx, upper_block, upper);
+    }
+
+    /// Jump from `block` to the entry of the selector in a table of jumps to
+    /// the destinations of the values from the least case to the greatest,
+    /// once it is known to be in between
+    fn lower_jump_table(
+        &self,
+        ctx: &mut LoweringContext<Ola>,
+        block: MachBasicBlockId,
+        cases: &[(u64, MachBasicBlockId)],
+    ) {
+        let (min, max) = (cases[0].0, cases[cases.len() - 1].0);
+
+        let above = ctx.mach_data.vregs.add_vreg_data(self.ty);
+        push(
+            ctx,
+            block,
+            Opcode::GTE,
+            vec![
+                MO::output(above.into()),
+                MO::input(self.sel.into()),
+                MO::new((max as i64 + 1).into()),
+            ],
+        );
+        self.jump_if(ctx, block, above, self.default);
+
+        // The index into the table is the selector less the least case
+        let (dispatch, index) = if min == 0 {
+            (block, self.sel)
+        } else {
+            let dispatch = ctx.mach_data.create_block();
+            let in_range = ctx.mach_data.vregs.add_vreg_data(self.ty);
+            push(
+                ctx,
+                block,
+                Opcode::GTE,
+                vec![
+                    MO::output(in_range.into()),
+                    MO::input(self.sel.into()),
+                    MO::new((min as i64).into()),
+                ],
+            );
+            self.jump_if(ctx, block, in_range, dispatch);
+            self.jump(ctx, block, self.default);
+
+            let neg_min = ctx.mach_data.vregs.add_vreg_data(self.ty);
+            push(
+                ctx,
+                dispatch,
+                Opcode::NOT,
+                vec![MO::output(neg_min.into()), MO::input((min as i64).into())],
+            );
+            push(
+                ctx,
+                dispatch,
+                Opcode::ADDri,
+                vec![
+                    MO::output(neg_min.into()),
+                    MO::input(neg_min.into()),
+                    MO::input(1i64.into()),
+                ],
+            );
+            let index = ctx.mach_data.vregs.add_vreg_data(self.ty);
+            push(
+                ctx,
+                dispatch,
+                Opcode::ADDrr,
+                vec![
+                    MO::output(index.into()),
+                    MO::input(self.sel.into()),
+                    MO::input(neg_min.into()),
+                ],
+            );
+            (dispatch, index)
+        };
+
+        let table = ctx.mach_data.create_block();
+        let offset = ctx.mach_data.vregs.add_vreg_data(self.ty);
+        push(
+            ctx,
+            dispatch,
+            Opcode::MULri,
+            vec![
+                MO::output(offset.into()),
+                MO::input(index.into()),
+                MO::new(JUMP_TABLE_ENTRY_SIZE.into()),
+            ],
+        );
+        let base = ctx.mach_data.vregs.add_vreg_data(self.ty);
+        push(
+            ctx,
+            dispatch,
+            Opcode::MOVri,
+            vec![MO::output(base.into()), MO::new(OperandData::Block(table))],
+        );
+        let entry = ctx.mach_data.vregs.add_vreg_data(self.ty);
+        push(
+            ctx,
+            dispatch,
+            Opcode::ADDrr,
+            vec![
+                MO::output(entry.into()),
+                MO::input(base.into()),
+                MO::input(offset.into()),
+            ],
+        );
+        push(ctx, dispatch, Opcode::JMPr, vec![MO::input(entry.into())]);
+        add_edge(ctx, dispatch, table);
+
+        let mut cases = cases.iter().peekable();
+        for value in min..=max {
+            let dest = match cases.next_if(|&&(case, _)| case == value) {
+                Some(&(_, dest)) => dest,
+                None => self.default,
+            };
+            self.jump(ctx, table, dest);
+        }
+    }
+
+    fn jump_if(
+        &self,
+        ctx: &mut LoweringContext<Ola>,
+        block: MachBasicBlockId,
+        cond: VReg,
+        dest: MachBasicBlockId,
+    ) {
+        push(
+            ctx,
+            block,
+            Opcode::CJMPr,
+            vec![MO::input(cond.into()), MO::new(OperandData::Block(dest))],
+        );
+        add_edge(ctx, block, dest);
+    }
+
+    fn jump(
+        &self,
+        ctx: &mut LoweringContext<Ola>,
+        block: MachBasicBlockId,
+        dest: MachBasicBlockId,
+    ) {
+        push(
+            ctx,
+            block,
+            Opcode::JMPr,
+            vec![MO::new(OperandData::Block(dest))],
+        );
+        add_edge(ctx, block, dest);
+    }
+}
+
+fn push(
+    ctx: &mut LoweringContext<Ola>,
+    block: MachBasicBlockId,
+    opcode: Opcode,
+    operands: Vec<MO>,
+) {
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData { opcode, operands },
+        block,
+    ));
+}
+
+fn add_edge(ctx: &mut LoweringContext<Ola>, from: MachBasicBlockId, to: MachBasicBlockId) {
+    ctx.mach_data.block_ref_mut(from).succs.insert(to);
+    ctx.mach_data.block_ref_mut(to).preds.insert(from);
+}
+
 #[cfg(test)]
 mod test {
+    use super::{switch_strategy, SwitchStrategy};
     use crate::codegen::{
-        core::ir::module::Module,
-        isa::ola::{asm::AsmProgram, Ola},
+        core::{ir::module::Module, pass::OptLevel},
+        isa::ola::{
+            asm::AsmProgram,
+            compile, Ola,
+        },
         lower::compile_module,
     };
+
+    /// A function returning the index of the case of a switch over `values`
+    fn switch_source(values: &[u64]) -> String {
+        let cases = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("    i64 {}, label %case{}\n", value, i))
+            .collect::<String>();
+        let blocks = (0..values.len())
+            .map(|i| format!("case{}:\n  ret i64 {}\n\n", i, i))
+            .collect::<String>();
+        format!(
+            "define i64 @select(i64 %0) {{\nentry:\n  switch i64 %0, label %default [\n{}  ]\n\n{}default:\n  ret i64 100\n}}\n",
+            cases, blocks
+        )
+    }
+
+    /// A function whose switch over `values` jumps to a block with a phi for
+    /// every case, and by default to a block which jumps there too
+    fn phi_switch_source(values: &[u64]) -> String {
+        let cases = values
+            .iter()
+            .map(|value| format!("    i64 {}, label %join\n", value))
+            .collect::<String>();
+        format!(
+            "define i64 @select(i64 %0) {{\nentry:\n  switch i64 %0, label %other [\n{}  ]\n\nother:\n  br label %join\n\njoin:\n  %1 = phi i64 [ 7, %entry ], [ 100, %other ]\n  ret i64 %1\n}}\n",
+            cases
+        )
+    }
+
+    fn count_lines(asm: &str, prefix: &str) -> usize {
+        asm.lines().filter(|line| line.starts_with(prefix)).count()
+    }
+
+    /// The label of the block which copies `value` into a phi, which must be
+    /// the first thing the block does
+    fn phi_copy_block(asm: &str, value: i64) -> String {
+        let lines = asm.lines().collect::<Vec<_>>();
+        let copy = format!(" {}", value);
+        let at = lines
+            .iter()
+            .position(|line| line.starts_with("  mov ") && line.ends_with(&copy))
+            .expect("no copy into the phi");
+        lines[at - 1]
+            .strip_suffix(':')
+            .expect("the copy into the phi is not on an edge of its own")
+            .to_string()
+    }
     #[test]
     fn codegen_eq_test() {
         // LLVM Assembly
@@ -856,4 +1291,116 @@ le:
 "
         );
     }
+
+    #[test]
+    fn switch_strategy_by_case_count_and_density() {
+        let dense = [1, 2, 3, 5, 6];
+        let sparse = [7, 1093482716, 1303130570, 2000000000];
+        assert_eq!(
+            switch_strategy(OptLevel::None, &dense),
+            SwitchStrategy::Linear
+        );
+        assert_eq!(
+            switch_strategy(OptLevel::Default, &[1, 2, 3]),
+            SwitchStrategy::Linear
+        );
+        assert_eq!(
+            switch_strategy(OptLevel::Default, &dense),
+            SwitchStrategy::JumpTable
+        );
+        assert_eq!(
+            switch_strategy(OptLevel::Default, &sparse),
+            SwitchStrategy::BinarySearch
+        );
+        assert_eq!(
+            switch_strategy(OptLevel::Default, &[0, 10, 20, 30]),
+            SwitchStrategy::BinarySearch
+        );
+    }
+
+    #[test]
+    fn codegen_switch_binary_search_test() {
+        let values = [3, 17, 256, 1000, 4096, 70000, 1093482716, 1303130570];
+        let source = switch_source(&values);
+
+        let linear = compile(&source, &Ola::default());
+        assert_eq!(count_lines(&linear, "  eq "), values.len());
+        assert_eq!(count_lines(&linear, "  gte "), 0);
+
+        // Halved twice with `gte`, and each case compared once
+        let search = compile(&source, &Ola::new(OptLevel::Default));
+        assert_eq!(count_lines(&search, "  gte "), 3);
+        assert_eq!(count_lines(&search, "  eq "), values.len());
+    }
+
+    #[test]
+    fn codegen_switch_jump_table_test() {
+        let source = switch_source(&[0, 1, 2, 4]);
+
+        // The entry of the selector is two words per value from the label of
+        // the table, which has one for each value from 0 to 4
+        assert_eq!(
+            compile(&source, &Ola::new(OptLevel::Default)),
+            "select:
+.LBL0_0:
+  gte r2 r1 5
+  cjmp r2 .LBL0_5
+  mul r1 r1 2
+  mov r2 .LBL0_6
+  add r1 r2 r1
+  jmp r1
+.LBL0_6:
+  jmp .LBL0_1
+  jmp .LBL0_2
+  jmp .LBL0_3
+  jmp .LBL0_5
+  jmp .LBL0_4
+.LBL0_1:
+  mov r0 0
+  ret
+.LBL0_2:
+  mov r0 1
+  ret
+.LBL0_3:
+  mov r0 2
+  ret
+.LBL0_4:
+  mov r0 3
+  ret
+.LBL0_5:
+  mov r0 100
+  ret
+"
+        );
+    }
+
+    #[test]
+    fn codegen_switch_binary_search_phi_test() {
+        let values = [3, 17, 256, 1000, 4096, 70000, 1093482716, 1303130570];
+        let asm = compile(&phi_switch_source(&values), &Ola::new(OptLevel::Default));
+        assert_eq!(count_lines(&asm, "  gte "), 3);
+
+        // Every case branches to the block copying the value from the switch
+        let edge = phi_copy_block(&asm, 7);
+        let branches = asm
+            .lines()
+            .filter(|line| line.starts_with("  cjmp ") && line.ends_with(&format!(" {}", edge)))
+            .count();
+        assert_eq!(branches, values.len());
+    }
+
+    #[test]
+    fn codegen_switch_jump_table_phi_test() {
+        let asm = compile(
+            &phi_switch_source(&[1, 2, 3, 5]),
+            &Ola::new(OptLevel::Default),
+        );
+        assert_eq!(count_lines(&asm, "  eq "), 0);
+
+        // The entries of the cases jump to the block copying the value from
+        // the switch, and the one of 4 to the default
+        let edge = phi_copy_block(&asm, 7);
+        let jump = format!("  jmp {}", edge);
+        assert_eq!(asm.lines().filter(|line| *line == jump).count(), 4);
+    }
 }
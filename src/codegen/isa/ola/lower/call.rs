@@ -4,11 +4,14 @@ use super::{
     get_operand_for_val, get_operands_for_val, get_vreg_for_val, new_empty_inst_output,
     new_empty_str_inst_output,
 };
-use crate::codegen::core::ir::{
-    function::instruction::InstructionId,
-    module::name::Name,
-    types::{CompoundType, FunctionType, Type},
-    value::{ConstantValue, Value, ValueId},
+use crate::codegen::core::{
+    ir::{
+        function::instruction::InstructionId,
+        module::name::Name,
+        types::{CompoundType, FunctionType, Type, I32},
+        value::{ConstantValue, Value, ValueId},
+    },
+    pass::OptLevel,
 };
 use crate::codegen::{
     function::instruction::Instruction as MachInstruction,
@@ -33,6 +36,9 @@ pub fn lower_call(
     let callee = args[0];
     let name = match &ctx.ir_data.values[callee] {
         Value::Constant(ConstantValue::GlobalRef(Name::Name(name), _)) => name.clone(),
+        Value::Instruction(_) | Value::Argument(_) => {
+            return lower_indirect_call(ctx, id, tys, args)
+        }
         callee => {
             return Err(LoweringError::Todo(format!("Unsupported callee: {:?}", callee)).into())
         }
@@ -74,18 +80,29 @@ pub fn lower_call(
                     ctx.block_map[&ctx.cur_block],
                 ));
             }
-            e => todo!("{:?}", e),
+            e => {
+                return Err(
+                    LoweringError::Unsupported(format!("builtin {} is not supported", e)).into(),
+                )
+            }
         }
         return Ok(());
     }
 
+    // When its result is used, sccall also writes the status of the call, 1 if
+    // the callee returned and 0 if it reverted, to its first operand
     if name.as_str() == "contract_call" {
         let addr = get_vreg_for_val(ctx, tys[1], args[1])?;
         let flag = get_operand_for_val(ctx, tys[2], args[2])?;
+        let mut operands = vec![MO::input(addr.into()), MO::input(flag)];
+        if !ctx.ir_data.users_of(id).is_empty() {
+            let status = new_empty_inst_output(ctx, tys[0], id);
+            operands.insert(0, MO::output(status[0].into()));
+        }
         ctx.inst_seq.push(MachInstruction::new(
             InstructionData {
                 opcode: Opcode::SCCALL,
-                operands: vec![MO::input(addr.into()), MO::input(flag)],
+                operands,
             },
             ctx.block_map[&ctx.cur_block],
         ));
@@ -209,6 +226,39 @@ pub fn lower_call(
         opcode = Opcode::PROPHET;
     }
 
+    lower_general_call(
+        ctx,
+        id,
+        tys,
+        args,
+        opcode,
+        MO::new(OperandData::Label(name)),
+    )
+}
+
+/// Lower a call through a function pointer. The address of the callee is put
+/// in a register before the arguments are passed, so that register cannot be
+/// one of the argument registers.
+fn lower_indirect_call(
+    ctx: &mut LoweringContext<Ola>,
+    id: InstructionId,
+    tys: &[Type],
+    args: &[ValueId],
+) -> Result<()> {
+    let ptr_ty = ctx.types.base_mut().pointer(I32);
+    let callee = get_vreg_for_val(ctx, ptr_ty, args[0])?;
+
+    lower_general_call(ctx, id, tys, args, Opcode::CALLr, MO::input(callee.into()))
+}
+
+fn lower_general_call(
+    ctx: &mut LoweringContext<Ola>,
+    id: InstructionId,
+    tys: &[Type],
+    args: &[ValueId],
+    opcode: Opcode,
+    callee: MO,
+) -> Result<()> {
     let result_ty = if let Some(ty) = ctx.types.get(tys[0])
         && let CompoundType::Function(FunctionType { ret, .. }) = &*ty
     {
@@ -219,6 +269,17 @@ pub fn lower_call(
 
     pass_args_to_regs(ctx, &tys[1..], &args[1..])?;
 
+    if is_tail_call(ctx, id, tys, opcode, result_ty) {
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: Opcode::JMPi,
+                operands: vec![callee],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+        return Ok(());
+    }
+
     let sz = ctx.isa.data_layout().get_size_of(ctx.types, result_ty) / 4;
     let output;
     let result_reg: Vec<Reg>;
@@ -234,7 +295,7 @@ pub fn lower_call(
         result_reg = [GR::R0.into()].to_vec();
         operands.push(MO::implicit_output(result_reg[0].into()));
     }
-    operands.push(MO::new(OperandData::Label(name)));
+    operands.push(callee);
 
     ctx.inst_seq.push(MachInstruction::new(
         InstructionData { opcode, operands },
@@ -260,6 +321,32 @@ pub fn lower_call(
     Ok(())
 }
 
+/// Whether the call is lowered as a jump to the callee, which then returns to
+/// the caller of this function in its place. The epilogue before the jump
+/// restores this function's callee-saved state as it would before a `ret`,
+/// popping its frame off `r9`, so the callee sees the frame of our caller. R5-R8
+/// hold nothing across a call, as regalloc spills every value live across one,
+/// so there is nothing else to restore. The arguments must all be passed in
+/// R1-R3 and the result must come back in R0 alone.
+fn is_tail_call(
+    ctx: &LoweringContext<Ola>,
+    id: InstructionId,
+    tys: &[Type],
+    opcode: Opcode,
+    result_ty: Type,
+) -> bool {
+    if ctx.isa.opt_level() == OptLevel::None
+        || opcode != Opcode::CALL
+        || !ctx.tail_calls.contains(&id)
+    {
+        return false;
+    }
+    let arg_regs = RegInfo::arg_reg_list(&ctx.call_conv).len();
+    tys.len() - 1 <= arg_regs
+        && tys[1..].iter().all(|ty| !ty.is_array(ctx.types))
+        && ctx.isa.data_layout().get_size_of(ctx.types, result_ty) <= 4
+}
+
 fn pass_args_to_regs(ctx: &mut LoweringContext<Ola>, tys: &[Type], args: &[ValueId]) -> Result<()> {
     let gpru = RegInfo::str_arg_reg_list(&ctx.call_conv);
     let mut gpr_used = args.len();
@@ -278,7 +365,7 @@ fn pass_args_to_regs(ctx: &mut LoweringContext<Ola>, tys: &[Type], args: &[Value
 
             let arg = get_operands_for_val(ctx, ty, arg0)?;
             for idx in 0..sz {
-                let reg = gpru[gpr_used].apply(&RegClass::for_type(ctx.types, e_ty));
+                let reg = gpru[gpr_used].apply(&RegClass::for_type(ctx.types, e_ty)?);
                 let opcode = match &arg[idx] {
                     OperandData::Int32(_) | OperandData::Int64(_) => Opcode::MOVri,
                     OperandData::Reg(_) | OperandData::VReg(_) => Opcode::MOVrr,
@@ -302,7 +389,7 @@ fn pass_args_to_regs(ctx: &mut LoweringContext<Ola>, tys: &[Type], args: &[Value
         } else {
             let arg = get_operand_for_val(ctx, ty, arg0)?;
             gpr_used -= 1;
-            let out = gpru[gpr_used].apply(&RegClass::for_type(ctx.types, ty));
+            let out = gpru[gpr_used].apply(&RegClass::for_type(ctx.types, ty)?);
 
             // TODO: pointer with ref passing
             /* if ty.is_pointer(ctx.types) {
@@ -385,7 +472,7 @@ fn pass_str_args_to_regs(
 
     for (gpr_used, arg) in arg_str.iter().enumerate() {
         let cur_ty = ctx.types.base().element(tys[0]).unwrap();
-        let out = gpru[gpr_used].apply(&RegClass::for_type(ctx.types, cur_ty));
+        let out = gpru[gpr_used].apply(&RegClass::for_type(ctx.types, cur_ty)?);
 
         let opcode = match &arg {
             OperandData::Int32(_) | OperandData::Int64(_) => Opcode::MOVri,
@@ -410,6 +497,15 @@ fn pass_str_args_to_regs(
 }
 
 pub fn lower_return(ctx: &mut LoweringContext<Ola>, arg: Option<(Type, ValueId)>) -> Result<()> {
+    // The callee of a tail call right before returns in place of this function
+    if ctx
+        .inst_seq
+        .last()
+        .map_or(false, |inst| inst.data.is_tail_call())
+    {
+        return Ok(());
+    }
+
     if let Some((ty, value)) = arg {
         let sz = ctx.isa.data_layout().get_size_of(ctx.types, ty) / 4;
         if sz > 1 {
@@ -449,7 +545,13 @@ pub fn lower_return(ctx: &mut LoweringContext<Ola>, arg: Option<(Type, ValueId)>
             assert!(ty.is_integer() || ty.is_pointer(ctx.types));
             let (reg, opcode) = match sz {
                 4 | 8 => (GR::R0.into(), Opcode::MOVrr),
-                _ => todo!(),
+                _ => {
+                    return Err(LoweringError::Unsupported(format!(
+                        "return value of size {} is not supported",
+                        sz
+                    ))
+                    .into())
+                }
             };
             debug_println!("reg {:#?},vreg {:#?}, sz {}", reg, vreg, sz);
             ctx.inst_seq.push(MachInstruction::new(
@@ -470,3 +572,162 @@ pub fn lower_return(ctx: &mut LoweringContext<Ola>, arg: Option<(Type, ValueId)>
     ));
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use crate::codegen::{
+        core::pass::OptLevel,
+        isa::ola::{compile, Ola},
+    };
+
+    #[test]
+    fn codegen_indirect_call_test() {
+        // A function pointer variable, as irgen makes it
+        let source = r#"
+define i64 @add(i64 %0, i64 %1) {
+entry:
+  %2 = add i64 %0, %1
+  ret i64 %2
+}
+
+define i64 @main() {
+entry:
+  %0 = alloca ptr, align 8
+  store ptr @add, ptr %0, align 8
+  %1 = load ptr, ptr %0, align 8
+  %2 = call i64 %1(i64 3, i64 4)
+  %3 = add i64 %2, 1
+  ret i64 %3
+}
+"#;
+        assert_eq!(
+            compile(source, &Ola::default()),
+            "add:
+.LBL0_0:
+  add r0 r1 r2
+  ret
+main:
+.LBL1_0:
+  add r9 r9 4
+  mstore [r9,-2] r9
+  mov r5 add
+  mstore [r9,-3] r5
+  mload r5 [r9,-3]
+  mov r2 4
+  mov r1 3
+  call r5
+  mov r5 r0
+  add r5 r5 1
+  mstore [r9,-4] r5
+  mload r0 [r9,-4]
+  add r9 r9 -4
+  end
+"
+        );
+    }
+
+    #[test]
+    fn codegen_tail_call_test() {
+        let source = r#"
+define i64 @main() {
+entry:
+  %0 = call i64 @count(i64 10)
+  ret i64 %0
+}
+
+define i64 @count(i64 %0) {
+entry:
+  %n = alloca i64, align 8
+  store i64 %0, ptr %n, align 8
+  %1 = load i64, ptr %n, align 8
+  %2 = icmp eq i64 %1, 0
+  br i1 %2, label %done, label %next
+
+done:
+  ret i64 0
+
+next:
+  %3 = load i64, ptr %n, align 8
+  %4 = sub i64 %3, 1
+  %5 = call i64 @count(i64 %4)
+  ret i64 %5
+}
+"#;
+        // The entry point has nothing to return to, so it keeps its call.
+        // `count` pops its frame before jumping to itself.
+        assert_eq!(
+            compile(source, &Ola::new(OptLevel::Default)),
+            "main:
+.LBL0_0:
+  add r9 r9 2
+  mstore [r9,-2] r9
+  mov r1 10
+  call count
+  add r9 r9 -2
+  end
+count:
+.LBL1_0:
+  add r9 r9 1
+  mstore [r9,-1] r1
+  eq r1 r1 0
+  cjmp r1 .LBL1_1
+.LBL1_2:
+  mload r2 [r9,-1]
+  not r7 1
+  add r7 r7 1
+  add r1 r2 r7
+  add r9 r9 -1
+  jmp count
+.LBL1_1:
+  mov r0 0
+  add r9 r9 -1
+  ret
+"
+        );
+    }
+
+    #[test]
+    fn codegen_tail_call_with_csr_test() {
+        // With a call in its entry block, `g` keeps its values in R5-R8
+        let source = r#"
+define i64 @f(i64 %0) {
+entry:
+  ret i64 %0
+}
+
+define i64 @g(i64 %0) {
+entry:
+  %1 = mul i64 %0, 3
+  %2 = call i64 @f(i64 %0)
+  %3 = add i64 %1, %2
+  %4 = call i64 @f(i64 %3)
+  ret i64 %4
+}
+"#;
+        assert_eq!(
+            compile(source, &Ola::new(OptLevel::Default)),
+            "f:
+.LBL0_0:
+  mov r0 r1
+  ret
+g:
+.LBL1_0:
+  add r9 r9 3
+  mstore [r9,-2] r9
+  mov r5 r1
+  mstore [r9,-3] r5
+  mov r1 r5
+  call f
+  mov r5 r0
+  mload r6 [r9,-3]
+  mul r6 r6 3
+  mstore [r9,-3] r6
+  add r5 r6 r5
+  mstore [r9,-3] r5
+  mov r1 r5
+  add r9 r9 -3
+  jmp f
+"
+        );
+    }
+}
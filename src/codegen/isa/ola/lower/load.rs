@@ -374,7 +374,13 @@ fn lower_load_gep(
                 MOperand::new(OperandData::None),
             ]
         }
-        e => todo!("Unsupported GEP pattern for load: {:?}", e),
+        e => {
+            return Err(LoweringError::Unsupported(format!(
+                "GEP pattern {:?} is not supported for load",
+                e
+            ))
+            .into())
+        }
     };
 
     //ctx.mark_as_merged(gep_id);
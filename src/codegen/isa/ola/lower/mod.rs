@@ -72,7 +72,7 @@ impl LowerTrait<Ola> for Lower {
                 let e_ty = ctx.types.base().element(*ty).unwrap();
                 let mut outputs = vec![];
                 for _ in 0..sz {
-                    let reg = args[gpr_used].apply(&RegClass::for_type(ctx.types, e_ty));
+                    let reg = args[gpr_used].apply(&RegClass::for_type(ctx.types, e_ty)?);
                     let output = ctx.mach_data.vregs.add_vreg_data(e_ty);
                     ctx.inst_seq.push(MachInstruction::new(
                         InstructionData {
@@ -86,7 +86,7 @@ impl LowerTrait<Ola> for Lower {
                 }
                 ctx.arg_idx_to_vreg.insert(para_idx, outputs);
             } else {
-                let reg = args[gpr_used].apply(&RegClass::for_type(ctx.types, *ty));
+                let reg = args[gpr_used].apply(&RegClass::for_type(ctx.types, *ty)?);
                 // debug!(reg);
                 // Copy reg to new vreg
                 let output = ctx.mach_data.vregs.add_vreg_data(*ty);
@@ -315,6 +315,21 @@ fn get_operand_for_const(
             assert!(ty.is_pointer(ctx.types));
             let addr = ctx.mach_data.vregs.add_vreg_data(*ty);
             let src = OperandData::Label(name.to_string().unwrap().to_owned());
+            // The address of a function is its label
+            if ctx
+                .module
+                .find_function_by_name(name.to_string().unwrap())
+                .is_some()
+            {
+                ctx.inst_seq.push(MachInstruction::new(
+                    InstructionData {
+                        opcode: Opcode::MOVri,
+                        operands: vec![MO::output(addr.into()), MO::new(src)],
+                    },
+                    ctx.block_map[&ctx.cur_block],
+                ));
+                return Ok(addr.into());
+            }
             ctx.inst_seq.push(MachInstruction::new(
                 InstructionData {
                     opcode: Opcode::MLOADr,
@@ -345,7 +360,7 @@ fn get_operand_for_const(
             ));
             Ok(addr.into())
         }
-        e => todo!("{:?}", e),
+        e => Err(LoweringError::Unsupported(format!("constant {:?} is not supported", e)).into()),
     }
 }
 
@@ -418,7 +433,13 @@ fn get_operands_for_const(
                     ConstantValue::Int(ConstantInt::Int32(i)) => OperandData::Int32(*i),
                     ConstantValue::Int(ConstantInt::Int64(i)) => OperandData::Int64(*i),
                     ConstantValue::Undef(_) => 0.into(),
-                    e => todo!("{:?}", e),
+                    e => {
+                        return Err(LoweringError::Unsupported(format!(
+                            "array element constant {:?} is not supported",
+                            e
+                        ))
+                        .into())
+                    }
                 };
                 debug_println!("array const operand vreg: {:?}", input);
                 inputs.push(input);
@@ -447,7 +468,11 @@ fn get_operands_for_const(
             }
             Ok(inputs)
         }
-        e => todo!("{:?}", e),
+        e => Err(LoweringError::Unsupported(format!(
+            "aggregate constant {:?} is not supported",
+            e
+        ))
+        .into()),
     }
 }
 
@@ -24,7 +24,13 @@ pub fn lower_phi(
     let mut operands = vec![MO::output(output[0].into())];
     for (arg, block) in args.iter().zip(blocks.iter()) {
         operands.push(MO::input(get_operand_for_val(ctx, ty, *arg)?));
-        operands.push(MO::new(OperandData::Block(ctx.block_map[block])))
+        // The values from a block which was split come from the block on the
+        // edge to this one
+        let pred = match ctx.edge_blocks.get(&(*block, ctx.cur_block)) {
+            Some(&edge_block) => edge_block,
+            None => ctx.block_map[block],
+        };
+        operands.push(MO::new(OperandData::Block(pred)))
     }
     ctx.inst_seq.push(MachInstruction::new(
         InstructionData {
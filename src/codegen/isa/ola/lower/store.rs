@@ -1,4 +1,4 @@
-use super::{get_inst_output, get_operand_for_val, get_operands_for_const};
+use super::{get_inst_output, get_operand_for_val, get_operands_for_const, get_vreg_for_val};
 use crate::codegen::core::ir::{
     function::instruction::{InstructionId, Opcode as IrOpcode},
     types::Type,
@@ -89,6 +89,9 @@ pub fn lower_store(
                     addrs.push(addr.into());
                 }
                 vreg = Some(addrs);
+            } else if let ConstantValue::GlobalRef(..) = c {
+                // The address of a function, which is put in a register
+                vreg = Some(vec![get_vreg_for_val(ctx, tys[0], src)?]);
             } else {
                 let addr = ctx.mach_data.vregs.add_vreg_data(tys[0]);
                 match c {
@@ -396,6 +399,22 @@ fn lower_store_gep(
                 MOperand::new(OperandData::None),
             ]
         }
+        [Value::Argument(a), Const(Int(Int64(idx0)))] => {
+            let mut vregs = vec![];
+            let ops = ctx.arg_idx_to_vreg.get(&a.nth).unwrap();
+            for idx in 0..ops.len() {
+                vregs.push(ops[idx]);
+            }
+            vec![
+                MOperand::new(OperandData::MemStart),
+                MOperand::new(OperandData::None),
+                MOperand::new(OperandData::None),
+                MOperand::new(OperandData::Int32(*idx0 as i32)),
+                MOperand::input(OperandData::VReg(vregs[0])),
+                MOperand::input(OperandData::None),
+                MOperand::new(OperandData::None),
+            ]
+        }
         [Value::Argument(a), Value::Instruction(idx1)] => {
             let mut vregs = vec![];
             let ops = ctx.arg_idx_to_vreg.get(&a.nth).unwrap();
@@ -5,23 +5,39 @@ pub mod pass;
 pub mod register;
 
 use super::TargetIsa;
-use crate::codegen::core::ir::module::data_layout::DataLayout;
+use crate::codegen::core::{ir::module::data_layout::DataLayout, pass::OptLevel};
 use crate::codegen::{call_conv::CallConvKind, isa::ola, module::Module, pass::regalloc};
 use anyhow::Result;
 
 #[derive(Clone)]
 pub struct Ola {
     data_layout: DataLayout,
+    opt_level: OptLevel,
 }
 
 impl Default for Ola {
     fn default() -> Self {
         Self {
             data_layout: DataLayout("".to_string()),
+            opt_level: OptLevel::None,
         }
     }
 }
 
+impl Ola {
+    /// An ISA which also optimizes the machine code at the given level
+    pub fn new(opt_level: OptLevel) -> Self {
+        Self {
+            opt_level,
+            ..Self::default()
+        }
+    }
+
+    pub fn opt_level(&self) -> OptLevel {
+        self.opt_level
+    }
+}
+
 impl TargetIsa for Ola {
     type Inst = instruction::InstructionData;
     type Lower = ola::lower::Lower;
@@ -35,8 +51,11 @@ impl TargetIsa for Ola {
             pass::add_peephole::run_on_module,
             pass::simple_reg_coalescing::run_on_module,
             pass::add_peephole::run_on_module,
+            pass::slot_coloring::run_on_module,
             pass::eliminate_slot::run_on_module,
             pass::pro_epi_inserter::run_on_module,
+            pass::peephole::run_on_module,
+            pass::branch_layout::run_on_module,
         ]
     }
 
@@ -48,3 +67,24 @@ impl TargetIsa for Ola {
         &self.data_layout
     }
 }
+
+/// Compile a module and return its assembly, for tests which compare it with
+/// the expected assembly or with that of another module
+#[cfg(test)]
+pub(crate) fn emit(
+    module: &crate::codegen::core::ir::module::Module,
+    isa: &Ola,
+) -> asm::AsmProgram {
+    let code = crate::codegen::lower::compile_module(isa, module).expect("failed to compile");
+    asm::emit(&code).expect("failed to emit")
+}
+
+/// Compile LLVM assembly and return the program text, for tests which
+/// compare it with the expected assembly
+#[cfg(test)]
+pub(crate) fn compile(source: &str, isa: &Ola) -> String {
+    use crate::codegen::core::ir::module::Module as IrModule;
+
+    let module = IrModule::try_from(source).expect("failed to parse LLVM IR");
+    emit(&module, isa).program
+}
@@ -35,16 +35,16 @@ pub fn run_on_function(function: &mut Function<Ola>) {
                                 replace_list.push(inst_id)
                             }
                             OperandData::Reg(reg) => {
-                                if reg == inst.data.operands[0].data.as_reg() {
+                                if Some(reg) == inst.data.operands[0].data.as_reg() {
                                     dead_list.push(inst_id)
                                 }
                             }
                             OperandData::VReg(reg) => {
-                                if reg == inst.data.operands[0].data.as_vreg() {
+                                if Some(reg) == inst.data.operands[0].data.as_vreg() {
                                     dead_list.push(inst_id)
                                 }
                             }
-                            _ => todo!(),
+                            _ => {}
                         }
                     }
                 }
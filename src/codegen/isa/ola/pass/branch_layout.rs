@@ -0,0 +1,551 @@
+use crate::codegen::{
+    core::pass::OptLevel,
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{Instruction, InstructionId},
+        Function,
+    },
+    isa::ola::{
+        instruction::{InstructionData, Opcode, Operand, OperandData},
+        Ola,
+    },
+    module::Module,
+};
+use anyhow::Result;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// Lay out the blocks of every function so that they fall through to their
+/// successors instead of jumping to them. The IR is lowered as it is when not
+/// optimizing, so nothing is done then.
+pub fn run_on_module(module: &mut Module<Ola>) -> Result<()> {
+    if module.isa.opt_level() == OptLevel::None {
+        return Ok(());
+    }
+    for (_, func) in &mut module.functions {
+        run_on_function(func);
+    }
+    Ok(())
+}
+
+pub fn run_on_function(function: &mut Function<Ola>) {
+    if function.is_declaration || !is_supported(function) {
+        return;
+    }
+
+    make_fallthrough_explicit(function);
+    thread_jumps(function);
+    remove_unreachable_blocks(function);
+    merge_blocks(function);
+    place_blocks(function);
+    remove_redundant_jumps(function);
+    update_cfg(function);
+}
+
+/// Every edge can be followed: jumps go to blocks or, for tail calls, to other
+/// functions, and only the last block may run off its end. The dispatch of a
+/// jump table ends in a `jmp` to a register, so a function with one is left
+/// as it was lowered, with its table right after the dispatch.
+fn is_supported(function: &Function<Ola>) -> bool {
+    let layout = &function.layout;
+    layout.block_iter().all(|block| {
+        let direct = layout.inst_iter(block).all(|id| {
+            let data = &function.data.inst_ref(id).data;
+            match data.opcode {
+                Opcode::JMPi | Opcode::JMPr => jump_target(data).is_some() || data.is_tail_call(),
+                Opcode::CJMPi | Opcode::CJMPr => cond_jump_target(data).is_some(),
+                _ => true,
+            }
+        });
+        let ends = match layout.last_inst_of(block) {
+            Some(last) => {
+                is_terminator(&function.data.inst_ref(last).data)
+                    || layout.next_block_of(block).is_some()
+            }
+            None => true,
+        };
+        direct && ends
+    })
+}
+
+fn jump_target(data: &InstructionData) -> Option<BasicBlockId> {
+    match (data.opcode, data.operands.first().map(|op| &op.data)) {
+        (Opcode::JMPi | Opcode::JMPr, Some(OperandData::Block(block))) => Some(*block),
+        _ => None,
+    }
+}
+
+fn cond_jump_target(data: &InstructionData) -> Option<BasicBlockId> {
+    match (data.opcode, data.operands.get(1).map(|op| &op.data)) {
+        (Opcode::CJMPi | Opcode::CJMPr, Some(OperandData::Block(block))) => Some(*block),
+        _ => None,
+    }
+}
+
+fn is_terminator(data: &InstructionData) -> bool {
+    matches!(
+        data.opcode,
+        Opcode::JMPi | Opcode::JMPr | Opcode::RET | Opcode::END
+    )
+}
+
+/// The unconditional jump ending `block`
+fn final_jump(
+    function: &Function<Ola>,
+    block: BasicBlockId,
+) -> Option<InstructionId<InstructionData>> {
+    let last = function.layout.last_inst_of(block)?;
+    jump_target(&function.data.inst_ref(last).data).map(|_| last)
+}
+
+/// The blocks `block` may go to. Empty blocks are emitted as a `ret`.
+fn succs_of(function: &Function<Ola>, block: BasicBlockId) -> Vec<BasicBlockId> {
+    function
+        .layout
+        .inst_iter(block)
+        .filter_map(|id| {
+            let data = &function.data.inst_ref(id).data;
+            jump_target(data).or_else(|| cond_jump_target(data))
+        })
+        .collect()
+}
+
+fn preds_of(function: &Function<Ola>) -> FxHashMap<BasicBlockId, FxHashSet<BasicBlockId>> {
+    let mut preds: FxHashMap<_, FxHashSet<_>> = FxHashMap::default();
+    for block in function.layout.block_iter() {
+        for succ in succs_of(function, block) {
+            preds.entry(succ).or_default().insert(block);
+        }
+    }
+    preds
+}
+
+fn set_target(
+    function: &mut Function<Ola>,
+    id: InstructionId<InstructionData>,
+    target: BasicBlockId,
+) {
+    let data = &mut function.data.inst_ref_mut(id).data;
+    let i = match data.opcode {
+        Opcode::JMPi | Opcode::JMPr => 0,
+        _ => 1,
+    };
+    data.operands[i].data = OperandData::Block(target);
+}
+
+/// End the blocks which fall through to the next one with a jump to it, so
+/// that the blocks can be moved around.
+fn make_fallthrough_explicit(function: &mut Function<Ola>) {
+    let blocks = function.layout.block_iter().collect::<Vec<_>>();
+    for block in blocks {
+        let Some(last) = function.layout.last_inst_of(block) else {
+            continue;
+        };
+        if is_terminator(&function.data.inst_ref(last).data) {
+            continue;
+        }
+        let next = function.layout.next_block_of(block).unwrap();
+        let jmp = function.data.create_inst(Instruction::new(
+            InstructionData {
+                opcode: Opcode::JMPr,
+                operands: vec![Operand::new(OperandData::Block(next))],
+            },
+            block,
+        ));
+        function.layout.append_inst(jmp, block);
+    }
+}
+
+/// Jump straight to where blocks which only jump elsewhere lead, and drop the
+/// conditional jumps to where the block goes anyway.
+fn thread_jumps(function: &mut Function<Ola>) {
+    let mut forward = FxHashMap::default();
+    for block in function.layout.block_iter() {
+        if function.layout.first_inst_of(block) != function.layout.last_inst_of(block) {
+            continue;
+        }
+        if let Some(jmp) = final_jump(function, block) {
+            forward.insert(
+                block,
+                jump_target(&function.data.inst_ref(jmp).data).unwrap(),
+            );
+        }
+    }
+    let destination = |mut block: BasicBlockId| {
+        let mut visited = FxHashSet::default();
+        while let Some(&next) = forward.get(&block) {
+            // a cycle of jumps never leaves it
+            if !visited.insert(block) {
+                break;
+            }
+            block = next;
+        }
+        block
+    };
+
+    let blocks = function.layout.block_iter().collect::<Vec<_>>();
+    for block in blocks {
+        let insts = function.layout.inst_iter(block).collect::<Vec<_>>();
+        for id in insts {
+            let data = &function.data.inst_ref(id).data;
+            let Some(target) = jump_target(data).or_else(|| cond_jump_target(data)) else {
+                continue;
+            };
+            let dest = destination(target);
+            if dest != target {
+                set_target(function, id, dest);
+            }
+        }
+
+        // `cjmp c L; jmp L` goes to `L` either way
+        let Some(jmp) = final_jump(function, block) else {
+            continue;
+        };
+        let target = jump_target(&function.data.inst_ref(jmp).data);
+        while let Some(prev) = function.layout.prev_inst_of(jmp) {
+            if cond_jump_target(&function.data.inst_ref(prev).data) != target {
+                break;
+            }
+            function.remove_inst(prev);
+        }
+    }
+}
+
+fn remove_unreachable_blocks(function: &mut Function<Ola>) {
+    let entry = function.layout.first_block.unwrap();
+    let mut reachable = FxHashSet::default();
+    let mut worklist = vec![entry];
+    while let Some(block) = worklist.pop() {
+        if reachable.insert(block) {
+            worklist.extend(succs_of(function, block));
+        }
+    }
+
+    let blocks = function.layout.block_iter().collect::<Vec<_>>();
+    for block in blocks {
+        if !reachable.contains(&block) {
+            function.layout.remove_block(block);
+        }
+    }
+}
+
+/// Append the blocks which are only entered from the block jumping to them to
+/// that block.
+fn merge_blocks(function: &mut Function<Ola>) {
+    let entry = function.layout.first_block.unwrap();
+    let mut preds = preds_of(function);
+
+    let blocks = function.layout.block_iter().collect::<Vec<_>>();
+    for block in blocks {
+        if !function.layout.is_block_inserted(block) {
+            continue;
+        }
+        loop {
+            let Some(jmp) = final_jump(function, block) else {
+                break;
+            };
+            let succ = jump_target(&function.data.inst_ref(jmp).data).unwrap();
+            if succs_of(function, block).len() != 1
+                || succ == block
+                || succ == entry
+                || preds[&succ].len() != 1
+                || function.layout.first_inst_of(succ).is_none()
+            {
+                break;
+            }
+
+            function.remove_inst(jmp);
+            let insts = function.layout.inst_iter(succ).collect::<Vec<_>>();
+            function.layout.move_insts_to_end(succ, block);
+            for id in insts {
+                function.data.inst_ref_mut(id).parent = block;
+            }
+            function.layout.remove_block(succ);
+            for next in succs_of(function, block) {
+                let next_preds = preds.get_mut(&next).unwrap();
+                next_preds.remove(&succ);
+                next_preds.insert(block);
+            }
+        }
+    }
+}
+
+/// Chain each block with the block its final jump goes to where possible, so
+/// that it falls through to it, and lay out the chains starting from the one
+/// of the entry block.
+fn place_blocks(function: &mut Function<Ola>) {
+    let blocks = function.layout.block_iter().collect::<Vec<_>>();
+    let entry = blocks[0];
+
+    let mut next_of = FxHashMap::default();
+    let mut prev_of = FxHashMap::default();
+    for &block in &blocks {
+        let Some(jmp) = final_jump(function, block) else {
+            continue;
+        };
+        let target = jump_target(&function.data.inst_ref(jmp).data).unwrap();
+        if target == entry || prev_of.contains_key(&target) {
+            continue;
+        }
+        // linking the end of a chain to its own head would close a cycle
+        let mut head = block;
+        while let Some(&prev) = prev_of.get(&head) {
+            head = prev;
+        }
+        if head == target {
+            continue;
+        }
+        next_of.insert(block, target);
+        prev_of.insert(target, block);
+    }
+
+    let mut order = vec![];
+    for &block in &blocks {
+        if prev_of.contains_key(&block) {
+            continue;
+        }
+        let mut cur = Some(block);
+        while let Some(block) = cur {
+            order.push(block);
+            cur = next_of.get(&block).copied();
+        }
+    }
+    function.layout.reorder_blocks(&order);
+}
+
+/// Remove the jumps to the block laid out right after, keeping the jump of a
+/// block which has nothing else, as empty blocks are emitted as a `ret`.
+fn remove_redundant_jumps(function: &mut Function<Ola>) {
+    let blocks = function.layout.block_iter().collect::<Vec<_>>();
+    for block in blocks {
+        let Some(jmp) = final_jump(function, block) else {
+            continue;
+        };
+        let next = function.layout.next_block_of(block);
+        if next.is_some()
+            && jump_target(&function.data.inst_ref(jmp).data) == next
+            && function.layout.first_inst_of(block) != Some(jmp)
+        {
+            function.remove_inst(jmp);
+        }
+    }
+}
+
+/// Record the edges of the new layout in the blocks
+fn update_cfg(function: &mut Function<Ola>) {
+    let blocks = function.layout.block_iter().collect::<Vec<_>>();
+    let mut succs = FxHashMap::default();
+    for &block in &blocks {
+        let mut block_succs = succs_of(function, block);
+        let falls_through = function.layout.last_inst_of(block).map_or(false, |last| {
+            !is_terminator(&function.data.inst_ref(last).data)
+        });
+        if falls_through {
+            block_succs.extend(function.layout.next_block_of(block));
+        }
+        succs.insert(block, block_succs);
+    }
+
+    for (_, block) in function.data.basic_blocks.iter_mut() {
+        block.preds.clear();
+        block.succs.clear();
+    }
+    for (block, block_succs) in succs {
+        for succ in block_succs {
+            function.data.block_ref_mut(block).succs.insert(succ);
+            function.data.block_ref_mut(succ).preds.insert(block);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::codegen::{
+        core::pass::OptLevel,
+        isa::ola::{compile, Ola},
+    };
+
+    #[test]
+    fn branch_layout_fallthrough() {
+        let asm = r#"
+define i32 @main(i32 %0) {
+entry:
+  %1 = icmp eq i32 %0, 1
+  br i1 %1, label %then, label %enif
+
+then:
+  ret i32 2
+
+enif:
+  ret i32 3
+}
+"#;
+        assert_eq!(
+            compile(asm, &Ola::default()),
+            "main:
+.LBL0_0:
+  eq r1 r1 1
+  cjmp r1 .LBL0_1
+  jmp .LBL0_2
+.LBL0_1:
+  mov r0 2
+  end
+.LBL0_2:
+  mov r0 3
+  end
+"
+        );
+        assert_eq!(
+            compile(asm, &Ola::new(OptLevel::Default)),
+            "main:
+.LBL0_0:
+  eq r1 r1 1
+  cjmp r1 .LBL0_1
+.LBL0_2:
+  mov r0 3
+  end
+.LBL0_1:
+  mov r0 2
+  end
+"
+        );
+    }
+
+    #[test]
+    fn branch_layout_loop() {
+        let asm = r#"
+define void @main(i64 %0) {
+entry:
+  br label %cond
+
+cond:
+  %i = phi i64 [ 0, %entry ], [ %next, %latch ]
+  %c = icmp ult i64 %i, 3
+  br i1 %c, label %body, label %done
+
+body:
+  %next = add i64 %i, 1
+  br label %latch
+
+latch:
+  br label %cond
+
+done:
+  ret void
+}
+"#;
+        assert_eq!(
+            compile(asm, &Ola::default()),
+            "main:
+.LBL0_0:
+  mov r2 0
+  jmp .LBL0_1
+.LBL0_1:
+  mov r1 3
+  gte r1 r1 r2
+  neq r3 r2 3
+  and r1 r1 r3
+  cjmp r1 .LBL0_2
+  jmp .LBL0_4
+.LBL0_2:
+  add r1 r2 1
+  jmp .LBL0_3
+.LBL0_3:
+  mov r2 r1
+  jmp .LBL0_1
+.LBL0_4:
+  end
+"
+        );
+        // the empty latch is merged into the body, and the exit is placed
+        // where the loop falls through to it
+        assert_eq!(
+            compile(asm, &Ola::new(OptLevel::Default)),
+            "main:
+.LBL0_0:
+  mov r2 0
+.LBL0_1:
+  mov r1 3
+  gte r1 r1 r2
+  neq r3 r2 3
+  and r1 r1 r3
+  cjmp r1 .LBL0_2
+.LBL0_4:
+  end
+.LBL0_2:
+  add r1 r2 1
+  mov r2 r1
+  jmp .LBL0_1
+"
+        );
+    }
+
+    #[test]
+    fn branch_layout_skips_jump_tables() {
+        let asm = r#"
+define i64 @select(i64 %0) {
+entry:
+  switch i64 %0, label %default [
+    i64 1, label %case0
+    i64 2, label %case1
+    i64 3, label %case2
+    i64 5, label %case3
+  ]
+
+case0:
+  ret i64 0
+
+case1:
+  ret i64 1
+
+case2:
+  ret i64 2
+
+case3:
+  ret i64 3
+
+default:
+  ret i64 100
+}
+"#;
+        // the jump to the default is kept after the range check, and the
+        // default stays last
+        assert_eq!(
+            compile(asm, &Ola::new(OptLevel::Default)),
+            "select:
+.LBL0_0:
+  gte r2 r1 6
+  cjmp r2 .LBL0_5
+  gte r2 r1 1
+  cjmp r2 .LBL0_6
+  jmp .LBL0_5
+.LBL0_6:
+  add r1 r1 18446744069414584320
+  mul r1 r1 2
+  mov r2 .LBL0_7
+  add r1 r2 r1
+  jmp r1
+.LBL0_7:
+  jmp .LBL0_1
+  jmp .LBL0_2
+  jmp .LBL0_3
+  jmp .LBL0_5
+  jmp .LBL0_4
+.LBL0_1:
+  mov r0 0
+  ret
+.LBL0_2:
+  mov r0 1
+  ret
+.LBL0_3:
+  mov r0 2
+  ret
+.LBL0_4:
+  mov r0 3
+  ret
+.LBL0_5:
+  mov r0 100
+  ret
+"
+        );
+    }
+}
@@ -4,6 +4,7 @@ use crate::codegen::{
         instruction::{InstructionData, Opcode, Operand, OperandData},
         Ola,
     },
+    lower::LoweringError,
     module::Module,
     register::Reg,
 };
@@ -12,12 +13,15 @@ use rustc_hash::FxHashMap;
 
 pub fn run_on_module(module: &mut Module<Ola>) -> Result<()> {
     for (_, func) in &mut module.functions {
-        run_on_function(func);
+        run_on_function(func).map_err(|e| LoweringError::Function {
+            name: func.ir.name().to_string(),
+            error: e.to_string(),
+        })?;
     }
     Ok(())
 }
 
-pub fn run_on_function(function: &mut Function<Ola>) {
+pub fn run_on_function(function: &mut Function<Ola>) -> Result<(), LoweringError> {
     let mut worklist = vec![];
     let mut map: FxHashMap<Reg, Vec<(OperandData, BasicBlockId)>> = FxHashMap::default();
 
@@ -28,10 +32,23 @@ pub fn run_on_function(function: &mut Function<Ola>) {
                 continue;
             }
             worklist.push(inst_id);
-            let output = *inst.data.operands[0].data.as_reg();
+            let output = *inst.data.operands[0].data.as_reg().ok_or_else(|| {
+                LoweringError::Unsupported(format!(
+                    "phi to {:?} is not supported",
+                    inst.data.operands[0].data
+                ))
+            })?;
             for i in (0..inst.data.operands[1..].len()).step_by(2) {
                 let val = inst.data.operands[1 + i].data.clone();
-                let block = *inst.data.operands[1 + i + 1].data.as_block();
+                let block = *inst.data.operands[1 + i + 1]
+                    .data
+                    .as_block()
+                    .ok_or_else(|| {
+                        LoweringError::Unsupported(format!(
+                            "phi from {:?} is not supported",
+                            inst.data.operands[1 + i + 1].data
+                        ))
+                    })?;
                 map.entry(output)
                     .or_insert_with(Vec::new)
                     .push((val, block));
@@ -63,7 +80,12 @@ pub fn run_on_function(function: &mut Function<Ola>) {
                     },
                     block,
                 ),
-                _ => todo!(),
+                e => {
+                    return Err(LoweringError::Unsupported(format!(
+                        "phi of {:?} is not supported",
+                        e
+                    )))
+                }
             };
             let copy = function.data.create_inst(copy);
             function.layout.insert_inst_before(maybe_term, copy, block);
@@ -73,6 +95,7 @@ pub fn run_on_function(function: &mut Function<Ola>) {
     for inst_id in worklist {
         function.remove_inst(inst_id);
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -2,6 +2,7 @@ use crate::codegen::core::ir::types;
 use crate::codegen::{
     function::{instruction::TargetInst, Function},
     isa::ola::{instruction::Opcode, instruction::OperandData, register::GR, Ola},
+    lower::LoweringError,
     module::Module,
 };
 use anyhow::Result;
@@ -9,12 +10,15 @@ use debug_print::debug_println;
 
 pub fn run_on_module(module: &mut Module<Ola>) -> Result<()> {
     for (_, func) in &mut module.functions {
-        run_on_function(func);
+        run_on_function(func).map_err(|e| LoweringError::Function {
+            name: func.ir.name().to_string(),
+            error: e.to_string(),
+        })?;
     }
     Ok(())
 }
 
-pub fn run_on_function(function: &mut Function<Ola>) {
+pub fn run_on_function(function: &mut Function<Ola>) -> Result<(), LoweringError> {
     let mut worklist = vec![];
     let mut call = false;
 
@@ -52,25 +56,20 @@ pub fn run_on_function(function: &mut Function<Ola>) {
         let mut i = 0;
         let len = inst.data.operands.len();
 
-        if inst.data.opcode == Opcode::ADDri
-            && matches!(&inst.data.operands[len - 1].data, &OperandData::Slot(_))
+        if let (Opcode::ADDri, OperandData::Slot(slot)) =
+            (inst.data.opcode, &inst.data.operands[len - 1].data)
         {
             debug_println!("add slot data {:?}", inst.data.operands[len - 1].data);
-            match &inst.data.operands[len - 1].data {
-                OperandData::Slot(slot) => {
-                    let off = function.slots.get(*slot).offset;
-                    let mut size = off as i32 / 4;
-                    if call {
-                        size = -size - 2;
-                    }
-                    if size > 0 {
-                        size = -size;
-                    }
-                    inst.data.operands[len - 1].data = OperandData::Int32(size);
-                    function.data.instructions[inst_id] = inst;
-                }
-                _ => todo!(),
+            let off = function.slots.get(*slot).offset;
+            let mut size = off as i32 / 4;
+            if call {
+                size = -size - 2;
+            }
+            if size > 0 {
+                size = -size;
             }
+            inst.data.operands[len - 1].data = OperandData::Int32(size);
+            function.data.instructions[inst_id] = inst;
             continue;
         }
 
@@ -172,7 +171,14 @@ pub fn run_on_function(function: &mut Function<Ola>) {
                     mem[3].data = OperandData::Reg(GR::R9.into());
                     debug_println!("slot pattern 4 with slot+reg");
                 }
-                e => todo!("{:?}", e),
+                // Memory operands without a slot have nothing to eliminate
+                (OperandData::None, _) => {}
+                (slot, offset) => {
+                    return Err(LoweringError::Unsupported(format!(
+                        "offset {:?} from {:?} is not supported",
+                        offset, slot
+                    )))
+                }
             }
 
             i += 6;
@@ -180,4 +186,5 @@ pub fn run_on_function(function: &mut Function<Ola>) {
 
         function.data.instructions[inst_id] = inst;
     }
+    Ok(())
 }
@@ -1,5 +1,8 @@
 pub mod add_peephole;
+pub mod branch_layout;
 pub mod eliminate_phi;
 pub mod eliminate_slot;
+pub mod peephole;
 pub mod pro_epi_inserter;
 pub mod simple_reg_coalescing;
+pub mod slot_coloring;
@@ -0,0 +1,918 @@
+use crate::codegen::{
+    core::pass::OptLevel,
+    function::{basic_block::BasicBlockId, instruction::InstructionId, Function},
+    isa::{
+        ola::{
+            instruction::{InstructionData, Opcode, Operand, OperandData},
+            register::GR,
+            Ola,
+        },
+        TargetIsa,
+    },
+    module::Module,
+    register::Reg,
+};
+use anyhow::Result;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// The order of the field the registers hold elements of
+const FIELD_ORDER: u64 = 0xFFFF_FFFF_0000_0001;
+
+/// A function is rewritten until nothing matches, or this many times
+const MAX_ROUNDS: usize = 4;
+
+/// A rewrite of the instructions starting at some point of a block. The
+/// `opcodes` are the ones of the instructions in order, each one of a set, and
+/// `rewrite` decides whether and how to rewrite them.
+struct Pattern {
+    opcodes: &'static [&'static [Opcode]],
+    rewrite: fn(&Cursor) -> Option<Rewrite>,
+}
+
+/// The instructions to replace and to remove, relative to the first one of
+/// the pattern
+#[derive(Default)]
+struct Rewrite {
+    replace: Vec<(usize, InstructionData)>,
+    remove: Vec<usize>,
+}
+
+const ALU: &[Opcode] = &[
+    Opcode::ADDri,
+    Opcode::ADDrr,
+    Opcode::MULri,
+    Opcode::MULrr,
+    Opcode::ANDri,
+    Opcode::ANDrr,
+    Opcode::ORri,
+    Opcode::ORrr,
+    Opcode::XORri,
+    Opcode::XORrr,
+    Opcode::EQri,
+    Opcode::EQrr,
+    Opcode::NEQ,
+    Opcode::GTE,
+];
+
+/// The instructions which do nothing but define their first operand
+const PURE: &[Opcode] = &[
+    Opcode::ADDri,
+    Opcode::ADDrr,
+    Opcode::MULri,
+    Opcode::MULrr,
+    Opcode::ANDri,
+    Opcode::ANDrr,
+    Opcode::ORri,
+    Opcode::ORrr,
+    Opcode::XORri,
+    Opcode::XORrr,
+    Opcode::EQri,
+    Opcode::EQrr,
+    Opcode::NEQ,
+    Opcode::GTE,
+    Opcode::NOT,
+    Opcode::MOVri,
+    Opcode::MOVrr,
+    Opcode::MLOADi,
+    Opcode::MLOADr,
+];
+
+const CJMP: &[Opcode] = &[Opcode::CJMPi, Opcode::CJMPr];
+const MLOAD: &[Opcode] = &[Opcode::MLOADi, Opcode::MLOADr];
+
+/// The patterns in the order they are tried at each instruction
+const PATTERNS: &[Pattern] = &[
+    Pattern {
+        opcodes: &[MLOAD],
+        rewrite: forward_stored_value,
+    },
+    Pattern {
+        opcodes: &[&[Opcode::MOVri]],
+        rewrite: remove_redundant_constant,
+    },
+    Pattern {
+        opcodes: &[&[Opcode::MOVri], &[Opcode::EQrr], CJMP],
+        rewrite: fuse_constant_eq_cjmp,
+    },
+    Pattern {
+        opcodes: &[
+            &[Opcode::NOT],
+            &[Opcode::ADDri],
+            &[Opcode::ADDri, Opcode::ADDrr],
+        ],
+        rewrite: fold_negation,
+    },
+    Pattern {
+        opcodes: &[PURE],
+        rewrite: remove_dead_definition,
+    },
+];
+
+/// Run the peephole optimizer over every function when optimizing.
+pub fn run_on_module(module: &mut Module<Ola>) -> Result<()> {
+    if module.isa.opt_level() == OptLevel::None {
+        return Ok(());
+    }
+    for (_, func) in &mut module.functions {
+        run_on_function(func);
+    }
+    Ok(())
+}
+
+pub fn run_on_function(function: &mut Function<Ola>) {
+    if function.is_declaration {
+        return;
+    }
+
+    for _ in 0..MAX_ROUNDS {
+        let live_out = live_out(function);
+        let blocks = function.layout.block_iter().collect::<Vec<_>>();
+        let mut changed = false;
+        for block in blocks {
+            changed |= run_on_block(function, block, &live_out[&block]);
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+fn run_on_block(function: &mut Function<Ola>, block: BasicBlockId, live_out: &Regs) -> bool {
+    let mut insts = function.layout.inst_iter(block).collect::<Vec<_>>();
+    let mut facts = Facts::default();
+    let mut changed = false;
+
+    let mut i = 0;
+    while i < insts.len() {
+        let rewrite = PATTERNS.iter().find_map(|pattern| {
+            let matches = pattern.opcodes.len() <= insts.len() - i
+                && pattern
+                    .opcodes
+                    .iter()
+                    .zip(&insts[i..])
+                    .all(|(opcodes, &id)| {
+                        opcodes.contains(&function.data.inst_ref(id).data.opcode)
+                    });
+            if !matches {
+                return None;
+            }
+            (pattern.rewrite)(&Cursor {
+                function,
+                insts: &insts[i..],
+                facts: &facts,
+                live_out,
+            })
+        });
+
+        // The rewritten instructions are looked at again, as they may match
+        // another pattern
+        if let Some(rewrite) = rewrite {
+            for (n, data) in rewrite.replace {
+                function.data.inst_ref_mut(insts[i + n]).data = data;
+            }
+            let mut remove = rewrite.remove;
+            remove.sort_unstable();
+            for n in remove.into_iter().rev() {
+                function.remove_inst(insts.remove(i + n));
+            }
+            changed = true;
+            continue;
+        }
+
+        facts.update(&function.data.inst_ref(insts[i]).data);
+        i += 1;
+    }
+
+    changed
+}
+
+type Regs = FxHashSet<Reg>;
+
+fn all_regs() -> Regs {
+    [
+        GR::R0,
+        GR::R1,
+        GR::R2,
+        GR::R3,
+        GR::R4,
+        GR::R5,
+        GR::R6,
+        GR::R7,
+        GR::R8,
+        GR::R9,
+    ]
+    .into_iter()
+    .map(Reg::from)
+    .collect()
+}
+
+/// The registers an instruction reads and writes. The effects of the
+/// instructions which are not `known` are not modelled: they may read every
+/// register and memory, and write memory.
+struct Effects {
+    known: bool,
+    defs: Vec<Reg>,
+    uses: Vec<Reg>,
+}
+
+fn reg_of(operand: &Operand) -> Option<Reg> {
+    match operand.data {
+        OperandData::Reg(reg) => Some(reg),
+        _ => None,
+    }
+}
+
+fn regs_of(operands: &[Operand]) -> Vec<Reg> {
+    operands.iter().filter_map(reg_of).collect()
+}
+
+/// Whether `data` stores to memory, with the address as its first operands
+fn is_store(data: &InstructionData) -> bool {
+    matches!(data.opcode, Opcode::MSTOREi | Opcode::MSTOREr)
+        && data.operands.len() == 8
+        && matches!(data.operands[0].data, OperandData::MemStart)
+}
+
+/// Whether `data` loads from memory into its first operand
+fn is_load(data: &InstructionData) -> bool {
+    MLOAD.contains(&data.opcode)
+        && data.operands.len() == 8
+        && matches!(data.operands[1].data, OperandData::MemStart)
+}
+
+fn effects(data: &InstructionData) -> Effects {
+    let ops = &data.operands;
+    let defines_first = match data.opcode {
+        _ if ALU.contains(&data.opcode) => ops.len() == 3,
+        Opcode::MOVri | Opcode::MOVrr | Opcode::NOT => ops.len() == 2,
+        _ => is_load(data),
+    };
+    match ops.first().and_then(reg_of) {
+        Some(def) if defines_first => {
+            return Effects {
+                known: true,
+                defs: vec![def],
+                uses: regs_of(&ops[1..]),
+            }
+        }
+        _ => {}
+    }
+
+    let known = is_store(data)
+        || matches!(
+            data.opcode,
+            Opcode::ASSERTri
+                | Opcode::ASSERTrr
+                | Opcode::RANGECHECK
+                | Opcode::JMPi
+                | Opcode::JMPr
+                | Opcode::CJMPi
+                | Opcode::CJMPr
+                | Opcode::RET
+        );
+    Effects {
+        known,
+        defs: vec![],
+        uses: regs_of(ops),
+    }
+}
+
+/// The registers a return reads: those of the returned value, the
+/// callee-saved ones and the frame pointer
+fn live_at_return(function: &Function<Ola>) -> Regs {
+    let size = function
+        .isa
+        .data_layout()
+        .get_size_of(&function.types, function.ir.result_ty);
+    [GR::R0, GR::R1, GR::R2, GR::R3]
+        .into_iter()
+        .take((size + 3) / 4)
+        .chain([GR::R5, GR::R6, GR::R7, GR::R8, GR::R9])
+        .map(Reg::from)
+        .collect()
+}
+
+/// The block `data` may jump to
+fn jump_target(data: &InstructionData) -> Option<&OperandData> {
+    let target = match data.opcode {
+        Opcode::JMPi | Opcode::JMPr => data.operands.first(),
+        Opcode::CJMPi | Opcode::CJMPr => data.operands.get(1),
+        _ => None,
+    };
+    target.map(|op| &op.data)
+}
+
+/// The registers which are live at the end of each block
+fn live_out(function: &Function<Ola>) -> FxHashMap<BasicBlockId, Regs> {
+    let layout = &function.layout;
+    let blocks = layout.block_iter().collect::<Vec<_>>();
+
+    // Blocks which may leave the function keep the registers read once it is
+    // left, which are all of them but after a return
+    let mut succs = FxHashMap::default();
+    let mut exits = FxHashMap::default();
+    for &block in &blocks {
+        let mut block_succs = vec![];
+        for id in layout.inst_iter(block) {
+            match jump_target(&function.data.inst_ref(id).data) {
+                Some(OperandData::Block(target)) => block_succs.push(*target),
+                Some(_) => {
+                    exits.insert(block, all_regs());
+                }
+                None => {}
+            }
+        }
+        let last = layout
+            .last_inst_of(block)
+            .map(|last| function.data.inst_ref(last).data.opcode);
+        match last {
+            Some(Opcode::RET) => {
+                exits.insert(block, live_at_return(function));
+            }
+            Some(Opcode::JMPi | Opcode::JMPr) => {}
+            _ => match layout.next_block_of(block) {
+                Some(next) if last.is_some() => block_succs.push(next),
+                _ => {
+                    exits.insert(block, all_regs());
+                }
+            },
+        }
+        succs.insert(block, block_succs);
+    }
+
+    let mut live_in: FxHashMap<BasicBlockId, Regs> = FxHashMap::default();
+    let mut live_out: FxHashMap<BasicBlockId, Regs> = FxHashMap::default();
+    loop {
+        let mut changed = false;
+        for &block in blocks.iter().rev() {
+            let mut live = exits.get(&block).cloned().unwrap_or_default();
+            for succ in &succs[&block] {
+                live.extend(live_in.get(succ).into_iter().flatten());
+            }
+            live_out.insert(block, live.clone());
+
+            for id in layout
+                .inst_iter(block)
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+            {
+                let data = &function.data.inst_ref(id).data;
+                let effects = effects(data);
+                if !effects.known {
+                    live = all_regs();
+                    continue;
+                }
+                for def in &effects.defs {
+                    live.remove(def);
+                }
+                live.extend(effects.uses);
+                // What a jump in the middle of the block leads to is read
+                // whatever the rest of the block defines
+                if let Some(OperandData::Block(target)) = jump_target(data) {
+                    live.extend(live_in.get(target).into_iter().flatten());
+                }
+            }
+            if live_in.get(&block) != Some(&live) {
+                live_in.insert(block, live);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    live_out
+}
+
+/// What is known about the registers and the memory at a point of a block
+#[derive(Default)]
+struct Facts {
+    /// The constants the registers are known to hold
+    consts: FxHashMap<Reg, i64>,
+    /// The addresses which are known to hold the value of a register
+    stores: Vec<(Vec<OperandData>, Reg)>,
+}
+
+impl Facts {
+    fn update(&mut self, data: &InstructionData) {
+        let effects = effects(data);
+        if !effects.known {
+            *self = Facts::default();
+            return;
+        }
+
+        if is_store(data) {
+            self.stores.clear();
+            if let Some(value) = reg_of(&data.operands[7]) {
+                self.stores.push((
+                    data.operands[1..7]
+                        .iter()
+                        .map(|op| op.data.clone())
+                        .collect(),
+                    value,
+                ));
+            }
+        }
+
+        for def in &effects.defs {
+            self.consts.remove(def);
+            self.stores.retain(|(addr, value)| {
+                value != def
+                    && !addr
+                        .iter()
+                        .any(|op| matches!(op, OperandData::Reg(r) if r == def))
+            });
+        }
+
+        match data.opcode {
+            Opcode::MOVri => {
+                if let Some(imm) = data.operands[1].data.sext_as_i64() {
+                    self.consts.insert(effects.defs[0], imm);
+                }
+            }
+            Opcode::MOVrr => {
+                let konst =
+                    reg_of(&data.operands[1]).and_then(|src| self.consts.get(&src).copied());
+                if let Some(imm) = konst {
+                    self.consts.insert(effects.defs[0], imm);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The register holding the value stored at the address of a load
+    fn stored_value(&self, addr: &[Operand]) -> Option<Reg> {
+        self.stores.iter().find_map(|(stored, value)| {
+            let same = stored
+                .iter()
+                .zip(addr)
+                .all(|(a, b)| same_operand(a, &b.data));
+            same.then_some(*value)
+        })
+    }
+}
+
+fn same_operand(a: &OperandData, b: &OperandData) -> bool {
+    match (a, b) {
+        (OperandData::Reg(a), OperandData::Reg(b)) => a == b,
+        (OperandData::Label(a), OperandData::Label(b)) => a == b,
+        (OperandData::None, OperandData::None) => true,
+        _ => match (a.sext_as_i64(), b.sext_as_i64()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        },
+    }
+}
+
+/// The instructions a pattern matched, followed by the rest of the block
+struct Cursor<'a, 'b> {
+    function: &'a Function<'b, Ola>,
+    insts: &'a [InstructionId<InstructionData>],
+    facts: &'a Facts,
+    live_out: &'a Regs,
+}
+
+impl Cursor<'_, '_> {
+    fn inst(&self, n: usize) -> &InstructionData {
+        &self.function.data.inst_ref(self.insts[n]).data
+    }
+
+    fn reg(&self, n: usize, operand: usize) -> Option<Reg> {
+        self.inst(n).operands.get(operand).and_then(reg_of)
+    }
+
+    /// Whether the value of `reg` after the `n`th instruction is never read
+    fn dead_after(&self, reg: Reg, n: usize) -> bool {
+        for &id in &self.insts[n + 1..] {
+            let data = &self.function.data.inst_ref(id).data;
+            let effects = effects(data);
+            if !effects.known || effects.uses.contains(&reg) {
+                return false;
+            }
+            // A jump out of the middle of the block may lead to a read
+            if jump_target(data).is_some() && self.live_out.contains(&reg) {
+                return false;
+            }
+            if effects.defs.contains(&reg) {
+                return true;
+            }
+        }
+        !self.live_out.contains(&reg)
+    }
+}
+
+/// `mstore [a] r; ...; mload d [a]` loads what is still in `r`
+fn forward_stored_value(cursor: &Cursor) -> Option<Rewrite> {
+    let load = cursor.inst(0);
+    if !is_load(load) {
+        return None;
+    }
+    let dest = cursor.reg(0, 0)?;
+    let value = cursor.facts.stored_value(&load.operands[2..8])?;
+    if dest == value {
+        return Some(Rewrite {
+            remove: vec![0],
+            ..Rewrite::default()
+        });
+    }
+    Some(Rewrite {
+        replace: vec![(
+            0,
+            InstructionData {
+                opcode: Opcode::MOVrr,
+                operands: vec![Operand::output(dest.into()), Operand::input(value.into())],
+            },
+        )],
+        ..Rewrite::default()
+    })
+}
+
+/// `mov r c` where `r` already holds `c`
+fn remove_redundant_constant(cursor: &Cursor) -> Option<Rewrite> {
+    let dest = cursor.reg(0, 0)?;
+    let imm = cursor.inst(0).operands.get(1)?.data.sext_as_i64()?;
+    (cursor.facts.consts.get(&dest) == Some(&imm)).then(|| Rewrite {
+        remove: vec![0],
+        ..Rewrite::default()
+    })
+}
+
+/// `mov t k; eq c a t; cjmp c l` compares with the constant directly, as
+/// does `mov t k; eq c t a; cjmp c l`
+fn fuse_constant_eq_cjmp(cursor: &Cursor) -> Option<Rewrite> {
+    let tmp = cursor.reg(0, 0)?;
+    let imm = cursor.inst(0).operands.get(1)?.data.sext_as_i64()?;
+    let cond = cursor.reg(1, 0)?;
+    let other = match (cursor.reg(1, 1)?, cursor.reg(1, 2)?) {
+        (lhs, rhs) if rhs == tmp && lhs != tmp => lhs,
+        (lhs, rhs) if lhs == tmp && rhs != tmp => rhs,
+        _ => return None,
+    };
+    if cursor.reg(2, 0)? != cond {
+        return None;
+    }
+    if tmp != cond && !cursor.dead_after(tmp, 1) {
+        return None;
+    }
+    let mut eq = cursor.inst(1).clone();
+    eq.opcode = Opcode::EQri;
+    eq.operands[1] = Operand::input(other.into());
+    eq.operands[2] = Operand::new(OperandData::Int64(imm));
+    Some(Rewrite {
+        replace: vec![(1, eq)],
+        remove: vec![0],
+    })
+}
+
+/// `not t k; add t t 1; add d a t` adds the negation of `k`, which is known
+fn fold_negation(cursor: &Cursor) -> Option<Rewrite> {
+    let tmp = cursor.reg(0, 0)?;
+    let imm = cursor.inst(0).operands.get(1)?.data.sext_as_i64()? as u64;
+    let one = cursor.inst(1).operands.get(2)?.data.sext_as_i64()?;
+    if cursor.reg(1, 0)? != tmp || cursor.reg(1, 1)? != tmp || one != 1 || imm >= FIELD_ORDER {
+        return None;
+    }
+    let dest = cursor.reg(2, 0)?;
+    if cursor.reg(2, 2)? != tmp || cursor.reg(2, 1)? == tmp {
+        return None;
+    }
+    if tmp != dest && !cursor.dead_after(tmp, 2) {
+        return None;
+    }
+
+    let neg = if imm == 0 { 0 } else { FIELD_ORDER - imm };
+    let mut add = cursor.inst(2).clone();
+    add.opcode = Opcode::ADDri;
+    add.operands[2] = Operand::new(OperandData::Int64(neg as i64));
+    Some(Rewrite {
+        replace: vec![(2, add)],
+        remove: vec![0, 1],
+    })
+}
+
+/// An instruction whose result is never read
+fn remove_dead_definition(cursor: &Cursor) -> Option<Rewrite> {
+    let first = cursor.inst(0).operands.first()?;
+    if !first.output {
+        return None;
+    }
+    let effects = effects(cursor.inst(0));
+    let &[def] = &effects.defs[..] else {
+        return None;
+    };
+    cursor.dead_after(def, 0).then(|| Rewrite {
+        remove: vec![0],
+        ..Rewrite::default()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::codegen::{
+        core::pass::OptLevel,
+        isa::ola::{compile, Ola},
+    };
+
+    #[test]
+    fn peephole_store_load_forwarding() {
+        let source = include_str!("../../../../../tests/codegen/regression/store.ll");
+        let before = "test_store_dst_gep_arg_inst:
+.LBL0_0:
+  add r9 r9 1
+  mov r3 100
+  mstore [r9,-1] r3
+  mload r3 [r9,-1]
+  mload r1 [r1,r3]
+  mstore [r2,r3] r1
+  add r9 r9 -1
+  ret
+test_store_dst_arg:
+.LBL1_0:
+  mov r2 200
+  mstore [r1] r2
+  ret
+";
+        assert_eq!(compile(source, &Ola::default()), before);
+        assert_eq!(
+            compile(source, &Ola::new(OptLevel::Default)),
+            before.replace("  mload r3 [r9,-1]\n", "")
+        );
+
+        // The value is loaded back as a pointer, so the IR passes do not
+        // forward it
+        let source = r#"
+define i64 @test_store_forwarding(ptr %0, i64 %1) {
+entry:
+  store i64 %1, ptr %0, align 4
+  %2 = load ptr, ptr %0, align 4
+  %3 = load i64, ptr %2, align 4
+  ret i64 %3
+}
+"#;
+        assert_eq!(
+            compile(source, &Ola::default()),
+            "test_store_forwarding:
+.LBL0_0:
+  mstore [r1] r2
+  mload r1 [r1]
+  mload r0 [r1]
+  ret
+"
+        );
+        assert_eq!(
+            compile(source, &Ola::new(OptLevel::Default)),
+            "test_store_forwarding:
+.LBL0_0:
+  mstore [r1] r2
+  mov r1 r2
+  mload r0 [r1]
+  ret
+"
+        );
+    }
+
+    #[test]
+    fn peephole_redundant_constant() {
+        let source = r#"
+define void @main(ptr %0) {
+entry:
+  store i64 5, ptr %0
+  %1 = getelementptr i64, ptr %0, i64 1
+  store i64 5, ptr %1
+  ret void
+}
+"#;
+        assert_eq!(
+            compile(source, &Ola::default()),
+            "main:
+.LBL0_0:
+  mov r2 5
+  mstore [r1] r2
+  mov r2 5
+  mstore [r1,+1] r2
+  end
+"
+        );
+        assert_eq!(
+            compile(source, &Ola::new(OptLevel::Default)),
+            "main:
+.LBL0_0:
+  mov r2 5
+  mstore [r1] r2
+  mstore [r1,+1] r2
+  end
+"
+        );
+    }
+
+    #[test]
+    fn peephole_constant_eq_cjmp() {
+        let source = r#"
+define i64 @main(i64 %0) {
+entry:
+  %1 = icmp eq i64 7, %0
+  br i1 %1, label %yes, label %no
+yes:
+  ret i64 2
+no:
+  ret i64 3
+}
+"#;
+        assert_eq!(
+            compile(source, &Ola::default()),
+            "main:
+.LBL0_0:
+  mov r2 7
+  eq r1 r2 r1
+  cjmp r1 .LBL0_1
+  jmp .LBL0_2
+.LBL0_1:
+  mov r0 2
+  end
+.LBL0_2:
+  mov r0 3
+  end
+"
+        );
+        assert_eq!(
+            compile(source, &Ola::new(OptLevel::Default)),
+            "main:
+.LBL0_0:
+  eq r1 r1 7
+  cjmp r1 .LBL0_1
+.LBL0_2:
+  mov r0 3
+  end
+.LBL0_1:
+  mov r0 2
+  end
+"
+        );
+    }
+
+    #[test]
+    fn peephole_constant_eq_used_after_cjmp() {
+        // The result of the comparison is copied to branch on and returned
+        let source = r#"
+define i64 @main(i64 %0) {
+entry:
+  %1 = icmp eq i64 7, %0
+  %2 = zext i1 %1 to i64
+  %3 = trunc i64 %2 to i1
+  br i1 %3, label %yes, label %no
+yes:
+  ret i64 %2
+no:
+  ret i64 3
+}
+"#;
+        assert_eq!(
+            compile(source, &Ola::new(OptLevel::Default)),
+            "main:
+.LBL0_0:
+  mov r2 7
+  eq r0 r2 r1
+  mov r1 r0
+  cjmp r1 .LBL0_1
+.LBL0_2:
+  mov r0 3
+  end
+.LBL0_1:
+  end
+"
+        );
+
+        // The call may read the register of the constant
+        let source = r#"
+define i64 @main(i64 %0) {
+entry:
+  %1 = icmp eq i64 7, %0
+  br i1 %1, label %yes, label %no
+yes:
+  %2 = call i64 @f(i64 1)
+  ret i64 %2
+no:
+  ret i64 3
+}
+
+define i64 @f(i64 %0) {
+entry:
+  ret i64 %0
+}
+"#;
+        assert_eq!(
+            compile(source, &Ola::new(OptLevel::Default)),
+            "main:
+.LBL0_0:
+  add r9 r9 2
+  mstore [r9,-2] r9
+  mov r2 7
+  eq r1 r2 r1
+  cjmp r1 .LBL0_1
+.LBL0_2:
+  mov r0 3
+  add r9 r9 -2
+  end
+.LBL0_1:
+  mov r1 1
+  call f
+  add r9 r9 -2
+  end
+f:
+.LBL1_0:
+  mov r0 r1
+  ret
+"
+        );
+    }
+
+    #[test]
+    fn peephole_negated_constant() {
+        let source = r#"
+define i64 @main(i64 %0) {
+entry:
+  %1 = sub i64 %0, 5
+  %2 = sub i64 %1, 3
+  ret i64 %2
+}
+"#;
+        assert_eq!(
+            compile(source, &Ola::default()),
+            "main:
+.LBL0_0:
+  not r7 5
+  add r7 r7 1
+  add r2 r1 r7
+  not r7 3
+  add r7 r7 1
+  add r0 r2 r7
+  end
+"
+        );
+        // The second negation is kept, as `r7` is callee-saved
+        assert_eq!(
+            compile(source, &Ola::new(OptLevel::Default)),
+            "main:
+.LBL0_0:
+  add r2 r1 18446744069414584316
+  not r7 3
+  add r7 r7 1
+  add r0 r2 r7
+  end
+"
+        );
+    }
+
+    #[test]
+    fn peephole_dead_definition() {
+        let source = r#"
+define i64 @main(i64 %0, i64 %1) {
+entry:
+  br label %loop
+loop:
+  %i = phi i64 [ 0, %entry ], [ %n, %loop ]
+  %n = add i64 %i, 1
+  %c = icmp eq i64 %n, %1
+  br i1 %c, label %end, label %loop
+end:
+  ret i64 %n
+}
+"#;
+        assert_eq!(
+            compile(source, &Ola::default()),
+            "main:
+.LBL0_0:
+  mov r1 r2
+  mov r2 0
+  jmp .LBL0_1
+.LBL0_1:
+  add r0 r2 1
+  add r0 r2 1
+  eq r2 r0 r1
+  cjmp r2 .LBL0_2
+  mov r2 r0
+  jmp .LBL0_1
+.LBL0_2:
+  end
+"
+        );
+        assert_eq!(
+            compile(source, &Ola::new(OptLevel::Default)),
+            "main:
+.LBL0_0:
+  mov r1 r2
+  mov r2 0
+.LBL0_1:
+  add r0 r2 1
+  eq r2 r0 r1
+  cjmp r2 .LBL0_2
+  mov r2 r0
+  jmp .LBL0_1
+.LBL0_2:
+  end
+"
+        );
+    }
+}
@@ -92,7 +92,8 @@ pub fn run_on_function(function: &mut Function<Ola>) {
     for block in function.layout.block_iter() {
         for inst_id in function.layout.inst_iter(block) {
             let inst = function.data.inst_ref(inst_id);
-            if !matches!(inst.data.opcode, Opcode::RET) {
+            // a tail call leaves the function as well
+            if !matches!(inst.data.opcode, Opcode::RET) && !inst.data.is_tail_call() {
                 continue;
             }
             epilogues.push((block, inst_id));
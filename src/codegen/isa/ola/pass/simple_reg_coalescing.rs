@@ -19,14 +19,16 @@ pub fn run_on_function(function: &mut Function<Ola>) {
     for block_id in function.layout.block_iter() {
         for inst_id in function.layout.inst_iter(block_id) {
             let inst = function.data.inst_ref(inst_id);
-            match inst.data.opcode {
-                Opcode::MOVrr | Opcode::MOVrr
-                    if RegInfo::to_reg_unit(*inst.data.operands[0].data.as_reg())
-                        == RegInfo::to_reg_unit(*inst.data.operands[1].data.as_reg()) =>
-                {
+            if inst.data.opcode != Opcode::MOVrr {
+                continue;
+            }
+            if let (Some(dst), Some(src)) = (
+                inst.data.operands[0].data.as_reg(),
+                inst.data.operands[1].data.as_reg(),
+            ) {
+                if RegInfo::to_reg_unit(*dst) == RegInfo::to_reg_unit(*src) {
                     worklist.push(inst_id)
                 }
-                _ => {}
             }
         }
     }
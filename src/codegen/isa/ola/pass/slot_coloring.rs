@@ -0,0 +1,420 @@
+use crate::codegen::{
+    core::pass::OptLevel,
+    function::{
+        basic_block::BasicBlockId, instruction::InstructionId, slot::SlotId, Function,
+    },
+    isa::ola::{
+        instruction::{InstructionData, Opcode, OperandData},
+        Ola,
+    },
+    module::Module,
+    pass::liveness::{LiveRange, LiveSegment, ProgramPoint},
+};
+use anyhow::Result;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// Share the frame slots of every function between spilled registers and
+/// allocas whose values are never needed at the same time.
+pub fn run_on_module(module: &mut Module<Ola>) -> Result<()> {
+    if module.isa.opt_level() == OptLevel::None {
+        return Ok(());
+    }
+    for (_, func) in &mut module.functions {
+        run_on_function(func);
+    }
+    Ok(())
+}
+
+pub fn run_on_function(function: &mut Function<Ola>) {
+    if function.is_declaration {
+        return;
+    }
+
+    let (accesses, escaped) = collect_accesses(function);
+    let colorable = function
+        .slots
+        .arena
+        .iter()
+        .map(|(id, _)| id)
+        .filter(|id| !escaped.contains(id))
+        .collect::<FxHashSet<_>>();
+    if colorable.len() < 2 {
+        return;
+    }
+
+    let ranges = live_ranges(function, &accesses, &colorable);
+    let mut order = colorable.into_iter().collect::<Vec<_>>();
+    order.sort_by_key(|slot| (ranges[slot].first_seg().map(|seg| seg.start), slot.index()));
+
+    // Each slot goes to the first slot of the same shape whose range it does
+    // not overlap
+    let mut colors: Vec<(SlotId, LiveRange)> = vec![];
+    let mut color_of = FxHashMap::default();
+    for slot in order {
+        let range = &ranges[&slot];
+        let shape = |id: SlotId| {
+            let slot = function.slots.get(id);
+            (slot.size, slot.align)
+        };
+        match colors
+            .iter_mut()
+            .find(|(rep, lr)| shape(*rep) == shape(slot) && !lr.interfere(range))
+        {
+            Some((rep, lr)) => {
+                lr.merge(range);
+                color_of.insert(slot, *rep);
+            }
+            None => colors.push((slot, range.clone())),
+        }
+    }
+    if color_of.is_empty() {
+        return;
+    }
+
+    for block in function.layout.block_iter() {
+        for id in function.layout.inst_iter(block) {
+            for op in &mut function.data.instructions[id].data.operands {
+                if let OperandData::Slot(slot) = op.data
+                    && let Some(&rep) = color_of.get(&slot)
+                {
+                    op.data = OperandData::Slot(rep);
+                }
+            }
+        }
+    }
+    for &slot in color_of.keys() {
+        function.slots.remove_slot(slot);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Access {
+    Load(SlotId),
+    /// Whether the store overwrites the whole slot
+    Store(SlotId, bool),
+}
+
+/// The loads and stores of each instruction to slots, and the slots which are
+/// accessed in other ways, such as by taking their address
+fn collect_accesses(
+    function: &Function<Ola>,
+) -> (
+    FxHashMap<InstructionId<InstructionData>, Vec<Access>>,
+    FxHashSet<SlotId>,
+) {
+    let mut accesses = FxHashMap::default();
+    let mut escaped = FxHashSet::default();
+    for block in function.layout.block_iter() {
+        for id in function.layout.inst_iter(block) {
+            let data = &function.data.inst_ref(id).data;
+            let mut inst_accesses = vec![];
+            for (i, op) in data.operands.iter().enumerate() {
+                let OperandData::Slot(slot) = op.data else {
+                    continue;
+                };
+                match access(function, data, i, slot) {
+                    Some(access) => inst_accesses.push(access),
+                    None => {
+                        escaped.insert(slot);
+                    }
+                }
+            }
+            if !inst_accesses.is_empty() {
+                accesses.insert(id, inst_accesses);
+            }
+        }
+    }
+    (accesses, escaped)
+}
+
+/// How the `i`th operand of `data` accesses `slot`. Memory operands are the
+/// label, slot, offset and registers following `MemStart`, and only those
+/// with a constant offset are known to stay within the slot.
+fn access(
+    function: &Function<Ola>,
+    data: &InstructionData,
+    i: usize,
+    slot: SlotId,
+) -> Option<Access> {
+    if i < 2 || !matches!(data.operands[i - 2].data, OperandData::MemStart) {
+        return None;
+    }
+    let mem = data.operands.get(i - 1..i + 5)?;
+    let offset = match &mem[2].data {
+        OperandData::None => 0,
+        imm => imm.sext_as_i64()?,
+    };
+    if !matches!(mem[3].data, OperandData::None) || !matches!(mem[4].data, OperandData::None) {
+        return None;
+    }
+    match data.opcode {
+        Opcode::MLOADi | Opcode::MLOADr => Some(Access::Load(slot)),
+        Opcode::MSTOREi | Opcode::MSTOREr => {
+            let whole = offset == 0 && function.slots.get(slot).size == 4;
+            Some(Access::Store(slot, whole))
+        }
+        _ => None,
+    }
+}
+
+/// The live ranges of the slots, where a slot is live from a store to the
+/// loads which may read it. A store which does not overwrite the whole slot
+/// keeps the slot live above it.
+///
+/// `Liveness` cannot be reused for this, as it only follows the registers an
+/// instruction defines and uses, and a slot is a memory operand it never sees.
+/// This runs the same backward dataflow over the loads and stores of slots
+/// instead, and gives its result as `LiveRange`s so slots interfere the same
+/// way registers do.
+fn live_ranges(
+    function: &Function<Ola>,
+    accesses: &FxHashMap<InstructionId<InstructionData>, Vec<Access>>,
+    colorable: &FxHashSet<SlotId>,
+) -> FxHashMap<SlotId, LiveRange> {
+    let layout = &function.layout;
+    let blocks = layout.block_iter().collect::<Vec<_>>();
+    let block_accesses = |block: BasicBlockId| {
+        layout
+            .inst_iter(block)
+            .flat_map(|id| accesses.get(&id).into_iter().flatten().copied())
+            .filter(|access| match access {
+                Access::Load(slot) | Access::Store(slot, _) => colorable.contains(slot),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // The slots read before being overwritten and the ones overwritten before
+    // being read in each block
+    let mut gen_kill = FxHashMap::default();
+    for &block in &blocks {
+        let mut gen = FxHashSet::default();
+        let mut kill = FxHashSet::default();
+        for access in block_accesses(block) {
+            match access {
+                Access::Load(slot) if !kill.contains(&slot) => {
+                    gen.insert(slot);
+                }
+                Access::Store(slot, true) if !gen.contains(&slot) => {
+                    kill.insert(slot);
+                }
+                _ => {}
+            }
+        }
+        gen_kill.insert(block, (gen, kill));
+    }
+
+    let mut live_in: FxHashMap<BasicBlockId, FxHashSet<SlotId>> = FxHashMap::default();
+    let mut live_out: FxHashMap<BasicBlockId, FxHashSet<SlotId>> = FxHashMap::default();
+    loop {
+        let mut changed = false;
+        for &block in blocks.iter().rev() {
+            let mut out = FxHashSet::default();
+            for succ in &function.data.block_ref(block).succs {
+                out.extend(live_in.get(succ).into_iter().flatten());
+            }
+            let (gen, kill) = &gen_kill[&block];
+            let mut live = out.difference(kill).copied().collect::<FxHashSet<_>>();
+            live.extend(gen);
+            live_out.insert(block, out);
+            if live_in.get(&block) != Some(&live) {
+                live_in.insert(block, live);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    // Instructions are at even points, so that a store whose value is never
+    // read still occupies its slot until the next one
+    let mut ranges: FxHashMap<SlotId, LiveRange> = colorable
+        .iter()
+        .map(|&slot| (slot, LiveRange(vec![])))
+        .collect();
+    for (block_num, &block) in blocks.iter().enumerate() {
+        let block_num = block_num as u64;
+        let insts = layout.inst_iter(block).collect::<Vec<_>>();
+        let mut segments = vec![];
+
+        let block_end = ProgramPoint(block_num, 2 * insts.len() as u64 + 2);
+        let mut ends = live_out[&block]
+            .iter()
+            .map(|&slot| (slot, block_end))
+            .collect::<FxHashMap<_, _>>();
+        for (n, id) in insts.iter().enumerate().rev() {
+            let pp = ProgramPoint(block_num, 2 * n as u64 + 2);
+            for &access in accesses.get(id).into_iter().flatten().rev() {
+                match access {
+                    Access::Load(slot) if colorable.contains(&slot) => {
+                        ends.entry(slot).or_insert(pp);
+                    }
+                    Access::Store(slot, whole) if colorable.contains(&slot) => {
+                        match ends.get(&slot) {
+                            Some(&end) if whole => {
+                                segments.push((slot, LiveSegment { start: pp, end }));
+                                ends.remove(&slot);
+                            }
+                            Some(_) => {}
+                            None => segments.push((
+                                slot,
+                                LiveSegment {
+                                    start: pp,
+                                    end: ProgramPoint(block_num, pp.1 + 1),
+                                },
+                            )),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for (slot, end) in ends {
+            segments.push((
+                slot,
+                LiveSegment {
+                    start: ProgramPoint(block_num, 0),
+                    end,
+                },
+            ));
+        }
+
+        for (slot, segment) in segments {
+            ranges
+                .get_mut(&slot)
+                .unwrap()
+                .merge(&LiveRange(vec![segment]));
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod test {
+    use crate::codegen::{
+        core::pass::OptLevel,
+        isa::ola::{compile, Ola},
+    };
+
+    /// The size of the frame `name` allocates in its prologue
+    fn frame_size(asm: &str, name: &str) -> i64 {
+        let mut lines = asm.lines().skip_while(|line| *line != format!("{}:", name));
+        lines
+            .find_map(|line| line.strip_prefix("  add r9 r9 "))
+            .and_then(|adj| adj.parse().ok())
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn slot_coloring_shrinks_frames() {
+        let tload = include_str!("../../../../../tests/codegen/regression/tload.ll");
+        let cases = [
+            (tload, "field_memcmp_ugt", 20, 13),
+            (tload, "u32_div_mod", 5, 3),
+            (tload, "setNonce", 96, 7),
+            (tload, "vote_for", 56, 5),
+            (tload, "main", 8, 5),
+            // both allocas are passed to the callee by address
+            (
+                include_str!("../../../../../tests/codegen/regression/alloca_params_pattern.ll"),
+                "alloca_params_pattern",
+                4,
+                4,
+            ),
+            // a single slot has nothing to share with
+            (
+                include_str!("../../../../../tests/codegen/regression/store.ll"),
+                "test_store_dst_gep_arg_inst",
+                1,
+                1,
+            ),
+        ];
+        for (source, name, before, after) in cases {
+            let o0 = compile(source, &Ola::default());
+            let o2 = compile(source, &Ola::new(OptLevel::Default));
+            assert_eq!(
+                (frame_size(&o0, name), frame_size(&o2, name)),
+                (before, after),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn slot_coloring_keeps_overlapping_slots_apart() {
+        // both values are stored before either is loaded back
+        let overlapping = r#"
+define i64 @pair(i64 %0, i64 %1) {
+entry:
+  %a = alloca i64, align 8
+  %b = alloca i64, align 8
+  store i64 %0, ptr %a, align 8
+  store i64 %1, ptr %b, align 8
+  %2 = load i64, ptr %a, align 8
+  %3 = load i64, ptr %b, align 8
+  %4 = add i64 %2, %3
+  ret i64 %4
+}
+"#;
+        assert_eq!(
+            compile(overlapping, &Ola::default()),
+            "pair:
+.LBL0_0:
+  add r9 r9 2
+  mstore [r9,-1] r1
+  mstore [r9,-2] r2
+  mload r1 [r9,-1]
+  mload r2 [r9,-2]
+  add r0 r1 r2
+  add r9 r9 -2
+  ret
+"
+        );
+        assert_eq!(
+            compile(overlapping, &Ola::new(OptLevel::Default)),
+            "pair:
+.LBL0_0:
+  add r9 r9 2
+  mstore [r9,-1] r1
+  mstore [r9,-2] r2
+  mload r1 [r9,-1]
+  add r0 r1 r2
+  add r9 r9 -2
+  ret
+"
+        );
+
+        // the first value is dead by the time the second is stored
+        let disjoint = overlapping.replace(
+            "  store i64 %1, ptr %b, align 8\n  %2 = load i64, ptr %a, align 8\n",
+            "  %2 = load i64, ptr %a, align 8\n  store i64 %1, ptr %b, align 8\n",
+        );
+        assert_eq!(
+            compile(&disjoint, &Ola::default()),
+            "pair:
+.LBL0_0:
+  add r9 r9 2
+  mstore [r9,-1] r1
+  mload r1 [r9,-1]
+  mstore [r9,-2] r2
+  mload r2 [r9,-2]
+  add r0 r1 r2
+  add r9 r9 -2
+  ret
+"
+        );
+        assert_eq!(
+            compile(&disjoint, &Ola::new(OptLevel::Default)),
+            "pair:
+.LBL0_0:
+  add r9 r9 1
+  mstore [r9,-1] r1
+  mstore [r9,-1] r2
+  add r0 r1 r2
+  add r9 r9 -1
+  ret
+"
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use crate::codegen::core::ir::types::{self, Type, Types};
 use crate::codegen::{
     call_conv::CallConvKind,
+    lower::LoweringError,
     register::{Reg, RegUnit, RegisterClass, RegisterInfo},
 };
 use std::fmt;
@@ -87,14 +88,17 @@ impl RegisterInfo for RegInfo {
 }
 
 impl RegisterClass for RegClass {
-    fn for_type(types: &Types, ty: Type) -> Self {
+    fn for_type(types: &Types, ty: Type) -> Result<Self, LoweringError> {
         match ty {
             types::VOID | types::I1 | types::I8 | types::I16 | types::I32 | types::I64 => {
-                RegClass::GR
+                Ok(RegClass::GR)
             }
-            _ if ty.is_pointer(types) => RegClass::GR,
-            _ if ty.is_array(types) => RegClass::GR,
-            e => todo!("{}", types.to_string(e)),
+            _ if ty.is_pointer(types) => Ok(RegClass::GR),
+            _ if ty.is_array(types) => Ok(RegClass::GR),
+            e => Err(LoweringError::Unsupported(format!(
+                "values of type {} cannot be held in registers",
+                types.to_string(e)
+            ))),
         }
     }
 
@@ -157,10 +161,38 @@ impl fmt::Display for GR {
     }
 }
 
-pub fn reg_to_str(r: &Reg) -> &'static str {
+pub fn reg_to_str(r: &Reg) -> Result<&'static str, LoweringError> {
     let gr = ["r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8", "r9"];
     match r {
-        Reg(0, i) => gr[*i as usize],
-        e => todo!("{:?}", e),
+        Reg(0, i) if (*i as usize) < gr.len() => Ok(gr[*i as usize]),
+        e => Err(LoweringError::Unsupported(format!(
+            "register {:?} does not exist",
+            e
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unsupported_registers_are_errors() {
+        let tys = Types::new();
+        let pair = tys
+            .base_mut()
+            .anonymous_struct(vec![types::I64, types::I64], false);
+        assert!(matches!(
+            RegClass::for_type(&tys, pair),
+            Err(LoweringError::Unsupported(_))
+        ));
+        assert!(matches!(
+            RegClass::for_type(&tys, types::I64),
+            Ok(RegClass::GR)
+        ));
+
+        assert_eq!(reg_to_str(&GR::R9.into()).unwrap(), "r9");
+        assert!(reg_to_str(&Reg(0, 10)).is_err());
+        assert!(reg_to_str(&Reg(1, 0)).is_err());
     }
 }
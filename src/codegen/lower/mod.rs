@@ -12,15 +12,18 @@ use super::{
     module::Module as MachModule,
     register::VReg,
 };
-use crate::codegen::core::ir::{
-    function::{
-        basic_block::BasicBlockId as IrBasicBlockId,
-        data::Data as IrData,
-        instruction::{Instruction as IrInstruction, InstructionId as IrInstructionId, Opcode},
-        Function as IrFunction, Parameter,
+use crate::codegen::core::{
+    ir::{
+        function::{
+            basic_block::BasicBlockId as IrBasicBlockId,
+            data::Data as IrData,
+            instruction::{Instruction as IrInstruction, InstructionId as IrInstructionId, Opcode},
+            Function as IrFunction, Parameter,
+        },
+        module::Module as IrModule,
+        types::Types,
     },
-    module::Module as IrModule,
-    types::Types,
+    pass::analysis::tail_call::tail_calls,
 };
 use anyhow::Result;
 use id_arena::Arena;
@@ -34,6 +37,9 @@ pub trait Lower<T: TargetIsa> {
 
 // TODO: So confusing. Need refactoring.
 pub struct LoweringContext<'a, 'isa: 'a, T: TargetIsa> {
+    /// The module of the function being lowered, to tell functions apart from
+    /// global variables
+    pub module: &'a IrModule,
     pub ir_data: &'a IrData,
     pub mach_data: &'a mut Data<T::Inst>,
     pub slots: &'a mut Slots<'isa, T>,
@@ -43,7 +49,12 @@ pub struct LoweringContext<'a, 'isa: 'a, T: TargetIsa> {
     pub types: &'a Types,
     pub inst_id_to_vreg: &'a mut FxHashMap<IrInstructionId, Vec<VReg>>,
     pub merged_inst: &'a mut FxHashSet<IrInstructionId>,
+    /// The calls in tail position, which may be lowered as jumps
+    pub tail_calls: &'a FxHashSet<IrInstructionId>,
     pub block_map: &'a FxHashMap<IrBasicBlockId, MachBasicBlockId>,
+    /// The blocks put on the edges from a block, the first, to another, whose
+    /// phis take their values from them instead
+    pub edge_blocks: &'a mut FxHashMap<(IrBasicBlockId, IrBasicBlockId), MachBasicBlockId>,
     pub call_conv: CallConvKind,
     pub cur_block: IrBasicBlockId,
     pub isa: &'isa T,
@@ -52,6 +63,13 @@ pub struct LoweringContext<'a, 'isa: 'a, T: TargetIsa> {
 #[derive(Debug)]
 pub enum LoweringError {
     Todo(String),
+    /// The IR uses a construct which the target cannot lower
+    Unsupported(String),
+    /// An error raised while compiling the named function
+    Function {
+        name: String,
+        error: String,
+    },
 }
 
 pub fn compile_module<'a, T: TargetIsa>(
@@ -61,7 +79,12 @@ pub fn compile_module<'a, T: TargetIsa>(
     let mut functions = Arena::new();
 
     for (_, function) in module.functions() {
-        functions.alloc(compile_function(isa, function)?);
+        let function =
+            compile_function(isa, module, function).map_err(|e| LoweringError::Function {
+                name: function.name().to_string(),
+                error: e.to_string(),
+            })?;
+        functions.alloc(function);
     }
 
     let mut mach_module = MachModule {
@@ -80,6 +103,7 @@ pub fn compile_module<'a, T: TargetIsa>(
 
 pub fn compile_function<'a, T: TargetIsa>(
     isa: &'a T,
+    module: &'a IrModule,
     function: &'a IrFunction,
 ) -> Result<MachFunction<'a, T>> {
     let mut slots = Slots::new(isa);
@@ -111,6 +135,13 @@ pub fn compile_function<'a, T: TargetIsa>(
     let mut inst_id_to_vreg = FxHashMap::default();
     let mut arg_idx_to_vreg = FxHashMap::default();
     let mut merged_inst = FxHashSet::default();
+    let mut edge_blocks = FxHashMap::default();
+    // Entry points end the program instead of returning, so there is no caller
+    // a callee could return to in their place
+    let tail_calls = match function.name().as_str() {
+        "main" | "call" => FxHashSet::default(),
+        _ => tail_calls(function),
+    };
     let call_conv = T::default_call_conv();
 
     for (i, block_id) in function.layout.block_iter().enumerate() {
@@ -121,6 +152,7 @@ pub fn compile_function<'a, T: TargetIsa>(
             // if "function_dispatch" != function.name.as_str() {
             T::Lower::copy_args_to_vregs(
                 &mut LoweringContext {
+                    module,
                     ir_data: &function.data,
                     mach_data: &mut data,
                     slots: &mut slots,
@@ -130,7 +162,9 @@ pub fn compile_function<'a, T: TargetIsa>(
                     types: &function.types,
                     inst_id_to_vreg: &mut inst_id_to_vreg,
                     merged_inst: &mut merged_inst,
+                    tail_calls: &tail_calls,
                     block_map: &block_map,
+                    edge_blocks: &mut edge_blocks,
                     call_conv,
                     cur_block: block_id,
                     isa,
@@ -149,6 +183,7 @@ pub fn compile_function<'a, T: TargetIsa>(
             }
             T::Lower::lower(
                 &mut LoweringContext {
+                    module,
                     ir_data: &function.data,
                     mach_data: &mut data,
                     slots: &mut slots,
@@ -158,7 +193,9 @@ pub fn compile_function<'a, T: TargetIsa>(
                     types: &function.types,
                     inst_id_to_vreg: &mut inst_id_to_vreg,
                     merged_inst: &mut merged_inst,
+                    tail_calls: &tail_calls,
                     block_map: &block_map,
+                    edge_blocks: &mut edge_blocks,
                     call_conv,
                     cur_block: block_id,
                     isa,
@@ -183,6 +220,7 @@ pub fn compile_function<'a, T: TargetIsa>(
 
             T::Lower::lower(
                 &mut LoweringContext {
+                    module,
                     ir_data: &function.data,
                     mach_data: &mut data,
                     slots: &mut slots,
@@ -192,7 +230,9 @@ pub fn compile_function<'a, T: TargetIsa>(
                     types: &function.types,
                     inst_id_to_vreg: &mut inst_id_to_vreg,
                     merged_inst: &mut merged_inst,
+                    tail_calls: &tail_calls,
                     block_map: &block_map,
+                    edge_blocks: &mut edge_blocks,
                     call_conv,
                     cur_block: block_id,
                     isa,
@@ -201,9 +241,17 @@ pub fn compile_function<'a, T: TargetIsa>(
             )?;
         }
 
+        // Lowering may split the block, as for a switch, and the new blocks
+        // follow it in the order their instructions appear
+        let mut last_block = block_map[&block_id];
         for mach_inst in inst_seq {
+            let block = mach_inst.parent;
+            if !layout.is_block_inserted(block) {
+                layout.insert_block_after(block, last_block);
+                last_block = block;
+            }
             let mach_inst = data.create_inst(mach_inst);
-            layout.append_inst(mach_inst, block_map[&block_id])
+            layout.append_inst(mach_inst, block)
         }
     }
 
@@ -239,6 +287,8 @@ impl fmt::Display for LoweringError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Todo(msg) => write!(f, "Todo: {}", msg),
+            Self::Unsupported(msg) => write!(f, "{}", msg),
+            Self::Function { name, error } => write!(f, "{} in function '{}'", error, name),
         }
     }
 }
@@ -4,6 +4,7 @@ use crate::codegen::{
         Function,
     },
     isa::TargetIsa,
+    lower::LoweringError,
     module::Module,
     pass::liveness,
     pass::spiller,
@@ -15,13 +16,16 @@ use std::collections::VecDeque;
 
 pub fn run_on_module<T: TargetIsa>(module: &mut Module<T>) -> Result<()> {
     for (_, func) in &mut module.functions {
-        run_on_function(func);
+        run_on_function(func).map_err(|e| LoweringError::Function {
+            name: func.ir.name().to_string(),
+            error: e.to_string(),
+        })?;
     }
     Ok(())
 }
 
 // Linear-scan
-pub fn run_on_function<T: TargetIsa>(function: &mut Function<T>) {
+pub fn run_on_function<T: TargetIsa>(function: &mut Function<T>) -> Result<(), LoweringError> {
     let mut liveness = liveness::Liveness::<T>::new();
     liveness.analyze_function(function);
 
@@ -61,24 +65,17 @@ pub fn run_on_function<T: TargetIsa>(function: &mut Function<T>) {
     // TODO: Refactoring.
     let mut spill_regs = FxHashSet::default();
     while let Some(vreg) = worklist.pop_front() {
-        let mut availables =
-            T::RegClass::for_type(&function.types, function.data.vregs.type_for(vreg)).gpr_list();
-        availables.append(
-            &mut T::RegClass::for_type(&function.types, function.data.vregs.type_for(vreg))
-                .csr_list(),
-        );
+        let class = T::RegClass::for_type(&function.types, function.data.vregs.type_for(vreg))?;
+        let mut availables = class.gpr_list();
+        availables.append(&mut class.csr_list());
         if function.ir.name() == "function_dispatch" {
-            availables =
-            T::RegClass::for_type(&function.types, function.data.vregs.type_for(vreg))
-                .csr_list();
+            availables = class.csr_list();
         }
         for block_id in function.layout.block_iter() {
             for inst_id in function.layout.inst_iter(block_id) {
                 let inst = function.data.inst_ref(inst_id);
                 if inst.data.is_call() {
-                    availables =
-                        T::RegClass::for_type(&function.types, function.data.vregs.type_for(vreg))
-                            .csr_list();
+                    availables = class.csr_list();
                     break;
                 }
             }
@@ -141,6 +138,7 @@ pub fn run_on_function<T: TargetIsa>(function: &mut Function<T>) {
             }
         })
         .collect();
+    Ok(())
 }
 
 pub fn collect_vregs_alive_around_call<T: TargetIsa>(
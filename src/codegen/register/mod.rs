@@ -2,6 +2,7 @@ use crate::codegen::core::ir::types::{Type, Types};
 use crate::codegen::{
     call_conv::CallConvKind,
     function::instruction::{InstructionId, TargetInst},
+    lower::LoweringError,
 };
 use rustc_hash::FxHashMap;
 
@@ -45,7 +46,9 @@ pub trait RegisterInfo {
 }
 
 pub trait RegisterClass {
-    fn for_type(types: &Types, id: Type) -> Self;
+    fn for_type(types: &Types, id: Type) -> Result<Self, LoweringError>
+    where
+        Self: Sized;
     fn gpr_list(&self) -> Vec<Reg>;
     fn csr_list(&self) -> Vec<Reg>;
     fn apply_for(&self, ru: RegUnit) -> Reg;
@@ -1,5 +1,16 @@
-use crate::sema::ast::{ArrayLength, Namespace, Type};
+use crate::codegen::core::ir::module::Module as CoreModule;
+use crate::codegen::core::pass::{optimize, OptLevel};
+use crate::codegen::isa::ola::{
+    asm::{self, AsmProgram},
+    Ola,
+};
+use crate::codegen::lower::compile_module;
+use crate::sema::ast::{ArrayLength, Diagnostic, Namespace, Type};
+use crate::sema::diagnostics::Diagnostics;
 use crate::sema::expression::FIELD_ORDER;
+use ola_parser::program::Loc;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::path::Path;
 use std::str;
 
@@ -29,6 +40,16 @@ pub struct Binary<'a> {
     pub(crate) context: &'a Context,
     pub loops: Vec<(BasicBlock<'a>, BasicBlock<'a>)>,
     pub heap_address: GlobalValue<'a>,
+    /// Errors for constructs which passed sema but cannot be generated
+    pub diagnostics: RefCell<Diagnostics>,
+    /// Location of the statement currently being generated
+    pub(crate) loc: Loc,
+    /// Optimization level of the contracts this contract creates, whose code
+    /// hash must match the one they are deployed with
+    pub(crate) opt_level: OptLevel,
+    /// Code hashes of the contracts this contract creates, so that each is
+    /// compiled once however many times it is created
+    code_hashes: RefCell<HashMap<usize, Result<[u64; 4], String>>>,
 }
 
 impl<'a> Binary<'a> {
@@ -36,10 +57,12 @@ impl<'a> Binary<'a> {
     pub fn gen_ir(
         context: &'a Context,
         contract_no: usize,
-        ns: &'a Namespace,
-        filename: &'a str,
+        ns: &Namespace,
+        filename: &str,
+        opt_level: OptLevel,
     ) -> Self {
         let mut binary = Binary::new(&context, ns.contracts[contract_no].name.as_str(), filename);
+        binary.opt_level = opt_level;
         gen_lib_functions(&mut binary, ns);
         gen_functions(&mut binary, contract_no, ns);
         gen_func_dispatch(&mut binary, contract_no, ns);
@@ -77,15 +100,87 @@ impl<'a> Binary<'a> {
             context,
             loops: Vec::new(),
             heap_address,
+            diagnostics: RefCell::new(Diagnostics::default()),
+            loc: Loc::IRgen,
+            opt_level: OptLevel::None,
+            code_hashes: RefCell::new(HashMap::new()),
         }
     }
 
+    /// The code hash of another contract, for creating it from this one. The
+    /// contract is compiled the first time its hash is asked for.
+    pub(crate) fn code_hash(&self, contract_no: usize, ns: &Namespace) -> Result<[u64; 4], String> {
+        if let Some(code_hash) = self.code_hashes.borrow().get(&contract_no) {
+            return code_hash.clone();
+        }
+        let code_hash = self
+            .compile_contract(contract_no, ns)
+            .map(|program| program.code_hash());
+        self.code_hashes
+            .borrow_mut()
+            .insert(contract_no, code_hash.clone());
+        code_hash
+    }
+
+    /// Compile another contract down to its program
+    fn compile_contract(&self, contract_no: usize, ns: &Namespace) -> Result<AsmProgram, String> {
+        let context = Context::create();
+        let name = &ns.contracts[contract_no].name;
+        let binary = Binary::gen_ir(&context, contract_no, ns, name, self.opt_level);
+        if binary.take_diagnostics(&mut Diagnostics::default()) {
+            return Err(format!("contract '{}' cannot be compiled", name));
+        }
+        let mut module = CoreModule::try_from(&binary.module)
+            .map_err(|err| format!("failed to translate contract '{}': {}", name, err))?;
+        optimize(&mut module, self.opt_level);
+        compile_module(&Ola::new(self.opt_level), &module)
+            .and_then(|code| asm::emit(&code).map_err(|e| e.into()))
+            .map_err(|err| format!("failed to compile contract '{}': {}", name, err))
+    }
+
+    /// Record an error for a construct which cannot be generated, and return
+    /// a zero value of the expected type so that generation can carry on and
+    /// report any further errors.
+    pub(crate) fn unsupported(
+        &self,
+        loc: Loc,
+        message: String,
+        ty: &Type,
+        ns: &Namespace,
+    ) -> BasicValueEnum<'a> {
+        let loc = if matches!(loc, Loc::File(..)) {
+            loc
+        } else {
+            self.loc
+        };
+        self.diagnostics
+            .borrow_mut()
+            .push(Diagnostic::error(loc, message));
+
+        match ty {
+            Type::Void | Type::Unreachable | Type::Unresolved => {
+                self.context.i64_type().const_zero().into()
+            }
+            _ => self.llvm_var_ty(ty, ns).const_zero(),
+        }
+    }
+
+    /// Move the errors collected during generation into `diagnostics`.
+    /// Returns true if any error was found, in which case the module
+    /// must not be compiled further.
+    pub fn take_diagnostics(&self, diagnostics: &mut Diagnostics) -> bool {
+        let errors = self.diagnostics.take();
+        let any_errors = errors.any_errors();
+        diagnostics.extend(errors);
+        any_errors
+    }
+
     /// Convert a BigInt number to llvm const value
     pub(crate) fn number_literal(
         &self,
         ty: &Type,
         n: &BigInt,
-        _ns: &Namespace,
+        ns: &Namespace,
     ) -> BasicValueEnum<'a> {
         match ty {
             // Map all i32 data to a field-based data type,
@@ -155,7 +250,15 @@ impl<'a> Binary<'a> {
                 }
                 heap_ptr.into()
             }
-            _ => panic!("number_literal: unhandled type {:?}", ty),
+            _ => self.unsupported(
+                Loc::IRgen,
+                format!(
+                    "number literal of type {} is not supported",
+                    ty.to_string(ns)
+                ),
+                ty,
+                ns,
+            ),
         }
     }
 
@@ -13,7 +13,7 @@ use super::u256_op::{
 };
 use super::u32_op::{define_u32_div_mod, define_u32_power, define_u32_sqrt};
 
-static PROPHET_FUNCTIONS: Lazy<[&str; 15]> = Lazy::new(|| {
+static PROPHET_FUNCTIONS: Lazy<[&str; 14]> = Lazy::new(|| {
     [
         "prophet_u32_sqrt",
         "prophet_u32_div",
@@ -27,17 +27,17 @@ static PROPHET_FUNCTIONS: Lazy<[&str; 15]> = Lazy::new(|| {
         "get_storage",
         "set_storage",
         "poseidon_hash",
-        "contract_call",
         "prophet_printf",
         "emit_event",
     ]
 });
 
-static BUILTIN_FUNCTIONS: Lazy<[&str; 3]> = Lazy::new(|| {
+static BUILTIN_FUNCTIONS: Lazy<[&str; 4]> = Lazy::new(|| {
     [
         "builtin_assert",
         "builtin_range_check",
         "builtin_check_ecdsa",
+        "contract_call",
     ]
 });
 
@@ -195,13 +195,6 @@ fn declare_prophets(bin: &mut Binary) {
                 void_type.fn_type(&[ptr_type.into(), ptr_type.into(), i64_type.into()], false);
             bin.module.add_function(p, ftype, None);
         }
-        "contract_call" => {
-            let void_type = bin.context.void_type();
-            let call_type = bin.context.i64_type();
-            let address_type = bin.context.i64_type().ptr_type(AddressSpace::default());
-            let ftype = void_type.fn_type(&[address_type.into(), call_type.into()], false);
-            bin.module.add_function(p, ftype, None);
-        }
 
         "prophet_printf" => {
             let void_type = bin.context.void_type();
@@ -241,6 +234,15 @@ fn declare_builtins(bin: &mut Binary) {
             let ftype = i64_type.fn_type(&[ptr_type.into()], false);
             bin.module.add_function(p, ftype, None);
         }
+        // @param callee address
+        // @param call type, 0 for call, 1 for delegatecall and 2 for staticcall
+        // @return 1 if the callee returned, 0 if it reverted
+        "contract_call" => {
+            let i64_type = bin.context.i64_type();
+            let address_type = i64_type.ptr_type(AddressSpace::default());
+            let ftype = i64_type.fn_type(&[address_type.into(), i64_type.into()], false);
+            bin.module.add_function(p, ftype, None);
+        }
         _ => {}
     });
 }
@@ -57,7 +57,9 @@ pub fn gen_contract_entrance(init: Option<FunctionValue>, bin: &mut Binary) {
 pub fn gen_func_dispatch(bin: &mut Binary, contract_no: usize, ns: &Namespace) {
     let mut funcs = Vec::new();
     for func_no in ns.contracts[contract_no].all_functions.keys() {
-        funcs.push(&ns.functions[*func_no]);
+        if !ns.functions[*func_no].is_internal(ns) {
+            funcs.push(&ns.functions[*func_no]);
+        }
     }
     let ty = bin.context.void_type().fn_type(
         &[
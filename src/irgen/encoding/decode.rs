@@ -90,12 +90,6 @@ fn decode_array<'a>(
                 .ptr_type(AddressSpace::default()),
             "array_ptr",
         );
-        // The function decode_complex_array assumes that, if the dimension is fixed,
-        // there is no need to allocate an array
-        // if matches!(dims.last(), Some(ArrayLength::Fixed(_))) {
-        //     let array_literal = bin.heap_malloc(bin.context.i64_type().const_zero());
-        //     bin.builder.build_store(array_var, array_literal);
-        // }
         decode_complex_array(
             bin,
             buffer,
@@ -333,10 +327,11 @@ fn decode_complex_array<'a>(
     // allocated the outer dimension, i.e., we are about to read a 'int[3][4]' item.
     // Arrays whose elements are dynamic cannot be verified.
 
-    // Dynamic dimensions mean that the subarray we are processing must be allocated
-    // in memory.
-
-    if dims[dimension] == ArrayLength::Dynamic {
+    // Every subarray we are processing must be allocated in memory. A dynamic
+    // dimension is a vector whose length is read from the buffer, while a fixed one
+    // is a plain block of its elements.
+    let new_ty = Type::Array(Box::new(elem_ty.clone()), dims[0..(dimension + 1)].to_vec());
+    let allocated_array = if dims[dimension] == ArrayLength::Dynamic {
         let length_ptr = unsafe {
             bin.builder.build_gep(
                 bin.context.i64_type().ptr_type(AddressSpace::default()),
@@ -357,31 +352,36 @@ fn decode_complex_array<'a>(
 
         bin.builder.build_store(offset_var, array_start);
 
-        let new_ty = Type::Array(Box::new(elem_ty.clone()), dims[0..(dimension + 1)].to_vec());
-        let allocated_array =
-            bin.alloca_dynamic_array(func_value, &new_ty, length, None, false, ns);
+        bin.alloca_dynamic_array(func_value, &new_ty, length, None, false, ns)
+    } else {
+        let array_size = bin
+            .context
+            .i64_type()
+            .const_int(new_ty.type_size_of(ns).to_u64().unwrap(), false);
 
-        if indexes.is_empty() {
-            bin.builder.build_store(array_var, allocated_array);
-        } else {
-            let array = bin.builder.build_load(
-                bin.context.i64_type().ptr_type(AddressSpace::default()),
-                array_var,
-                "load_array",
-            );
-            let sub_arr = index_array(
-                bin,
-                &mut array.clone(),
-                &mut array_ty.clone(),
-                dims,
-                indexes,
-                func_value,
-                ns,
-                true,
-            );
-            bin.builder
-                .build_store(sub_arr.into_pointer_value(), allocated_array);
-        }
+        bin.heap_malloc(array_size)
+    };
+
+    if indexes.is_empty() {
+        bin.builder.build_store(array_var, allocated_array);
+    } else {
+        let array = bin.builder.build_load(
+            bin.context.i64_type().ptr_type(AddressSpace::default()),
+            array_var,
+            "load_array",
+        );
+        let sub_arr = index_array(
+            bin,
+            &mut array.clone(),
+            &mut array_ty.clone(),
+            dims,
+            indexes,
+            func_value,
+            ns,
+            true,
+        );
+        bin.builder
+            .build_store(sub_arr.into_pointer_value(), allocated_array);
     }
 
     let array = bin.builder.build_load(
@@ -402,22 +402,21 @@ fn decode_complex_array<'a>(
     );
     bin.builder.position_at_end(for_loop.body_block);
 
-    let offset = bin
-        .builder
-        .build_load(bin.context.i64_type(), offset_var, "")
-        .into_int_value();
-
-    let buffer = unsafe {
-        bin.builder.build_gep(
-            bin.context.i64_type().ptr_type(AddressSpace::default()),
-            buffer,
-            &[offset],
-            "",
-        )
-    };
-
     if 0 == dimension {
-        let (read_value, advance) = read_from_buffer(buffer, bin, elem_ty, func_value, ns);
+        let offset = bin
+            .builder
+            .build_load(bin.context.i64_type(), offset_var, "")
+            .into_int_value();
+
+        let elem_buffer = unsafe {
+            bin.builder.build_gep(
+                bin.context.i64_type().ptr_type(AddressSpace::default()),
+                buffer,
+                &[offset],
+                "",
+            )
+        };
+        let (read_value, advance) = read_from_buffer(elem_buffer, bin, elem_ty, func_value, ns);
         let array = bin.builder.build_load(
             bin.context.i64_type().ptr_type(AddressSpace::default()),
             array_var,
@@ -441,6 +440,8 @@ fn decode_complex_array<'a>(
 
         bin.builder.build_store(offset_var, offset);
     } else {
+        // The offset is from the start of the array, so the inner dimensions
+        // read from the same buffer
         decode_complex_array(
             bin,
             buffer,
@@ -268,7 +268,7 @@ fn encode_complex_array<'a>(
         let sub_array = index_array(
             bin,
             &mut array.clone(),
-            &mut array_ty,
+            &mut array_ty.clone(),
             dims,
             indexes,
             func_value,
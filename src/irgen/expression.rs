@@ -11,10 +11,10 @@ use inkwell::values::{
 use inkwell::{AddressSpace, IntPredicate};
 use num_bigint::{BigInt, Sign};
 use num_traits::ToPrimitive;
-use ola_parser::program;
+use ola_parser::program::{self, CodeLocation};
 
 use crate::sema::{
-    ast::{Expression, LibFunc, Namespace, RetrieveType, Type},
+    ast::{selector, Expression, LibFunc, Namespace, RetrieveType, Type},
     diagnostics::Diagnostics,
     expression::integers::bigint_to_expression,
     expression::ResolveTo,
@@ -59,57 +59,139 @@ pub fn expression<'a>(
         Expression::Add { left, right, .. } => match left.ty() {
             Type::Uint(32) => u32_add(left, right, bin, func_value, var_table, ns),
             Type::Uint(256) => u256_add(left, right, bin, func_value, var_table, ns),
-            _ => unimplemented!("add for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!("add is not supported on type {}", left.ty().to_string(ns)),
+                &left.ty(),
+                ns,
+            ),
         },
         Expression::Subtract { left, right, .. } => match left.ty() {
             Type::Uint(32) => u32_sub(left, right, bin, func_value, var_table, ns),
             Type::Uint(256) => u256_sub(left, right, bin, func_value, var_table, ns),
-            _ => unimplemented!("subtract for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!(
+                    "subtract is not supported on type {}",
+                    left.ty().to_string(ns)
+                ),
+                &left.ty(),
+                ns,
+            ),
         },
         Expression::Multiply { left, right, .. } => match left.ty() {
             Type::Uint(32) => u32_mul(left, right, bin, func_value, var_table, ns),
             Type::Uint(256) => u256_mul(left, right, bin, func_value, var_table, ns),
-            _ => unimplemented!("multiply for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!(
+                    "multiply is not supported on type {}",
+                    left.ty().to_string(ns)
+                ),
+                &left.ty(),
+                ns,
+            ),
         },
         Expression::Divide { left, right, .. } => match left.ty() {
             Type::Uint(32) => u32_div(left, right, bin, func_value, var_table, ns),
             Type::Uint(256) => u256_div(left, right, bin, func_value, var_table, ns),
-            _ => unimplemented!("divide for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!(
+                    "divide is not supported on type {}",
+                    left.ty().to_string(ns)
+                ),
+                &left.ty(),
+                ns,
+            ),
         },
         Expression::Modulo { left, right, .. } => match left.ty() {
             Type::Uint(32) => u32_mod(left, right, bin, func_value, var_table, ns),
             Type::Uint(256) => u256_mod(left, right, bin, func_value, var_table, ns),
-            _ => unimplemented!("modulo for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!(
+                    "modulo is not supported on type {}",
+                    left.ty().to_string(ns)
+                ),
+                &left.ty(),
+                ns,
+            ),
         },
         Expression::Power { base, exp, .. } => match base.ty() {
             Type::Uint(32) => u32_power(base, exp, bin, func_value, var_table, ns),
             Type::Uint(256) => u256_power(base, exp, bin, func_value, var_table, ns),
-            _ => unimplemented!("power for type {:?}", base.ty()),
+            _ => bin.unsupported(
+                base.loc(),
+                format!("power is not supported on type {}", base.ty().to_string(ns)),
+                &base.ty(),
+                ns,
+            ),
         },
         Expression::BitwiseOr { left, right, .. } => match left.ty() {
             Type::Uint(32) => u32_bitwise_or(left, right, bin, func_value, var_table, ns),
             Type::Uint(256) => u256_bitwise_or(left, right, bin, func_value, var_table, ns),
-            _ => unimplemented!("bitwise or for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!(
+                    "bitwise or is not supported on type {}",
+                    left.ty().to_string(ns)
+                ),
+                &left.ty(),
+                ns,
+            ),
         },
         Expression::BitwiseAnd { left, right, .. } => match left.ty() {
             Type::Uint(32) => u32_bitwise_and(left, right, bin, func_value, var_table, ns),
             Type::Uint(256) => u256_bitwise_and(left, right, bin, func_value, var_table, ns),
-            _ => unimplemented!("bitwise and for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!(
+                    "bitwise and is not supported on type {}",
+                    left.ty().to_string(ns)
+                ),
+                &left.ty(),
+                ns,
+            ),
         },
         Expression::BitwiseXor { left, right, .. } => match left.ty() {
             Type::Uint(32) => u32_bitwise_xor(left, right, bin, func_value, var_table, ns),
             Type::Uint(256) => u256_bitwise_xor(left, right, bin, func_value, var_table, ns),
-            _ => unimplemented!("bitwise xor for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!(
+                    "bitwise xor is not supported on type {}",
+                    left.ty().to_string(ns)
+                ),
+                &left.ty(),
+                ns,
+            ),
         },
         Expression::ShiftLeft { left, right, .. } => match left.ty() {
             Type::Uint(32) => u32_shift_left(left, right, bin, func_value, var_table, ns),
             Type::Uint(256) => u256_shift_left(left, right, bin, func_value, var_table, ns),
-            _ => unimplemented!("shift left for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!(
+                    "shift left is not supported on type {}",
+                    left.ty().to_string(ns)
+                ),
+                &left.ty(),
+                ns,
+            ),
         },
         Expression::ShiftRight { left, right, .. } => match left.ty() {
             Type::Uint(32) => u32_shift_right(left, right, bin, func_value, var_table, ns),
             Type::Uint(256) => u256_shift_right(left, right, bin, func_value, var_table, ns),
-            _ => unimplemented!("shift right for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!(
+                    "shift right is not supported on type {}",
+                    left.ty().to_string(ns)
+                ),
+                &left.ty(),
+                ns,
+            ),
         },
         Expression::Equal { left, right, .. } => match left.ty() {
             Type::Address | Type::Contract(_) | Type::Hash => address_or_hash_compare(
@@ -139,7 +221,12 @@ pub fn expression<'a>(
                 ns,
                 IntPredicate::EQ,
             ),
-            _ => unimplemented!("equal for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!("equal is not supported on type {}", left.ty().to_string(ns)),
+                &Type::Bool,
+                ns,
+            ),
         },
         Expression::NotEqual { left, right, .. } => match left.ty() {
             Type::Address | Type::Contract(_) | Type::Hash => address_or_hash_compare(
@@ -169,7 +256,15 @@ pub fn expression<'a>(
                 ns,
                 IntPredicate::NE,
             ),
-            _ => unimplemented!("not equal for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!(
+                    "not equal is not supported on type {}",
+                    left.ty().to_string(ns)
+                ),
+                &Type::Bool,
+                ns,
+            ),
         },
         Expression::More { left, right, .. } => match left.ty() {
             Type::Address | Type::Contract(_) | Type::Hash => address_or_hash_compare(
@@ -200,7 +295,12 @@ pub fn expression<'a>(
                 ns,
                 IntPredicate::UGT,
             ),
-            _ => unimplemented!("more for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!("more is not supported on type {}", left.ty().to_string(ns)),
+                &Type::Bool,
+                ns,
+            ),
         },
         Expression::MoreEqual { left, right, .. } => match left.ty() {
             Type::Address | Type::Contract(_) | Type::Hash => address_or_hash_compare(
@@ -230,7 +330,15 @@ pub fn expression<'a>(
                 ns,
                 IntPredicate::UGE,
             ),
-            _ => unimplemented!("more equal for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!(
+                    "more equal is not supported on type {}",
+                    left.ty().to_string(ns)
+                ),
+                &Type::Bool,
+                ns,
+            ),
         },
         Expression::Less { left, right, .. } => match left.ty() {
             Type::Address | Type::Contract(_) | Type::Hash => address_or_hash_compare(
@@ -260,7 +368,12 @@ pub fn expression<'a>(
                 ns,
                 IntPredicate::ULT,
             ),
-            _ => unimplemented!("less for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!("less is not supported on type {}", left.ty().to_string(ns)),
+                &Type::Bool,
+                ns,
+            ),
         },
         Expression::LessEqual { left, right, .. } => match left.ty() {
             Type::Address | Type::Contract(_) | Type::Hash => address_or_hash_compare(
@@ -290,14 +403,30 @@ pub fn expression<'a>(
                 ns,
                 IntPredicate::ULE,
             ),
-            _ => unimplemented!("less equal for type {:?}", left.ty()),
+            _ => bin.unsupported(
+                left.loc(),
+                format!(
+                    "less equal is not supported on type {}",
+                    left.ty().to_string(ns)
+                ),
+                &Type::Bool,
+                ns,
+            ),
         },
 
         Expression::Not { expr, .. } => logic_not(expr, bin, func_value, var_table, ns),
         Expression::BitwiseNot { expr, .. } => match expr.ty() {
             Type::Uint(32) => u32_bitwise_not(expr, bin, func_value, var_table, ns),
             Type::Uint(256) => u256_bitwise_not(expr, bin, func_value, var_table, ns),
-            _ => unimplemented!("bitwise not for type {:?}", expr.ty()),
+            _ => bin.unsupported(
+                expr.loc(),
+                format!(
+                    "bitwise not is not supported on type {}",
+                    expr.ty().to_string(ns)
+                ),
+                &expr.ty(),
+                ns,
+            ),
         },
         Expression::Or { left, right, .. } => logic_or(left, right, bin, func_value, var_table, ns),
         Expression::And { left, right, .. } => {
@@ -338,7 +467,15 @@ pub fn expression<'a>(
                     let one = expression(&u256_one, bin, func_value, var_table, ns);
                     u256_sub_internal(v, one, bin)
                 }
-                _ => unimplemented!("increment for type {:?}", expr.ty()),
+                _ => bin.unsupported(
+                    expr.loc(),
+                    format!(
+                        "decrement is not supported on type {}",
+                        expr.ty().to_string(ns)
+                    ),
+                    expr.ty().deref_any(),
+                    ns,
+                ),
             };
             match expr.as_ref() {
                 Expression::Variable { var_no, .. } => {
@@ -408,7 +545,15 @@ pub fn expression<'a>(
                     let one = expression(&u256_one, bin, func_value, var_table, ns);
                     u256_add_internal(v, one, bin)
                 }
-                _ => unimplemented!("increment for type {:?}", expr.ty()),
+                _ => bin.unsupported(
+                    expr.loc(),
+                    format!(
+                        "increment is not supported on type {}",
+                        expr.ty().to_string(ns)
+                    ),
+                    expr.ty().deref_any(),
+                    ns,
+                ),
             };
 
             match expr.as_ref() {
@@ -980,7 +1125,12 @@ pub fn expression<'a>(
                 storage_array_push(bin, args, func_value, var_table, ns)
             } else {
                 // TODO Add memory array push support
-                unimplemented!("memory array push")
+                bin.unsupported(
+                    args[0].loc(),
+                    "push is not supported on memory arrays".to_string(),
+                    &Type::Void,
+                    ns,
+                )
             }
         }
         Expression::LibFunction {
@@ -992,7 +1142,12 @@ pub fn expression<'a>(
                 storage_array_pop(bin, args, func_value, var_table, ns)
             } else {
                 // TODO implement memory array pop
-                unimplemented!("memory array pop")
+                bin.unsupported(
+                    args[0].loc(),
+                    "pop is not supported on memory arrays".to_string(),
+                    &expr.ty(),
+                    ns,
+                )
             }
         }
         Expression::LibFunction {
@@ -1139,12 +1294,52 @@ pub fn expression<'a>(
                     bin.builder.build_store(value_gep, value);
                     u256_ptr.into()
                 } else {
-                    unimplemented!("zero extend for type {:?}", to)
+                    bin.unsupported(
+                        expr.loc(),
+                        format!("zero extend to type {} is not supported", to.to_string(ns)),
+                        to,
+                        ns,
+                    )
                 }
             }
-            _ => unimplemented!("zero extend for type {:?}", expr.ty()),
+            _ => bin.unsupported(
+                expr.loc(),
+                format!(
+                    "zero extend is not supported on type {}",
+                    expr.ty().to_string(ns)
+                ),
+                to,
+                ns,
+            ),
         },
-        _ => unimplemented!("{:?}", expr),
+        Expression::Constructor {
+            loc,
+            contract_no,
+            constructor_no,
+            args,
+        } => create_contract(
+            bin,
+            *loc,
+            *contract_no,
+            *constructor_no,
+            args,
+            func_value,
+            var_table,
+            ns,
+        ),
+        Expression::Function { function_no, .. } => bin
+            .module
+            .get_function(&ns.functions[*function_no].name)
+            .unwrap()
+            .as_global_value()
+            .as_pointer_value()
+            .into(),
+        _ => bin.unsupported(
+            expr.loc(),
+            "expression is not supported by code generation".to_string(),
+            &expr.ty(),
+            ns,
+        ),
     }
 }
 
@@ -1188,6 +1383,34 @@ pub fn array_literal_to_memory_array<'a>(
     array_vector.as_basic_value_enum()
 }
 
+/// Report a call through a callee which cannot be generated, and return a
+/// placeholder for each of its return values.
+fn unsupported_call<'a>(
+    bin: &Binary<'a>,
+    function: &Expression,
+    returns: &[Type],
+    ns: &Namespace,
+) -> Vec<BasicValueEnum<'a>> {
+    let message = format!(
+        "call through value of type {} is not supported",
+        function.ty().to_string(ns)
+    );
+    let first = bin.unsupported(
+        function.loc(),
+        message,
+        returns.first().unwrap_or(&Type::Void),
+        ns,
+    );
+    let mut values = vec![first];
+    for ty in returns.iter().skip(1) {
+        values.push(match ty {
+            Type::Void | Type::Unreachable => bin.context.i64_type().const_zero().into(),
+            _ => bin.llvm_var_ty(ty, ns).const_zero(),
+        });
+    }
+    values
+}
+
 pub fn emit_function_call<'a>(
     expr: &Expression,
     bin: &Binary<'a>,
@@ -1196,7 +1419,12 @@ pub fn emit_function_call<'a>(
     ns: &Namespace,
 ) -> Vec<BasicValueEnum<'a>> {
     match expr {
-        Expression::FunctionCall { function, args, .. } => {
+        Expression::FunctionCall {
+            function,
+            args,
+            returns,
+            ..
+        } => {
             if let Expression::Function { function_no, .. } = function.as_ref() {
                 let callee = &ns.functions[*function_no];
                 let callee_value = bin.module.get_function(&callee.name).unwrap();
@@ -1238,8 +1466,53 @@ pub fn emit_function_call<'a>(
                     }
                     returns
                 }
+            } else if let Type::Function {
+                params: param_tys,
+                returns: return_tys,
+            } = function.ty()
+            {
+                // call through a function pointer
+                let callee_ptr = expression(function, bin, func_value, var_table, ns);
+                let mut params = args
+                    .iter()
+                    .map(|a| expression(a, bin, func_value, var_table, ns).into())
+                    .collect::<Vec<BasicMetadataValueEnum>>();
+
+                if return_tys.len() > 1 {
+                    for ty in return_tys.iter() {
+                        params.push(
+                            bin.build_alloca(func_value, bin.llvm_var_ty(ty, ns), "")
+                                .into(),
+                        );
+                    }
+                }
+
+                let ret_value = bin
+                    .builder
+                    .build_indirect_call(
+                        bin.function_type(&param_tys, &return_tys, ns),
+                        callee_ptr.into_pointer_value(),
+                        &params,
+                        "",
+                    )
+                    .try_as_basic_value()
+                    .left();
+                if return_tys.len() <= 1 {
+                    vec![ret_value.unwrap_or(bin.context.i64_type().const_zero().into())]
+                } else {
+                    let mut returns = Vec::new();
+                    for (i, ty) in return_tys.iter().enumerate() {
+                        let val = bin.builder.build_load(
+                            bin.llvm_var_ty(ty, ns),
+                            params[args.len() + i].into_pointer_value(),
+                            "",
+                        );
+                        returns.push(val.into());
+                    }
+                    returns
+                }
             } else {
-                unimplemented!()
+                unsupported_call(bin, function, returns, ns)
             }
         }
 
@@ -1249,35 +1522,12 @@ pub fn emit_function_call<'a>(
             args,
             ..
         } => {
-            if let Expression::ExternalFunction {
-                function_no,
-                address,
-                ..
-            } = function.as_ref()
-            {
+            if let Expression::ExternalFunction { function_no, .. } = function.as_ref() {
                 let dest_func = &ns.functions[*function_no];
-                let tys: Vec<Type> = args.iter().map(|a| a.ty()).collect();
-                let args: Vec<BasicValueEnum> = args
-                    .iter()
-                    .map(|a| expression(a, bin, func_value, var_table, ns))
-                    .collect();
-                let address = expression(address, bin, func_value, var_table, ns);
-                let selector = bin.context.i64_type().const_int(
-                    BigInt::from_bytes_be(Sign::Plus, &dest_func.selector())
-                        .to_u64()
-                        .unwrap(),
-                    false,
-                );
-                let payload = abi_encode_with_selector(
-                    bin,
-                    selector.as_basic_value_enum(),
-                    args,
-                    &tys,
-                    func_value,
-                    ns,
-                );
-                let return_data =
-                    external_call(bin, payload.as_basic_value_enum(), address, CallTy::Regular);
+                let (success, return_data) =
+                    external_function_call(bin, function, args, func_value, var_table, ns);
+                // A failed call reverts the caller too
+                assert_success(bin, success);
                 // If the first element of returns is Void, we can discard the returns
                 if !dest_func.returns.is_empty() && returns[0] != Type::Void {
                     let tys = dest_func
@@ -1290,7 +1540,7 @@ pub fn emit_function_call<'a>(
                     vec![]
                 }
             } else {
-                unimplemented!()
+                unsupported_call(bin, function, returns, ns)
             }
         }
 
@@ -1300,8 +1550,8 @@ pub fn emit_function_call<'a>(
             let args = expression(args, bin, func_value, var_table, ns);
             let address = expression(address, bin, func_value, var_table, ns);
 
-            let return_data = external_call(bin, args, address, ty.clone());
-            vec![return_data]
+            let (success, return_data) = external_call(bin, args, address, ty.clone());
+            vec![success.into(), return_data]
         }
         Expression::LibFunction {
             tys,
@@ -1369,7 +1619,7 @@ pub fn array_subscript<'a>(
     ns: &Namespace,
 ) -> BasicValueEnum<'a> {
     if array_ty.is_mapping() {
-        return mapping_subscript(array, index, index_ty, bin);
+        return mapping_subscript(array, index, index_ty, bin, ns);
     }
     let (array_length, fixed) = match array_ty.deref_any() {
         Type::Array(..) | Type::String | Type::DynamicBytes => match array_ty.array_length() {
@@ -1532,6 +1782,7 @@ pub fn mapping_subscript<'a>(
     index: BasicValueEnum<'a>,
     index_ty: &Type,
     bin: &Binary<'a>,
+    ns: &Namespace,
 ) -> BasicValueEnum<'a> {
     let mut inputs = Vec::with_capacity(2);
     let slot_value = match array.get_type() {
@@ -1553,7 +1804,17 @@ pub fn mapping_subscript<'a>(
         Type::Uint(256) => {
             inputs.push((index, bin.context.i64_type().const_int(8, false)));
         }
-        _ => unimplemented!("mapping index type {:?}", index_ty),
+        _ => {
+            return bin.unsupported(
+                program::Loc::IRgen,
+                format!(
+                    "mapping index of type {} is not supported",
+                    index_ty.to_string(ns)
+                ),
+                &Type::Hash,
+                ns,
+            )
+        }
     }
 
     bin.poseidon_hash(inputs)
@@ -1704,13 +1965,155 @@ fn hash_or_address_to_fields<'a>(
     new_fields.as_basic_value_enum()
 }
 
-/// Call external binary
+/// Address of the system contract which deploys contracts
+const DEPLOYER_ADDRESS: u64 = 0x8005;
+
+/// Function of the deployer which creates a contract at an address derived
+/// from the creator, a salt, the code hash and the calldata the contract is
+/// initialized with, and returns that address. See
+/// `examples/source/system/ContractDeployer.ola`.
+const DEPLOYER_CREATE2: &str = "create2(hash,hash,hash,fields)";
+
+/// Create a contract. The code hash of the new contract is embedded, and the
+/// deployer is called with it and the encoded call to its constructor. The
+/// salt is zero, so a contract creating the same contract twice with the same
+/// arguments fails, as the address is taken. The code hash of a program is the
+/// hash of its text, so it is passed as the raw hash too.
+fn create_contract<'a>(
+    bin: &Binary<'a>,
+    loc: program::Loc,
+    contract_no: usize,
+    constructor_no: Option<usize>,
+    args: &[Expression],
+    func_value: FunctionValue<'a>,
+    var_table: &mut Vartable<'a>,
+    ns: &Namespace,
+) -> BasicValueEnum<'a> {
+    let ty = Type::Contract(contract_no);
+    let code_hash = match bin.code_hash(contract_no, ns) {
+        Ok(code_hash) => code_hash,
+        Err(message) => return bin.unsupported(loc, message, &ty, ns),
+    };
+    let code_hash = bin.number_literal(&Type::Hash, &words_to_bigint(&code_hash), ns);
+    let salt = bin.number_literal(&Type::Hash, &BigInt::from(0), ns);
+
+    let calldata = match constructor_no {
+        Some(function_no) => {
+            let tys: Vec<Type> = args.iter().map(|a| a.ty()).collect();
+            let args: Vec<BasicValueEnum> = args
+                .iter()
+                .map(|a| expression(a, bin, func_value, var_table, ns))
+                .collect();
+            let selector = selector_value(bin, &ns.functions[function_no].selector());
+            abi_encode_with_selector(bin, selector, args, &tys, func_value, ns)
+        }
+        None => abi_encode(bin, vec![], &vec![], func_value, ns),
+    };
+
+    let payload = abi_encode_with_selector(
+        bin,
+        selector_value(bin, &selector(DEPLOYER_CREATE2)),
+        vec![salt, code_hash, code_hash, calldata.as_basic_value_enum()],
+        &vec![Type::Hash, Type::Hash, Type::Hash, Type::DynamicBytes],
+        func_value,
+        ns,
+    );
+    let deployer = bin.number_literal(&Type::Address, &BigInt::from(DEPLOYER_ADDRESS), ns);
+    let (success, return_data) = external_call(
+        bin,
+        payload.as_basic_value_enum(),
+        deployer,
+        CallTy::Regular,
+    );
+    assert_success(bin, success);
+
+    abi_decode(
+        bin,
+        return_data.into_pointer_value(),
+        &vec![ty],
+        func_value,
+        ns,
+    )
+    .remove(0)
+}
+
+fn selector_value<'a>(bin: &Binary<'a>, selector: &[u8]) -> BasicValueEnum<'a> {
+    bin.context
+        .i64_type()
+        .const_int(
+            BigInt::from_bytes_be(Sign::Plus, selector)
+                .to_u64()
+                .unwrap(),
+            false,
+        )
+        .as_basic_value_enum()
+}
+
+/// Convert four field elements, most significant first, to a number
+fn words_to_bigint(words: &[u64; 4]) -> BigInt {
+    words.iter().fold(BigInt::from(0), |acc, word| {
+        (acc << 64) + BigInt::from(*word)
+    })
+}
+
+/// Encode the arguments of a call to a function of another contract, and call
+/// it. Returns whether the call succeeded, and the data it returned or reverted
+/// with.
+pub(crate) fn external_function_call<'a>(
+    bin: &Binary<'a>,
+    function: &Expression,
+    args: &[Expression],
+    func_value: FunctionValue<'a>,
+    var_table: &mut Vartable<'a>,
+    ns: &Namespace,
+) -> (IntValue<'a>, BasicValueEnum<'a>) {
+    let Expression::ExternalFunction {
+        function_no,
+        address,
+        ..
+    } = function
+    else {
+        unreachable!("external call to {:?}", function)
+    };
+
+    let tys: Vec<Type> = args.iter().map(|a| a.ty()).collect();
+    let args: Vec<BasicValueEnum> = args
+        .iter()
+        .map(|a| expression(a, bin, func_value, var_table, ns))
+        .collect();
+    let address = expression(address, bin, func_value, var_table, ns);
+    let callee = &ns.functions[*function_no];
+    let selector = selector_value(bin, &callee.selector());
+    let payload = abi_encode_with_selector(bin, selector, args, &tys, func_value, ns);
+
+    // functions which cannot write storage are called statically
+    let call_type = if callee.mutability.writes_storage() {
+        CallTy::Regular
+    } else {
+        CallTy::Static
+    };
+
+    external_call(bin, payload.as_basic_value_enum(), address, call_type)
+}
+
+/// Revert if an external call did not succeed
+fn assert_success<'a>(bin: &Binary<'a>, success: IntValue<'a>) {
+    bin.builder.build_call(
+        bin.module.get_function("builtin_assert").unwrap(),
+        &[success.into()],
+        "",
+    );
+}
+
+/// Call external binary. The status is 1 if the callee returned and 0 if it
+/// reverted; either way the returned data, or the revert data, is loaded from
+/// the tape.
 fn external_call<'a>(
     bin: &Binary<'a>,
     args: BasicValueEnum<'a>,
     address: BasicValueEnum<'a>,
     call_type: CallTy,
-) -> BasicValueEnum<'a> {
+) -> (IntValue<'a>, BasicValueEnum<'a>) {
     let payload_len = bin.vector_len(args);
     let payload_ptr = bin.vector_data(args);
     // store payload and payload len to tape
@@ -1722,11 +2125,17 @@ fn external_call<'a>(
         CallTy::Static => bin.context.i64_type().const_int(2, false),
     };
 
-    bin.builder.build_call(
-        bin.module.get_function("contract_call").unwrap(),
-        &[address.into(), call_type.into()],
-        "",
-    );
+    let success = bin
+        .builder
+        .build_call(
+            bin.module.get_function("contract_call").unwrap(),
+            &[address.into(), call_type.into()],
+            "success",
+        )
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_int_value();
 
     // length start from index = 0 in tape data
     let length_size = bin.context.i64_type().const_int(1, false);
@@ -1767,7 +2176,7 @@ fn external_call<'a>(
     };
 
     bin.tape_data_load(return_data_start, tape_size);
-    heap_start_ptr.as_basic_value_enum()
+    (success, heap_start_ptr.as_basic_value_enum())
 }
 
 pub(crate) fn debug_print<'a>(
@@ -1920,8 +2329,14 @@ pub(crate) fn debug_print<'a>(
                 debug_print(bin, elem.into(), &field.ty, func_value, ns);
             }
         }
-        Type::Mapping(_) => todo!(),
-        Type::Contract(_) => todo!(),
+        Type::Mapping(_) | Type::Contract(_) => {
+            bin.unsupported(
+                program::Loc::IRgen,
+                format!("printing type {} is not supported", ty.to_string(ns)),
+                &Type::Void,
+                ns,
+            );
+        }
         Type::Ref(ty) => {
             let ref_value = if ty.is_reference_type(ns) && !ty.is_fixed_reference_type() {
                 let loaded_type = bin.llvm_type(ty, ns).ptr_type(AddressSpace::default());
@@ -1938,6 +2353,13 @@ pub(crate) fn debug_print<'a>(
             let value = storage_load(bin, ty, &mut arg.into(), func_value, ns);
             debug_print(bin, value, ty, func_value, ns);
         }
-        _ => unimplemented!("{:?}", ty),
+        _ => {
+            bin.unsupported(
+                program::Loc::IRgen,
+                format!("printing type {} is not supported", ty.to_string(ns)),
+                &Type::Void,
+                ns,
+            );
+        }
     }
 }
@@ -1,9 +1,10 @@
 use crate::irgen::binary::Binary;
 use crate::irgen::statements::statement;
 use crate::sema;
-use crate::sema::ast::{Function, FunctionAttributes, Namespace};
+use crate::sema::ast::{Function, FunctionAttributes, Inline, Namespace};
 use crate::sema::ast::{Statement, Type};
 use indexmap::IndexMap;
+use inkwell::attributes::{Attribute, AttributeLoc};
 use inkwell::values::{BasicValueEnum, FunctionValue};
 
 pub type Vartable<'a> = IndexMap<usize, BasicValueEnum<'a>>;
@@ -12,7 +13,7 @@ pub type Vartable<'a> = IndexMap<usize, BasicValueEnum<'a>>;
 pub(super) fn gen_functions<'a>(
     bin: &mut Binary<'a>,
     contract_no: usize,
-    ns: &'a sema::ast::Namespace,
+    ns: &sema::ast::Namespace,
 ) {
     let mut funcs = Vec::new();
     for func_no in ns.contracts[contract_no].all_functions.keys() {
@@ -35,13 +36,24 @@ pub(super) fn gen_functions<'a>(
             ns,
         );
 
-        if let Some(func) = bin.module.get_function(&func.name) {
+        let func_value = if let Some(func) = bin.module.get_function(&func.name) {
             // must not have a body yet
             assert_eq!(func.get_first_basic_block(), None);
             func
         } else {
             bin.module.add_function(&func.name, ftype, None)
         };
+
+        if let Some(inline) = func.inline {
+            let kind = match inline {
+                Inline::Always => "alwaysinline",
+                Inline::Never => "noinline",
+            };
+            let attribute = bin
+                .context
+                .create_enum_attribute(Attribute::get_named_enum_kind_id(kind), 0);
+            func_value.add_attribute(AttributeLoc::Function, attribute);
+        }
     }
 
     // gen function definition
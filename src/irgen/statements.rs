@@ -1,20 +1,20 @@
 use indexmap::IndexMap;
 use inkwell::basic_block::BasicBlock;
 use inkwell::values::{BasicValue, BasicValueEnum, FunctionValue};
-use inkwell::AddressSpace;
+use inkwell::{AddressSpace, IntPredicate};
 use num_bigint::BigInt;
 use num_traits::ToPrimitive;
-use ola_parser::program;
+use ola_parser::program::{self, CodeLocation};
 
-use super::encoding::abi_encode;
-use super::expression::expression;
+use super::encoding::{abi_decode, abi_encode};
+use super::expression::{expression, external_function_call};
 use super::functions::Vartable;
 use super::storage::{storage_delete, storage_store, uint_to_slot};
 use crate::irgen::binary::Binary;
 use crate::irgen::expression::emit_function_call;
 use crate::sema::ast::{
     self, ArrayLength, DestructureField, Expression, Function, Namespace, RetrieveType, Statement,
-    Type,
+    TryCatch, Type,
 };
 use ola_parser::program::Loc::IRgen;
 
@@ -28,6 +28,8 @@ pub(crate) fn statement<'a>(
     var_table: &mut Vartable<'a>,
     ns: &Namespace,
 ) {
+    bin.loc = stmt.loc();
+
     match stmt {
         Statement::Block { statements, .. } => {
             for stmt in statements {
@@ -292,6 +294,98 @@ pub(crate) fn statement<'a>(
         } => {
             emit_event(loc, event_no, args, bin, func_value, var_table, ns);
         }
+        Statement::TryCatch(_, _, try_stmt) => {
+            try_catch(try_stmt, bin, func_value, func, var_table, ns);
+        }
+    }
+}
+
+/// Generate try-catch. The external call is made directly rather than through
+/// the expression, since a failed call must not revert here.
+fn try_catch<'a>(
+    try_stmt: &TryCatch,
+    bin: &mut Binary<'a>,
+    func_value: FunctionValue<'a>,
+    func: &Function,
+    var_table: &mut Vartable<'a>,
+    ns: &Namespace,
+) {
+    let Expression::ExternalFunctionCall { function, args, .. } = &try_stmt.expr else {
+        unreachable!("try of {:?}", try_stmt.expr)
+    };
+    let (success, return_data) =
+        external_function_call(bin, function, args, func_value, var_table, ns);
+
+    let success = bin.builder.build_int_compare(
+        IntPredicate::NE,
+        success,
+        bin.context.i64_type().const_zero(),
+        "",
+    );
+    let ok = bin.context.append_basic_block(func_value, "try_ok");
+    let catch = bin.context.append_basic_block(func_value, "try_catch");
+    let mut finally: Option<BasicBlock> = None;
+
+    bin.builder.build_conditional_branch(success, ok, catch);
+    bin.builder.position_at_end(ok);
+
+    if !try_stmt.returns.is_empty() {
+        let tys: Vec<Type> = try_stmt
+            .returns
+            .iter()
+            .map(|(_, param)| param.ty.clone())
+            .collect();
+        let values = abi_decode(bin, return_data.into_pointer_value(), &tys, func_value, ns);
+
+        for ((pos, param), value) in try_stmt.returns.iter().zip(values) {
+            if let Some(pos) = pos {
+                let value = if param.ty.is_reference_type(ns) && !param.ty.is_contract_storage() {
+                    value
+                } else {
+                    let alloca = bin.build_alloca(
+                        func_value,
+                        bin.llvm_type(&param.ty, ns),
+                        param.name_as_str(),
+                    );
+                    bin.builder.build_store(alloca, value);
+                    alloca.as_basic_value_enum()
+                };
+                var_table.insert(*pos, value);
+            }
+        }
+    }
+
+    let mut ok_reachable = true;
+    for stmt in &try_stmt.ok_stmt {
+        statement(stmt, bin, func_value, func, var_table, ns);
+        ok_reachable = stmt.reachable();
+    }
+    if ok_reachable {
+        let block = *finally
+            .get_or_insert_with(|| bin.context.append_basic_block(func_value, "try_finally"));
+        bin.builder.build_unconditional_branch(block);
+    }
+
+    bin.builder.position_at_end(catch);
+
+    // the revert data is laid out like any other fields value
+    if let Some(pos) = try_stmt.catch_param_pos {
+        var_table.insert(pos, return_data);
+    }
+
+    let mut catch_reachable = true;
+    for stmt in &try_stmt.catch_stmt {
+        statement(stmt, bin, func_value, func, var_table, ns);
+        catch_reachable = stmt.reachable();
+    }
+    if catch_reachable {
+        let block = *finally
+            .get_or_insert_with(|| bin.context.append_basic_block(func_value, "try_finally"));
+        bin.builder.build_unconditional_branch(block);
+    }
+
+    if let Some(finally) = finally {
+        bin.builder.position_at_end(finally);
     }
 }
 
@@ -412,7 +506,8 @@ fn returns<'a>(
 
     let cast_values: Vec<BasicValueEnum<'_>> = uncast_values
         .iter()
-        .map(|right| try_load_and_cast(&expr.ty(), *right, bin))
+        .zip(expr.tys())
+        .map(|(right, ty)| try_load_and_cast(&ty, *right, bin))
         .collect();
 
     // TODO Should we do type conversion here?
@@ -5,6 +5,7 @@ use crate::sema::ast::{ArrayLength, Contract, Expression, Namespace, RetrieveTyp
 use inkwell::types::BasicTypeEnum;
 use inkwell::values::{BasicValue, BasicValueEnum, FunctionValue, IntValue, PointerValue};
 use num_traits::ToPrimitive;
+use ola_parser::program::Loc;
 
 use super::expression::expression;
 use super::functions::Vartable;
@@ -309,9 +310,12 @@ pub(crate) fn storage_load<'a>(
             );
             ret
         }
-        _ => {
-            unimplemented!("load {}", ty.to_string(ns))
-        }
+        _ => bin.unsupported(
+            Loc::IRgen,
+            format!("storage load of type {} is not supported", ty.to_string(ns)),
+            ty,
+            ns,
+        ),
     }
 }
 
@@ -505,7 +509,15 @@ pub(crate) fn storage_store<'a>(
             storage_store_internal(bin, *slot, dest)
         }
         _ => {
-            unimplemented!("{:?}", ty)
+            bin.unsupported(
+                Loc::IRgen,
+                format!(
+                    "storage store of type {} is not supported",
+                    ty.to_string(ns)
+                ),
+                &Type::Void,
+                ns,
+            );
         }
     }
 }
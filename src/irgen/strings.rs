@@ -1,8 +1,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use inkwell::values::{FunctionValue, PointerValue};
+use ola_parser::program::Loc;
 
-use crate::sema::ast::{Expression, Namespace, StringLocation};
+use crate::sema::ast::{Expression, Namespace, StringLocation, Type};
 
 use super::{binary::Binary, expression::expression, functions::Vartable};
 
@@ -15,7 +16,14 @@ pub(super) fn string_location<'a>(
     ns: &Namespace,
 ) -> PointerValue<'a> {
     match location {
-        StringLocation::CompileTime(..) => unimplemented!("compile time string"),
+        StringLocation::CompileTime(..) => bin
+            .unsupported(
+                Loc::IRgen,
+                "compile time string is not supported".to_string(),
+                &Type::String,
+                ns,
+            )
+            .into_pointer_value(),
         StringLocation::RunTime(e) => {
             expression(e, bin, function, var_table, ns).into_pointer_value()
         }
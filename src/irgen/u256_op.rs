@@ -3,6 +3,7 @@ use crate::irgen::expression::expression;
 use crate::sema::ast::{Expression, Namespace, Type};
 use inkwell::values::{BasicValueEnum, FunctionValue};
 use inkwell::IntPredicate;
+use ola_parser::program::CodeLocation;
 
 use super::functions::Vartable;
 
@@ -111,7 +112,12 @@ pub fn u256_div<'a>(
     var_table: &mut Vartable<'a>,
     ns: &Namespace,
 ) -> BasicValueEnum<'a> {
-    unimplemented!()
+    bin.unsupported(
+        l.loc(),
+        "division is not supported on type u256".to_string(),
+        &Type::Uint(256),
+        ns,
+    )
 }
 
 #[allow(unused_variables)]
@@ -123,7 +129,12 @@ pub fn u256_mod<'a>(
     var_table: &mut Vartable<'a>,
     ns: &Namespace,
 ) -> BasicValueEnum<'a> {
-    unimplemented!()
+    bin.unsupported(
+        l.loc(),
+        "modulo is not supported on type u256".to_string(),
+        &Type::Uint(256),
+        ns,
+    )
 }
 
 pub fn u256_bitwise_and<'a>(
@@ -196,7 +207,12 @@ pub fn u256_shift_left<'a>(
     var_table: &mut Vartable<'a>,
     ns: &Namespace,
 ) -> BasicValueEnum<'a> {
-    unimplemented!()
+    bin.unsupported(
+        l.loc(),
+        "shift left is not supported on type u256".to_string(),
+        &Type::Uint(256),
+        ns,
+    )
 }
 
 #[allow(unused_variables)]
@@ -208,7 +224,12 @@ pub fn u256_shift_right<'a>(
     var_table: &mut Vartable<'a>,
     ns: &Namespace,
 ) -> BasicValueEnum<'a> {
-    unimplemented!()
+    bin.unsupported(
+        l.loc(),
+        "shift right is not supported on type u256".to_string(),
+        &Type::Uint(256),
+        ns,
+    )
 }
 
 pub fn u256_compare<'a>(
@@ -270,7 +291,12 @@ pub fn u256_power<'a>(
     var_table: &mut Vartable<'a>,
     ns: &Namespace,
 ) -> BasicValueEnum<'a> {
-    unimplemented!()
+    bin.unsupported(
+        l.loc(),
+        "power is not supported on type u256".to_string(),
+        &Type::Uint(256),
+        ns,
+    )
 }
 
 pub fn define_u256_add<'a>(bin: &Binary<'a>, func_value: FunctionValue<'a>) {
@@ -9,6 +9,8 @@ pub mod codegen;
 
 pub mod file_resolver;
 
+pub mod manifest;
+
 pub mod standard_json;
 
 pub mod abi;
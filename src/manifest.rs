@@ -0,0 +1,299 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! The `Ola.toml` project manifest, read by `olac build`.
+//!
+//! ```toml
+//! [package]
+//! name = "token"
+//!
+//! [build]
+//! sources = "contracts"
+//! output = "build"
+//! opt-level = 1
+//!
+//! [remappings]
+//! std = "lib/std"
+//!
+//! [dependencies]
+//! math = { path = "../math" }
+//! ```
+//!
+//! Every path is relative to the directory of the manifest. A dependency is
+//! another local Ola package, whose sources can be imported with its name as
+//! the first component of the import path, e.g. `import "math/u256.ola";`.
+
+use crate::codegen::core::pass::OptLevel;
+use crate::file_resolver::FileResolver;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_NAME: &str = "Ola.toml";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub package: Package,
+    #[serde(default)]
+    pub build: Build,
+    /// Import path prefixes mapped to directories
+    #[serde(default)]
+    pub remappings: BTreeMap<String, PathBuf>,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Package {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Build {
+    /// Directory searched for `.ola` files
+    #[serde(default = "default_sources")]
+    pub sources: PathBuf,
+    /// Directory the compiled contracts are written to
+    #[serde(default = "default_output")]
+    pub output: PathBuf,
+    #[serde(default)]
+    pub opt_level: u8,
+}
+
+impl Default for Build {
+    fn default() -> Self {
+        Build {
+            sources: default_sources(),
+            output: default_output(),
+            opt_level: 0,
+        }
+    }
+}
+
+fn default_sources() -> PathBuf {
+    PathBuf::from("src")
+}
+
+fn default_output() -> PathBuf {
+    PathBuf::from("out")
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Dependency {
+    pub path: PathBuf,
+}
+
+impl Manifest {
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let manifest: Manifest = toml::from_str(contents).map_err(|err| err.to_string())?;
+        OptLevel::try_from(manifest.build.opt_level)?;
+        Ok(manifest)
+    }
+}
+
+/// A manifest together with the directory it was found in.
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub root: PathBuf,
+    pub manifest: Manifest,
+}
+
+impl Project {
+    /// Load the manifest at `path`, which is either the manifest itself or
+    /// the directory containing it.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let path = if path.is_dir() {
+            path.join(MANIFEST_NAME)
+        } else {
+            path.to_path_buf()
+        };
+        let contents = fs::read_to_string(&path)
+            .map_err(|err| format!("cannot read manifest '{}': {}", path.display(), err))?;
+        let manifest = Manifest::parse(&contents)
+            .map_err(|err| format!("invalid manifest '{}': {}", path.display(), err))?;
+        let root = path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .canonicalize()
+            .map_err(|err| format!("cannot find '{}': {}", path.display(), err))?;
+
+        Ok(Project { root, manifest })
+    }
+
+    pub fn sources_dir(&self) -> PathBuf {
+        self.root.join(&self.manifest.build.sources)
+    }
+
+    pub fn output_dir(&self) -> PathBuf {
+        self.root.join(&self.manifest.build.output)
+    }
+
+    pub fn opt_level(&self) -> OptLevel {
+        OptLevel::try_from(self.manifest.build.opt_level).unwrap()
+    }
+
+    /// All `.ola` files below the sources directory, relative to it and in a
+    /// stable order
+    pub fn source_files(&self) -> Result<Vec<PathBuf>, String> {
+        let sources = self.sources_dir();
+        let mut files = Vec::new();
+        collect_sources(&sources, &mut files)?;
+        files.sort();
+
+        Ok(files
+            .into_iter()
+            .map(|file| file.strip_prefix(&sources).unwrap().to_path_buf())
+            .collect())
+    }
+
+    /// Create a file resolver which finds the project sources, the remapped
+    /// directories and the sources of all dependencies, including the
+    /// dependencies of dependencies.
+    pub fn resolver(&self) -> Result<FileResolver, String> {
+        let mut maps = BTreeMap::new();
+        self.import_maps(&mut maps, &mut vec![self.root.clone()])?;
+
+        let mut resolver = FileResolver::new();
+        let sources = self.sources_dir();
+        resolver.add_import_path(&sources).map_err(|err| {
+            format!(
+                "cannot add sources directory '{}': {}",
+                sources.display(),
+                err
+            )
+        })?;
+        for (map, path) in maps {
+            resolver
+                .add_import_map(OsString::from(&map), path.clone())
+                .map_err(|err| format!("cannot map '{}' to '{}': {}", map, path.display(), err))?;
+        }
+
+        Ok(resolver)
+    }
+
+    fn import_maps(
+        &self,
+        maps: &mut BTreeMap<String, PathBuf>,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<(), String> {
+        for (map, path) in &self.manifest.remappings {
+            add_map(maps, map, self.root.join(path))?;
+        }
+
+        for (name, dependency) in &self.manifest.dependencies {
+            let dependency = Project::load(&self.root.join(&dependency.path))
+                .map_err(|err| format!("dependency '{}': {}", name, err))?;
+            if stack.contains(&dependency.root) {
+                return Err(format!(
+                    "dependency cycle: package '{}' depends on '{}' which depends on it",
+                    self.manifest.package.name, name
+                ));
+            }
+            add_map(maps, name, dependency.sources_dir())?;
+
+            stack.push(dependency.root.clone());
+            dependency.import_maps(maps, stack)?;
+            stack.pop();
+        }
+
+        Ok(())
+    }
+}
+
+fn add_map(maps: &mut BTreeMap<String, PathBuf>, map: &str, path: PathBuf) -> Result<(), String> {
+    let path = path
+        .canonicalize()
+        .map_err(|err| format!("cannot find '{}' for '{}': {}", path.display(), map, err))?;
+    match maps.get(map) {
+        Some(existing) if existing != &path => Err(format!(
+            "'{}' refers to both '{}' and '{}'",
+            map,
+            existing.display(),
+            path.display()
+        )),
+        _ => {
+            maps.insert(map.to_owned(), path);
+            Ok(())
+        }
+    }
+}
+
+fn collect_sources(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|err| format!("cannot read directory '{}': {}", dir.display(), err))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|err| format!("cannot read directory '{}': {}", dir.display(), err))?
+            .path();
+        if path.is_dir() {
+            collect_sources(&path, files)?;
+        } else if path.extension().map_or(false, |ext| ext == "ola") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write_package(dir: &Path, manifest: &str) {
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join(MANIFEST_NAME), manifest).unwrap();
+    }
+
+    #[test]
+    fn defaults() {
+        let manifest = Manifest::parse("[package]\nname = \"token\"\n").unwrap();
+        assert_eq!(manifest.build.sources, PathBuf::from("src"));
+        assert_eq!(manifest.build.output, PathBuf::from("out"));
+        assert_eq!(manifest.build.opt_level, 0);
+        assert!(manifest.dependencies.is_empty());
+
+        assert!(Manifest::parse("[package]\nname = \"token\"\n[build]\nopt-level = 9\n").is_err());
+        assert!(Manifest::parse("[package]\nname = \"token\"\n[build]\nsource = \"s\"\n").is_err());
+    }
+
+    #[test]
+    fn dependencies() {
+        let dir = std::env::temp_dir().join("ola_manifest_dependencies");
+        let _ = fs::remove_dir_all(&dir);
+        write_package(
+            &dir.join("app"),
+            "[package]\nname = \"app\"\n[dependencies]\nmath = { path = \"../math\" }\n",
+        );
+        write_package(
+            &dir.join("math"),
+            "[package]\nname = \"math\"\n[dependencies]\nbits = { path = \"../bits\" }\n",
+        );
+        write_package(&dir.join("bits"), "[package]\nname = \"bits\"\n");
+        fs::write(dir.join("bits/src/shift.ola"), "").unwrap();
+        fs::write(dir.join("app/src/main.ola"), "").unwrap();
+
+        let project = Project::load(&dir.join("app")).unwrap();
+        assert_eq!(project.source_files().unwrap(), [PathBuf::from("main.ola")]);
+
+        let mut resolver = project.resolver().unwrap();
+        let file = resolver
+            .resolve_file(None, &OsString::from("bits/shift.ola"))
+            .unwrap();
+        assert_eq!(
+            file.full_path,
+            dir.join("bits/src/shift.ola").canonicalize().unwrap()
+        );
+
+        write_package(
+            &dir.join("bits"),
+            "[package]\nname = \"bits\"\n[dependencies]\napp = { path = \"../app\" }\n",
+        );
+        assert!(project.resolver().err().unwrap().contains("cycle"));
+    }
+}
@@ -196,9 +196,7 @@ impl EventDecl {
     }
 
     pub fn selector(&self) -> BigInt {
-        let hash_result = unsafe_poseidon_bytes_auto_padded(self.signature.as_bytes());
-        let hash_bytes: Vec<u8> = hash_result.iter().flat_map(|w| w.to_be_bytes()).collect();
-        BigInt::from_bytes_be(num_bigint::Sign::Plus, &hash_bytes)
+        event_selector(&self.signature)
     }
 }
 
@@ -237,20 +235,62 @@ pub struct Function {
     pub signature: String,
     pub params: Arc<Vec<Parameter>>,
     pub returns: Arc<Vec<Parameter>>,
+    pub mutability: Mutability,
 
     /// The selector (known as discriminator on Solana/Anchor)
     pub selector: Option<Vec<u8>>,
+    /// Set by the `#[inline]` or `#[noinline]` attribute
+    pub inline: Option<Inline>,
     /// Was the function declared with a body
     pub has_body: bool,
     /// The resolved body (if any)
     pub body: Vec<Statement>,
     pub symtable: Symtable,
 
-    pub emits_events: Vec<usize>,
     // For overloaded functions this is the mangled (unique) name.
     pub mangled_name: String,
 }
 
+/// Overrides the cost model of the inliner for calls to a function
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Inline {
+    Always,
+    Never,
+}
+
+/// Whether a function may read or write contract storage
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Mutability {
+    Mutable,
+    /// May read storage, but not write it
+    View(program::Loc),
+    /// May neither read nor write storage
+    Pure(program::Loc),
+}
+
+impl fmt::Display for Mutability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mutability::Mutable => write!(f, "mutable"),
+            Mutability::View(_) => write!(f, "view"),
+            Mutability::Pure(_) => write!(f, "pure"),
+        }
+    }
+}
+
+impl Mutability {
+    /// Can a function with this mutability write storage, emit events or
+    /// create contracts
+    pub fn writes_storage(&self) -> bool {
+        matches!(self, Mutability::Mutable)
+    }
+
+    /// Can a function with this mutability read storage
+    pub fn reads_storage(&self) -> bool {
+        !matches!(self, Mutability::Pure(_))
+    }
+}
+
 /// This trait provides a single interface for fetching paramenters, returns and
 /// the symbol table
 pub trait FunctionAttributes {
@@ -299,27 +339,31 @@ impl Function {
             signature,
             params: Arc::new(params),
             returns: Arc::new(returns),
+            mutability: Mutability::Mutable,
             selector: None,
+            inline: None,
             has_body: false,
             body: Vec::new(),
             symtable: Symtable::new(),
-            emits_events: Vec::new(),
             mangled_name,
         }
     }
 
+    /// A function which takes or returns an internal function can only be
+    /// called from within the contract, so it gets no selector
+    pub fn is_internal(&self, ns: &Namespace) -> bool {
+        self.params
+            .iter()
+            .chain(self.returns.iter())
+            .any(|p| p.ty.contains_function(ns))
+    }
+
     /// Generate selector for this function
     pub fn selector(&self) -> Vec<u8> {
         if let Some(selector) = &self.selector {
             selector.clone()
         } else {
-            let mut res = [0u8; 32];
-
-            let mut hasher = Keccak::v256();
-            hasher.update(self.signature.as_bytes());
-            hasher.finalize(&mut res);
-
-            res[..4].to_vec()
+            selector(&self.signature)
         }
     }
 
@@ -333,6 +377,25 @@ impl Function {
     }
 }
 
+/// Generate the selector for a function signature, which is the first four
+/// bytes of its keccak256 hash
+pub fn selector(signature: &str) -> Vec<u8> {
+    let mut res = [0u8; 32];
+
+    let mut hasher = Keccak::v256();
+    hasher.update(signature.as_bytes());
+    hasher.finalize(&mut res);
+
+    res[..4].to_vec()
+}
+
+/// Generate the topic of an event signature, which is its poseidon hash
+pub fn event_selector(signature: &str) -> BigInt {
+    let hash_result = unsafe_poseidon_bytes_auto_padded(signature.as_bytes());
+    let hash_bytes: Vec<u8> = hash_result.iter().flat_map(|w| w.to_be_bytes()).collect();
+    BigInt::from_bytes_be(num_bigint::Sign::Plus, &hash_bytes)
+}
+
 impl From<&program::Type> for Type {
     fn from(p: &program::Type) -> Type {
         match p {
@@ -340,7 +403,7 @@ impl From<&program::Type> for Type {
             program::Type::Address => Type::Address,
             program::Type::Uint(n) => Type::Uint(*n),
             program::Type::String => Type::String,
-            program::Type::Mapping { .. } => unimplemented!(),
+            program::Type::Mapping { .. } | program::Type::Function { .. } => unimplemented!(),
             program::Type::DynamicBytes => Type::DynamicBytes,
             program::Type::Field => Type::Field,
             program::Type::Hash => Type::Hash,
@@ -445,6 +508,19 @@ pub struct Namespace {
     pub next_id: usize,
 
     pub called_lib_functions: Vec<String>,
+    /// `using` directives at file scope
+    pub using: Vec<Using>,
+}
+
+/// A `using <library> for <type>;` directive, which makes the functions of the
+/// library callable as methods of values of the type
+#[derive(Clone, Debug)]
+pub struct Using {
+    pub loc: program::Loc,
+    pub library_no: usize,
+    pub ty: Type,
+    /// The file a directive at file scope applies to
+    pub file_no: Option<usize>,
 }
 
 pub struct Layout {
@@ -469,9 +545,13 @@ pub struct Contract {
 
     /// List of events this contract may emit
     pub emits_events: Vec<usize>,
+    /// List of contracts this contract creates
+    pub creates: Vec<usize>,
     pub instantiable: bool,
     /// CFG number of this contract's dispatch function
     pub dispatch_no: usize,
+    /// `using` directives in this contract
+    pub using: Vec<Using>,
 }
 
 impl Contract {
@@ -792,6 +872,15 @@ pub enum Expression {
         call_args: CallArgs,
     },
 
+    /// Create a new contract. The arguments are passed to its function
+    /// named `constructor`, if it has one.
+    Constructor {
+        loc: program::Loc,
+        contract_no: usize,
+        constructor_no: Option<usize>,
+        args: Vec<Expression>,
+    },
+
     LibFunction {
         loc: program::Loc,
         tys: Vec<Type>,
@@ -902,6 +991,20 @@ impl Recurse for Expression {
                         e.recurse(cx, f);
                     }
                 }
+                Expression::ExternalFunction { address, .. } => address.recurse(cx, f),
+                Expression::ExternalFunctionCall {
+                    function,
+                    args,
+                    call_args,
+                    ..
+                } => {
+                    function.recurse(cx, f);
+
+                    for e in args {
+                        e.recurse(cx, f);
+                    }
+                    call_args.recurse(cx, f);
+                }
                 Expression::ExternalFunctionCallRaw {
                     address,
                     args,
@@ -913,6 +1016,7 @@ impl Recurse for Expression {
                     call_args.recurse(cx, f);
                 }
                 Expression::LibFunction { args: exprs, .. }
+                | Expression::Constructor { args: exprs, .. }
                 | Expression::List { list: exprs, .. } => {
                     for e in exprs {
                         e.recurse(cx, f);
@@ -975,6 +1079,7 @@ impl CodeLocation for Expression {
             | Expression::FunctionCall { loc, .. }
             | Expression::ExternalFunctionCall { loc, .. }
             | Expression::ExternalFunctionCallRaw { loc, .. }
+            | Expression::Constructor { loc, .. }
             | Expression::Increment { loc, .. }
             | Expression::Decrement { loc, .. }
             | Expression::LibFunction { loc, .. }
@@ -1000,7 +1105,8 @@ impl CodeLocation for Statement {
             | Statement::Continue(loc, ..)
             | Statement::Break(loc, ..)
             | Statement::Return(loc, ..)
-            | Statement::Emit { loc, .. } => *loc,
+            | Statement::Emit { loc, .. }
+            | Statement::TryCatch(loc, ..) => *loc,
         }
     }
 }
@@ -1097,6 +1203,19 @@ pub enum Statement {
         event_loc: program::Loc,
         args: Vec<Expression>,
     },
+    TryCatch(program::Loc, bool, TryCatch),
+}
+
+/// A try statement, which runs `ok_stmt` with the decoded return values if the
+/// external call succeeds and `catch_stmt` with the revert data if it fails
+#[derive(Clone, Debug)]
+pub struct TryCatch {
+    pub expr: Expression,
+    pub returns: Vec<(Option<usize>, Parameter)>,
+    pub ok_stmt: Vec<Statement>,
+    pub catch_param: Option<Parameter>,
+    pub catch_param_pos: Option<usize>,
+    pub catch_stmt: Vec<Statement>,
 }
 
 #[derive(Clone, Debug)]
@@ -1155,6 +1274,15 @@ impl Recurse for Statement {
                         stmt.recurse(cx, f);
                     }
                 }
+                Statement::TryCatch(_, _, try_catch) => {
+                    for stmt in &try_catch.ok_stmt {
+                        stmt.recurse(cx, f);
+                    }
+
+                    for stmt in &try_catch.catch_stmt {
+                        stmt.recurse(cx, f);
+                    }
+                }
                 _ => (),
             }
         }
@@ -1176,7 +1304,8 @@ impl Statement {
             | Statement::While(_, reachable, ..)
             | Statement::DoWhile(_, reachable, ..)
             | Statement::Expression(_, reachable, _)
-            | Statement::For { reachable, .. } => *reachable,
+            | Statement::For { reachable, .. }
+            | Statement::TryCatch(_, reachable, _) => *reachable,
         }
     }
 }
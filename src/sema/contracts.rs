@@ -5,7 +5,7 @@ use num_traits::Zero;
 use ola_parser::program::{self, ContractDefinition};
 use std::collections::BTreeMap;
 
-use super::{ast, functions, statements, variables};
+use super::{ast, functions, mutability, statements, using, variables};
 
 use crate::sema::unused_variable::emit_warning_local_variable;
 
@@ -26,7 +26,9 @@ impl ast::Contract {
             layout: Vec::new(),
             fixed_layout_size: BigInt::zero(),
             emits_events: Vec::new(),
+            creates: Vec::new(),
             instantiable,
+            using: Vec::new(),
         }
     }
 }
@@ -100,6 +102,14 @@ fn resolve_declarations<'a>(
         .initializers
         .extend(variables::contract_variables(def, file_no, contract_no, ns));
 
+    for part in &def.parts {
+        if let program::ContractPart::Using(u) = part {
+            if let Some(u) = using::using_decl(u, file_no, Some(contract_no), ns) {
+                ns.contracts[contract_no].using.push(u);
+            }
+        }
+    }
+
     for part in &def.parts {
         if let program::ContractPart::FunctionDefinition(ref f) = part {
             if let Some(function_no) = functions::contract_function(f, file_no, contract_no, ns) {
@@ -158,10 +168,14 @@ fn resolve_bodies(
             .is_err()
         {
             broken = true;
-        } else if !ns.diagnostics.any_errors() {
-            for variable in ns.functions[function_no].symtable.vars.values() {
-                if let Some(warning) = emit_warning_local_variable(variable, ns) {
-                    ns.diagnostics.push(warning);
+        } else {
+            mutability::mutability(function_no, ns);
+
+            if !ns.diagnostics.any_errors() {
+                for variable in ns.functions[function_no].symtable.vars.values() {
+                    if let Some(warning) = emit_warning_local_variable(variable, ns) {
+                        ns.diagnostics.push(warning);
+                    }
                 }
             }
         }
@@ -169,3 +183,26 @@ fn resolve_bodies(
 
     broken
 }
+
+/// Layout the contract. We determine the layout of variables and deal with
+/// overriding variables
+pub fn layout(contract_no: usize, ns: &mut ast::Namespace) {
+    let mut slot = BigInt::zero();
+
+    for var_no in 0..ns.contracts[contract_no].variables.len() {
+        if !ns.contracts[contract_no].variables[var_no].constant {
+            let ty = ns.contracts[contract_no].variables[var_no].ty.clone();
+
+            ns.contracts[contract_no].layout.push(ast::Layout {
+                slot: slot.clone(),
+                contract_no,
+                var_no,
+                ty: ty.clone(),
+            });
+
+            slot += ty.storage_slots(ns);
+        }
+    }
+
+    ns.contracts[contract_no].fixed_layout_size = slot;
+}
@@ -1037,6 +1037,28 @@ impl Dot {
                 self.add_call_args(call_args, func, ns, node);
             }
 
+            Expression::Constructor {
+                loc,
+                contract_no,
+                args,
+                ..
+            } => {
+                let labels = vec![
+                    format!("constructor contract {}", ns.contracts[*contract_no].name),
+                    ns.loc_to_string(loc),
+                ];
+
+                let node = self.add_node(
+                    Node::new("constructor", labels),
+                    Some(parent),
+                    Some(parent_rel),
+                );
+
+                for (no, arg) in args.iter().enumerate() {
+                    self.add_expression(arg, func, ns, node, format!("arg #{no}"));
+                }
+            }
+
             Expression::LibFunction {
                 loc, kind, args, ..
             } => {
@@ -1302,6 +1324,61 @@ impl Dot {
                         self.add_expression(arg, Some(func), ns, parent, format!("arg #{no}"));
                     }
                 }
+
+                Statement::TryCatch(loc, _, try_catch) => {
+                    let labels = vec![String::from("try"), ns.loc_to_string(loc)];
+
+                    parent =
+                        self.add_node(Node::new("try", labels), Some(parent), Some(parent_rel));
+
+                    self.add_expression(
+                        &try_catch.expr,
+                        Some(func),
+                        ns,
+                        parent,
+                        String::from("expr"),
+                    );
+
+                    for (no, (_, param)) in try_catch.returns.iter().enumerate() {
+                        self.add_node(
+                            Node::new(
+                                "return",
+                                vec![format!(
+                                    "{} {}",
+                                    param.ty.to_string(ns),
+                                    param.name_as_str()
+                                )],
+                            ),
+                            Some(parent),
+                            Some(format!("return #{no}")),
+                        );
+                    }
+
+                    self.add_statement(&try_catch.ok_stmt, func, ns, parent, String::from("ok"));
+
+                    if let Some(param) = &try_catch.catch_param {
+                        self.add_node(
+                            Node::new(
+                                "catch_param",
+                                vec![format!(
+                                    "{} {}",
+                                    param.ty.to_string(ns),
+                                    param.name_as_str()
+                                )],
+                            ),
+                            Some(parent),
+                            Some(String::from("catch param")),
+                        );
+                    }
+
+                    self.add_statement(
+                        &try_catch.catch_stmt,
+                        func,
+                        ns,
+                        parent,
+                        String::from("catch"),
+                    );
+                }
             }
             parent_rel = String::from("next");
         }
@@ -28,6 +28,10 @@ pub fn new(
 
     let ty = ns.resolve_type(context.file_no, context.contract_no, ty, diagnostics)?;
 
+    if let Type::Contract(contract_no) = ty {
+        return new_contract(loc, contract_no, args, context, ns, symtable, diagnostics);
+    }
+
     match &ty {
         Type::Array(ty, dim) => {
             if matches!(dim.last(), Some(ArrayLength::Fixed(_))) {
@@ -92,3 +96,123 @@ pub fn new(
         init: None,
     })
 }
+
+/// Resolve the creation of a contract with `new Contract(args)`. The arguments
+/// are passed to the function named `constructor` of the new contract, so
+/// there must be none if it does not declare one.
+fn new_contract(
+    loc: &program::Loc,
+    contract_no: usize,
+    args: &[program::Expression],
+    context: &mut ExprContext,
+    ns: &mut Namespace,
+    symtable: &mut Symtable,
+    diagnostics: &mut Diagnostics,
+) -> Result<Expression, ()> {
+    let contract = &ns.contracts[contract_no];
+
+    if !contract.instantiable {
+        diagnostics.push(Diagnostic::error(
+            *loc,
+            format!("cannot create {} '{}'", contract.ty, contract.name),
+        ));
+        return Err(());
+    }
+
+    let creator_no = match context.contract_no {
+        Some(creator_no) => creator_no,
+        None => {
+            diagnostics.push(Diagnostic::error(
+                *loc,
+                format!(
+                    "contract '{}' can only be created from within a contract",
+                    contract.name
+                ),
+            ));
+            return Err(());
+        }
+    };
+
+    // A contract embeds the code hash of the contracts it creates, so they
+    // must not create it in turn
+    if circular_reference(contract_no, creator_no, ns) {
+        diagnostics.push(Diagnostic::error(
+            *loc,
+            format!(
+                "circular reference creating contract '{}'",
+                ns.contracts[contract_no].name
+            ),
+        ));
+        return Err(());
+    }
+
+    let constructor_no = ns.contracts[contract_no]
+        .all_functions
+        .keys()
+        .find(|function_no| {
+            let func = &ns.functions[**function_no];
+            func.name == "constructor" && func.has_body
+        })
+        .copied();
+    let params = constructor_no
+        .map(|function_no| ns.functions[function_no].params.clone())
+        .unwrap_or_default();
+
+    if params.len() != args.len() {
+        diagnostics.push(Diagnostic::error(
+            *loc,
+            format!(
+                "constructor of contract '{}' expects {} arguments, {} provided",
+                ns.contracts[contract_no].name,
+                params.len(),
+                args.len()
+            ),
+        ));
+        return Err(());
+    }
+
+    let mut cast_args = Vec::new();
+    for (arg, param) in args.iter().zip(params.iter()) {
+        let expr = expression(
+            arg,
+            context,
+            ns,
+            symtable,
+            diagnostics,
+            ResolveTo::Type(&param.ty),
+        )?;
+        cast_args.push(expr.cast(&arg.loc(), &param.ty, ns, diagnostics)?);
+    }
+
+    if !ns.contracts[creator_no].creates.contains(&contract_no) {
+        ns.contracts[creator_no].creates.push(contract_no);
+    }
+
+    Ok(Expression::Constructor {
+        loc: *loc,
+        contract_no,
+        constructor_no,
+        args: cast_args,
+    })
+}
+
+/// Check whether contract `creator_no` is created by contract `contract_no`,
+/// directly or by any of the contracts it creates.
+fn circular_reference(contract_no: usize, creator_no: usize, ns: &Namespace) -> bool {
+    let mut seen = vec![contract_no];
+    let mut worklist = vec![contract_no];
+
+    while let Some(no) = worklist.pop() {
+        if no == creator_no {
+            return true;
+        }
+        for created in &ns.contracts[no].creates {
+            if !seen.contains(created) {
+                seen.push(*created);
+                worklist.push(*created);
+            }
+        }
+    }
+
+    false
+}
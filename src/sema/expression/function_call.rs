@@ -146,6 +146,75 @@ pub fn function_call_pos_args(
     Err(())
 }
 
+/// Resolve a call through an expression of function type, e.g. a parameter
+/// or variable which holds an internal function
+fn function_pointer_call(
+    loc: &Loc,
+    function: &program::Expression,
+    args: &[program::Expression],
+    context: &mut ExprContext,
+    ns: &mut Namespace,
+    symtable: &mut Symtable,
+    diagnostics: &mut Diagnostics,
+    resolve_to: ResolveTo,
+) -> Result<Expression, ()> {
+    let function = expression(
+        function,
+        context,
+        ns,
+        symtable,
+        diagnostics,
+        ResolveTo::Unknown,
+    )?;
+
+    let (params, returns) = match function.ty() {
+        Type::Function { params, returns } => (params, returns),
+        _ => {
+            diagnostics.push(Diagnostic::error(
+                *loc,
+                "expression is not a function".to_string(),
+            ));
+            return Err(());
+        }
+    };
+
+    if params.len() != args.len() {
+        diagnostics.push(Diagnostic::error(
+            *loc,
+            format!(
+                "fn expects {} arguments, {} provided",
+                params.len(),
+                args.len()
+            ),
+        ));
+        return Err(());
+    }
+
+    let mut matches = true;
+    let mut cast_args = Vec::new();
+
+    for (arg, ty) in args.iter().zip(&params) {
+        matches &= evaluate_argument(arg, context, ns, symtable, ty, diagnostics, &mut cast_args);
+    }
+
+    if !matches {
+        return Err(());
+    }
+
+    let returns = if returns.is_empty() || matches!(resolve_to, ResolveTo::Discard) {
+        vec![Type::Void]
+    } else {
+        returns
+    };
+
+    Ok(Expression::FunctionCall {
+        loc: *loc,
+        returns,
+        function: Box::new(function),
+        args: cast_args,
+    })
+}
+
 /// Resolve a function call with named arguments
 fn function_call_named_args(
     loc: &Loc,
@@ -728,7 +797,131 @@ fn try_type_method(
         _ => (),
     }
 
-    Ok(None)
+    try_using_call(
+        loc,
+        func,
+        args,
+        context,
+        var_expr,
+        ns,
+        symtable,
+        diagnostics,
+        resolve_to,
+    )
+}
+
+/// Resolve `x.f(args)` as `Lib.f(x, args)` when a `using Lib for T;` directive
+/// in the current contract or file attaches `Lib` to the type of `x`
+fn try_using_call(
+    loc: &program::Loc,
+    func: &program::Identifier,
+    args: &[program::Expression],
+    context: &mut ExprContext,
+    var_expr: &Expression,
+    ns: &mut Namespace,
+    symtable: &mut Symtable,
+    diagnostics: &mut Diagnostics,
+    resolve_to: ResolveTo,
+) -> Result<Option<Expression>, ()> {
+    let var_ty = var_expr.ty();
+    let mut function_nos = Vec::new();
+
+    let contract_using = context
+        .contract_no
+        .iter()
+        .flat_map(|contract_no| ns.contracts[*contract_no].using.iter());
+    let file_using = ns
+        .using
+        .iter()
+        .filter(|using| using.file_no == Some(context.file_no));
+
+    for using in contract_using.chain(file_using) {
+        if &using.ty != var_ty.deref_any() {
+            continue;
+        }
+
+        for function_no in available_functions(&func.name, Some(using.library_no), ns) {
+            // the value is passed as the first argument
+            if !ns.functions[function_no].params.is_empty() && !function_nos.contains(&function_no)
+            {
+                function_nos.push(function_no);
+            }
+        }
+    }
+
+    if function_nos.is_empty() {
+        return Ok(None);
+    }
+
+    let mut errors = Diagnostics::default();
+
+    for function_no in &function_nos {
+        let params_len = ns.functions[*function_no].params.len();
+
+        if params_len != args.len() + 1 {
+            errors.push(Diagnostic::error(
+                *loc,
+                format!(
+                    "fn expects {} arguments, {} provided",
+                    params_len - 1,
+                    args.len()
+                ),
+            ));
+            continue;
+        }
+
+        let mut cast_args = Vec::new();
+
+        let ty = ns.functions[*function_no].params[0].ty.clone();
+        let mut matches = var_expr
+            .cast(&var_expr.loc(), &ty, ns, &mut errors)
+            .map(|expr| cast_args.push(expr))
+            .is_ok();
+
+        // check if arguments can be implicitly casted
+        for (i, arg) in args.iter().enumerate() {
+            let ty = ns.functions[*function_no].params[i + 1].ty.clone();
+
+            matches &=
+                evaluate_argument(arg, context, ns, symtable, &ty, &mut errors, &mut cast_args);
+        }
+
+        if !matches {
+            if function_nos.len() > 1 && diagnostics.extend_non_casting(&errors) {
+                return Err(());
+            }
+
+            continue;
+        }
+
+        let func = &ns.functions[*function_no];
+
+        let returns = function_returns(func, resolve_to);
+        let ty = function_type(func, false, resolve_to);
+
+        return Ok(Some(Expression::FunctionCall {
+            loc: *loc,
+            returns,
+            function: Box::new(Expression::Function {
+                loc: *loc,
+                ty,
+                function_no: *function_no,
+                signature: None,
+            }),
+            args: cast_args,
+        }));
+    }
+
+    if function_nos.len() == 1 {
+        diagnostics.extend(errors);
+    } else {
+        diagnostics.push(Diagnostic::error(
+            *loc,
+            "cannot find overloaded fn which matches signature".to_string(),
+        ));
+    }
+
+    Err(())
 }
 
 /// Parse call arguments for external calls
@@ -1152,6 +1345,20 @@ pub fn function_call_expr(
                 return Err(());
             }
 
+            // a local variable or parameter holding a function
+            if symtable.find(&id.name).is_some() {
+                return function_pointer_call(
+                    loc,
+                    ty,
+                    args,
+                    context,
+                    ns,
+                    symtable,
+                    diagnostics,
+                    resolve_to,
+                );
+            }
+
             function_call_pos_args(
                 loc,
                 id,
@@ -1165,11 +1372,24 @@ pub fn function_call_expr(
             )
         }
         _ => {
-            diagnostics.push(Diagnostic::error(
-                *loc,
-                "expression is not a function".to_string(),
-            ));
-            Err(())
+            if context.constant {
+                diagnostics.push(Diagnostic::error(
+                    *loc,
+                    "cannot call function in constant expression".to_string(),
+                ));
+                return Err(());
+            }
+
+            function_pointer_call(
+                loc,
+                ty,
+                args,
+                context,
+                ns,
+                symtable,
+                diagnostics,
+                resolve_to,
+            )
         }
     }
 }
@@ -1435,7 +1655,10 @@ fn preprocess_contract_call(
     let mut name_matches: Vec<usize> = Vec::new();
 
     for function_no in ns.contracts[external_contract_no].all_functions.keys() {
-        if func.name != ns.functions[*function_no].name {
+        // functions which take or return a function cannot be called externally
+        if func.name != ns.functions[*function_no].name
+            || ns.functions[*function_no].is_internal(ns)
+        {
             continue;
         }
 
@@ -50,9 +50,10 @@ impl Expression {
     pub fn tys(&self) -> Vec<Type> {
         match self {
             Expression::LibFunction { tys: returns, .. }
-            | Expression::FunctionCall { returns, .. } => returns.to_vec(),
+            | Expression::FunctionCall { returns, .. }
+            | Expression::ExternalFunctionCall { returns, .. } => returns.to_vec(),
             Expression::List { list, .. } => list.iter().map(|e| e.ty()).collect(),
-            Expression::ExternalFunctionCallRaw { .. } => vec![Type::DynamicBytes],
+            Expression::ExternalFunctionCallRaw { .. } => vec![Type::Bool, Type::DynamicBytes],
             _ => vec![self.ty()],
         }
     }
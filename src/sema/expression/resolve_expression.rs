@@ -376,16 +376,29 @@ pub fn expression(
             Err(())
         }
 
-        program::Expression::FunctionCall(loc, ty, args) => call_expr(
-            loc,
-            ty,
-            args,
-            context,
-            ns,
-            symtable,
-            diagnostics,
-            resolve_to,
-        ),
+        program::Expression::FunctionCall(loc, ty, args) => {
+            let expr = call_expr(
+                loc,
+                ty,
+                args,
+                context,
+                ns,
+                symtable,
+                diagnostics,
+                resolve_to,
+            )?;
+
+            if expr.tys().len() > 1 {
+                diagnostics.push(Diagnostic::error(
+                    *loc,
+                    "destucturing statement needed for function that returns multiple values"
+                        .to_string(),
+                ));
+                return Err(());
+            }
+
+            Ok(expr)
+        }
         program::Expression::ArraySubscript(loc, _, None) => {
             diagnostics.push(Diagnostic::error(
                 *loc,
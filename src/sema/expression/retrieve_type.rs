@@ -50,7 +50,10 @@ impl RetrieveType for Expression {
             | Expression::Cast { to, .. }
             | Expression::BytesCast { to, .. } => to.clone(),
             Expression::StorageArrayLength { ty, .. } => ty.clone(),
-            Expression::ExternalFunctionCallRaw { .. } => Type::DynamicBytes,
+            // The success flag, which comes first of the values a raw call
+            // returns; tys() has the return data as well
+            Expression::ExternalFunctionCallRaw { .. } => Type::Bool,
+            Expression::Constructor { contract_no, .. } => Type::Contract(*contract_no),
             Expression::LibFunction { tys: returns, .. }
             | Expression::FunctionCall { returns, .. }
             | Expression::ExternalFunctionCall { returns, .. } => {
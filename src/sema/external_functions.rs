@@ -8,13 +8,17 @@ use ola_parser::program;
 #[derive(Default)]
 struct CallList {
     pub items: IndexSet<usize>,
+    /// The events emitted by the bodies visited
+    pub events: IndexSet<usize>,
 }
 
 /// After generating the AST for a contract, we should have a list of
 /// all the functions a contract calls in `all_functions`. This should
-/// include any libraries and global functions
+/// include any libraries and global functions. The events emitted by any of
+/// these functions are listed in `emits_events`.
 pub fn add_external_functions(contract_no: usize, ns: &mut Namespace) {
     let mut call_list = CallList::default();
+    let mut events = IndexSet::new();
 
     for var in &ns.contracts[contract_no].variables {
         if let Some(init) = &var.initializer {
@@ -28,6 +32,7 @@ pub fn add_external_functions(contract_no: usize, ns: &mut Namespace) {
             stmt.recurse(&mut call_list, check_statement);
         }
     }
+    events.extend(call_list.events.drain(..));
 
     // we've now collected all the functions which are called.
     while !call_list.items.is_empty() {
@@ -40,6 +45,7 @@ pub fn add_external_functions(contract_no: usize, ns: &mut Namespace) {
                 stmt.recurse(&mut new_call_list, check_statement);
             }
         }
+        events.extend(new_call_list.events.drain(..));
 
         // add functions to contract functions list
         for function_no in &call_list.items {
@@ -61,20 +67,7 @@ pub fn add_external_functions(contract_no: usize, ns: &mut Namespace) {
         }
     }
 
-    // now that we have the final list of functions, we can populate the list
-    // of events this contract emits
-    let mut emits_events = Vec::new();
-
-    for function_no in ns.contracts[contract_no].all_functions.keys() {
-        let func = &ns.functions[*function_no];
-
-        for event_no in &func.emits_events {
-            if !emits_events.contains(event_no) {
-                emits_events.push(*event_no);
-            }
-        }
-    }
-    ns.contracts[contract_no].emits_events = emits_events;
+    ns.contracts[contract_no].emits_events = events.into_iter().collect();
 }
 
 fn check_expression(expr: &Expression, call_list: &mut CallList) -> bool {
@@ -101,12 +94,14 @@ fn check_statement(stmt: &Statement, call_list: &mut CallList) -> bool {
         Statement::If(_, _, cond, _, _) => {
             cond.recurse(call_list, check_expression);
         }
-        Statement::For {
-            cond: Some(cond), ..
-        } => {
-            cond.recurse(call_list, check_expression);
+        Statement::For { cond, next, .. } => {
+            if let Some(cond) = cond {
+                cond.recurse(call_list, check_expression);
+            }
+            if let Some(next) = next {
+                next.recurse(call_list, check_expression);
+            }
         }
-        Statement::For { cond: None, .. } => (),
         Statement::DoWhile(_, _, _, cond) | Statement::While(_, _, cond, _) => {
             cond.recurse(call_list, check_expression);
         }
@@ -135,12 +130,18 @@ fn check_statement(stmt: &Statement, call_list: &mut CallList) -> bool {
             }
         }
 
-        Statement::Emit { args, .. } => {
+        Statement::Emit { event_no, args, .. } => {
+            call_list.events.insert(*event_no);
+
             for e in args {
                 e.recurse(call_list, check_expression);
             }
         }
 
+        Statement::TryCatch(_, _, try_catch) => {
+            try_catch.expr.recurse(call_list, check_expression);
+        }
+
         Statement::Block { .. } | Statement::Break(_) | Statement::Continue(_) => (),
     }
 
@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::{
-    ast::{Diagnostic, Function, Namespace, Parameter, Symbol, Type},
+    ast::{Diagnostic, Function, Inline, Mutability, Namespace, Parameter, Symbol, Type},
     diagnostics::Diagnostics,
 };
 
@@ -70,6 +70,22 @@ pub fn contract_function(
         }
     }
 
+    let mutability = match &func.mutability {
+        Some(program::Mutability::View(loc)) => Mutability::View(*loc),
+        Some(program::Mutability::Pure(loc)) => Mutability::Pure(*loc),
+        None => Mutability::Mutable,
+    };
+
+    if let Some(m) = &func.mutability {
+        if func.name.as_ref().unwrap().name == "constructor" {
+            ns.diagnostics.push(Diagnostic::error(
+                m.loc(),
+                format!("constructor cannot be declared '{}'", m),
+            ));
+            success = false;
+        }
+    }
+
     if !success || !returns_success || !params_success {
         return None;
     }
@@ -84,6 +100,19 @@ pub fn contract_function(
     let mut fdecl = Function::new(func.loc, name, Some(contract_no), params, returns, ns);
 
     fdecl.has_body = func.body.is_some();
+    fdecl.mutability = mutability;
+
+    if !function_attributes(func, &mut fdecl, ns) {
+        return None;
+    }
+
+    if ns.contracts[contract_no].is_interface() && fdecl.is_internal(ns) {
+        ns.diagnostics.push(Diagnostic::error(
+            func.loc,
+            "function in an interface cannot take or return a function".to_string(),
+        ));
+        return None;
+    }
 
     let id = func.name.as_ref().unwrap();
 
@@ -128,6 +157,81 @@ pub fn contract_function(
     Some(func_no)
 }
 
+/// Resolve the attributes written before a function, e.g.
+/// `#[selector(0x12345678)]`
+fn function_attributes(
+    func: &program::FunctionDefinition,
+    fdecl: &mut Function,
+    ns: &mut Namespace,
+) -> bool {
+    let mut success = true;
+
+    for attr in &func.attributes {
+        match attr.name.name.as_str() {
+            "selector" => {
+                if fdecl.selector.is_some() {
+                    ns.diagnostics.push(Diagnostic::error(
+                        attr.loc,
+                        "duplicate selector attribute".to_string(),
+                    ));
+                    success = false;
+                    continue;
+                }
+
+                match attr.args.as_slice() {
+                    [program::Expression::HexNumberLiteral(_, hex)] if hex.len() == 8 => {
+                        fdecl.selector = Some(hex::decode(hex).unwrap());
+                    }
+                    _ => {
+                        ns.diagnostics.push(Diagnostic::error(
+                            attr.loc,
+                            "selector must be 4 bytes in hex, e.g. '#[selector(0x12345678)]'"
+                                .to_string(),
+                        ));
+                        success = false;
+                    }
+                }
+            }
+            name @ ("inline" | "noinline") => {
+                if !attr.args.is_empty() {
+                    ns.diagnostics.push(Diagnostic::error(
+                        attr.loc,
+                        format!("attribute '{}' does not take arguments", name),
+                    ));
+                    success = false;
+                } else if fdecl.inline.is_some() {
+                    ns.diagnostics.push(Diagnostic::error(
+                        attr.loc,
+                        "function can only have one of '#[inline]' and '#[noinline]'".to_string(),
+                    ));
+                    success = false;
+                } else if name == "inline" {
+                    fdecl.inline = Some(Inline::Always);
+                } else {
+                    fdecl.inline = Some(Inline::Never);
+                }
+            }
+            name => {
+                ns.diagnostics.push(Diagnostic::error(
+                    attr.name.loc,
+                    format!("unknown function attribute '{}'", name),
+                ));
+                success = false;
+            }
+        }
+    }
+
+    if fdecl.selector.is_some() && fdecl.is_internal(ns) {
+        ns.diagnostics.push(Diagnostic::error(
+            func.loc,
+            "function which takes or returns a function is not dispatched, so it cannot have a selector".to_string(),
+        ));
+        success = false;
+    }
+
+    success
+}
+
 /// Resolve the parameters
 pub fn resolve_params(
     parameters: &[(program::Loc, Option<program::Parameter>)],
@@ -9,7 +9,7 @@ use std::ffi::OsStr;
 use self::unused_variable::check_unused_events;
 
 pub mod ast;
-pub(crate) mod contracts;
+pub mod contracts;
 pub mod corelib;
 pub mod diagnostics;
 mod dotgraphviz;
@@ -18,12 +18,15 @@ pub(crate) mod expression;
 mod external_functions;
 mod file;
 mod functions;
+mod mutability;
 mod namespace;
+mod selectors;
 mod statements;
 pub mod symtable;
 mod tests;
 mod types;
 mod unused_variable;
+mod using;
 mod variables;
 
 pub type ArrayDimension = Option<(program::Loc, BigInt)>;
@@ -34,6 +37,10 @@ pub fn sema(file: &ResolvedFile, resolver: &mut FileResolver, ns: &mut ast::Name
     sema_file(file, resolver, ns);
 
     if !ns.diagnostics.any_errors() {
+        for contract_no in 0..ns.contracts.len() {
+            selectors::check_selectors(contract_no, ns);
+        }
+
         // Checks for unused variables
         check_unused_namespace_variables(ns);
         check_unused_events(ns);
@@ -92,6 +99,15 @@ fn sema_file(file: &ResolvedFile, resolver: &mut FileResolver, ns: &mut ast::Nam
     // elsewhere.
     types::resolve_fields(fields, file_no, ns);
 
+    // the types and libraries of `using` directives are known by now
+    for part in &pt.0 {
+        if let program::SourceUnitPart::Using(u) = part {
+            if let Some(u) = using::using_decl(u, file_no, None, ns) {
+                ns.using.push(u);
+            }
+        }
+    }
+
     // now resolve the contracts
     contracts::resolve(&contracts_to_resolve, file_no, ns);
 
@@ -0,0 +1,209 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::ast::{
+    CallTy, DestructureField, Diagnostic, Expression, Function, LibFunc, Mutability, Namespace,
+    RetrieveType, Statement,
+};
+use super::diagnostics::Diagnostics;
+use crate::sema::Recurse;
+use ola_parser::program::{self, CodeLocation};
+
+/// State for checking the body of a `view` or `pure` function
+struct StateCheck<'a> {
+    diagnostics: Diagnostics,
+    func: &'a Function,
+    ns: &'a Namespace,
+}
+
+impl<'a> StateCheck<'a> {
+    fn write(&mut self, loc: &program::Loc, what: &str) {
+        if !self.func.mutability.writes_storage() {
+            self.diagnostics.push(Diagnostic::error(
+                *loc,
+                format!(
+                    "function declared '{}' but this {}",
+                    self.func.mutability, what
+                ),
+            ));
+        }
+    }
+
+    fn read(&mut self, loc: &program::Loc, what: &str) {
+        if !self.func.mutability.reads_storage() {
+            self.diagnostics.push(Diagnostic::error(
+                *loc,
+                format!(
+                    "function declared '{}' but this {}",
+                    self.func.mutability, what
+                ),
+            ));
+        }
+    }
+
+    /// A call to a function must not be less restrictive than the caller
+    fn call(&mut self, loc: &program::Loc, function_no: usize) {
+        let callee = &self.ns.functions[function_no];
+
+        if callee.mutability.writes_storage() {
+            self.write(
+                loc,
+                &format!(
+                    "expression calls '{}' which may write to storage",
+                    callee.name
+                ),
+            );
+        } else if callee.mutability.reads_storage() {
+            self.read(
+                loc,
+                &format!(
+                    "expression calls '{}' which may read from storage",
+                    callee.name
+                ),
+            );
+        }
+    }
+}
+
+/// Check that the body of a `view` function never writes to storage, emits an
+/// event or creates a contract, and that the body of a `pure` function does
+/// not read storage either. Calls are checked against the mutability the
+/// callee was declared with.
+pub fn mutability(function_no: usize, ns: &mut Namespace) {
+    let func = &ns.functions[function_no];
+
+    if func.mutability == Mutability::Mutable {
+        return;
+    }
+
+    let mut state = StateCheck {
+        diagnostics: Diagnostics::default(),
+        func,
+        ns,
+    };
+
+    for stmt in &func.body {
+        stmt.recurse(&mut state, check_statement);
+    }
+
+    let diagnostics = state.diagnostics;
+
+    ns.diagnostics.extend(diagnostics);
+}
+
+fn check_statement(stmt: &Statement, state: &mut StateCheck) -> bool {
+    match stmt {
+        Statement::VariableDecl(_, _, _, Some(expr)) => {
+            expr.recurse(state, check_expression);
+        }
+        Statement::VariableDecl(_, _, _, None) => (),
+        Statement::If(_, _, cond, _, _) => {
+            cond.recurse(state, check_expression);
+        }
+        Statement::For { cond, next, .. } => {
+            if let Some(cond) = cond {
+                cond.recurse(state, check_expression);
+            }
+            if let Some(next) = next {
+                next.recurse(state, check_expression);
+            }
+        }
+        Statement::DoWhile(_, _, _, cond) | Statement::While(_, _, cond, _) => {
+            cond.recurse(state, check_expression);
+        }
+        Statement::Expression(_, _, expr) => {
+            expr.recurse(state, check_expression);
+        }
+        Statement::Delete(loc, _, _) => {
+            state.write(loc, "statement deletes from storage");
+        }
+        Statement::Destructure(_, fields, expr) => {
+            expr.recurse(state, check_expression);
+
+            for field in fields {
+                if let DestructureField::Expression(expr) = field {
+                    if expr.ty().is_contract_storage() {
+                        state.write(&expr.loc(), "expression writes to storage");
+                    } else {
+                        expr.recurse(state, check_expression);
+                    }
+                }
+            }
+        }
+        Statement::Return(_, Some(expr)) => {
+            expr.recurse(state, check_expression);
+        }
+        Statement::Return(_, None) => (),
+        Statement::Emit { loc, args, .. } => {
+            state.write(loc, "statement emits an event");
+
+            for e in args {
+                e.recurse(state, check_expression);
+            }
+        }
+        Statement::TryCatch(_, _, try_catch) => {
+            try_catch.expr.recurse(state, check_expression);
+        }
+        Statement::Block { .. } | Statement::Break(_) | Statement::Continue(_) => (),
+    }
+
+    true
+}
+
+fn check_expression(expr: &Expression, state: &mut StateCheck) -> bool {
+    match expr {
+        Expression::StorageVariable { loc, .. } => {
+            state.read(loc, "expression reads from storage");
+        }
+        Expression::Assign {
+            loc, left, right, ..
+        } if left.ty().is_contract_storage() => {
+            state.write(loc, "expression writes to storage");
+
+            right.recurse(state, check_expression);
+            return false;
+        }
+        Expression::Increment { loc, expr, .. } | Expression::Decrement { loc, expr, .. }
+            if expr.ty().is_contract_storage() =>
+        {
+            state.write(loc, "expression writes to storage");
+            return false;
+        }
+        Expression::LibFunction {
+            loc,
+            kind: LibFunc::ArrayPush | LibFunc::ArrayPop,
+            args,
+            ..
+        } if args[0].ty().is_contract_storage() => {
+            state.write(loc, "expression writes to storage");
+
+            for e in &args[1..] {
+                e.recurse(state, check_expression);
+            }
+            return false;
+        }
+        Expression::FunctionCall { loc, function, .. }
+        | Expression::ExternalFunctionCall { loc, function, .. } => {
+            if let Expression::Function { function_no, .. }
+            | Expression::ExternalFunction { function_no, .. } = function.as_ref()
+            {
+                state.call(loc, *function_no);
+            } else {
+                // the function behind a pointer is not known
+                state.write(
+                    loc,
+                    "expression calls a function pointer which may write to storage",
+                );
+            }
+        }
+        Expression::ExternalFunctionCallRaw { loc, ty, .. } => match ty {
+            CallTy::Static => state.read(loc, "call may read from storage"),
+            CallTy::Regular | CallTy::Delegate => state.write(loc, "call may write to storage"),
+        },
+        Expression::Constructor { loc, .. } => {
+            state.write(loc, "expression creates a contract");
+        }
+        _ => (),
+    }
+
+    true
+}
@@ -35,6 +35,7 @@ impl Namespace {
             diagnostics: Diagnostics::default(),
             next_id: 0,
             called_lib_functions: Vec::new(),
+            using: Vec::new(),
         }
     }
 
@@ -685,6 +686,36 @@ impl Namespace {
                         }),
                     }
                 }
+                program::Type::Function { params, returns } => {
+                    let mut resolve_list = |list: &program::ParameterList, what: &str| {
+                        let mut tys = Vec::new();
+
+                        for (loc, p) in list {
+                            match p {
+                                Some(p) => tys.push(self.resolve_type(
+                                    file_no,
+                                    contract_no,
+                                    &p.ty,
+                                    diagnostics,
+                                )?),
+                                None => {
+                                    diagnostics.push(Diagnostic::decl_error(
+                                        *loc,
+                                        format!("missing {} type", what),
+                                    ));
+                                    return Err(());
+                                }
+                            }
+                        }
+
+                        Ok(tys)
+                    };
+
+                    Type::Function {
+                        params: resolve_list(params, "parameter")?,
+                        returns: resolve_list(returns, "return")?,
+                    }
+                }
 
                 _ => Type::from(ty),
             };
@@ -0,0 +1,44 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::ast::{Diagnostic, Namespace};
+use super::diagnostics::Diagnostics;
+use std::collections::HashMap;
+
+/// The dispatcher of a contract switches on the selectors of its functions,
+/// so no two of them may share a selector. This includes the functions of
+/// libraries it calls, which are dispatched as well.
+pub fn check_selectors(contract_no: usize, ns: &mut Namespace) {
+    let mut selectors: HashMap<Vec<u8>, usize> = HashMap::new();
+    let mut diagnostics = Diagnostics::default();
+
+    for function_no in ns.contracts[contract_no].all_functions.keys() {
+        let func = &ns.functions[*function_no];
+
+        if func.is_internal(ns) {
+            continue;
+        }
+
+        let selector = func.selector();
+
+        if let Some(other_no) = selectors.get(&selector) {
+            let other = &ns.functions[*other_no];
+
+            diagnostics.push(Diagnostic::error_with_note(
+                func.loc,
+                format!(
+                    "selector 0x{} of function '{}' is already used by function '{}' in contract '{}'",
+                    hex::encode(&selector),
+                    func.signature,
+                    other.signature,
+                    ns.contracts[contract_no].name,
+                ),
+                other.loc,
+                format!("location of function '{}'", other.signature),
+            ));
+        } else {
+            selectors.insert(selector, *function_no);
+        }
+    }
+
+    ns.diagnostics.extend(diagnostics);
+}
@@ -659,10 +659,223 @@ fn statement(
             Ok(true)
         }
 
+        program::Statement::Try(loc, expr, returns, ok, catch) => {
+            let (try_catch, reachable) = try_catch(
+                loc,
+                expr,
+                returns,
+                ok,
+                catch,
+                context,
+                symtable,
+                loops,
+                ns,
+                diagnostics,
+            )?;
+            res.push(Statement::TryCatch(*loc, reachable, try_catch));
+
+            Ok(reachable)
+        }
+
         program::Statement::Error(_) => unimplemented!(),
     }
 }
 
+/// Resolve try statement
+#[allow(clippy::too_many_arguments)]
+fn try_catch(
+    loc: &program::Loc,
+    expr: &program::Expression,
+    returns: &[(program::Loc, Option<program::Parameter>)],
+    ok: &program::Statement,
+    catch: &program::CatchClause,
+    context: &mut ExprContext,
+    symtable: &mut Symtable,
+    loops: &mut LoopScopes,
+    ns: &mut Namespace,
+    diagnostics: &mut Diagnostics,
+) -> Result<(TryCatch, bool), ()> {
+    let expr = match expr.remove_parenthesis() {
+        program::Expression::FunctionCall(loc, ty, args) => function_call_expr(
+            loc,
+            ty,
+            args,
+            context,
+            ns,
+            symtable,
+            diagnostics,
+            ResolveTo::Unknown,
+        )?,
+        program::Expression::NamedFunctionCall(loc, ty, args) => named_function_call_expr(
+            loc,
+            ty,
+            args,
+            context,
+            ns,
+            symtable,
+            diagnostics,
+            ResolveTo::Unknown,
+        )?,
+        _ => {
+            diagnostics.push(Diagnostic::error(
+                expr.loc(),
+                "try only supports external calls".to_string(),
+            ));
+            return Err(());
+        }
+    };
+
+    let Expression::ExternalFunctionCall {
+        returns: func_returns,
+        ..
+    } = &expr
+    else {
+        diagnostics.push(Diagnostic::error(
+            expr.loc(),
+            "try only supports external calls".to_string(),
+        ));
+        return Err(());
+    };
+    check_function_call(ns, &expr, symtable);
+
+    let func_returns = if func_returns == &[Type::Void] {
+        Vec::new()
+    } else {
+        func_returns.clone()
+    };
+
+    if !returns.is_empty() && returns.len() != func_returns.len() {
+        diagnostics.push(Diagnostic::error(
+            *loc,
+            format!(
+                "try returns list has {} entries while function returns {} values",
+                returns.len(),
+                func_returns.len()
+            ),
+        ));
+        return Err(());
+    }
+
+    symtable.new_scope();
+
+    let mut params = Vec::new();
+
+    for ((loc, param), ret_ty) in returns.iter().zip(func_returns.iter()) {
+        let Some(param) = param else {
+            diagnostics.push(Diagnostic::error(*loc, "missing return type".to_string()));
+            return Err(());
+        };
+
+        let (ty, ty_loc) =
+            resolve_var_decl_ty(&param.ty, &param.storage, context, ns, diagnostics)?;
+
+        Expression::Variable {
+            loc: param.loc,
+            ty: ret_ty.clone(),
+            var_no: 0,
+        }
+        .cast(&param.loc, ty.deref_memory(), ns, diagnostics)?;
+
+        let pos = match &param.name {
+            Some(name) => {
+                let pos = symtable.add(
+                    name,
+                    ty.clone(),
+                    ns,
+                    VariableInitializer::Ola(None),
+                    VariableUsage::TryCatchReturns,
+                    param.storage.clone(),
+                );
+                ns.check_shadowing(context.file_no, context.contract_no, name);
+                pos
+            }
+            None => None,
+        };
+
+        params.push((
+            pos,
+            Parameter {
+                loc: param.loc,
+                ty,
+                ty_loc: Some(ty_loc),
+                id: param.name.clone(),
+                indexed: false,
+                infinite_size: false,
+                recursive: false,
+            },
+        ));
+    }
+
+    let mut ok_stmt = Vec::new();
+    let mut reachable = statement(ok, &mut ok_stmt, context, symtable, loops, ns, diagnostics)?;
+
+    symtable.leave_scope();
+    symtable.new_scope();
+
+    let mut catch_param = None;
+    let mut catch_param_pos = None;
+
+    if let Some(param) = &catch.param {
+        let (ty, ty_loc) =
+            resolve_var_decl_ty(&param.ty, &param.storage, context, ns, diagnostics)?;
+
+        if ty != Type::DynamicBytes {
+            diagnostics.push(Diagnostic::error(
+                param.ty.loc(),
+                format!("catch can only take 'fields', not '{}'", ty.to_string(ns)),
+            ));
+            return Err(());
+        }
+
+        if let Some(name) = &param.name {
+            catch_param_pos = symtable.add(
+                name,
+                ty.clone(),
+                ns,
+                VariableInitializer::Ola(None),
+                VariableUsage::TryCatchErrorBytes,
+                param.storage.clone(),
+            );
+            ns.check_shadowing(context.file_no, context.contract_no, name);
+        }
+
+        catch_param = Some(Parameter {
+            loc: param.loc,
+            ty,
+            ty_loc: Some(ty_loc),
+            id: param.name.clone(),
+            indexed: false,
+            infinite_size: false,
+            recursive: false,
+        });
+    }
+
+    let mut catch_stmt = Vec::new();
+    reachable |= statement(
+        &catch.body,
+        &mut catch_stmt,
+        context,
+        symtable,
+        loops,
+        ns,
+        diagnostics,
+    )?;
+
+    symtable.leave_scope();
+
+    Ok((
+        TryCatch {
+            expr,
+            returns: params,
+            ok_stmt,
+            catch_param,
+            catch_param_pos,
+            catch_stmt,
+        },
+        reachable,
+    ))
+}
+
 /// Resolve emit event
 fn emit_event(
     loc: &program::Loc,
@@ -672,18 +885,12 @@ fn emit_event(
     ns: &mut Namespace,
     diagnostics: &mut Diagnostics,
 ) -> Result<Statement, ()> {
-    let function_no = context.function_no.unwrap();
     let to_stmt =
-        |ns: &mut Namespace, event_no: usize, event_loc: program::Loc, args: Vec<Expression>| {
-            if !ns.functions[function_no].emits_events.contains(&event_no) {
-                ns.functions[function_no].emits_events.push(event_no);
-            }
-            Statement::Emit {
-                loc: *loc,
-                event_no,
-                event_loc,
-                args,
-            }
+        |event_no: usize, event_loc: program::Loc, args: Vec<Expression>| Statement::Emit {
+            loc: *loc,
+            event_no,
+            event_loc,
+            args,
         };
 
     match ty {
@@ -718,7 +925,7 @@ fn emit_event(
 
             if emit_diagnostics.any_errors() {
                 diagnostics.extend(emit_diagnostics);
-                return Ok(to_stmt(ns, event_nos[0], event_loc, valid_args));
+                return Ok(to_stmt(event_nos[0], event_loc, valid_args));
             }
 
             let mut resolved_events = Vec::new();
@@ -789,7 +996,7 @@ fn emit_event(
                     resolved_events.push((
                         *event_no,
                         candidate_diagnostics,
-                        to_stmt(ns, *event_no, event_loc, cast_args),
+                        to_stmt(*event_no, event_loc, cast_args),
                     ));
                 }
             }
@@ -869,7 +1076,7 @@ fn emit_event(
 
             if emit_diagnostics.any_errors() {
                 diagnostics.extend(emit_diagnostics);
-                return Ok(to_stmt(ns, event_nos[0], event_loc, valid_args));
+                return Ok(to_stmt(event_nos[0], event_loc, valid_args));
             }
 
             for event_no in &event_nos {
@@ -964,7 +1171,7 @@ fn emit_event(
                     resolved_events.push((
                         *event_no,
                         candidate_diagnostics,
-                        to_stmt(ns, *event_no, event_loc, cast_args),
+                        to_stmt(*event_no, event_loc, cast_args),
                     ));
                 }
             }
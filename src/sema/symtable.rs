@@ -68,6 +68,8 @@ pub enum VariableUsage {
     AnonymousReturnVariable,
     LocalVariable,
     DestructureVariable,
+    TryCatchReturns,
+    TryCatchErrorBytes,
 }
 
 #[derive(Debug, Clone)]
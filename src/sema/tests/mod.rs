@@ -438,3 +438,485 @@ fn test_contract_address() {
 
     assert_eq!(errors.len(), 0);
 }
+
+#[test]
+fn test_contract_creation() {
+    let file = r#"
+    contract Token {
+        u32 supply;
+
+        fn constructor(u32 _supply) {
+            supply = _supply;
+        }
+    }
+
+    contract Factory {
+        fn create(u32 supply) -> (Token) {
+            return new Token(supply);
+        }
+    }
+        "#;
+    let ns = parse(file);
+    assert_eq!(ns.diagnostics.errors().len(), 0);
+    assert_eq!(ns.contracts[1].creates, vec![0]);
+
+    let file = r#"
+    contract Token {
+        fn mint() {}
+    }
+
+    contract Factory {
+        fn create() -> (Token) {
+            return new Token(1);
+        }
+    }
+        "#;
+    let ns = parse(file);
+    let errors = ns.diagnostics.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].message,
+        "constructor of contract 'Token' expects 0 arguments, 1 provided"
+    );
+}
+
+#[test]
+fn test_circular_contract_creation() {
+    let file = r#"
+    contract A {
+        fn create() {
+            B b = new B();
+        }
+    }
+
+    contract B {
+        fn create() {
+            C c = new C();
+        }
+    }
+
+    contract C {
+        fn create() {
+            A a = new A();
+        }
+    }
+        "#;
+    let ns = parse(file);
+    let errors = ns.diagnostics.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].message,
+        "circular reference creating contract 'A'"
+    );
+}
+
+#[test]
+fn test_try_catch() {
+    let file = r#"
+    interface Adder {
+        fn add(u32 a, u32 b) -> (u32);
+    }
+
+    contract Caller {
+        fn try_add(Adder adder, u32 a, u32 b) -> (u32) {
+            try adder.add(a, b) -> (u32 r) {
+                return r;
+            } catch (fields err) {
+                assert(err.length == 0);
+                return 0;
+            }
+        }
+
+        fn raw_call(address to, fields data) -> (bool) {
+            (bool success, fields returndata) = to.call(data);
+            assert(returndata.length == 0);
+            return success;
+        }
+    }
+        "#;
+    let ns = parse(file);
+    assert_eq!(ns.diagnostics.errors().len(), 0);
+
+    let file = r#"
+    interface Adder {
+        fn add(u32 a, u32 b) -> (u32);
+    }
+
+    contract Caller {
+        fn sum(u32 a, u32 b) -> (u32) {
+            return a + b;
+        }
+
+        fn too_many_returns(Adder adder, u32 a, u32 b) {
+            try adder.add(a, b) -> (u32 r, u32 s) {
+            } catch {
+            }
+        }
+
+        fn internal_call(u32 a, u32 b) {
+            try sum(a, b) {
+            } catch {
+            }
+        }
+
+        fn catch_type(Adder adder, u32 a, u32 b) {
+            try adder.add(a, b) {
+            } catch (u32 err) {
+            }
+        }
+
+        fn raw_call(address to, fields data) {
+            fields returndata = to.call(data);
+        }
+    }
+        "#;
+    let ns = parse(file);
+    let errors = ns.diagnostics.errors();
+    assert_eq!(errors.len(), 4);
+    assert_eq!(
+        errors[0].message,
+        "try returns list has 2 entries while function returns 1 values"
+    );
+    assert_eq!(errors[1].message, "try only supports external calls");
+    assert_eq!(errors[2].message, "catch can only take 'fields', not 'u32'");
+    assert_eq!(
+        errors[3].message,
+        "destucturing statement needed for function that returns multiple values"
+    );
+}
+
+#[test]
+fn test_mutability() {
+    let file = r#"
+    interface Counter {
+        fn get() view -> (u32);
+    }
+
+    contract Store {
+        u32 count;
+
+        fn get() view -> (u32) {
+            return count + double(1);
+        }
+
+        fn double(u32 a) pure -> (u32) {
+            return a * 2;
+        }
+
+        fn remote(Counter counter) view -> (u32) {
+            return counter.get();
+        }
+
+        fn increment() {
+            count = get() + 1;
+        }
+    }
+        "#;
+    let ns = parse(file);
+    assert_eq!(ns.diagnostics.errors().len(), 0);
+    assert_eq!(
+        ns.functions
+            .iter()
+            .map(|func| func.mutability.to_string())
+            .collect::<Vec<String>>(),
+        ["view", "view", "pure", "view", "mutable"]
+    );
+
+    let file = r#"
+    contract Store {
+        u32 count;
+
+        event Stored(u32 value);
+
+        fn write() view {
+            count = 1;
+        }
+
+        fn increment() view {
+            count++;
+        }
+
+        fn emits() view {
+            emit Stored(1);
+        }
+
+        fn read() pure -> (u32) {
+            return count;
+        }
+
+        fn calls_view() pure -> (u32) {
+            return read_view();
+        }
+
+        fn read_view() view -> (u32) {
+            return count;
+        }
+
+        fn calls_mutable() view {
+            write_count();
+        }
+
+        fn write_count() {
+            count = 2;
+        }
+
+        fn static_call(address to, fields data) pure {
+            (bool success, fields returndata) = to.staticcall(data);
+        }
+
+        fn constructor() view {
+        }
+    }
+        "#;
+    let ns = parse(file);
+    let errors = ns.diagnostics.errors();
+    assert_eq!(errors.len(), 8);
+    assert_eq!(
+        errors[0].message,
+        "function declared 'view' but this expression writes to storage"
+    );
+    assert_eq!(
+        errors[1].message,
+        "function declared 'view' but this expression writes to storage"
+    );
+    assert_eq!(
+        errors[2].message,
+        "function declared 'view' but this statement emits an event"
+    );
+    assert_eq!(
+        errors[3].message,
+        "function declared 'pure' but this expression reads from storage"
+    );
+    assert_eq!(
+        errors[4].message,
+        "function declared 'pure' but this expression calls 'read_view' which may read from storage"
+    );
+    assert_eq!(
+        errors[5].message,
+        "function declared 'view' but this expression calls 'write_count' which may write to storage"
+    );
+    assert_eq!(
+        errors[6].message,
+        "function declared 'pure' but this call may read from storage"
+    );
+    assert_eq!(errors[7].message, "constructor cannot be declared 'view'");
+}
+
+#[test]
+fn test_function_pointer() {
+    let file = r#"
+    contract Apply {
+        fn(u32) -> (u32) stored;
+
+        fn double(u32 a) -> (u32) {
+            return a * 2;
+        }
+
+        fn apply(fn(u32) -> (u32) f, u32 a) -> (u32) {
+            return f(a);
+        }
+
+        fn run() -> (u32) {
+            fn(u32) -> (u32) f = double;
+            return apply(f, 3);
+        }
+
+        fn wrong_args(fn(u32) -> (u32) f) -> (u32) {
+            return f(1, 2);
+        }
+
+        fn not_function(u32 a) -> (u32) {
+            return a(1);
+        }
+
+        fn pure_call(fn(u32) -> (u32) f) pure -> (u32) {
+            return f(1);
+        }
+    }
+        "#;
+    let ns = parse(file);
+    let errors = ns.diagnostics.errors();
+    assert_eq!(errors.len(), 4);
+    assert_eq!(
+        errors[0].message,
+        "variable of type 'fn(u32) -> (u32)' cannot be declared outside a function"
+    );
+    assert_eq!(errors[1].message, "fn expects 1 arguments, 2 provided");
+    assert_eq!(errors[2].message, "expression is not a function");
+    assert_eq!(
+        errors[3].message,
+        "function declared 'pure' but this expression calls a function pointer which may write to storage"
+    );
+}
+
+#[test]
+fn test_using() {
+    let file = r#"
+    library SafeMath {
+        fn add(u32 a, u32 b) -> (u32) {
+            u32 c = a + b;
+            assert(c >= a);
+            return c;
+        }
+    }
+
+    library Points {
+        struct Point {
+            u32 x;
+            u32 y;
+        }
+
+        fn sum(Point p) -> (u32) {
+            return p.x + p.y;
+        }
+    }
+
+    using SafeMath for u32;
+
+    contract Calc {
+        using Points for Points.Point;
+
+        fn valid(u32 a) -> (u32) {
+            Points.Point p = Points.Point(a, 2);
+            return a.add(1).add(p.sum());
+        }
+
+        fn wrong_args(u32 a) -> (u32) {
+            return a.add();
+        }
+
+        fn not_attached(u256 a) -> (u256) {
+            return a.add(1);
+        }
+    }
+
+    contract Other {
+        using Calc for u32;
+
+        fn other(Points.Point p) -> (u32) {
+            return p.sum();
+        }
+    }
+        "#;
+    let ns = parse(file);
+    let errors = ns.diagnostics.errors();
+    assert_eq!(errors.len(), 4);
+    assert_eq!(errors[0].message, "fn expects 1 arguments, 0 provided");
+    assert_eq!(errors[1].message, "method 'add' does not exist");
+    assert_eq!(errors[2].message, "'Calc' is not a library");
+    assert_eq!(errors[3].message, "method 'sum' does not exist");
+}
+
+#[test]
+fn test_selectors() {
+    let file = r#"
+    contract Token {
+        #[selector(0x12345678)]
+        fn transfer(address to, u32 amount) -> (bool) {
+            return true;
+        }
+
+        #[selector(0x12345678)]
+        fn approve(address spender, u32 amount) -> (bool) {
+            return true;
+        }
+
+        #[selector(0x9abcdef0)]
+        fn balance() view -> (u32) {
+            return 0;
+        }
+    }
+
+    contract Attributes {
+        #[selector(0x1234)]
+        fn short() {}
+
+        #[selector(0x12345678)]
+        #[selector(0x9abcdef0)]
+        fn twice() {}
+
+        #[payable]
+        fn unknown() {}
+
+        #[selector(0x12345678)]
+        fn internal(fn(u32) -> (u32) f) {}
+    }
+        "#;
+    let ns = parse(file);
+    let errors = ns.diagnostics.errors();
+    assert_eq!(errors.len(), 4);
+    assert_eq!(
+        errors[0].message,
+        "selector must be 4 bytes in hex, e.g. '#[selector(0x12345678)]'"
+    );
+    assert_eq!(errors[1].message, "duplicate selector attribute");
+    assert_eq!(errors[2].message, "unknown function attribute 'payable'");
+    assert_eq!(
+        errors[3].message,
+        "function which takes or returns a function is not dispatched, so it cannot have a selector"
+    );
+
+    // collisions are only checked once everything else resolves
+    let ns = parse(&file[..file.find("contract Attributes").unwrap()]);
+    let errors = ns.diagnostics.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].message,
+        "selector 0x12345678 of function 'approve(address,u32)' is already used by function 'transfer(address,u32)' in contract 'Token'"
+    );
+    assert_eq!(errors[0].notes[0].message, "location of function 'transfer(address,u32)'");
+
+    let token = &ns.contracts[0];
+    let balance = token
+        .functions
+        .iter()
+        .map(|function_no| &ns.functions[*function_no])
+        .find(|func| func.name == "balance")
+        .unwrap();
+    assert_eq!(balance.selector(), [0x9a, 0xbc, 0xde, 0xf0]);
+}
+
+#[test]
+fn test_inline_attributes() {
+    let file = r#"
+    contract Math {
+        #[inline]
+        fn square(u32 x) -> (u32) {
+            return x * x;
+        }
+
+        #[noinline]
+        fn cube(u32 x) -> (u32) {
+            return x * square(x);
+        }
+
+        #[inline]
+        #[noinline]
+        fn both() {}
+
+        #[inline(always)]
+        fn args() {}
+    }
+        "#;
+    let ns = parse(file);
+    let errors = ns.diagnostics.errors();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(
+        errors[0].message,
+        "function can only have one of '#[inline]' and '#[noinline]'"
+    );
+    assert_eq!(
+        errors[1].message,
+        "attribute 'inline' does not take arguments"
+    );
+
+    let inline = |name: &str| {
+        ns.functions
+            .iter()
+            .find(|func| func.name == name)
+            .unwrap()
+            .inline
+    };
+    assert_eq!(inline("square"), Some(ast::Inline::Always));
+    assert_eq!(inline("cube"), Some(ast::Inline::Never));
+}
@@ -749,7 +749,7 @@ impl Type {
             }
             Type::ExternalFunction { params, returns } | Type::Function { params, returns } => {
                 let mut s = format!(
-                    "fn({}) ",
+                    "fn({})",
                     params
                         .iter()
                         .map(|ty| ty.to_string(ns))
@@ -760,7 +760,7 @@ impl Type {
                 if !returns.is_empty() {
                     write!(
                         s,
-                        " returns ({})",
+                        " -> ({})",
                         returns
                             .iter()
                             .map(|ty| ty.to_string(ns))
@@ -993,7 +993,8 @@ impl Type {
             | Type::DynamicBytes
             | Type::Ref(_)
             | Type::Slice(_)
-            | Type::StorageRef(..) => BigInt::one(),
+            | Type::StorageRef(..)
+            | Type::Function { .. } => BigInt::one(),
             Type::Unresolved => BigInt::zero(),
             Type::UserType(no) => ns.user_types[*no]
                 .ty
@@ -1281,6 +1282,32 @@ impl Type {
         })
     }
 
+    /// Does the type contain any function type
+    pub fn contains_function(&self, ns: &Namespace) -> bool {
+        self.contains_function_internal(ns, &mut HashSet::new())
+    }
+
+    fn contains_function_internal(
+        &self,
+        ns: &Namespace,
+        structs_visited: &mut HashSet<usize>,
+    ) -> bool {
+        self.guarded_recursion(structs_visited, false, |structs_visited| match self {
+            Type::Function { .. } => true,
+            Type::Array(ty, _) => ty.contains_function_internal(ns, structs_visited),
+            Type::Mapping(Mapping { key, value, .. }) => {
+                key.contains_function_internal(ns, structs_visited)
+                    || value.contains_function_internal(ns, structs_visited)
+            }
+            Type::Struct(no) => ns.structs[*no]
+                .fields
+                .iter()
+                .any(|f| f.ty.contains_function_internal(ns, structs_visited)),
+            Type::StorageRef(r) | Type::Ref(r) => r.contains_function_internal(ns, structs_visited),
+            _ => false,
+        })
+    }
+
     /// If the type is Ref or StorageRef, get the underlying type
     pub fn deref_any(&self) -> &Self {
         match self {
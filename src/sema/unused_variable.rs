@@ -83,7 +83,9 @@ fn subscript_variable(ns: &mut Namespace, exp: &Expression, symtable: &mut Symta
             subscript_variable(ns, index, symtable);
         }
 
-        Expression::FunctionCall { .. } | Expression::ExternalFunctionCall { .. } => {
+        Expression::FunctionCall { .. }
+        | Expression::ExternalFunctionCall { .. }
+        | Expression::Constructor { .. } => {
             check_function_call(ns, exp, symtable);
         }
 
@@ -229,6 +231,11 @@ pub fn check_function_call(ns: &mut Namespace, exp: &Expression, symtable: &mut
             used_variable(ns, address, symtable);
             check_call_args(ns, call_args, symtable);
         }
+        Expression::Constructor { args, .. } => {
+            for arg in args {
+                used_variable(ns, arg, symtable);
+            }
+        }
         Expression::LibFunction {
             kind: expr_type,
             args,
@@ -352,6 +359,32 @@ pub fn emit_warning_local_variable(
 
             None
         }
+        VariableUsage::TryCatchReturns => {
+            if !variable.read {
+                return Some(Diagnostic::warning(
+                    variable.id.loc,
+                    format!(
+                        "try-catch returns variable '{}' has never been read",
+                        variable.id.name
+                    ),
+                ));
+            }
+
+            None
+        }
+        VariableUsage::TryCatchErrorBytes => {
+            if !variable.read {
+                return Some(Diagnostic::warning(
+                    variable.id.loc,
+                    format!(
+                        "try-catch error bytes '{}' has never been used",
+                        variable.id.name
+                    ),
+                ));
+            }
+
+            None
+        }
         VariableUsage::AnonymousReturnVariable => None,
     }
 }
@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::ast::{Diagnostic, Namespace, Using};
+use super::diagnostics::Diagnostics;
+use ola_parser::program;
+
+/// Resolve a `using <library> for <type>;` directive in a file, or in a
+/// contract when `contract_no` is set.
+pub(crate) fn using_decl(
+    using: &program::Using,
+    file_no: usize,
+    contract_no: Option<usize>,
+    ns: &mut Namespace,
+) -> Option<Using> {
+    let library_no = match ns.resolve_contract(file_no, &using.library) {
+        Some(library_no) if ns.contracts[library_no].is_library() => library_no,
+        Some(_) => {
+            ns.diagnostics.push(Diagnostic::error(
+                using.library.loc,
+                format!("'{}' is not a library", using.library.name),
+            ));
+            return None;
+        }
+        None => {
+            ns.diagnostics.push(Diagnostic::error(
+                using.library.loc,
+                format!("library '{}' not found", using.library.name),
+            ));
+            return None;
+        }
+    };
+
+    let mut diagnostics = Diagnostics::default();
+
+    let ty = match ns.resolve_type(file_no, contract_no, &using.ty, &mut diagnostics) {
+        Ok(ty) => ty,
+        Err(()) => {
+            ns.diagnostics.extend(diagnostics);
+            return None;
+        }
+    };
+
+    Some(Using {
+        loc: using.loc,
+        library_no,
+        ty,
+        file_no: if contract_no.is_none() {
+            Some(file_no)
+        } else {
+            None
+        },
+    })
+}
@@ -61,6 +61,17 @@ pub fn variable_decl<'a>(
         }
     };
 
+    if ty.contains_function(ns) {
+        ns.diagnostics.push(Diagnostic::error(
+            def.ty.loc(),
+            format!(
+                "variable of type '{}' cannot be declared outside a function",
+                ty.to_string(ns)
+            ),
+        ));
+        return None;
+    }
+
     let mut constant = false;
 
     for attr in attrs {
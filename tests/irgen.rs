@@ -2,10 +2,14 @@
 
 use assert_cmd::Command;
 use rayon::prelude::*;
+use serde_json::Value;
 use std::ffi::OsString;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 #[test]
 fn u32_testcases() {
@@ -37,6 +41,26 @@ fn dynamic_array_testcases() {
     run_test_for_path("./tests/irgen_testcases/dynamic_array");
 }
 
+#[test]
+fn create_testcases() {
+    run_test_for_path("./tests/irgen_testcases/create");
+}
+
+#[test]
+fn try_catch_testcases() {
+    run_test_for_path("./tests/irgen_testcases/try_catch");
+}
+
+#[test]
+fn mutability_testcases() {
+    run_test_for_path("./tests/irgen_testcases/mutability");
+}
+
+#[test]
+fn function_pointer_testcases() {
+    run_test_for_path("./tests/irgen_testcases/function_pointer");
+}
+
 fn run_test_for_path(path: &str) {
     let mut tests = Vec::new();
 
@@ -184,3 +208,108 @@ fn testcase(path: PathBuf) {
         );
     }
 }
+
+#[test]
+fn unsupported_construct_is_reported() {
+    let path = PathBuf::from("./tests/irgen_testcases/unsupported/u256_div.ola");
+    let mut cmd = Command::cargo_bin("olac").unwrap();
+
+    let assert = cmd
+        .arg("compile")
+        .arg("--gen=asm")
+        .arg(format!("-o={}", path.parent().unwrap().display()))
+        .arg(format!("{}", path.canonicalize().unwrap().display()))
+        .assert();
+    let output = assert.get_output();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("division is not supported on type u256"));
+    assert!(stderr.contains("u256_div.ola:4:"));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn each_contract_gets_its_own_artifacts() {
+    let path = PathBuf::from("./tests/irgen_testcases/artifacts/two_contracts.ola");
+    let out = std::env::temp_dir().join("olac_two_contracts");
+    let mut cmd = Command::cargo_bin("olac").unwrap();
+
+    let assert = cmd
+        .arg("compile")
+        .arg(format!("-o={}", out.display()))
+        .arg(format!("{}", path.canonicalize().unwrap().display()))
+        .assert();
+    let output = assert.get_output();
+    if !output.status.success() {
+        panic!(
+            "FAILED TO COMPILE: {}, ERROR:\n{}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr),
+        );
+    }
+
+    for (contract, function) in [("Adder", "add(u32,u32)"), ("Multiplier", "mul(u32,u32)")] {
+        for suffix in ["abi", "asm"] {
+            assert!(out
+                .join(format!("two_contracts_{contract}_{suffix}.json"))
+                .exists());
+        }
+        let artifact =
+            fs::read_to_string(out.join(format!("two_contracts_{contract}_artifact.json")))
+                .unwrap();
+        let artifact: Value = serde_json::from_str(&artifact).unwrap();
+        assert_eq!(artifact["contract"], contract);
+        assert_eq!(artifact["compiler"]["version"], env!("CARGO_PKG_VERSION"));
+        assert!(artifact["source_hash"].as_str().unwrap().starts_with("0x"));
+        assert!(artifact["selectors"][function].is_string());
+        assert!(artifact["abi"].is_array());
+        assert!(artifact["program"]["program"].is_string());
+    }
+}
+
+#[test]
+fn build_project_with_dependency() {
+    let project = std::env::temp_dir().join("olac_build_project");
+    let _ = fs::remove_dir_all(&project);
+    copy_dir(Path::new("./tests/irgen_testcases/project"), &project);
+    let mut cmd = Command::cargo_bin("olac").unwrap();
+
+    let assert = cmd
+        .arg("build")
+        .arg("--manifest-path")
+        .arg(project.join("app"))
+        .assert();
+    let output = assert.get_output();
+    if !output.status.success() {
+        panic!(
+            "FAILED TO BUILD: {}, ERROR:\n{}",
+            project.display(),
+            String::from_utf8_lossy(&output.stderr),
+        );
+    }
+
+    let out = project.join("app/out");
+    for (dir, contract) in [("counter", "Counter"), ("vault/vault", "Vault")] {
+        for suffix in ["abi", "asm", "artifact"] {
+            assert!(out
+                .join(dir)
+                .join(format!("{contract}_{suffix}.json"))
+                .exists());
+        }
+    }
+    assert!(!out.join("math_lib").exists());
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap() {
+        let path = entry.unwrap().path();
+        let target = to.join(path.file_name().unwrap());
+        if path.is_dir() {
+            copy_dir(&path, &target);
+        } else {
+            fs::copy(&path, &target).unwrap();
+        }
+    }
+}